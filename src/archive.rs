@@ -0,0 +1,114 @@
+//! Plugins packaged as zip archives, see [`crate::LuaManager::mount_archive`].
+
+use std::{
+    fs::File,
+    io::{self, Read, Seek},
+    path::Path,
+};
+
+use indexmap::IndexMap;
+
+use crate::source::SourceProvider;
+
+/// Extensions of plugin archives.
+pub const ARCHIVE_EXTENSIONS: [&str; 2] = ["zip", "pluxpkg"];
+
+/// Reads plugin sources from a zip archive, kept in memory.
+///
+/// The archive holds the content of the plugin directory: `config.toml` at
+/// its root, or inside a single top-level directory.
+#[derive(Debug, Clone)]
+pub struct ArchiveSourceProvider {
+    files: IndexMap<String, Vec<u8>>,
+}
+
+impl ArchiveSourceProvider {
+    /// Reads the archive at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::from_reader(File::open(path)?)
+    }
+
+    /// Reads an archive from `reader`.
+    pub fn from_reader<R: Read + Seek>(reader: R) -> io::Result<Self> {
+        let mut archive = zip::ZipArchive::new(reader).map_err(io::Error::other)?;
+        let mut files = IndexMap::new();
+        for index in 0..archive.len() {
+            let mut file = archive.by_index(index).map_err(io::Error::other)?;
+            if !file.is_file() {
+                continue;
+            }
+            let Some(path) = file.enclosed_name() else {
+                continue;
+            };
+            let rel_path = path
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            let mut content = vec![];
+            file.read_to_end(&mut content)?;
+            files.insert(rel_path, content);
+        }
+
+        // Archives of the plugin directory itself rather than its content
+        if !files.contains_key("config.toml") {
+            let root = files
+                .keys()
+                .find(|file| file.ends_with("/config.toml") && file.matches('/').count() == 1)
+                .map(|file| file.trim_end_matches("config.toml").to_string());
+            if let Some(root) = root {
+                files = files
+                    .into_iter()
+                    .filter_map(|(file, content)| {
+                        Some((file.strip_prefix(&root)?.to_string(), content))
+                    })
+                    .collect();
+            }
+        }
+
+        Ok(Self { files })
+    }
+}
+
+impl SourceProvider for ArchiveSourceProvider {
+    fn read_source(&self, rel_path: &str) -> io::Result<String> {
+        let content = self.files.get(rel_path).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{rel_path} not in archive"),
+            )
+        })?;
+        String::from_utf8(content.clone())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn exists(&self, rel_path: &str) -> bool {
+        self.files.contains_key(rel_path)
+    }
+
+    fn list(&self, prefix: &str) -> io::Result<Vec<String>> {
+        let mut files: Vec<String> = self
+            .files
+            .keys()
+            .filter(|file| file.starts_with(prefix))
+            .cloned()
+            .collect();
+        files.sort();
+        Ok(files)
+    }
+}
+
+/// Returns the name of the plugin directory an archive stands for:
+/// `my_plugin-v1.0.0.zip` and `my_plugin-v1.0.0.lua.zip` are both mounted as
+/// `my_plugin-v1.0.0.lua`.
+pub(crate) fn plugin_dir_name(archive: &Path) -> Option<String> {
+    let extension = archive.extension()?.to_str()?;
+    if !ARCHIVE_EXTENSIONS.contains(&extension) {
+        return None;
+    }
+    let stem = archive.file_stem()?.to_str()?;
+    Some(match stem.ends_with(".lua") {
+        true => stem.to_string(),
+        false => format!("{stem}.lua"),
+    })
+}
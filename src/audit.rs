@@ -0,0 +1,82 @@
+//! Audit log of the calls plugins make to host functions and the plugin API,
+//! see [`crate::LuaManager::with_audit_log`].
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use plux_rs::Bundle;
+
+use crate::sync::MutexExt;
+
+/// A call made by a plugin to a host function or to the plugin API.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditRecord {
+    /// The calling plugin.
+    pub plugin: Bundle,
+    /// The called function, e.g. `host.read_file` or `api.storage.write`.
+    pub function: String,
+    /// A summary of the arguments of the call.
+    pub args: String,
+    /// When the call started.
+    pub timestamp: SystemTime,
+    /// How long the call ran.
+    pub duration: Duration,
+    /// Whether the call succeeded.
+    pub ok: bool,
+}
+
+/// Receives every [`AuditRecord`], see [`crate::LuaManager::with_audit_sink`].
+pub type AuditSink = Arc<dyn Fn(&AuditRecord) + Send + Sync>;
+
+/// The recent audit records of a manager and the sink receiving them.
+pub(crate) struct AuditLog {
+    /// Number of records kept, none if 0
+    capacity: usize,
+    records: Mutex<VecDeque<AuditRecord>>,
+    sink: Option<AuditSink>,
+}
+
+impl AuditLog {
+    pub(crate) fn new(capacity: usize, sink: Option<AuditSink>) -> Self {
+        Self {
+            capacity,
+            records: Mutex::new(VecDeque::with_capacity(capacity)),
+            sink,
+        }
+    }
+
+    /// Returns the number of records kept.
+    pub(crate) fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the sink receiving the records.
+    pub(crate) fn sink(&self) -> Option<AuditSink> {
+        self.sink.clone()
+    }
+
+    /// Hands `record` to the sink and keeps it, dropping the oldest record
+    /// once the log is full.
+    pub(crate) fn record(&self, record: AuditRecord) {
+        if let Some(sink) = &self.sink {
+            sink(&record);
+        }
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut records = self.records.lock_unpoisoned();
+        if records.len() == self.capacity {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+
+    /// Returns the records kept, oldest first.
+    pub(crate) fn records(&self) -> Vec<AuditRecord> {
+        self.records.lock_unpoisoned().iter().cloned().collect()
+    }
+}
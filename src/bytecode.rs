@@ -0,0 +1,121 @@
+//! Cache of compiled plugin chunks, see [`crate::LuaManager::with_bytecode_cache`].
+//!
+//! Entry scripts and required modules are compiled to Lua bytecode the first
+//! time they are loaded. The bytecode is keyed by a hash of the chunk name and
+//! source, so an edited source is compiled again while an unchanged one skips
+//! parsing, across loads, reloads and plugins sharing identical files.
+//!
+//! With the disk cache enabled, the bytecode is also written to the
+//! `.plux-cache` directory of plugins read from the filesystem. Lua does not
+//! verify bytecode, so the cache directory must be as trusted as the plugin
+//! sources themselves.
+
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use mlua::{Chunk, ChunkMode, Lua};
+
+use crate::sync::MutexExt;
+
+/// Name of the cache directory inside a plugin directory.
+pub(crate) const CACHE_DIR: &str = ".plux-cache";
+
+/// Compiled chunks, shared by all the plugins of a manager.
+#[derive(Debug, Default)]
+pub(crate) struct BytecodeCache {
+    chunks: Mutex<HashMap<u64, Arc<Vec<u8>>>>,
+    disk: bool,
+}
+
+impl BytecodeCache {
+    pub(crate) fn new(disk: bool) -> Self {
+        Self {
+            chunks: Mutex::default(),
+            disk,
+        }
+    }
+
+    /// Returns the chunk `name` with the bytecode of `src`, compiling it if
+    /// it is neither in memory nor in `cache_dir`.
+    pub(crate) fn load<'a>(
+        &self,
+        lua: &'a Lua,
+        name: &str,
+        src: &str,
+        cache_dir: Option<&Path>,
+    ) -> mlua::Result<Chunk<'a>> {
+        let key = hash(name, src);
+        let cached = self.chunks.lock_unpoisoned().get(&key).cloned();
+        let bytecode = match cached {
+            Some(bytecode) => bytecode,
+            None => {
+                let bytecode = Arc::new(self.compile(lua, name, src, key, cache_dir)?);
+                self.chunks.lock_unpoisoned().insert(key, bytecode.clone());
+                bytecode
+            }
+        };
+
+        Ok(lua
+            .load(bytecode.as_slice().to_vec())
+            .set_name(name)
+            .set_mode(ChunkMode::Binary))
+    }
+
+    /// Reads the bytecode of a chunk from the disk cache, or compiles it and
+    /// writes it there.
+    fn compile(
+        &self,
+        lua: &Lua,
+        name: &str,
+        src: &str,
+        key: u64,
+        cache_dir: Option<&Path>,
+    ) -> mlua::Result<Vec<u8>> {
+        let file = cache_dir
+            .filter(|_| self.disk)
+            .map(|dir| dir.join(format!("{key:016x}.luac")));
+        if let Some(file) = &file
+            && let Ok(bytecode) = std::fs::read(file)
+        {
+            return Ok(bytecode);
+        }
+
+        let bytecode = lua.load(src).set_name(name).into_function()?.dump(false);
+        if let Some(file) = &file {
+            let written = file
+                .parent()
+                .map_or(Ok(()), std::fs::create_dir_all)
+                .and_then(|_| std::fs::write(file, &bytecode));
+            if let Err(e) = written {
+                log::warn!("Cannot cache chunk {} in {}: {}", name, file.display(), e);
+            }
+        }
+        Ok(bytecode)
+    }
+}
+
+/// FNV-1a hash of a chunk, stable across builds so the disk cache survives
+/// host updates.
+pub(crate) fn hash(name: &str, src: &str) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in name.bytes().chain([0]).chain(src.bytes()) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_depends_on_name_and_source() {
+        assert_eq!(hash("@main.lua", "return 1"), hash("@main.lua", "return 1"));
+        assert_ne!(hash("@main.lua", "return 1"), hash("@main.lua", "return 2"));
+        assert_ne!(hash("@main.lua", "return 1"), hash("@init.lua", "return 1"));
+    }
+}
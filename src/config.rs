@@ -6,27 +6,69 @@
 //!
 //! # Example
 //!
-//! ```toml
-//! # config.toml
+//! ```
+//! use plux_lua_manager::{MemorySourceProvider, load_config_from};
+//!
+//! let config = r#"
 //! name = "my_plugin"
 //! description = "A sample plugin"
 //! author = "Plugin Author"
 //! version = "0.1.0"
+//! entry = "src/init.lua"
 //!
-//! [dependencies]
+//! [depends]
 //! other_plugin = "^1.0.0"
 //!
-//! [optional_dependencies]
+//! [optional_depends]
 //! optional_feature = "^2.0.0"
+//!
+//! [features]
+//! telemetry = false
+//! "#;
+//!
+//! let provider = MemorySourceProvider::new().with_file("config.toml", config);
+//! let (config, info) = load_config_from(&provider).unwrap();
+//! assert_eq!(config.entry.as_deref(), Some("src/init.lua"));
+//! assert_eq!(info.depends[0].id, "other_plugin");
+//! assert_eq!(info.optional_depends[0].id, "optional_feature");
+//! ```
+//!
+//! # Plugin packs
+//!
+//! A single directory can bundle several plugins sharing the same Lua
+//! libraries by declaring them as `[[plugins]]` tables:
+//!
+//! ```toml
+//! name = "my_pack"
+//! description = "A pack of plugins"
+//! author = "Plugin Author"
+//!
+//! [[plugins]]
+//! name = "core"
+//! version = "1.0.0"
+//! entry = "core/main.lua"
+//!
+//! [[plugins]]
+//! name = "extras"
+//! version = "1.0.0"
+//! entry = "extras/main.lua"
+//! depends = { core = "^1.0" }
 //! ```
+//!
+//! The pack is not a plugin itself: [`crate::LuaManager::mount_pack`] mounts
+//! each sub-plugin as a plugin of its own, e.g. `core-v1.0.0.lua` and
+//! `extras-v1.0.0.lua`, which are then loaded, called and depended on like
+//! any other plugin.
 
-use std::{collections::HashMap, path::PathBuf};
+use std::{collections::HashMap, path::Path};
 
-use plux_rs::{Depend, StdInfo};
-use semver::VersionReq;
+use plux_rs::{Bundle, Depend, StdInfo, variable::Variable};
+use semver::{Version, VersionReq};
 use serde::{Deserialize, Serialize};
 
 use crate::error::ConfigError;
+use crate::sandbox::{LuaLib, Permission};
+use crate::source::{FsSourceProvider, SourceProvider};
 
 /// Plugin configuration loaded from a `config.toml` file.
 ///
@@ -48,6 +90,12 @@ pub struct Config {
     /// The author of the plugin.
     pub author: String,
 
+    /// The version of the plugin.
+    ///
+    /// plux identifies plugins by their directory name, so when declared it
+    /// must match the version in that name (`my_plugin-v0.1.0.lua`).
+    pub version: Option<Version>,
+
     /// An optional SPDX license identifier.
     pub license: Option<String>,
 
@@ -62,6 +110,463 @@ pub struct Config {
     /// These dependencies are not required for the plugin to function,
     /// but may enable additional features if available.
     pub optional_depends: Option<HashMap<String, VersionReq>>,
+
+    /// Features the plugin intends to use, see [`KNOWN_CAPABILITIES`].
+    ///
+    /// API functions gated by a capability fail if it is not declared.
+    pub capabilities: Option<Vec<String>>,
+
+    /// Standard libraries the plugin needs, all those the host's
+    /// [`SandboxPolicy`](crate::SandboxPolicy) allows by default.
+    ///
+    /// Libraries the host does not allow are not opened.
+    pub libs: Option<Vec<LuaLib>>,
+
+    /// Access to the host system the plugin needs, unrestricted if not set.
+    ///
+    /// Once declared, functions of the standard libraries not covered by a
+    /// [`Permission`] are removed, and each permission grants the capability
+    /// of the same name.
+    pub permissions: Option<Vec<Permission>>,
+
+    /// Functions of the `api` and `host` tables the plugin uses, all if not
+    /// set.
+    ///
+    /// Each entry names a function or a table of functions, such as
+    /// `host.read_file` or `api.util`. Once declared, the other functions are
+    /// removed, see [`crate::LuaManager::with_api_filter`].
+    pub api: Option<Vec<String>>,
+
+    /// How strings are converted at the plugin's boundary, `utf8` by default.
+    pub strings: Option<StringPolicy>,
+
+    /// How numbers are converted at the plugin's boundary, `full` by default.
+    pub numbers: Option<NumberPolicy>,
+
+    /// Whether the `__pairs` metamethod of tables passed to the host is
+    /// honored, `false` by default.
+    ///
+    /// Tables are otherwise read raw. When enabled, a metamethod running
+    /// longer than the conversion budget fails the conversion.
+    pub metamethods: Option<bool>,
+
+    /// Whether nils inside lists passed to the host are an error, `false` by
+    /// default, in which case they become `Null`.
+    pub strict_nils: Option<bool>,
+
+    /// Path of the entry script, relative to the plugin root. The manager's
+    /// entry (see [`crate::LuaManager::with_entry`]) if not set.
+    ///
+    /// Plugin packs declare an entry for each of their `[[plugins]]` instead.
+    pub entry: Option<String>,
+
+    /// Plugins bundled in this directory when it is a plugin pack.
+    ///
+    /// Each entry is declared as a `[[plugins]]` table. Sub-plugins share the
+    /// pack directory for `require` and may depend on each other. Packs are
+    /// mounted with [`crate::LuaManager::mount_pack`], which turns each
+    /// sub-plugin into a plugin of its own, and cannot be loaded directly.
+    pub plugins: Option<Vec<PackPlugin>>,
+
+    /// The functions the entry script exports, declared as `[[exports]]`
+    /// tables.
+    ///
+    /// Lets the plugin be loaded lazily, see
+    /// [`crate::LuaManager::with_lazy_loading`].
+    pub exports: Option<Vec<ExportDeclaration>>,
+
+    /// Optional features of the plugin, declared as a `[features]` table
+    /// mapping their names to whether they are enabled by default.
+    ///
+    /// The host may toggle them when the plugin is registered, see
+    /// [`crate::LuaManager::with_feature_toggle`]. The plugin reads them from
+    /// `plugin.features`.
+    pub features: Option<HashMap<String, bool>>,
+
+    /// Settings the user of the plugin may change, declared as
+    /// `[user_settings.<name>]` tables, see [`SettingDeclaration`].
+    pub user_settings: Option<HashMap<String, SettingDeclaration>>,
+
+    /// Values for the plugin itself, declared as a `[settings]` table and
+    /// read by the plugin from `plugin.config.settings`.
+    pub settings: Option<toml::Table>,
+
+    /// The platforms the plugin runs on, all if not set.
+    ///
+    /// Registering the plugin on another platform fails with
+    /// [`ConfigError::UnsupportedPlatform`].
+    pub target: Option<Target>,
+}
+
+/// The platforms a plugin runs on, declared as a `[target]` table:
+///
+/// ```toml
+/// [target]
+/// os = ["linux", "macos"]
+/// arch = ["x86_64", "aarch64"]
+/// ```
+///
+/// Names are those of [`std::env::consts::OS`] and
+/// [`std::env::consts::ARCH`], and `unix` or `windows` match a whole family
+/// of operating systems, as [`std::env::consts::FAMILY`].
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Target {
+    /// The operating systems the plugin runs on, all if not set.
+    pub os: Option<Vec<String>>,
+
+    /// The CPU architectures the plugin runs on, all if not set.
+    pub arch: Option<Vec<String>>,
+}
+
+impl Target {
+    /// Returns `true` if the plugin runs on the operating system `os` of the
+    /// family `family`, with the architecture `arch`.
+    pub fn supports(&self, os: &str, family: &str, arch: &str) -> bool {
+        let os_supported = self.os.as_ref().is_none_or(|targets| {
+            targets
+                .iter()
+                .any(|target| target == os || target == family)
+        });
+        let arch_supported = self
+            .arch
+            .as_ref()
+            .is_none_or(|targets| targets.iter().any(|target| target == arch));
+        os_supported && arch_supported
+    }
+}
+
+/// Capabilities a plugin can declare in its config.
+///
+/// - `events`: the event bus
+/// - `fs`: filesystem access
+/// - `net`: network access
+/// - `spawn`: background tasks with `api.spawn`
+/// - `subprocess`: other processes
+/// - `timers`: timers
+pub const KNOWN_CAPABILITIES: &[&str] = &["events", "fs", "net", "spawn", "subprocess", "timers"];
+
+/// The descriptive fields of a plugin's config.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PluginMetadata {
+    /// The name of the plugin.
+    pub name: String,
+    /// A brief description of what the plugin does.
+    pub description: String,
+    /// The author of the plugin.
+    pub author: String,
+    /// An optional SPDX license identifier.
+    pub license: Option<String>,
+}
+
+impl Config {
+    /// Returns the descriptive fields of the config.
+    pub fn metadata(&self) -> PluginMetadata {
+        PluginMetadata {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            author: self.author.clone(),
+            license: self.license.clone(),
+        }
+    }
+
+    /// Checks that the declared version, if any, is the one of `bundle`.
+    pub fn check_version(&self, bundle: &Bundle) -> Result<(), ConfigError> {
+        match &self.version {
+            Some(version) if *version != bundle.version => Err(ConfigError::VersionMismatch {
+                declared: version.clone(),
+                bundle: bundle.version.clone(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks that the plugin runs on the current platform, see [`Target`].
+    pub fn check_platform(&self) -> Result<(), ConfigError> {
+        use std::env::consts::{ARCH, FAMILY, OS};
+
+        match &self.target {
+            Some(target) if !target.supports(OS, FAMILY, ARCH) => {
+                Err(ConfigError::UnsupportedPlatform {
+                    os: OS.to_string(),
+                    arch: ARCH.to_string(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the version requirement declared for the dependency `id`,
+    /// required or optional.
+    pub fn depend_requirement(&self, id: &str) -> Option<&VersionReq> {
+        self.depends
+            .iter()
+            .chain(self.optional_depends.iter())
+            .find_map(|depends| depends.get(id))
+    }
+
+    /// Returns the declared capabilities, and those granted by the declared
+    /// permissions.
+    pub fn granted_capabilities(&self) -> Vec<String> {
+        let mut capabilities = self.capabilities.clone().unwrap_or_default();
+        for permission in self.permissions.iter().flatten() {
+            if !capabilities.iter().any(|c| c == permission.name()) {
+                capabilities.push(permission.name().to_string());
+            }
+        }
+        capabilities
+    }
+
+    /// Returns the declared capabilities that are not in [`KNOWN_CAPABILITIES`].
+    pub fn unknown_capabilities(&self) -> Vec<String> {
+        self.capabilities
+            .iter()
+            .flatten()
+            .filter(|capability| !KNOWN_CAPABILITIES.contains(&capability.as_str()))
+            .cloned()
+            .collect()
+    }
+}
+
+/// How a plugin's strings are converted between Lua and plux.
+///
+/// Lua strings are byte strings, while plux strings are UTF-8.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StringPolicy {
+    /// Strings must be valid UTF-8, invalid strings are an error.
+    #[default]
+    Utf8,
+    /// Strings are passed as lists of `U8` bytes, and non-empty lists of
+    /// bytes passed to the plugin become Lua strings.
+    Bytes,
+    /// Invalid UTF-8 sequences are replaced with U+FFFD.
+    Lossy,
+}
+
+/// How a plugin's numbers are converted from Lua to plux.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NumberPolicy {
+    /// Integers become `I64` and floats `F64`, as Lua represents them.
+    #[default]
+    Full,
+    /// Integers become `I32` and floats `F32`, truncating values that do not
+    /// fit, as in versions before `I64` and `F64` were used.
+    Legacy,
+}
+
+/// A plugin declared inside a plugin pack.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct PackPlugin {
+    /// The name of the sub-plugin, unique within the pack.
+    pub name: String,
+
+    /// The version of the sub-plugin.
+    pub version: Version,
+
+    /// Path of the entry script, relative to the pack directory.
+    pub entry: String,
+
+    /// Dependencies of the sub-plugin, either on other sub-plugins of the
+    /// same pack or on external plugins, added to those of the pack.
+    pub depends: Option<HashMap<String, VersionReq>>,
+}
+
+/// A setting the user of a plugin may change, declared as a
+/// `[user_settings.<name>]` table:
+///
+/// ```toml
+/// [user_settings.volume]
+/// type = "f64"
+/// default = 0.5
+/// description = "How loud notifications are"
+/// ```
+///
+/// Types are those declared for the inputs of exported functions, e.g. `i64`,
+/// `f64`, `bool`, `string` or `list`.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SettingDeclaration {
+    /// The type of the value, any type if not set.
+    #[serde(rename = "type")]
+    pub ty: Option<String>,
+
+    /// The value of the setting until the user changes it, `nil` if not set.
+    #[serde(
+        default,
+        with = "plain_variable",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub default: Option<Variable>,
+
+    /// What the setting does.
+    pub description: Option<String>,
+}
+
+/// A function a plugin declares in its config.
+///
+/// The fields follow the tables returned by entry scripts.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ExportDeclaration {
+    /// The name of the function.
+    pub name: String,
+
+    /// The arguments, e.g. `"x: i64"` or `{ name = "x", type = "i64" }`.
+    #[serde(default)]
+    pub inputs: Vec<InputDeclaration>,
+
+    /// The type of the result, any type if not set.
+    pub output: Option<String>,
+
+    /// What the function does.
+    pub description: Option<String>,
+
+    /// Example calls of the function.
+    #[serde(default)]
+    pub examples: Vec<String>,
+
+    /// The version of the plugin that introduced the function.
+    pub since: Option<String>,
+}
+
+impl ExportDeclaration {
+    /// Returns the documentation of the function.
+    pub fn doc(&self) -> FunctionDoc {
+        FunctionDoc {
+            description: self.description.clone(),
+            examples: self.examples.clone(),
+            since: self.since.clone(),
+        }
+    }
+}
+
+/// The documentation of an exported function, see
+/// [`crate::LuaManager::function_doc`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct FunctionDoc {
+    /// What the function does.
+    pub description: Option<String>,
+    /// Example calls of the function.
+    pub examples: Vec<String>,
+    /// The version of the plugin that introduced the function.
+    pub since: Option<String>,
+}
+
+/// An argument of an exported function, in the config or the table returned
+/// by the entry script.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[serde(untagged)]
+pub enum InputDeclaration {
+    /// `"name"` or `"name: type"`, `"...name"` for a variadic argument.
+    Short(String),
+    /// `{ name = "name", type = "type", optional = true, default = value }`,
+    /// any type if `type` is not set.
+    Full {
+        /// The name of the argument.
+        name: String,
+        /// The type of the argument.
+        #[serde(rename = "type")]
+        ty: Option<String>,
+        /// Whether callers may leave the argument out, implied by `default`.
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        optional: bool,
+        /// The value of the argument when callers leave it out or pass `nil`.
+        #[serde(
+            default,
+            with = "plain_variable",
+            skip_serializing_if = "Option::is_none"
+        )]
+        default: Option<Variable>,
+        /// Whether the argument collects all the remaining arguments of a
+        /// call in a list, allowed on the last argument only.
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        variadic: bool,
+    },
+}
+
+/// (De)serializes variables as plain values, e.g. `3` rather than `{ I64 = 3 }`.
+pub(crate) mod plain_variable {
+    use plux_rs::variable::Variable;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Deserialize, Serialize)]
+    #[serde(untagged)]
+    enum Plain {
+        Bool(bool),
+        Int(i64),
+        Float(f64),
+        String(String),
+        List(Vec<Plain>),
+    }
+
+    impl From<Plain> for Variable {
+        fn from(plain: Plain) -> Self {
+            match plain {
+                Plain::Bool(x) => Variable::Bool(x),
+                Plain::Int(x) => Variable::I64(x),
+                Plain::Float(x) => Variable::F64(x),
+                Plain::String(x) => Variable::String(x),
+                Plain::List(list) => Variable::List(list.into_iter().map(Into::into).collect()),
+            }
+        }
+    }
+
+    /// Returns the plain value of `var`, `None` for `Null`.
+    fn plain(var: &Variable) -> Option<Plain> {
+        Some(match var {
+            Variable::Null => return None,
+            Variable::I8(x) => Plain::Int(*x as i64),
+            Variable::I16(x) => Plain::Int(*x as i64),
+            Variable::I32(x) => Plain::Int(*x as i64),
+            Variable::I64(x) => Plain::Int(*x),
+            Variable::U8(x) => Plain::Int(*x as i64),
+            Variable::U16(x) => Plain::Int(*x as i64),
+            Variable::U32(x) => Plain::Int(*x as i64),
+            Variable::U64(x) => Plain::Int(*x as i64),
+            Variable::F32(x) => Plain::Float(*x as f64),
+            Variable::F64(x) => Plain::Float(*x),
+            Variable::Bool(x) => Plain::Bool(*x),
+            Variable::Char(x) => Plain::String(x.to_string()),
+            Variable::String(x) => Plain::String(x.clone()),
+            Variable::List(list) => Plain::List(list.iter().filter_map(plain).collect()),
+        })
+    }
+
+    pub(crate) fn serialize<S: Serializer>(
+        var: &Option<Variable>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        var.as_ref().and_then(plain).serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Variable>, D::Error> {
+        Ok(Option::<Plain>::deserialize(deserializer)?.map(Into::into))
+    }
+}
+
+impl std::fmt::Display for InputDeclaration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Short(declaration) => write!(f, "{declaration}"),
+            Self::Full {
+                name, ty, variadic, ..
+            } => {
+                if *variadic {
+                    write!(f, "...")?;
+                }
+                write!(f, "{name}")?;
+                match ty {
+                    Some(ty) => write!(f, ": {ty}"),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
 }
 
 /// Loads and validates a plugin's configuration.
@@ -80,22 +585,42 @@ pub struct Config {
 /// - The config file is missing or unreadable
 /// - The config file contains invalid TOML
 /// - Required fields are missing or have invalid values
-pub fn load_config(plugin_path: &PathBuf) -> Result<(Config, StdInfo), ConfigError> {
-    let config_path = plugin_path.join("config.toml");
-    if !config_path.exists() {
+pub fn load_config(plugin_path: &Path) -> Result<(Config, StdInfo), ConfigError> {
+    load_config_from(&FsSourceProvider::new(plugin_path))
+}
+
+/// Loads and validates a plugin's configuration from a [`SourceProvider`].
+///
+/// This is the provider-agnostic counterpart of [`load_config`].
+///
+/// # Errors
+///
+/// Returns the same errors as [`load_config`].
+pub fn load_config_from(provider: &dyn SourceProvider) -> Result<(Config, StdInfo), ConfigError> {
+    if !provider.exists("config.toml") {
         return Err(ConfigError::NotFound);
     }
 
-    let config_content = std::fs::read_to_string(&config_path)?;
+    let config_content = provider.read_config()?;
     let config: Config = toml::from_str(&config_content)?;
 
+    if config.plugins.is_some() {
+        return Err(ConfigError::InvalidPack(
+            "its plugins are loaded once mounted with `LuaManager::mount_pack`".to_string(),
+        ));
+    }
+    if let Some(entry) = &config.entry {
+        check_entry(entry)?;
+    }
+
     let info = StdInfo {
-        depends: config.depends.clone().map_or(vec![], |depends| {
-            depends
-                .into_iter()
-                .map(|(id, version)| Depend::new(id, version))
-                .collect()
-        }),
+        depends: config
+            .depends
+            .clone()
+            .into_iter()
+            .flatten()
+            .map(|(id, version)| Depend::new(id, version))
+            .collect(),
         optional_depends: config.optional_depends.clone().map_or(vec![], |depends| {
             depends
                 .into_iter()
@@ -106,3 +631,247 @@ pub fn load_config(plugin_path: &PathBuf) -> Result<(Config, StdInfo), ConfigErr
 
     Ok((config, info))
 }
+
+/// Checks that an entry script is a relative path inside the plugin root.
+fn check_entry(entry: &str) -> Result<(), ConfigError> {
+    let inside_root = !entry.is_empty()
+        && !entry.starts_with('/')
+        && !entry.contains('\\')
+        && entry.split('/').all(|component| component != "..");
+    match inside_root {
+        true => Ok(()),
+        false => Err(ConfigError::InvalidEntry(entry.to_string())),
+    }
+}
+
+/// Orders the sub-plugins of a pack so that every sub-plugin comes after the
+/// sub-plugins it depends on.
+///
+/// # Errors
+///
+/// Returns an error if a name is declared twice, a dependency on another
+/// sub-plugin does not match its version, or the dependencies form a cycle.
+pub fn pack_load_order(plugins: &[PackPlugin]) -> Result<Vec<&PackPlugin>, ConfigError> {
+    for (index, plugin) in plugins.iter().enumerate() {
+        if plugins[..index].iter().any(|p| p.name == plugin.name) {
+            return Err(ConfigError::InvalidPack(format!(
+                "plugin `{}` is declared twice",
+                plugin.name
+            )));
+        }
+    }
+
+    fn visit<'a>(
+        plugin: &'a PackPlugin,
+        plugins: &'a [PackPlugin],
+        visiting: &mut Vec<&'a str>,
+        order: &mut Vec<&'a PackPlugin>,
+    ) -> Result<(), ConfigError> {
+        if order.iter().any(|p| p.name == plugin.name) {
+            return Ok(());
+        }
+        if visiting.contains(&plugin.name.as_str()) {
+            return Err(ConfigError::InvalidPack(format!(
+                "dependency cycle through `{}`",
+                plugin.name
+            )));
+        }
+
+        visiting.push(&plugin.name);
+        for (id, version) in plugin.depends.iter().flatten() {
+            if let Some(depend) = plugins.iter().find(|p| p.name == *id) {
+                if !version.matches(&depend.version) {
+                    return Err(ConfigError::InvalidPack(format!(
+                        "`{}` requires `{}` {}, but the pack provides {}",
+                        plugin.name, id, version, depend.version
+                    )));
+                }
+                visit(depend, plugins, visiting, order)?;
+            }
+        }
+        visiting.pop();
+
+        order.push(plugin);
+        Ok(())
+    }
+
+    let mut order = vec![];
+    for plugin in plugins {
+        visit(plugin, plugins, &mut vec![], &mut order)?;
+    }
+    Ok(order)
+}
+
+/// Describes the dependencies that are provided only in versions they do not accept.
+///
+/// Dependencies missing from `available` altogether are not reported, the
+/// loader already refuses to load plugins whose required dependencies are missing.
+pub(crate) fn dependency_mismatches<'a>(
+    depends: &[Depend],
+    available: impl IntoIterator<Item = &'a Bundle> + Clone,
+) -> Vec<String> {
+    depends
+        .iter()
+        .filter_map(|depend| {
+            let found: Vec<_> = available
+                .clone()
+                .into_iter()
+                .filter(|bundle| bundle.id == depend.id)
+                .map(|bundle| &bundle.version)
+                .collect();
+            if found.is_empty() || found.iter().any(|version| depend.version.matches(version)) {
+                return None;
+            }
+
+            let found = found
+                .iter()
+                .map(|version| version.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            Some(format!(
+                "`{}` {} is required, but only {} is available",
+                depend.id, depend.version, found
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pack_plugin(name: &str, depends: &[(&str, &str)]) -> PackPlugin {
+        PackPlugin {
+            name: name.to_string(),
+            version: Version::new(1, 0, 0),
+            entry: format!("{name}.lua"),
+            depends: Some(
+                depends
+                    .iter()
+                    .map(|(id, req)| (id.to_string(), VersionReq::parse(req).unwrap()))
+                    .collect(),
+            ),
+        }
+    }
+
+    #[test]
+    fn test_dependency_mismatches() {
+        let bundle = |id: &str, version: &str| Bundle {
+            id: id.to_string(),
+            version: Version::parse(version).unwrap(),
+            format: "lua".to_string(),
+        };
+        let available = [
+            bundle("ok", "1.2.0"),
+            bundle("old", "1.9.0"),
+            bundle("both", "1.0.0"),
+            bundle("both", "2.1.0"),
+        ];
+        let depends = [
+            Depend::new("ok".to_string(), VersionReq::parse("^1.0").unwrap()),
+            Depend::new("old".to_string(), VersionReq::parse("^2.0").unwrap()),
+            Depend::new("both".to_string(), VersionReq::parse("^2.0").unwrap()),
+            Depend::new("missing".to_string(), VersionReq::parse("^1.0").unwrap()),
+        ];
+
+        let mismatches = dependency_mismatches(&depends, &available);
+        assert_eq!(
+            mismatches,
+            vec!["`old` ^2.0 is required, but only 1.9.0 is available".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_pack_load_order() {
+        let plugins = vec![
+            pack_plugin("report", &[("math", "^1.0"), ("external", "^2.0")]),
+            pack_plugin("math", &[]),
+        ];
+
+        let order = pack_load_order(&plugins).unwrap();
+        let names: Vec<_> = order.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["math", "report"]);
+    }
+
+    #[test]
+    fn test_pack_load_order_errors() {
+        let cycle = vec![
+            pack_plugin("a", &[("b", "*")]),
+            pack_plugin("b", &[("a", "*")]),
+        ];
+        assert!(matches!(
+            pack_load_order(&cycle),
+            Err(ConfigError::InvalidPack(_))
+        ));
+
+        let mismatch = vec![pack_plugin("a", &[("b", "^2.0")]), pack_plugin("b", &[])];
+        assert!(matches!(
+            pack_load_order(&mismatch),
+            Err(ConfigError::InvalidPack(_))
+        ));
+    }
+
+    #[test]
+    fn test_check_entry() {
+        assert!(check_entry("main.lua").is_ok());
+        assert!(check_entry("src/init.lua").is_ok());
+        for entry in [
+            "",
+            "/etc/init.lua",
+            "../main.lua",
+            "src/../../main.lua",
+            "src\\init.lua",
+        ] {
+            assert!(
+                matches!(check_entry(entry), Err(ConfigError::InvalidEntry(_))),
+                "{entry}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_check_version() {
+        let config: Config = toml::from_str(
+            "name = \"versioned\"\ndescription = \"\"\nauthor = \"\"\nversion = \"1.2.0\"",
+        )
+        .unwrap();
+        let bundle = |version: &str| Bundle {
+            id: "versioned".to_string(),
+            version: Version::parse(version).unwrap(),
+            format: "lua".to_string(),
+        };
+
+        assert!(config.check_version(&bundle("1.2.0")).is_ok());
+        assert!(matches!(
+            config.check_version(&bundle("1.3.0")),
+            Err(ConfigError::VersionMismatch { .. })
+        ));
+        assert!(
+            toml::from_str::<Config>(
+                "name = \"\"\ndescription = \"\"\nauthor = \"\"\nversion = \"1.x\""
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_target() {
+        let config: Config = toml::from_str(
+            "name = \"native\"\ndescription = \"\"\nauthor = \"\"\n\n[target]\nos = [\"linux\", \"windows\"]\narch = [\"x86_64\"]",
+        )
+        .unwrap();
+        let target = config.target.unwrap();
+
+        assert!(target.supports("linux", "unix", "x86_64"));
+        assert!(target.supports("windows", "windows", "x86_64"));
+        assert!(!target.supports("macos", "unix", "x86_64"));
+        assert!(!target.supports("linux", "unix", "aarch64"));
+        assert!(Target::default().supports("macos", "unix", "aarch64"));
+
+        let unix = Target {
+            os: Some(vec!["unix".to_string()]),
+            arch: None,
+        };
+        assert!(unix.supports("freebsd", "unix", "riscv64"));
+    }
+}
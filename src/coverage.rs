@@ -0,0 +1,129 @@
+//! Lines of plugin sources run while coverage is enabled, see
+//! [`crate::LuaManager::with_coverage`].
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{Arc, Mutex},
+};
+
+use indexmap::IndexMap;
+use plux_rs::Bundle;
+use serde::Serialize;
+
+use crate::sync::MutexExt;
+
+/// The lines of a source file of a plugin that ran.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileCoverage {
+    /// The plugin, as its bundle `<id>-v<version>.<format>`.
+    pub plugin: String,
+    /// The path of the file, relative to the plugin root.
+    pub path: String,
+    /// The number of times each line ran, by line number.
+    pub lines: BTreeMap<usize, u64>,
+}
+
+/// Coverage of the plugins run since coverage was enabled or reset.
+///
+/// Serializable with serde, e.g. to JSON, or exported as LCOV with
+/// [`CoverageReport::to_lcov`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CoverageReport {
+    /// The files that ran, by plugin in load order, then by path.
+    pub files: Vec<FileCoverage>,
+}
+
+impl CoverageReport {
+    /// Returns the report in the LCOV tracefile format, one test per plugin.
+    ///
+    /// Only lines that ran are known, so every line listed has hits.
+    pub fn to_lcov(&self) -> String {
+        let mut lcov = String::new();
+        for file in &self.files {
+            let _ = writeln!(lcov, "TN:{}", file.plugin);
+            let _ = writeln!(lcov, "SF:{}", file.path);
+            for (line, hits) in &file.lines {
+                let _ = writeln!(lcov, "DA:{line},{hits}");
+            }
+            let _ = writeln!(lcov, "LH:{}", file.lines.len());
+            let _ = writeln!(lcov, "LF:{}", file.lines.len());
+            lcov.push_str("end_of_record\n");
+        }
+        lcov
+    }
+}
+
+/// Hits of each line, by plugin and source file
+type Hits = IndexMap<Bundle, BTreeMap<String, BTreeMap<usize, u64>>>;
+
+/// The lines run by the plugins of a manager.
+#[derive(Default)]
+pub(crate) struct Coverage {
+    hits: Mutex<Hits>,
+}
+
+impl Coverage {
+    /// Counts a run of `line` of the file `path` of `bundle`.
+    pub(crate) fn record(&self, bundle: &Bundle, path: &str, line: usize) {
+        let mut hits = self.hits.lock_unpoisoned();
+        let files = match hits.get_mut(bundle) {
+            Some(files) => files,
+            None => hits.entry(bundle.clone()).or_default(),
+        };
+        let lines = match files.get_mut(path) {
+            Some(lines) => lines,
+            None => files.entry(path.to_string()).or_default(),
+        };
+        *lines.entry(line).or_default() += 1;
+    }
+
+    /// Returns the lines run so far.
+    pub(crate) fn report(&self) -> CoverageReport {
+        let hits = self.hits.lock_unpoisoned();
+        let files = hits
+            .iter()
+            .flat_map(|(bundle, files)| {
+                files.iter().map(move |(path, lines)| FileCoverage {
+                    plugin: bundle.to_string(),
+                    path: path.clone(),
+                    lines: lines.clone(),
+                })
+            })
+            .collect();
+        CoverageReport { files }
+    }
+
+    /// Forgets the lines run so far.
+    pub(crate) fn reset(&self) {
+        self.hits.lock_unpoisoned().clear();
+    }
+}
+
+/// The coverage a Lua state records its lines in, with the plugin owning it
+#[derive(Clone)]
+pub(crate) struct CoverageRecorder {
+    pub(crate) bundle: Bundle,
+    pub(crate) coverage: Arc<Coverage>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lcov_lists_the_lines_run() {
+        let coverage = Coverage::default();
+        let bundle = Bundle::from_filename("cov-v1.0.0.lua").unwrap();
+        coverage.record(&bundle, "main.lua", 3);
+        coverage.record(&bundle, "main.lua", 3);
+        coverage.record(&bundle, "main.lua", 1);
+
+        assert_eq!(
+            coverage.report().to_lcov(),
+            "TN:cov-v1.0.0.lua\nSF:main.lua\nDA:1,1\nDA:3,2\nLH:2\nLF:2\nend_of_record\n"
+        );
+        coverage.reset();
+        assert_eq!(coverage.report(), CoverageReport::default());
+    }
+}
@@ -8,8 +8,10 @@
 //! - [`ConfigError`]: Errors related to plugin configuration
 //! - [`PluginError`]: Errors specific to plugin operations
 //! - [`ManagerError`]: Top-level error type that can represent any error in the manager
+//! - [`TestingError`]: Errors of the [`crate::testing`] helpers
 
 use mlua::Error as LuaError;
+use plux_rs::{Bundle, variable::Variable};
 use thiserror::Error;
 
 /// Errors that can occur when working with plugin configuration.
@@ -29,6 +31,42 @@ pub enum ConfigError {
     /// An I/O error occurred while reading the configuration file.
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
+
+    /// The config declares capabilities unknown to the manager.
+    #[error("Unknown capabilities: {}", .0.join(", "))]
+    UnknownCapabilities(Vec<String>),
+
+    /// The version declared in the config is not the one of the plugin
+    /// directory.
+    #[error("Version {declared} does not match the plugin version {bundle}")]
+    VersionMismatch {
+        /// The version declared in the config.
+        declared: semver::Version,
+        /// The version in the plugin directory name.
+        bundle: semver::Version,
+    },
+
+    /// An entry script is not a relative path inside the plugin root.
+    #[error("Invalid entry `{0}`: expected a relative path inside the plugin")]
+    InvalidEntry(String),
+
+    /// The `[[plugins]]` declarations of a plugin pack are inconsistent.
+    #[error("Invalid plugin pack: {0}")]
+    InvalidPack(String),
+
+    /// A `[user_settings]` declaration of the config has an unknown type or a
+    /// default of another type.
+    #[error("Invalid setting `{0}`: {1}")]
+    InvalidSetting(String, String),
+
+    /// The `[target]` of the config excludes the current platform.
+    #[error("The plugin does not run on {os} ({arch})")]
+    UnsupportedPlatform {
+        /// The current operating system.
+        os: String,
+        /// The current CPU architecture.
+        arch: String,
+    },
 }
 
 /// Errors that can occur during plugin operations.
@@ -45,11 +83,171 @@ pub enum PluginError {
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
 
+    /// The plugin is not loaded by this manager.
+    #[error("Plugin `{0}` is not loaded")]
+    NotLoaded(String),
+
+    /// The plugin is not registered by this manager.
+    #[error("Plugin `{0}` is not registered")]
+    NotRegistered(String),
+
+    /// The plugin is paused.
+    #[error("Plugin `{0}` is paused")]
+    Paused(String),
+
+    /// The plugin was quarantined after repeated call failures.
+    #[error("Plugin `{0}` is quarantined")]
+    Quarantined(String),
+
+    /// The plugin failed to load with [`crate::LuaManager::with_fault_isolation`]
+    /// and has no state until it is reloaded.
+    #[error("Plugin `{0}` faulted while loading")]
+    Faulted(String),
+
+    /// The plugin's Lua state ran out of the memory allowed by
+    /// [`crate::LuaManager::with_memory_limit`].
+    #[error("Plugin `{0}` exceeded its memory limit")]
+    MemoryLimitExceeded(String),
+
+    /// A call into the plugin ran past the time allowed by
+    /// [`crate::LuaManager::with_call_timeout`] and was aborted.
+    #[error("Call to plugin `{0}` timed out after {1:?}")]
+    Timeout(String, std::time::Duration),
+
+    /// Lua code of the plugin failed while loading or running.
+    #[error("Plugin `{plugin}` failed: {source}")]
+    Runtime {
+        /// The plugin whose code failed.
+        plugin: String,
+        /// The Lua stack traceback at the point of failure, if captured.
+        traceback: Option<String>,
+        /// The Lua error, without its traceback.
+        source: LuaError,
+    },
+
+    /// A call to a plugin or host function failed.
+    #[error(transparent)]
+    Call(Box<CallError>),
+
+    /// A lazily loaded plugin was called back while its state was created,
+    /// see [`crate::LuaManager::with_lazy_loading`].
+    #[error("Plugin `{0}` was called while it was loading")]
+    StillLoading(String),
+
+    /// Calls into plugins nested deeper than [`crate::MAX_CALL_DEPTH`].
+    #[error("Call to plugin `{0}` exceeds the maximum call depth of {1}")]
+    CallDepthExceeded(String, usize),
+
+    /// The plugin does not export a function with the given name.
+    #[error("Function `{0}` not found")]
+    FunctionNotFound(String),
+
+    /// An argument or the result of a call does not have the type declared
+    /// by the function.
+    #[error("Function `{function}`: {value} is {found}, expected {expected}")]
+    TypeMismatch {
+        /// The name of the function.
+        function: String,
+        /// The mismatched value, e.g. ``argument `x` ``.
+        value: String,
+        /// The declared type.
+        expected: String,
+        /// The type of the value.
+        found: String,
+    },
+
+    /// The plugin does not declare a setting with the given name.
+    #[error("Setting `{0}` not found")]
+    SettingNotFound(String),
+
+    /// A value given to a setting does not have the type the setting declares.
+    #[error("Setting `{setting}`: value is {found}, expected {expected}")]
+    SettingTypeMismatch {
+        /// The name of the setting.
+        setting: String,
+        /// The declared type.
+        expected: String,
+        /// The type of the value.
+        found: String,
+    },
+
+    /// A call left out an argument the function does not declare optional.
+    #[error("Function `{0}`: missing argument `{1}`")]
+    MissingArgument(String, String),
+
+    /// A function's declared signature does not match the requested one.
+    #[error("Function `{0}` does not match the requested signature: {1}")]
+    SignatureMismatch(String, String),
+
+    /// Dependencies are only available in versions the plugin does not accept.
+    #[error("Dependency version mismatch: {}", .0.join("; "))]
+    DependencyMismatch(Vec<String>),
+
     /// An error occurred while registering plugin functions.
     #[error("Plugin register function error: {0}")]
     RegisterFunctionError(#[from] plux_rs::utils::PluginRegisterFunctionError),
 }
 
+/// A failed call to a function, with the plugin it belongs to and the
+/// arguments it was given.
+#[derive(Error, Debug)]
+#[error(
+    "Call to `{function}({args})`{} failed: {source}",
+    .plugin.as_ref().map(|plugin| format!(" of plugin {plugin}")).unwrap_or_default()
+)]
+pub struct CallError {
+    /// The plugin exporting the function, `None` for host functions.
+    pub plugin: Option<Bundle>,
+    /// The name of the function.
+    pub function: String,
+    /// A summary of the arguments of the call.
+    pub args: String,
+    /// The Lua stack traceback at the point of failure, if captured.
+    pub traceback: Option<String>,
+    /// The underlying error.
+    pub source: LuaError,
+}
+
+impl CallError {
+    /// Longest summary of a single argument.
+    const MAX_ARG_LEN: usize = 64;
+
+    pub(crate) fn new(
+        plugin: Option<Bundle>,
+        function: &str,
+        args: &[Variable],
+        traceback: Option<String>,
+        source: LuaError,
+    ) -> Self {
+        let args = args
+            .iter()
+            .map(|arg| {
+                let mut arg = format!("{arg:?}");
+                if let Some((end, _)) = arg.char_indices().nth(Self::MAX_ARG_LEN) {
+                    arg.truncate(end);
+                    arg.push_str("...");
+                }
+                arg
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        Self {
+            plugin,
+            function: function.to_string(),
+            args,
+            traceback,
+            source,
+        }
+    }
+}
+
+impl From<CallError> for PluginError {
+    fn from(e: CallError) -> Self {
+        PluginError::Call(Box::new(e))
+    }
+}
+
 /// The top-level error type for the Lua manager.
 ///
 /// This enum represents all possible errors that can occur when working
@@ -67,4 +265,99 @@ pub enum ManagerError {
     /// An error related to plugin operations.
     #[error("Plugin error: {0}")]
     Plugin(#[from] PluginError),
-}
\ No newline at end of file
+}
+
+impl ManagerError {
+    /// Attributes a Lua error raised in the state of `bundle` to the plugin.
+    ///
+    /// Allocation failures become [`PluginError::MemoryLimitExceeded`], other
+    /// Lua errors [`PluginError::Runtime`] with their traceback split out.
+    pub(crate) fn in_plugin(self, bundle: &Bundle) -> Self {
+        fn is_memory_error(e: &LuaError) -> bool {
+            match e {
+                LuaError::MemoryError(_) => true,
+                LuaError::CallbackError { cause, .. } | LuaError::WithContext { cause, .. } => {
+                    is_memory_error(cause)
+                }
+                _ => false,
+            }
+        }
+
+        match self {
+            ManagerError::Lua(e) if is_memory_error(&e) => {
+                PluginError::MemoryLimitExceeded(bundle.to_string()).into()
+            }
+            ManagerError::Lua(e) => {
+                let (source, traceback) = split_traceback(e);
+                PluginError::Runtime {
+                    plugin: bundle.to_string(),
+                    traceback,
+                    source,
+                }
+                .into()
+            }
+            _ => self,
+        }
+    }
+}
+
+impl ManagerError {
+    /// Turns a [`PluginError::Runtime`] raised by a call to `function` of
+    /// `bundle` with `args` into a [`PluginError::Call`].
+    pub(crate) fn in_call(self, bundle: &Bundle, function: &str, args: &[Variable]) -> Self {
+        match self {
+            ManagerError::Plugin(PluginError::Runtime {
+                traceback, source, ..
+            }) => PluginError::from(CallError::new(
+                Some(bundle.clone()),
+                function,
+                args,
+                traceback,
+                source,
+            ))
+            .into(),
+            _ => self,
+        }
+    }
+}
+
+/// Errors of the [`crate::testing`] helpers.
+#[derive(Error, Debug)]
+pub enum TestingError {
+    /// The plugin directory could not be written.
+    #[error("Cannot write the plugin: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// The plugin at the given path could not be registered or loaded.
+    #[error("Cannot load the plugin {0}: {1}")]
+    Load(String, String),
+
+    /// The plugin is not loaded.
+    #[error("Plugin {0} is not loaded")]
+    NotLoaded(String),
+
+    /// A call to the given function failed.
+    #[error("Call to `{0}` failed: {1}")]
+    Call(String, String),
+}
+
+/// Separates a Lua error from the stack traceback captured with it.
+fn split_traceback(e: LuaError) -> (LuaError, Option<String>) {
+    const TRACEBACK: &str = "stack traceback:\n";
+
+    match e {
+        LuaError::RuntimeError(message) => match message.split_once(&format!("\n{TRACEBACK}")) {
+            Some((message, traceback)) => (
+                LuaError::RuntimeError(message.to_string()),
+                Some(traceback.to_string()),
+            ),
+            None => (LuaError::RuntimeError(message), None),
+        },
+        LuaError::CallbackError { traceback, cause } => {
+            let (cause, inner) = split_traceback(cause.as_ref().clone());
+            let traceback = traceback.strip_prefix(TRACEBACK).unwrap_or(&traceback);
+            (cause, inner.or_else(|| Some(traceback.to_string())))
+        }
+        e => (e, None),
+    }
+}
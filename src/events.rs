@@ -0,0 +1,66 @@
+//! Host-side routing of the events plugins exchange through `api.events`.
+//!
+//! Every loaded plugin has a mailbox. An emitted event is copied into the
+//! mailbox of every plugin, the emitter included, and delivered to the
+//! plugin's handlers by [`LuaManager::tick`]. Payloads are stored as
+//! [`Variable`]s, so plugins never alias each other's Lua values.
+//!
+//! [`LuaManager::tick`]: crate::LuaManager::tick
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use indexmap::IndexMap;
+use plux_rs::{Bundle, variable::Variable};
+
+use crate::sync::MutexExt;
+
+/// An event waiting to be delivered.
+#[derive(Debug, Clone)]
+pub(crate) struct Event {
+    pub name: String,
+    pub payload: Variable,
+    /// Id of the emitting plugin
+    pub source: String,
+}
+
+/// The mailboxes of the loaded plugins, in load order.
+#[derive(Clone, Default)]
+pub(crate) struct EventBus(Arc<Mutex<IndexMap<Bundle, VecDeque<Event>>>>);
+
+impl EventBus {
+    /// Creates the mailbox of a plugin.
+    pub fn open(&self, bundle: &Bundle) {
+        self.0.lock_unpoisoned().entry(bundle.clone()).or_default();
+    }
+
+    /// Drops the mailbox of a plugin with the events it did not receive.
+    pub fn close(&self, bundle: &Bundle) {
+        self.0.lock_unpoisoned().shift_remove(bundle);
+    }
+
+    /// Copies `event` into every mailbox.
+    pub fn emit(&self, event: Event) {
+        for mailbox in self.0.lock_unpoisoned().values_mut() {
+            mailbox.push_back(event.clone());
+        }
+    }
+
+    /// Returns the number of events waiting in the mailbox of a plugin.
+    pub fn pending(&self, bundle: &Bundle) -> usize {
+        self.0
+            .lock_unpoisoned()
+            .get(bundle)
+            .map_or(0, VecDeque::len)
+    }
+
+    /// Takes the oldest event waiting in the mailbox of a plugin.
+    pub fn next(&self, bundle: &Bundle) -> Option<Event> {
+        self.0
+            .lock_unpoisoned()
+            .get_mut(bundle)
+            .and_then(VecDeque::pop_front)
+    }
+}
@@ -0,0 +1,54 @@
+//! Compilation of Fennel sources to Lua, see
+//! [`crate::LuaManager::with_fennel_compiler`].
+
+use std::{collections::HashMap, sync::Mutex};
+
+use mlua::{Function, Lua};
+
+use crate::{bytecode::hash, error::PluginError, source::SourceTransform, sync::MutexExt};
+
+/// Extension of Fennel sources.
+pub(crate) const FENNEL_EXTENSION: &str = "fnl";
+
+/// The Fennel compiler, running in a Lua state of its own, and the Lua code
+/// it generated, by source.
+pub(crate) struct FennelCompiler {
+    lua: Mutex<(Lua, Function)>,
+    compiled: Mutex<HashMap<u64, String>>,
+}
+
+impl FennelCompiler {
+    /// Loads the compiler from the source of the `fennel` module.
+    pub(crate) fn new(source: &[u8]) -> mlua::Result<Self> {
+        let lua = Lua::new();
+        let fennel: mlua::Table = lua.load(source).set_name("=fennel").eval()?;
+        let compile = fennel.get("compileString")?;
+        Ok(Self {
+            lua: Mutex::new((lua, compile)),
+            compiled: Mutex::new(HashMap::new()),
+        })
+    }
+}
+
+impl SourceTransform for FennelCompiler {
+    /// Compiles the Fennel source `src` of the file `path` to Lua, or returns
+    /// the code generated for the same source before.
+    ///
+    /// Parse and compile errors fail with a [`PluginError::SourceError`].
+    fn transform(&self, path: &str, src: &str) -> Result<String, PluginError> {
+        let key = hash(path, src);
+        if let Some(code) = self.compiled.lock_unpoisoned().get(&key) {
+            return Ok(code.clone());
+        }
+
+        let code = {
+            let lua = self.lua.lock_unpoisoned();
+            let (lua, compile) = &*lua;
+            lua.create_table_from([("filename", path)])
+                .and_then(|options| compile.call::<String>((src, options)))
+                .map_err(|e| PluginError::SourceError(format!("cannot compile {path}: {e}")))?
+        };
+        self.compiled.lock_unpoisoned().insert(key, code.clone());
+        Ok(code)
+    }
+}
@@ -0,0 +1,247 @@
+//! Dependency graph of the plugins registered through a manager.
+
+use std::fmt::Write;
+
+use plux_rs::{Bundle, Depend, StdInfo};
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+
+/// Dependency graph as seen by a manager, see [`LuaManager::dependency_graph`].
+///
+/// [`LuaManager::dependency_graph`]: crate::LuaManager::dependency_graph
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DependencyGraph {
+    /// The registered plugins, in registration order.
+    pub nodes: Vec<GraphNode>,
+    /// The declared dependencies of the registered plugins.
+    pub edges: Vec<GraphEdge>,
+}
+
+/// A plugin registered through the manager.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GraphNode {
+    /// The plugin's bundle.
+    pub bundle: Bundle,
+    /// Whether the plugin is currently loaded.
+    pub loaded: bool,
+}
+
+/// A dependency declared by a registered plugin.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GraphEdge {
+    /// The plugin declaring the dependency.
+    pub from: Bundle,
+    /// The id of the dependency.
+    pub to: String,
+    /// The accepted versions of the dependency.
+    pub requirement: VersionReq,
+    /// Whether the dependency is optional.
+    pub optional: bool,
+    /// The version the dependency resolves to, the highest accepted registered version.
+    pub resolved: Option<Version>,
+    /// How the dependency resolves.
+    pub status: EdgeStatus,
+}
+
+/// Resolution status of a [`GraphEdge`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EdgeStatus {
+    /// Resolves to a loaded plugin.
+    Loaded,
+    /// Resolves to a registered plugin that is not loaded.
+    Registered,
+    /// Plugins with this id are registered, but in no accepted version.
+    Mismatch,
+    /// No plugin with this id is registered.
+    Missing,
+}
+
+impl DependencyGraph {
+    /// Builds the graph of the `registered` plugins, of which `loaded` are loaded.
+    pub(crate) fn new<'a>(
+        registered: impl IntoIterator<Item = (&'a Bundle, &'a StdInfo)>,
+        loaded: &[Bundle],
+    ) -> Self {
+        let registered: Vec<_> = registered.into_iter().collect();
+
+        let nodes = registered
+            .iter()
+            .map(|(bundle, _)| GraphNode {
+                bundle: (*bundle).clone(),
+                loaded: loaded.contains(bundle),
+            })
+            .collect();
+
+        let edge = |from: &Bundle, depend: &Depend, optional: bool| {
+            let candidates: Vec<_> = registered
+                .iter()
+                .map(|(bundle, _)| *bundle)
+                .filter(|bundle| bundle.id == depend.id)
+                .collect();
+            let resolved = candidates
+                .iter()
+                .filter(|bundle| depend.version.matches(&bundle.version))
+                .max_by(|a, b| a.version.cmp(&b.version));
+
+            let status = match resolved {
+                Some(bundle) if loaded.contains(bundle) => EdgeStatus::Loaded,
+                Some(_) => EdgeStatus::Registered,
+                None if candidates.is_empty() => EdgeStatus::Missing,
+                None => EdgeStatus::Mismatch,
+            };
+
+            GraphEdge {
+                from: from.clone(),
+                to: depend.id.clone(),
+                requirement: depend.version.clone(),
+                optional,
+                resolved: resolved.map(|bundle| bundle.version.clone()),
+                status,
+            }
+        };
+
+        let edges = registered
+            .iter()
+            .flat_map(|(bundle, info)| {
+                let depends = info.depends.iter().map(|d| edge(bundle, d, false));
+                let optional = info.optional_depends.iter().map(|d| edge(bundle, d, true));
+                depends.chain(optional).collect::<Vec<_>>()
+            })
+            .collect();
+
+        Self { nodes, edges }
+    }
+
+    /// Renders the graph in the Graphviz DOT language.
+    ///
+    /// Loaded plugins are filled, optional dependencies are dashed and
+    /// unresolved dependencies point to dotted placeholder nodes.
+    pub fn to_dot(&self) -> String {
+        let node_name = |bundle: &Bundle| format!("{} v{}", bundle.id, bundle.version);
+
+        let mut dot = String::from("digraph dependencies {\n");
+        for node in self.nodes.iter() {
+            let style = match node.loaded {
+                true => ", style=filled",
+                false => "",
+            };
+            let name = node_name(&node.bundle);
+            writeln!(dot, "    \"{name}\" [label=\"{name}\"{style}];").unwrap();
+        }
+
+        for edge in self.edges.iter() {
+            let target = match (&edge.resolved, edge.status) {
+                (Some(version), _) => format!("{} v{}", edge.to, version),
+                (None, status) => {
+                    let target = format!("{} ({})", edge.to, status.as_str());
+                    writeln!(dot, "    \"{target}\" [label=\"{target}\", style=dotted];").unwrap();
+                    target
+                }
+            };
+            let style = match edge.optional {
+                true => ", style=dashed",
+                false => "",
+            };
+            writeln!(
+                dot,
+                "    \"{}\" -> \"{}\" [label=\"{}\"{}];",
+                node_name(&edge.from),
+                target,
+                edge.requirement,
+                style
+            )
+            .unwrap();
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Renders the graph as JSON.
+    ///
+    /// Nodes are objects with the `id`, `version`, `format` and `loaded`
+    /// fields of [`GraphNode`], edges objects with the fields of
+    /// [`GraphEdge`], `from` being the `id` and `version` of the declaring
+    /// plugin, versions and requirements strings and `resolved` `null` when
+    /// the dependency does not resolve:
+    ///
+    /// ```json
+    /// {"nodes":[{"id":"a","version":"1.0.0","format":"lua","loaded":true}],
+    ///  "edges":[{"from":{"id":"a","version":"1.0.0"},"to":"b","requirement":"^1.0",
+    ///            "optional":false,"resolved":null,"status":"missing"}]}
+    /// ```
+    pub fn to_json(&self) -> String {
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|node| {
+                format!(
+                    r#"{{"id":{},"version":"{}","format":{},"loaded":{}}}"#,
+                    json_string(&node.bundle.id),
+                    node.bundle.version,
+                    json_string(&node.bundle.format),
+                    node.loaded
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let edges = self
+            .edges
+            .iter()
+            .map(|edge| {
+                let resolved = match &edge.resolved {
+                    Some(version) => format!("\"{version}\""),
+                    None => "null".to_string(),
+                };
+                format!(
+                    r#"{{"from":{{"id":{},"version":"{}"}},"to":{},"requirement":"{}","optional":{},"resolved":{},"status":"{}"}}"#,
+                    json_string(&edge.from.id),
+                    edge.from.version,
+                    json_string(&edge.to),
+                    edge.requirement,
+                    edge.optional,
+                    resolved,
+                    edge.status.as_str()
+                )
+            })
+            .collect::<Vec<_>>();
+
+        format!(
+            r#"{{"nodes":[{}],"edges":[{}]}}"#,
+            nodes.join(","),
+            edges.join(",")
+        )
+    }
+}
+
+/// Quotes `s` as a JSON string.
+fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => write!(quoted, "\\u{:04x}", c as u32).unwrap(),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+impl EdgeStatus {
+    /// Returns the status name used in serialized graphs.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EdgeStatus::Loaded => "loaded",
+            EdgeStatus::Registered => "registered",
+            EdgeStatus::Mismatch => "mismatch",
+            EdgeStatus::Missing => "missing",
+        }
+    }
+}
@@ -0,0 +1,635 @@
+//! Gating of calls into plugins that are paused, or quarantined because
+//! their calls keep failing, time limit of the calls and history of the
+//! failures of each plugin.
+
+use std::{
+    cell::Cell,
+    collections::{HashMap, VecDeque},
+    fmt::Display,
+    sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant, SystemTime},
+};
+
+use log::LevelFilter;
+use plux_rs::{Bundle, variable::Variable};
+
+#[cfg(feature = "debug")]
+use crate::lua::hooks::Debugger;
+use crate::{
+    error::{ManagerError, PluginError},
+    lua::watchdog::Watchdog,
+    metrics::CallMetrics,
+    middleware::{CallFn, CallInfo, CallTarget, MiddlewareChain, run_chain},
+    rate_limit::{RateLimit, RateLimiter},
+    sync::MutexExt,
+};
+
+/// How consecutive call failures are counted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FailureScope {
+    /// One counter for all the functions of a plugin.
+    #[default]
+    Plugin,
+    /// One counter per function.
+    Function,
+}
+
+/// When a plugin gets quarantined, see [`crate::LuaManager::with_quarantine`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuarantinePolicy {
+    /// Number of consecutive failures after which the plugin is quarantined.
+    pub threshold: usize,
+    /// Whether failures are counted per plugin or per function.
+    pub scope: FailureScope,
+}
+
+impl QuarantinePolicy {
+    /// Quarantines a plugin after `threshold` consecutive failures of any of
+    /// its functions.
+    pub fn new(threshold: usize) -> Self {
+        Self {
+            threshold,
+            scope: FailureScope::Plugin,
+        }
+    }
+
+    /// Counts failures in `scope`.
+    pub fn with_scope(mut self, scope: FailureScope) -> Self {
+        self.scope = scope;
+        self
+    }
+}
+
+/// When faulted plugins are restarted, see
+/// [`crate::LuaManager::with_restart_policy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RestartPolicy {
+    /// Number of automatic restarts of a plugin while it is loaded.
+    pub max_restarts: usize,
+    /// Delay before the first restart, doubled after every restart.
+    pub backoff: Duration,
+    /// Longest delay between two restarts.
+    pub max_backoff: Duration,
+}
+
+impl RestartPolicy {
+    /// Restarts a faulted plugin up to `max_restarts` times, `backoff` after
+    /// it faulted and twice as long after every restart, up to a minute.
+    pub fn new(max_restarts: usize, backoff: Duration) -> Self {
+        Self {
+            max_restarts,
+            backoff,
+            max_backoff: Duration::from_secs(60).max(backoff),
+        }
+    }
+
+    /// Caps the delay between two restarts to `max_backoff`.
+    pub fn with_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Returns the delay before the restart following `restarts` restarts.
+    fn delay(&self, restarts: usize) -> Duration {
+        let factor = 1u32.checked_shl(restarts as u32).unwrap_or(u32::MAX);
+        self.backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+/// An automatic restart of a faulted plugin, see
+/// [`crate::LuaManager::with_restart_listener`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RestartAttempt {
+    /// Number of the restart, from 1.
+    pub attempt: usize,
+    /// Whether this was the last restart allowed by the policy.
+    pub last: bool,
+    /// The error the restart failed with, `None` if the plugin is back.
+    pub error: Option<String>,
+}
+
+/// Called with the bundle of a faulted plugin after every restart attempt.
+pub type RestartListener = Arc<dyn Fn(&Bundle, &RestartAttempt) + Send + Sync>;
+
+/// Restarts of a faulted plugin, see [`RestartPolicy`].
+#[derive(Debug, Default)]
+pub(crate) struct Restarts {
+    /// Number of restarts so far
+    count: usize,
+    /// When the next restart is due, once the plugin is seen faulted
+    due: Option<Instant>,
+}
+
+impl Restarts {
+    /// Returns the number of the restart to attempt now, scheduling it if
+    /// the plugin was not known to be faulted.
+    pub(crate) fn next(&mut self, policy: &RestartPolicy, now: Instant) -> Option<usize> {
+        if self.count >= policy.max_restarts {
+            return None;
+        }
+        let due = *self
+            .due
+            .get_or_insert_with(|| now + policy.delay(self.count));
+        if now < due {
+            return None;
+        }
+        self.count += 1;
+        self.due = None;
+        Some(self.count)
+    }
+
+    pub(crate) fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// How deep calls into plugins may nest on a thread, such as plugins calling
+/// each other recursively, before failing with
+/// [`PluginError::CallDepthExceeded`].
+///
+/// Every plugin runs in its own Lua state, so Lua's own limit on nested calls
+/// does not apply across plugins. The limit keeps the native stack used by
+/// nested calls within the 2 MiB of a default spawned thread.
+pub const MAX_CALL_DEPTH: usize = 32;
+
+thread_local! {
+    /// Number of calls into plugins running on this thread
+    static CALL_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// A call counted in [`CALL_DEPTH`] until dropped.
+struct NestedCall;
+
+impl NestedCall {
+    /// Counts a call, unless the calls on this thread are already nested
+    /// [`MAX_CALL_DEPTH`] deep.
+    fn enter() -> Option<Self> {
+        CALL_DEPTH.with(|depth| {
+            if depth.get() >= MAX_CALL_DEPTH {
+                return None;
+            }
+            depth.set(depth.get() + 1);
+            Some(NestedCall)
+        })
+    }
+}
+
+impl Drop for NestedCall {
+    fn drop(&mut self) {
+        CALL_DEPTH.with(|depth| depth.set(depth.get() - 1));
+    }
+}
+
+/// Called with the bundle of a plugin when it gets quarantined.
+pub type QuarantineListener = Arc<dyn Fn(&Bundle) + Send + Sync>;
+
+/// Number of faults kept per plugin, see [`crate::LuaManager::faults`].
+pub const FAULT_HISTORY: usize = 16;
+
+/// A failure of a plugin, see [`crate::LuaManager::faults`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fault {
+    /// The function whose call failed, `None` if the plugin failed to load.
+    pub function: Option<String>,
+    /// The error the plugin failed with.
+    pub error: String,
+    /// When the plugin failed.
+    pub timestamp: SystemTime,
+}
+
+/// The last [`FAULT_HISTORY`] faults of a plugin, kept while it is
+/// registered.
+#[derive(Debug, Default)]
+pub(crate) struct FaultLog(Mutex<VecDeque<Fault>>);
+
+impl FaultLog {
+    /// Records a failure of `function`, or of the load if `None`, dropping
+    /// the oldest fault once the history is full.
+    pub(crate) fn record(&self, function: Option<&str>, error: &dyn Display) {
+        let mut faults = self.0.lock_unpoisoned();
+        if faults.len() == FAULT_HISTORY {
+            faults.pop_front();
+        }
+        faults.push_back(Fault {
+            function: function.map(str::to_string),
+            error: error.to_string(),
+            timestamp: SystemTime::now(),
+        });
+    }
+
+    /// Returns the faults kept, oldest first.
+    pub(crate) fn faults(&self) -> Vec<Fault> {
+        self.0.lock_unpoisoned().iter().cloned().collect()
+    }
+
+    pub(crate) fn clear(&self) {
+        self.0.lock_unpoisoned().clear();
+    }
+}
+
+/// Pause flag, failure counters and quarantine flag of a plugin.
+#[derive(Debug, Default)]
+pub(crate) struct Health {
+    paused: bool,
+    quarantined: bool,
+    failures: HashMap<String, usize>,
+}
+
+impl Health {
+    pub(crate) fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub(crate) fn is_quarantined(&self) -> bool {
+        self.quarantined
+    }
+
+    /// Lifts the quarantine and resets the counters.
+    pub(crate) fn clear(&mut self) {
+        self.quarantined = false;
+        self.failures.clear();
+    }
+
+    /// Records the outcome of a call to `function`.
+    ///
+    /// Returns `true` if the failure put the plugin in quarantine.
+    fn record(&mut self, policy: &QuarantinePolicy, function: &str, ok: bool) -> bool {
+        let key = match policy.scope {
+            FailureScope::Plugin => "",
+            FailureScope::Function => function,
+        };
+
+        if ok {
+            self.failures.remove(key);
+            return false;
+        }
+
+        let failures = self.failures.entry(key.to_string()).or_default();
+        *failures += 1;
+        if *failures >= policy.threshold && !self.quarantined {
+            self.quarantined = true;
+            return true;
+        }
+        false
+    }
+}
+
+/// The health of a plugin, shared by the calls into it.
+#[derive(Default)]
+pub(crate) struct PluginHealth {
+    health: Mutex<Health>,
+    resumed: Condvar,
+    /// Deadline of the running call, installed on every state of the plugin
+    pub(crate) watchdog: Watchdog,
+    /// Statistics of the calls that ran
+    pub(crate) metrics: CallMetrics,
+    /// Limits the calls the plugin makes to the host
+    pub(crate) rate_limiter: Option<Arc<RateLimiter>>,
+    /// Failures of the plugin, shared with its registration
+    pub(crate) faults: Arc<FaultLog>,
+    /// Automatic restarts of the plugin while faulted
+    pub(crate) restarts: Mutex<Restarts>,
+    /// Debug hook installed on every state of the plugin
+    #[cfg(feature = "debug")]
+    pub(crate) debugger: Mutex<Option<Debugger>>,
+}
+
+impl PluginHealth {
+    /// Returns the health of a new plugin, whose calls to the host are
+    /// limited to `rate_limit` and whose failures are recorded in `faults`.
+    pub(crate) fn new(rate_limit: Option<RateLimit>, faults: Arc<FaultLog>) -> Self {
+        Self {
+            rate_limiter: rate_limit.map(|limit| Arc::new(RateLimiter::new(limit))),
+            faults,
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn lock(&self) -> MutexGuard<'_, Health> {
+        self.health.lock_unpoisoned()
+    }
+
+    pub(crate) fn pause(&self) {
+        self.lock().paused = true;
+    }
+
+    /// Resumes the plugin and wakes the calls waiting for it.
+    pub(crate) fn resume(&self) {
+        self.lock().paused = false;
+        self.resumed.notify_all();
+    }
+}
+
+/// Everything a call needs to honor the pause, quarantine and time limit of
+/// its plugin, and the middleware of the manager.
+#[derive(Clone)]
+pub(crate) struct CallGate {
+    pub(crate) policy: Option<QuarantinePolicy>,
+    pub(crate) listener: Option<QuarantineListener>,
+    pub(crate) pause_timeout: Option<Duration>,
+    pub(crate) call_timeout: Option<Duration>,
+    pub(crate) log_level: LevelFilter,
+    pub(crate) middleware: MiddlewareChain,
+    pub(crate) health: Arc<PluginHealth>,
+}
+
+impl CallGate {
+    /// Runs the call `f` to `function` of the plugin `bundle` with `args`,
+    /// through the middleware of the manager.
+    ///
+    /// While the plugin is paused, waits up to the pause timeout for it to
+    /// resume and fails with [`PluginError::Paused`] if it does not. Fails
+    /// fast with [`PluginError::Quarantined`] while the plugin is quarantined,
+    /// and with [`PluginError::CallDepthExceeded`] past [`MAX_CALL_DEPTH`]
+    /// nested calls. Otherwise runs `f` within the call timeout and counts its outcome,
+    /// allocation failures becoming [`PluginError::MemoryLimitExceeded`],
+    /// calls aborted by the watchdog [`PluginError::Timeout`] and other Lua
+    /// errors [`PluginError::Call`] with `args`. Calls that ran are recorded
+    /// in the plugin's metrics.
+    pub(crate) fn run(
+        &self,
+        bundle: &Bundle,
+        function: &str,
+        args: &[Variable],
+        f: impl Fn(&[Variable]) -> Result<Option<Variable>, ManagerError>,
+    ) -> Result<Option<Variable>, ManagerError> {
+        #[cfg(feature = "tracing")]
+        let _span = self.span(bundle, function).entered();
+
+        let call = CallInfo {
+            bundle,
+            function,
+            target: CallTarget::Plugin,
+        };
+        run_chain(&self.middleware, &call, args, &|args| {
+            self.run_gated(bundle, function, args, &f)
+        })
+    }
+
+    /// Runs the call `f` past the middleware, see [`run`](Self::run).
+    fn run_gated(
+        &self,
+        bundle: &Bundle,
+        function: &str,
+        args: &[Variable],
+        f: &CallFn<'_>,
+    ) -> Result<Option<Variable>, ManagerError> {
+        self.admit(bundle, self.pause_timeout)?;
+        let Some(_nested) = NestedCall::enter() else {
+            return Err(PluginError::CallDepthExceeded(bundle.to_string(), MAX_CALL_DEPTH).into());
+        };
+
+        let armed = self
+            .call_timeout
+            .and_then(|timeout| self.health.watchdog.arm(timeout));
+        let started = Instant::now();
+        let result = f(args).map_err(|e| e.in_plugin(bundle).in_call(bundle, function, args));
+        let elapsed = started.elapsed();
+        let result = match armed {
+            Some(armed) if armed.expired() => Err(PluginError::Timeout(
+                bundle.to_string(),
+                self.call_timeout.unwrap_or_default(),
+            )
+            .into()),
+            _ => result,
+        };
+        self.health
+            .metrics
+            .record(function, elapsed, result.is_ok());
+        self.record(bundle, function, result.as_ref().err());
+        result
+    }
+
+    /// Runs the call `f` to `function` of the plugin `bundle` once for every
+    /// set of arguments in `batches`, each through the middleware of the
+    /// manager, see [`run`](Self::run).
+    ///
+    /// The batch enters the gate once: it is admitted, counted as one nested
+    /// call and traced as a whole, the watchdog is armed once and renewed for
+    /// each item, and the metrics and failures of its items are recorded once
+    /// it ends. With `fail_fast`, the results end at the first failing item.
+    pub(crate) fn run_batch(
+        &self,
+        bundle: &Bundle,
+        function: &str,
+        batches: &[Vec<Variable>],
+        fail_fast: bool,
+        f: impl Fn(&[Variable]) -> Result<Option<Variable>, ManagerError>,
+    ) -> Result<Vec<Result<Option<Variable>, ManagerError>>, ManagerError> {
+        #[cfg(feature = "tracing")]
+        let _span = self.span(bundle, function).entered();
+
+        self.admit(bundle, self.pause_timeout)?;
+        let Some(_nested) = NestedCall::enter() else {
+            return Err(PluginError::CallDepthExceeded(bundle.to_string(), MAX_CALL_DEPTH).into());
+        };
+
+        let call = CallInfo {
+            bundle,
+            function,
+            target: CallTarget::Plugin,
+        };
+        let armed = self
+            .call_timeout
+            .and_then(|timeout| self.health.watchdog.arm(timeout));
+        let mut results = Vec::with_capacity(batches.len());
+        let mut calls = Vec::with_capacity(batches.len());
+        for args in batches {
+            let elapsed = Cell::new(None);
+            let result = run_chain(&self.middleware, &call, args, &|args| {
+                let started = Instant::now();
+                let result =
+                    f(args).map_err(|e| e.in_plugin(bundle).in_call(bundle, function, args));
+                elapsed.set(Some(started.elapsed()));
+                result
+            });
+            let result = match &armed {
+                Some(armed) if armed.renew() => Err(PluginError::Timeout(
+                    bundle.to_string(),
+                    self.call_timeout.unwrap_or_default(),
+                )
+                .into()),
+                _ => result,
+            };
+            // Calls skipped by the middleware did not run
+            if let Some(elapsed) = elapsed.get() {
+                calls.push((elapsed, result.is_ok()));
+            }
+
+            let failed = result.is_err();
+            results.push(result);
+            if failed && fail_fast {
+                break;
+            }
+        }
+        drop(armed);
+
+        self.health.metrics.record_all(function, &calls);
+        let errors: Vec<_> = results.iter().map(|result| result.as_ref().err()).collect();
+        self.record_all(bundle, function, &errors);
+        Ok(results)
+    }
+
+    /// Same as [`run`](Self::run) for the asynchronous call `f`.
+    ///
+    /// Paused plugins fail fast instead of blocking the executor, and the
+    /// call timeout does not apply, as the call may be suspended for any time.
+    /// The middleware of the manager does not run either.
+    #[cfg(feature = "async")]
+    pub(crate) async fn run_async<T>(
+        &self,
+        bundle: &Bundle,
+        function: &str,
+        args: &[Variable],
+        f: impl Future<Output = Result<T, ManagerError>>,
+    ) -> Result<T, ManagerError> {
+        let call = async {
+            self.admit(bundle, None)?;
+
+            let started = Instant::now();
+            let result = f
+                .await
+                .map_err(|e| e.in_plugin(bundle).in_call(bundle, function, args));
+            self.health
+                .metrics
+                .record(function, started.elapsed(), result.is_ok());
+            self.record(bundle, function, result.as_ref().err());
+            result
+        };
+        #[cfg(feature = "tracing")]
+        let call = tracing::Instrument::instrument(call, self.span(bundle, function));
+        call.await
+    }
+
+    /// Returns the `Debug` span of a call to `function`, disabled if the
+    /// manager's verbosity is lower.
+    #[cfg(feature = "tracing")]
+    fn span(&self, bundle: &Bundle, function: &str) -> tracing::Span {
+        if log::Level::Debug <= self.log_level {
+            tracing::debug_span!("call", bundle = %bundle, function)
+        } else {
+            tracing::Span::none()
+        }
+    }
+
+    /// Fails if the plugin is still paused after waiting up to `pause_timeout`
+    /// for it to resume, or if it is quarantined.
+    fn admit(&self, bundle: &Bundle, pause_timeout: Option<Duration>) -> Result<(), ManagerError> {
+        let mut health = self.health.lock();
+        if health.paused
+            && let Some(timeout) = pause_timeout
+        {
+            health = self
+                .health
+                .resumed
+                .wait_timeout_while(health, timeout, |health| health.paused)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+        }
+        if health.paused {
+            return Err(PluginError::Paused(bundle.to_string()).into());
+        }
+        if health.quarantined {
+            return Err(PluginError::Quarantined(bundle.to_string()).into());
+        }
+        Ok(())
+    }
+
+    /// Counts the outcome of a call, failed with `error` if any, recording
+    /// the failure and quarantining the plugin if needed.
+    fn record(&self, bundle: &Bundle, function: &str, error: Option<&ManagerError>) {
+        self.record_all(bundle, function, &[error]);
+    }
+
+    /// Counts the outcomes of the calls of a batch, in order, see
+    /// [`record`](Self::record).
+    fn record_all(&self, bundle: &Bundle, function: &str, errors: &[Option<&ManagerError>]) {
+        for error in errors.iter().flatten() {
+            self.health.faults.record(Some(function), *error);
+        }
+        let Some(policy) = &self.policy else {
+            return;
+        };
+
+        let mut health = self.health.lock();
+        let mut tripped = false;
+        for error in errors {
+            tripped |= health.record(policy, function, error.is_none());
+        }
+        drop(health);
+        if tripped {
+            if log::Level::Warn <= self.log_level {
+                log::warn!(
+                    "Plugin {} quarantined after {} consecutive failures",
+                    bundle,
+                    policy.threshold
+                );
+            }
+            if let Some(listener) = &self.listener {
+                listener(bundle);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_failures() {
+        let policy = QuarantinePolicy::new(2).with_scope(FailureScope::Function);
+        let mut health = Health::default();
+
+        assert!(!health.record(&policy, "a", false));
+        assert!(!health.record(&policy, "b", false));
+        assert!(!health.record(&policy, "a", true));
+        assert!(!health.record(&policy, "a", false));
+        assert!(health.record(&policy, "b", false));
+        assert!(health.is_quarantined());
+
+        health.clear();
+        assert!(!health.is_quarantined());
+        assert!(!health.record(&policy, "b", false));
+    }
+
+    #[test]
+    fn test_restart_backoff() {
+        let policy =
+            RestartPolicy::new(3, Duration::from_secs(1)).with_max_backoff(Duration::from_secs(3));
+        let mut restarts = Restarts::default();
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        assert_eq!(restarts.next(&policy, at(0)), None);
+        assert_eq!(restarts.next(&policy, at(1)), Some(1));
+        assert_eq!(restarts.next(&policy, at(2)), None);
+        assert_eq!(restarts.next(&policy, at(4)), Some(2));
+        // Capped to the maximum backoff
+        assert_eq!(restarts.next(&policy, at(4)), None);
+        assert_eq!(restarts.next(&policy, at(7)), Some(3));
+        assert_eq!(restarts.next(&policy, at(100)), None);
+
+        restarts.reset();
+        assert_eq!(restarts.next(&policy, at(100)), None);
+        assert_eq!(restarts.next(&policy, at(101)), Some(1));
+    }
+
+    #[test]
+    fn test_fault_history() {
+        let faults = FaultLog::default();
+        faults.record(None, &"syntax error");
+        for i in 0..FAULT_HISTORY {
+            faults.record(Some("work"), &i);
+        }
+
+        let kept = faults.faults();
+        assert_eq!(kept.len(), FAULT_HISTORY);
+        assert_eq!(kept[0].function.as_deref(), Some("work"));
+        assert_eq!(kept[0].error, "0");
+        assert_eq!(
+            kept[FAULT_HISTORY - 1].error,
+            (FAULT_HISTORY - 1).to_string()
+        );
+    }
+}
@@ -8,7 +8,7 @@
 //!
 //! ## Quick Start
 //!
-//! ```no_run
+//! ```ignore
 //! ...
 //!
 //! use plux_lua_manager::prelude::*;
@@ -24,14 +24,70 @@
 //!
 //! For more examples, see the [examples](https://github.com/BleynChannel/August/tree/master/managers/plux-lua-manager/examples) directory.
 
+#[cfg(feature = "archive")]
+mod archive;
+mod audit;
+mod bytecode;
 mod config;
+#[cfg(feature = "debug")]
+mod coverage;
 mod error;
+mod events;
+#[cfg(feature = "fennel")]
+mod fennel;
+mod graph;
+mod health;
 mod lua;
 mod manager;
+mod map;
+mod metrics;
+mod middleware;
+mod output;
+mod pack;
+mod rate_limit;
+#[cfg(feature = "repl")]
+mod repl;
+mod runtime;
+mod sandbox;
+mod script;
+mod settings;
+mod shared;
+mod source;
+mod sync;
+#[cfg(feature = "teal")]
+mod teal;
+pub mod testing;
+mod typed;
+#[cfg(feature = "watch")]
+mod watch;
 
+#[cfg(feature = "archive")]
+pub use archive::{ARCHIVE_EXTENSIONS, ArchiveSourceProvider};
+pub use audit::{AuditRecord, AuditSink};
 pub use config::*;
+#[cfg(feature = "debug")]
+pub use coverage::{CoverageReport, FileCoverage};
 pub use error::*;
+pub use graph::*;
+pub use health::{
+    FAULT_HISTORY, FailureScope, Fault, MAX_CALL_DEPTH, QuarantineListener, QuarantinePolicy,
+    RestartAttempt, RestartListener, RestartPolicy,
+};
+pub use lua::api::DependencyApi;
 pub use manager::*;
+pub use map::{MAP_TAG, map_entries, map_variable};
+pub use metrics::{FunctionMetrics, LATENCY_SAMPLES};
+pub use middleware::{CallInfo, CallTarget, Middleware, Next};
+pub use output::{OutputSink, OutputStream};
+pub use rate_limit::RateLimit;
+#[cfg(feature = "repl")]
+pub use repl::Repl;
+pub use runtime::*;
+pub use sandbox::{LuaLib, Permission, SandboxPolicy};
+pub use settings::{SETTINGS_FILE, SettingInfo};
+pub use shared::AccessPolicy;
+pub use source::*;
+pub use typed::{FunctionInfo, InputInfo, TypedArgs, TypedFn, TypedOutput, TypedValue};
 
 #[doc(hidden)]
 pub mod prelude {
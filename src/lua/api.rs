@@ -1,27 +1,112 @@
 //! API registration for Lua plugins
 
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
-use mlua::{Lua, MultiValue, Table, Value};
-use plux_rs::{function::FunctionOutput, Api, StdInfo};
-use semver::Version;
+use mlua::{Function, Lua, MultiValue, Table, Value};
+use plux_rs::{
+    Api, StdInfo, function::FunctionOutput, utils::CallFunctionDependError, variable::Variable,
+};
+use semver::{Version, VersionReq};
 
-use crate::error::ManagerError;
-use crate::lua::conversion::{lua_to_plux, plux_to_lua};
+use crate::config::Config;
+use crate::error::{ManagerError, PluginError};
+use crate::lua::conversion::{args_from_lua, conversion_options, plux_to_lua_with};
+use crate::lua::{errors, tasks, util};
+use crate::rate_limit::RateLimiter;
+
+/// The plugins a Lua plugin depends on, as seen by the functions of `api`
+/// calling them
+///
+/// Implemented by the plux [`Api`] of the plugin, and by
+/// [`MockApi`](crate::testing::MockApi) to test plugin scripts against canned
+/// dependencies.
+pub trait DependencyApi: Send + Sync {
+    /// Returns the loaded versions of the plugin `id`.
+    fn loaded_versions(&self, id: &str) -> Vec<Version>;
+
+    /// Returns `true` if the plugin `id` v`version` was resolved as a
+    /// dependency when the plugin was loaded.
+    fn is_resolved_depend(&self, id: &str, version: &Version) -> bool;
+
+    /// Calls the function `name` of the loaded plugin `id` v`version`.
+    fn call_function(
+        &self,
+        id: &str,
+        version: &Version,
+        name: &str,
+        args: &[Variable],
+    ) -> Result<FunctionOutput, CallFunctionDependError>;
+}
+
+impl DependencyApi for Api<FunctionOutput, StdInfo> {
+    fn loaded_versions(&self, id: &str) -> Vec<Version> {
+        self.get_plugins_by_id(id)
+            .into_iter()
+            .filter(|plugin| plugin.is_load())
+            .map(|plugin| plugin.info().bundle.version.clone())
+            .collect()
+    }
+
+    fn is_resolved_depend(&self, id: &str, version: &Version) -> bool {
+        self.depends()
+            .iter()
+            .chain(self.optional_depends())
+            .any(|depend| *depend == (id, version))
+    }
+
+    fn call_function(
+        &self,
+        id: &str,
+        version: &Version,
+        name: &str,
+        args: &[Variable],
+    ) -> Result<FunctionOutput, CallFunctionDependError> {
+        let plugin = self
+            .get_plugin(id, version)
+            .ok_or(CallFunctionDependError::DependNotFound)?;
+        Ok(plugin.call_function(name, args)?)
+    }
+}
 
 /// Registers the plugin API in the Lua environment
+///
+/// The version passed to `api.call_function_depend` and
+/// `api.call_function_optional_depend` is either an exact version, a semver
+/// requirement resolved to the highest loaded version matching it, or `nil`
+/// for the requirement declared in the plugin config.
+///
+/// Calls to `api.call_function_depend` beyond the rate of `limiter` fail
+/// with a `rate_limited` error.
 pub fn register_api(
     lua: &Lua,
-    api: &Arc<Api<FunctionOutput, StdInfo>>,
+    api: &Arc<dyn DependencyApi>,
+    config: &Config,
+    limiter: Option<Arc<RateLimiter>>,
 ) -> Result<(), ManagerError> {
     let globals = lua.globals();
 
     // Create the API table
     let api_table = lua.create_table()?;
 
+    // Requirements of the declared dependencies, for calls without a version
+    let declared: Arc<HashMap<String, VersionReq>> = Arc::new(
+        config
+            .depends
+            .iter()
+            .chain(config.optional_depends.iter())
+            .flatten()
+            .map(|(id, requirement)| (id.clone(), requirement.clone()))
+            .collect(),
+    );
+
     // Register the API functions
-    register_call_function_depend(lua, api.clone(), &api_table)?;
-    register_call_function_optional_depend(lua, api.clone(), &api_table)?;
+    register_call_function_depend(lua, api.clone(), declared.clone(), limiter, &api_table)?;
+    register_call_function_optional_depend(lua, api.clone(), declared.clone(), &api_table)?;
+    register_has_depend(lua, api.clone(), &api_table)?;
+    tasks::register_spawn(lua, &api_table)?;
+    util::register_util(lua, &api_table)?;
+
+    register_deps(lua, &declared, &api_table)?;
 
     // Set the table in the global namespace
     globals.set("api", api_table)?;
@@ -31,66 +116,85 @@ pub fn register_api(
 
 fn register_call_function_depend(
     lua: &Lua,
-    api: Arc<Api<FunctionOutput, StdInfo>>,
+    api: Arc<dyn DependencyApi>,
+    declared: Arc<HashMap<String, VersionReq>>,
+    limiter: Option<Arc<RateLimiter>>,
     api_table: &Table,
 ) -> Result<(), ManagerError> {
     let f = lua.create_function(
-        move |ctx, (id, version, name, args): (String, String, String, MultiValue)| {
-            let version =
-                Version::parse(&version).map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
+        move |ctx, (id, version, name, args): (String, Option<String>, String, MultiValue)| {
+            if let Some(limiter) = &limiter
+                && !limiter.acquire()
+            {
+                return errors::failure(ctx, errors::RATE_LIMITED, limiter.exceeded());
+            }
 
-            let args = args
-                .iter()
-                .map(lua_to_plux)
-                .collect::<Result<Vec<_>, _>>()?;
+            let wanted = VersionSpec::parse(&declared, &id, version.as_deref())?;
+            let missing = || {
+                errors::failure(
+                    ctx,
+                    errors::MISSING_DEPENDENCY,
+                    format!("dependency `{id}` {wanted} is not loaded"),
+                )
+            };
 
-            let output = api
-                .call_function_depend(&id, &version, &name, args.as_slice())
-                .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?
-                .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?
-                .map(|var| plux_to_lua(&var, ctx));
+            let Some(version) = wanted.resolve(api.as_ref(), &id) else {
+                return missing();
+            };
+
+            let options = conversion_options(ctx);
+            let args = args_from_lua(&args, &options, &name)?;
+
+            let output = match call_dependency(api.as_ref(), &declared, &id, &version, &name, &args)
+            {
+                Ok(Ok(output)) => output,
+                Err(CallFunctionDependError::DependNotFound) => return missing(),
+                Ok(Err(e)) if is_not_loaded_error(e.as_ref()) => return missing(),
+                Ok(Err(e)) if let Some(kind) = gate_error_kind(e.as_ref()) => {
+                    return errors::failure(ctx, kind, e.to_string());
+                }
+                Ok(Err(e)) => return Err(mlua::Error::RuntimeError(e.to_string())),
+                Err(e) => return Err(mlua::Error::RuntimeError(e.to_string())),
+            };
 
             match output {
-                Some(out) => Ok(out?),
-                None => Ok(Value::Nil),
+                Some(var) => errors::success(ctx, plux_to_lua_with(&var, ctx, options.strings)?),
+                None => errors::success(ctx, Value::Nil),
             }
         },
     )?;
-    api_table.set("call_function_depend", f)?;
+    api_table.set("call_function_depend", errors::structured(lua, f)?)?;
     Ok(())
 }
 
 fn register_call_function_optional_depend(
     lua: &Lua,
-    api: Arc<Api<FunctionOutput, StdInfo>>,
+    api: Arc<dyn DependencyApi>,
+    declared: Arc<HashMap<String, VersionReq>>,
     api_table: &Table,
 ) -> Result<(), ManagerError> {
     let f = lua.create_function(
-        move |ctx, (id, version, name, args): (String, String, String, MultiValue)| {
-            let version =
-                Version::parse(&version).map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
+        move |ctx, (id, version, name, args): (String, Option<String>, String, MultiValue)| {
+            let wanted = VersionSpec::parse(&declared, &id, version.as_deref())?;
+
+            // A dependency unloaded since the plugin was loaded is treated as absent
+            let Some(version) = wanted.resolve(api.as_ref(), &id) else {
+                return Ok((false, Value::Nil));
+            };
 
-            let args = args
-                .iter()
-                .map(lua_to_plux)
-                .collect::<Result<Vec<_>, _>>()?;
+            let options = conversion_options(ctx);
+            let args = args_from_lua(&args, &options, &name)?;
 
-            let output = api
-                .call_function_optional_depend(&id, &version, &name, args.as_slice())
-                .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
+            let output = match call_dependency(api.as_ref(), &declared, &id, &version, &name, &args)
+            {
+                Ok(output) => output.map_err(|e| mlua::Error::RuntimeError(e.to_string()))?,
+                Err(CallFunctionDependError::DependNotFound) => return Ok((false, Value::Nil)),
+                Err(e) => return Err(mlua::Error::RuntimeError(e.to_string())),
+            };
 
             match output {
-                Some(out) => Ok({
-                    let output = out
-                        .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?
-                        .map(|var| plux_to_lua(&var, ctx));
-
-                    match output {
-                        Some(out) => (true, out?),
-                        None => (true, Value::Nil),
-                    }
-                }),
-                None => Ok((false, Value::Nil)),
+                Some(var) => Ok((true, plux_to_lua_with(&var, ctx, options.strings)?)),
+                None => Ok((true, Value::Nil)),
             }
         },
     )?;
@@ -98,3 +202,247 @@ fn register_call_function_optional_depend(
     api_table.set("call_function_optional_depend", f)?;
     Ok(())
 }
+
+/// Registers the `deps` table, where `deps.<id>.<name>(...)` calls the
+/// function `name` of the declared dependency `id` like
+/// `api.call_function_depend(id, nil, name, ...)`
+fn register_deps(
+    lua: &Lua,
+    declared: &HashMap<String, VersionReq>,
+    api_table: &Table,
+) -> Result<(), ManagerError> {
+    let deps = lua.create_table()?;
+
+    for id in declared.keys() {
+        let proxy = lua.create_table()?;
+        let meta = lua.create_table()?;
+        let api_table = api_table.clone();
+        let dependency = id.clone();
+        meta.set(
+            "__index",
+            lua.create_function(move |lua, (proxy, name): (Table, String)| {
+                let api_table = api_table.clone();
+                let id = dependency.clone();
+                let function_name = name.clone();
+                // Looked up on every call, to go through the audit wrapper
+                let function = lua.create_function(move |_, args: MultiValue| {
+                    let Some(call) = api_table.get::<Option<Function>>("call_function_depend")?
+                    else {
+                        return Err(mlua::Error::RuntimeError(
+                            "api.call_function_depend is not exposed to this plugin".to_string(),
+                        ));
+                    };
+                    call.call::<MultiValue>((id.as_str(), Value::Nil, function_name.as_str(), args))
+                })?;
+                proxy.raw_set(name, function.clone())?;
+                Ok(function)
+            })?,
+        )?;
+        proxy.set_metatable(Some(meta))?;
+        deps.raw_set(id.as_str(), proxy)?;
+    }
+
+    lua.globals().set("deps", deps)?;
+    Ok(())
+}
+
+/// Registers `api.has_depend(id, version_req)`, telling whether a plugin `id`
+/// matching the requirement, any version if omitted, is loaded
+fn register_has_depend(
+    lua: &Lua,
+    api: Arc<dyn DependencyApi>,
+    api_table: &Table,
+) -> Result<(), ManagerError> {
+    let f = lua.create_function(move |_, (id, requirement): (String, Option<String>)| {
+        let requirement = match requirement {
+            Some(requirement) => VersionReq::parse(&requirement)
+                .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?,
+            None => VersionReq::STAR,
+        };
+        Ok(api
+            .loaded_versions(&id)
+            .iter()
+            .any(|version| requirement.matches(version)))
+    })?;
+
+    api_table.set("has_depend", f)?;
+    Ok(())
+}
+
+/// Calls the function `name` of the loaded dependency `id` v`version`
+///
+/// Unlike plux, which only knows the dependencies found when the plugin was
+/// loaded, this also accepts an optional dependency loaded later on, such as a
+/// plugin calling back into one that depends on it.
+fn call_dependency(
+    api: &dyn DependencyApi,
+    declared: &HashMap<String, VersionReq>,
+    id: &str,
+    version: &Version,
+    name: &str,
+    args: &[Variable],
+) -> Result<FunctionOutput, CallFunctionDependError> {
+    let is_depend = api.is_resolved_depend(id, version)
+        || declared
+            .get(id)
+            .is_some_and(|requirement| requirement.matches(version));
+    if !is_depend {
+        return Err(CallFunctionDependError::DependNotFound);
+    }
+
+    api.call_function(id, version, name, args)
+}
+
+/// Version of a dependency as passed to the API
+pub(crate) enum VersionSpec {
+    Exact(Version),
+    Requirement(VersionReq),
+}
+
+impl VersionSpec {
+    /// Parses `version`, an exact version or a requirement, falling back to
+    /// the requirement declared for `id` if it is `nil`
+    fn parse(
+        declared: &HashMap<String, VersionReq>,
+        id: &str,
+        version: Option<&str>,
+    ) -> mlua::Result<Self> {
+        let Some(version) = version else {
+            return declared
+                .get(id)
+                .map(|requirement| Self::Requirement(requirement.clone()))
+                .ok_or_else(|| {
+                    mlua::Error::RuntimeError(format!(
+                        "no version given for `{id}`, which is not a declared dependency"
+                    ))
+                });
+        };
+
+        Self::new(version)
+    }
+
+    /// Parses `version`, an exact version or a requirement
+    pub(crate) fn new(version: &str) -> mlua::Result<Self> {
+        match Version::parse(version) {
+            Ok(version) => Ok(Self::Exact(version)),
+            Err(_) => VersionReq::parse(version)
+                .map(Self::Requirement)
+                .map_err(|e| mlua::Error::RuntimeError(e.to_string())),
+        }
+    }
+
+    /// Returns `true` if `version` matches the spec
+    pub(crate) fn matches(&self, version: &Version) -> bool {
+        match self {
+            Self::Exact(exact) => exact == version,
+            Self::Requirement(requirement) => requirement.matches(version),
+        }
+    }
+
+    /// Returns the version of the loaded plugin `id` matching the spec, the
+    /// highest one for a requirement
+    fn resolve(&self, api: &dyn DependencyApi, id: &str) -> Option<Version> {
+        api.loaded_versions(id)
+            .into_iter()
+            .filter(|version| self.matches(version))
+            .max()
+    }
+}
+
+impl std::fmt::Display for VersionSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Exact(version) => write!(f, "v{version}"),
+            Self::Requirement(requirement) => write!(f, "{requirement}"),
+        }
+    }
+}
+
+/// Returns the error kind of a function that failed because its plugin is
+/// paused or quarantined
+fn gate_error_kind(
+    error: &(dyn std::error::Error + Send + Sync + 'static),
+) -> Option<&'static str> {
+    match error.downcast_ref::<ManagerError>() {
+        Some(ManagerError::Plugin(PluginError::Paused(_))) => Some(errors::PAUSED),
+        Some(ManagerError::Plugin(PluginError::Quarantined(_))) => Some(errors::QUARANTINED),
+        _ => None,
+    }
+}
+
+/// Returns `true` if a function failed because its plugin has been unloaded
+fn is_not_loaded_error(error: &(dyn std::error::Error + Send + Sync + 'static)) -> bool {
+    matches!(
+        error.downcast_ref::<ManagerError>(),
+        Some(ManagerError::Plugin(PluginError::NotLoaded(_)))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockApi;
+
+    const CONFIG: &str = r#"
+        name = "caller"
+        description = "Plugin calling canned dependencies"
+        author = "Plux"
+        depends = { math = "^1.0" }
+        optional_depends = { extra = "^2.0" }
+    "#;
+
+    #[test]
+    fn test_dependency_calls() {
+        let api = Arc::new(
+            MockApi::new()
+                .returns("math", "1.0.0", "version", Some(Variable::I64(1)))
+                .returns("math", "1.2.0", "version", Some(Variable::I64(2)))
+                .returns("math", "2.0.0", "version", Some(Variable::I64(3)))
+                .function("math", "1.2.0", "double", |args| match args {
+                    [Variable::I64(x)] => Ok(Some(Variable::I64(x * 2))),
+                    _ => Err("expected an integer".into()),
+                }),
+        );
+        let lua = Lua::new();
+        api.register(&lua, CONFIG).unwrap();
+
+        // Requirements resolve to the highest matching version
+        let version: i64 = lua.load("return deps.math.version()").eval().unwrap();
+        assert_eq!(version, 2);
+        let version: i64 = lua
+            .load(r#"return api.call_function_depend("math", "1.0.0", "version")"#)
+            .eval()
+            .unwrap();
+        assert_eq!(version, 1);
+        let doubled: i64 = lua.load("return deps.math.double(21)").eval().unwrap();
+        assert_eq!(doubled, 42);
+
+        let calls = api.calls();
+        assert_eq!(calls.len(), 3);
+        assert_eq!(calls[2].version, Version::new(1, 2, 0));
+        assert_eq!(calls[2].args, vec![Variable::I64(21)]);
+
+        let kind: String = lua
+            .load(
+                r#"
+                local ok, err = pcall(api.call_function_depend, "math", "^3", "version")
+                return err.kind
+                "#,
+            )
+            .eval()
+            .unwrap();
+        assert_eq!(kind, errors::MISSING_DEPENDENCY);
+        assert!(lua.load("deps.math.missing()").exec().is_err());
+
+        let (found, _): (bool, Value) = lua
+            .load(r#"return api.call_function_optional_depend("extra", nil, "f")"#)
+            .eval()
+            .unwrap();
+        assert!(!found);
+        let has: bool = lua
+            .load(r#"return api.has_depend("math", ">=2")"#)
+            .eval()
+            .unwrap();
+        assert!(has);
+    }
+}
@@ -0,0 +1,138 @@
+//! Recording of the calls plugins make to host functions and the plugin API
+//!
+//! Audited functions are wrapped in a Lua function timing the call, so that
+//! the errors they raise, structured ones included, reach the plugin as they
+//! were raised.
+
+use std::{
+    collections::HashSet,
+    sync::Arc,
+    time::{Instant, SystemTime},
+};
+
+use mlua::{AnyUserData, Function, Lua, MultiValue, Table, Value};
+use plux_rs::Bundle;
+
+use crate::audit::{AuditLog, AuditRecord};
+
+/// Longest summary of a single argument
+const MAX_ARG_LEN: usize = 64;
+
+/// A call in progress, handed from the start of the call to its end
+struct PendingCall {
+    args: String,
+    timestamp: SystemTime,
+    started: Instant,
+}
+
+/// Wraps `f`, called `name`, so that the calls the plugin `bundle` makes to
+/// it are recorded in `log`
+pub fn audited(
+    lua: &Lua,
+    bundle: &Bundle,
+    name: String,
+    f: Function,
+    log: &Arc<AuditLog>,
+) -> mlua::Result<Function> {
+    let started = lua.create_function(|lua, args: MultiValue| {
+        lua.create_any_userdata(PendingCall {
+            args: summarize(&args),
+            timestamp: SystemTime::now(),
+            started: Instant::now(),
+        })
+    })?;
+
+    let finished = {
+        let bundle = bundle.clone();
+        let log = log.clone();
+        lua.create_function(move |_, (call, ok): (AnyUserData, bool)| {
+            let call = call.take::<PendingCall>()?;
+            log.record(AuditRecord {
+                plugin: bundle.clone(),
+                function: name.clone(),
+                args: call.args,
+                timestamp: call.timestamp,
+                duration: call.started.elapsed(),
+                ok,
+            });
+            Ok(())
+        })?
+    };
+
+    lua.load(
+        r#"
+            local f, started, finished = ...
+            local pcall, error = pcall, error
+            local function done(call, ok, ...)
+                finished(call, ok)
+                if ok then
+                    return ...
+                end
+                error((...), 0)
+            end
+            return function(...)
+                return done(started(...), pcall(f, ...))
+            end
+        "#,
+    )
+    .set_name("=[plux audit]")
+    .call((f, started, finished))
+}
+
+/// Wraps the functions of `table`, and of the tables it holds, named after
+/// `prefix` and their key
+pub fn audit_table(
+    lua: &Lua,
+    bundle: &Bundle,
+    table: &Table,
+    prefix: &str,
+    log: &Arc<AuditLog>,
+) -> mlua::Result<()> {
+    audit_nested(lua, bundle, table, prefix, log, &mut HashSet::new())
+}
+
+fn audit_nested(
+    lua: &Lua,
+    bundle: &Bundle,
+    table: &Table,
+    prefix: &str,
+    log: &Arc<AuditLog>,
+    seen: &mut HashSet<*const std::ffi::c_void>,
+) -> mlua::Result<()> {
+    if !seen.insert(table.to_pointer()) {
+        return Ok(());
+    }
+
+    for pair in table.clone().pairs::<String, Value>() {
+        let (key, value) = pair?;
+        let name = format!("{prefix}.{key}");
+        match value {
+            Value::Function(f) => table.raw_set(key, audited(lua, bundle, name, f, log)?)?,
+            Value::Table(nested) => audit_nested(lua, bundle, &nested, &name, log, seen)?,
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Summarizes the arguments of a call
+fn summarize(args: &MultiValue) -> String {
+    args.iter()
+        .map(|arg| {
+            let mut arg = match arg {
+                Value::String(s) => format!("{:?}", s.to_string_lossy()),
+                Value::Nil | Value::Boolean(_) | Value::Integer(_) | Value::Number(_) => {
+                    arg.to_string().unwrap_or_default()
+                }
+                _ => arg.type_name().to_string(),
+            };
+            if let Some((end, _)) = arg.char_indices().nth(MAX_ARG_LEN) {
+                arg.truncate(end);
+                arg.push_str("...");
+            }
+            arg
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,103 @@
+//! Runtime enforcement of the capabilities declared in `config.toml`
+
+use std::collections::HashSet;
+
+use mlua::{Lua, Table, Value};
+use plux_rs::Bundle;
+
+use crate::manager::ApiFilter;
+
+/// Capabilities declared by the plugin owning a Lua state
+#[derive(Clone)]
+pub struct Capabilities(pub Vec<String>);
+
+/// Fails unless the plugin owning the Lua state declared `capability`
+pub fn require(lua: &Lua, capability: &str) -> mlua::Result<()> {
+    let declared = lua
+        .app_data_ref::<Capabilities>()
+        .is_some_and(|capabilities| capabilities.0.iter().any(|c| c == capability));
+
+    match declared {
+        true => Ok(()),
+        false => Err(mlua::Error::RuntimeError(format!(
+            "capability '{capability}' not declared"
+        ))),
+    }
+}
+
+/// Functions of the `api` and `host` tables exposed to the plugin owning a
+/// Lua state, named by their path such as `api.util.split` or `host.echo`
+#[derive(Clone)]
+pub struct ApiScope {
+    bundle: Bundle,
+    /// The paths declared in the plugin config, all if `None`
+    declared: Option<Vec<String>>,
+    filter: Option<ApiFilter>,
+}
+
+impl ApiScope {
+    pub fn new(bundle: Bundle, declared: Option<Vec<String>>, filter: Option<ApiFilter>) -> Self {
+        Self {
+            bundle,
+            declared,
+            filter,
+        }
+    }
+
+    /// Returns `true` if the function at `path` is exposed, that is if the
+    /// config declares it or one of the tables holding it, and the filter of
+    /// the host allows it
+    pub fn allows(&self, path: &str) -> bool {
+        let declared = self.declared.as_ref().is_none_or(|declared| {
+            declared.iter().any(|entry| {
+                path.strip_prefix(entry.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+            })
+        });
+        declared
+            && self
+                .filter
+                .as_ref()
+                .is_none_or(|filter| filter(&self.bundle, path))
+    }
+
+    /// Removes the functions of `table`, and of the tables it holds, that are
+    /// not exposed, named after `prefix` and their key
+    pub fn restrict(&self, table: &Table, prefix: &str) -> mlua::Result<()> {
+        self.restrict_nested(table, prefix, &mut HashSet::new())
+    }
+
+    fn restrict_nested(
+        &self,
+        table: &Table,
+        prefix: &str,
+        seen: &mut HashSet<*const std::ffi::c_void>,
+    ) -> mlua::Result<()> {
+        if !seen.insert(table.to_pointer()) {
+            return Ok(());
+        }
+
+        let mut hidden = vec![];
+        for pair in table.clone().pairs::<String, Value>() {
+            let (key, value) = pair?;
+            let path = format!("{prefix}.{key}");
+            match value {
+                Value::Function(_) if !self.allows(&path) => hidden.push(key),
+                Value::Table(nested) => self.restrict_nested(&nested, &path, seen)?,
+                _ => {}
+            }
+        }
+        for key in hidden {
+            table.raw_set(key, Value::Nil)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns `true` if the function at `path` is exposed to the plugin owning
+/// the Lua state, see [`ApiScope::allows`]
+pub fn exposes(lua: &Lua, path: &str) -> bool {
+    lua.app_data_ref::<ApiScope>()
+        .is_none_or(|scope| scope.allows(path))
+}
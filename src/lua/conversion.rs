@@ -1,25 +1,191 @@
 //! Type conversion between Lua and Rust types
+//!
+//! Integers become `Variable::I64` and floats `Variable::F64`, unless the
+//! plugin declares `numbers = "legacy"` to get `I32` and `F32`.
+//!
+//! Tables whose keys are all positive integers, or that carry an integer `n`
+//! field bounding them as `table.pack` produces, are lists: their length is
+//! the largest key or `n`, and holes become `Variable::Null`. `n` is only a
+//! length next to positional elements, so records like `{ n = 3 }` are maps.
+//! Lists containing
+//! `Null` are passed to Lua with `n` set, so that `table.unpack(t, 1, t.n)`
+//! round-trips. Plugins declaring `strict_nils = true` get an error instead
+//! of holes.
+//!
+//! Other tables are maps, encoded as described in [`crate::map_variable`]:
+//! their keys must be booleans, numbers or strings, and tagged lists passed
+//! to Lua become keyed tables again.
+//!
+//! Tables are read raw by default: `__pairs`, `__index` and `__len` are not
+//! consulted, so a plugin cannot run code from inside the conversion layer.
+//! Plugins declaring `metamethods = true` have the `__pairs` metamethod of
+//! their proxy tables honored, within a hard instruction and time budget.
 
-use mlua::{IntoLua, Lua, Value};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use mlua::{Function, IntoLua, Lua, MultiValue, Table, Value};
 use plux_rs::variable::Variable;
 
+use crate::{
+    config::{NumberPolicy, StringPolicy},
+    map::{map_entries, map_variable},
+    sync::MutexExt,
+};
+
+/// Maximum nesting depth of tables handled by the conversion layer
+///
+/// Deeper (or cyclic) tables are rejected instead of overflowing the stack.
+pub const MAX_DEPTH: usize = 64;
+
+/// Maximum number of Lua instructions a top-level conversion may spend in `__pairs`
+pub const MAX_METAMETHOD_INSTRUCTIONS: usize = 1_000_000;
+
+/// Maximum time a top-level conversion may spend in `__pairs`
+pub const MAX_METAMETHOD_TIME: Duration = Duration::from_millis(100);
+
+/// Maximum number of holes in a list, beyond which a table whose holes
+/// outnumber its elements is converted as a map
+pub const MAX_LIST_HOLES: usize = 1024;
+
+/// Options controlling how values cross a plugin's boundary
+#[derive(Clone, Default)]
+pub struct ConversionOptions {
+    /// How strings are converted
+    pub strings: StringPolicy,
+    /// How numbers are converted
+    pub numbers: NumberPolicy,
+    /// Budget of the state when `__pairs` metamethods are honored
+    pub metamethods: Option<MetamethodGuard>,
+    /// Whether holes in lists are an error instead of `Null`
+    pub strict_nils: bool,
+}
+
+/// App data marking a state whose plugin declared `strict_nils = true`
+pub struct StrictNils;
+
+/// Budget shared between the conversion layer and the instruction hook of a state
+///
+/// The budget is armed for the duration of a top-level conversion, nested
+/// conversions (e.g. of the arguments of a call made by `__pairs`) share it.
+#[derive(Clone, Default)]
+pub struct MetamethodGuard(Arc<Mutex<Option<Budget>>>);
+
+struct Budget {
+    started: Instant,
+    instructions: usize,
+}
+
+/// Disarms the budget of a [`MetamethodGuard`] when dropped
+struct Armed<'a>(&'a MetamethodGuard);
+
+impl Drop for Armed<'_> {
+    fn drop(&mut self) {
+        *self.0.0.lock_unpoisoned() = None;
+    }
+}
+
+impl MetamethodGuard {
+    /// Installs the guard on `lua`
+    pub fn install(&self, lua: &Lua) -> mlua::Result<()> {
+        lua.set_app_data(self.clone());
+        super::hooks::install(lua)
+    }
+
+    /// Arms the budget, unless a conversion already armed it
+    fn arm(&self) -> Option<Armed<'_>> {
+        let mut budget = self.0.lock_unpoisoned();
+        if budget.is_some() {
+            return None;
+        }
+        *budget = Some(Budget {
+            started: Instant::now(),
+            instructions: 0,
+        });
+        Some(Armed(self))
+    }
+
+    /// Charges `instructions` to the armed budget, failing once it is exhausted
+    pub fn spend(&self, instructions: usize) -> mlua::Result<()> {
+        let mut budget = self.0.lock_unpoisoned();
+        let Some(budget) = budget.as_mut() else {
+            return Ok(());
+        };
+
+        budget.instructions += instructions;
+        if budget.instructions > MAX_METAMETHOD_INSTRUCTIONS
+            || budget.started.elapsed() > MAX_METAMETHOD_TIME
+        {
+            return Err(mlua::Error::RuntimeError(format!(
+                "`__pairs` exceeded the conversion budget of {MAX_METAMETHOD_INSTRUCTIONS} instructions or {MAX_METAMETHOD_TIME:?}"
+            )));
+        }
+        Ok(())
+    }
+}
+
 /// Converts a Lua value to a Rust Variable
 pub fn lua_to_plux(lua_value: &Value) -> mlua::Result<Variable> {
+    lua_to_plux_with(lua_value, &ConversionOptions::default())
+}
+
+/// Converts a Lua value to a Rust Variable according to `options`
+pub fn lua_to_plux_with(lua_value: &Value, options: &ConversionOptions) -> mlua::Result<Variable> {
+    let _armed = options.metamethods.as_ref().and_then(MetamethodGuard::arm);
+    lua_to_plux_depth(lua_value, options, 0)
+}
+
+fn lua_to_plux_depth(
+    lua_value: &Value,
+    options: &ConversionOptions,
+    depth: usize,
+) -> mlua::Result<Variable> {
     match lua_value {
         Value::Nil => Ok(Variable::Null),
         Value::Boolean(var) => Ok(Variable::Bool(*var)),
         Value::LightUserData(_) => Err(mlua::Error::RuntimeError(
             "Unsupported variable type".to_string(),
         )),
-        Value::Integer(var) => Ok(Variable::I32(*var as i32)),
-        Value::Number(var) => Ok(Variable::F32(*var as f32)),
-        Value::String(var) => Ok(Variable::String(var.to_str()?.to_string())),
+        Value::Integer(var) => Ok(match options.numbers {
+            NumberPolicy::Full => Variable::I64(*var),
+            NumberPolicy::Legacy => Variable::I32(*var as i32),
+        }),
+        Value::Number(var) => Ok(match options.numbers {
+            NumberPolicy::Full => Variable::F64(*var),
+            NumberPolicy::Legacy => Variable::F32(*var as f32),
+        }),
+        Value::String(var) => match options.strings {
+            StringPolicy::Utf8 => match var.to_str() {
+                Ok(var) => Ok(Variable::String(var.to_string())),
+                Err(_) => Err(mlua::Error::RuntimeError(
+                    "invalid UTF-8 string (declare `strings = \"bytes\"` or `\"lossy\"`)"
+                        .to_string(),
+                )),
+            },
+            StringPolicy::Lossy => Ok(Variable::String(var.to_string_lossy())),
+            StringPolicy::Bytes => Ok(Variable::List(
+                var.as_bytes().iter().copied().map(Variable::U8).collect(),
+            )),
+        },
         Value::Table(var) => {
-            let mut list = vec![];
-            for pair in var.clone().pairs::<Value, Value>() {
-                list.push(lua_to_plux(&pair?.1)?);
+            if depth >= MAX_DEPTH {
+                return Err(depth_error());
             }
-            Ok(Variable::List(list))
+
+            let honored = match &options.metamethods {
+                Some(guard) => honored_pairs(var, guard)?,
+                None => None,
+            };
+            let entries = match honored {
+                Some(entries) => entries,
+                None => var
+                    .clone()
+                    .pairs::<Value, Value>()
+                    .collect::<mlua::Result<_>>()?,
+            };
+            table_to_plux(&entries, options, depth)
         }
         Value::Function(_) => Err(mlua::Error::RuntimeError(
             "Unsupported variable type".to_string(),
@@ -37,9 +203,265 @@ pub fn lua_to_plux(lua_value: &Value) -> mlua::Result<Variable> {
     }
 }
 
+/// Converts the entries of a table, as a list or as a map
+fn table_to_plux(
+    entries: &[(Value, Value)],
+    options: &ConversionOptions,
+    depth: usize,
+) -> mlua::Result<Variable> {
+    let Some(len) = list_len(entries) else {
+        let mut map = entries
+            .iter()
+            .map(|(key, value)| {
+                let key = match key {
+                    Value::Boolean(_) | Value::Integer(_) | Value::Number(_) | Value::String(_) => {
+                        lua_to_plux_depth(key, options, depth + 1)?
+                    }
+                    key => {
+                        return Err(mlua::Error::RuntimeError(format!(
+                            "unsupported {} table key",
+                            key.type_name()
+                        )));
+                    }
+                };
+                Ok((key, lua_to_plux_depth(value, options, depth + 1)?))
+            })
+            .collect::<mlua::Result<Vec<_>>>()?;
+        sort_map(&mut map);
+        return Ok(map_variable(map));
+    };
+
+    let mut list = vec![Variable::Null; len];
+    let mut filled = vec![false; len];
+    for (key, value) in entries {
+        if let Value::Integer(index) = key {
+            let index = *index as usize - 1;
+            list[index] = lua_to_plux_depth(value, options, depth + 1)?;
+            filled[index] = true;
+        }
+    }
+    if options.strict_nils
+        && let Some(index) = filled.iter().position(|filled| !filled)
+    {
+        return Err(mlua::Error::RuntimeError(format!(
+            "list has a nil at index {} of {len}",
+            index + 1
+        )));
+    }
+    Ok(Variable::List(list))
+}
+
+/// Returns the length of a table if its `entries` make a list, see the module documentation
+fn list_len(entries: &[(Value, Value)]) -> Option<usize> {
+    let mut declared = None;
+    let mut elements = 0;
+    let mut max = 0;
+    for (key, value) in entries {
+        match key {
+            Value::Integer(key) if *key > 0 => {
+                elements += 1;
+                max = max.max(*key as usize);
+            }
+            Value::String(key) if key.as_bytes() == b"n" => match value {
+                Value::Integer(n) if *n >= 0 => declared = Some(*n as usize),
+                _ => return None,
+            },
+            _ => return None,
+        }
+    }
+
+    let len = match declared {
+        Some(n) if elements == 0 || max > n => return None,
+        Some(n) => n,
+        None => max,
+    };
+    let holes = len - elements;
+    if holes > MAX_LIST_HOLES && holes > elements {
+        return None;
+    }
+    Some(len)
+}
+
+/// Sorts the entries of a map by key: numbers, then booleans, then strings
+fn sort_map(map: &mut [(Variable, Variable)]) {
+    fn order(key: &Variable) -> (u8, f64, String) {
+        match key {
+            Variable::I8(x) => (0, *x as f64, String::new()),
+            Variable::I16(x) => (0, *x as f64, String::new()),
+            Variable::I32(x) => (0, *x as f64, String::new()),
+            Variable::I64(x) => (0, *x as f64, String::new()),
+            Variable::U8(x) => (0, *x as f64, String::new()),
+            Variable::U16(x) => (0, *x as f64, String::new()),
+            Variable::U32(x) => (0, *x as f64, String::new()),
+            Variable::U64(x) => (0, *x as f64, String::new()),
+            Variable::F32(x) => (0, *x as f64, String::new()),
+            Variable::F64(x) => (0, *x, String::new()),
+            Variable::Bool(x) => (1, *x as u8 as f64, String::new()),
+            Variable::String(x) => (2, 0.0, x.clone()),
+            key => (3, 0.0, format!("{key:?}")),
+        }
+    }
+
+    map.sort_by(|(a, _), (b, _)| {
+        order(a)
+            .partial_cmp(&order(b))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+}
+
+/// Iterates `table` through its `__pairs` metamethod, if it has one
+///
+/// Returns the iterated entries, or `None` when the table has no `__pairs`.
+fn honored_pairs(
+    table: &Table,
+    guard: &MetamethodGuard,
+) -> mlua::Result<Option<Vec<(Value, Value)>>> {
+    let Some(meta) = table.metatable() else {
+        return Ok(None);
+    };
+    let Value::Function(pairs) = meta.raw_get::<Value>("__pairs")? else {
+        return Ok(None);
+    };
+
+    let (next, state, mut control): (Function, Value, Value) = pairs.call(table)?;
+    let mut entries = vec![];
+    loop {
+        // Iterators implemented in Rust do not trigger the hook
+        guard.spend(0)?;
+
+        let (key, value): (Value, Value) = next.call((state.clone(), control))?;
+        if key.is_nil() {
+            return Ok(Some(entries));
+        }
+        control = key.clone();
+        entries.push((key, value));
+    }
+}
+
+/// Converts a Lua value to a Rust Variable, skipping values that cannot be converted
+///
+/// Each skipped value is described in `warnings`, located by `path`.
+pub fn lua_to_plux_lossy(lua_value: &Value, path: &str, warnings: &mut Vec<String>) -> Variable {
+    lua_to_plux_lossy_depth(lua_value, path, warnings, 0)
+}
+
+fn lua_to_plux_lossy_depth(
+    lua_value: &Value,
+    path: &str,
+    warnings: &mut Vec<String>,
+    depth: usize,
+) -> Variable {
+    match lua_value {
+        Value::Table(_) if depth >= MAX_DEPTH => {
+            warnings.push(format!("`{path}`: {}", depth_error()));
+            Variable::Null
+        }
+        Value::Table(var) => {
+            let mut entries = vec![];
+            for pair in var.clone().pairs::<Value, Value>() {
+                match pair {
+                    Ok(pair) => entries.push(pair),
+                    Err(e) => warnings.push(format!("`{path}`: {e}")),
+                }
+            }
+
+            // Skipped items of a list stay in place as `Null`, and `n` is its length
+            if let Some(len) = list_len(&entries) {
+                let mut list = vec![Variable::Null; len];
+                for (key, value) in entries {
+                    let Value::Integer(index) = key else {
+                        continue;
+                    };
+                    let path = format!("{path}.{index}");
+                    if let Some(var) = lua_to_plux_lossy_item(&value, &path, warnings, depth + 1) {
+                        list[index as usize - 1] = var;
+                    }
+                }
+                return Variable::List(list);
+            }
+
+            let mut map = vec![];
+            for (key, value) in entries {
+                let name = key.to_string().unwrap_or_else(|_| "?".to_string());
+                let path = format!("{path}.{name}");
+                let Some(var) = lua_to_plux_lossy_item(&value, &path, warnings, depth + 1) else {
+                    continue;
+                };
+                match lua_to_plux(&key) {
+                    Ok(key) if !matches!(key, Variable::List(_)) => map.push((key, var)),
+                    _ => warnings.push(format!("`{path}`: cannot convert {} key", key.type_name())),
+                }
+            }
+            sort_map(&mut map);
+            map_variable(map)
+        }
+        value => lua_to_plux_lossy_item(value, path, warnings, depth).unwrap_or(Variable::Null),
+    }
+}
+
+fn lua_to_plux_lossy_item(
+    lua_value: &Value,
+    path: &str,
+    warnings: &mut Vec<String>,
+    depth: usize,
+) -> Option<Variable> {
+    match lua_value {
+        Value::Table(_) => Some(lua_to_plux_lossy_depth(lua_value, path, warnings, depth)),
+        value => match lua_to_plux(value) {
+            Ok(var) => Some(var),
+            Err(_) => {
+                warnings.push(format!("`{path}`: cannot convert {}", value.type_name()));
+                None
+            }
+        },
+    }
+}
+
+/// Error returned when a table is nested deeper than [`MAX_DEPTH`]
+pub fn depth_error() -> mlua::Error {
+    mlua::Error::RuntimeError(format!(
+        "Table nesting exceeds the maximum depth of {MAX_DEPTH} (is it cyclic?)"
+    ))
+}
+
 /// Converts a Rust Variable to a Lua value
 pub fn plux_to_lua(variable: &Variable, lua: &Lua) -> mlua::Result<Value> {
+    plux_to_lua_with(variable, lua, StringPolicy::Utf8)
+}
+
+/// Converts a Rust Variable to a Lua value according to the string `policy`
+///
+/// With the `bytes` policy, non-empty lists made only of `U8` values become Lua strings.
+pub fn plux_to_lua_with(
+    variable: &Variable,
+    lua: &Lua,
+    policy: StringPolicy,
+) -> mlua::Result<Value> {
     match variable {
+        Variable::List(_) if let Some(entries) = map_entries(variable) => {
+            let table = lua.create_table_with_capacity(0, entries.len())?;
+            for (key, value) in &entries {
+                table.raw_set(
+                    plux_to_lua_with(key, lua, policy)?,
+                    plux_to_lua_with(value, lua, policy)?,
+                )?;
+            }
+            Ok(Value::Table(table))
+        }
+        Variable::List(var)
+            if policy == StringPolicy::Bytes
+                && !var.is_empty()
+                && var.iter().all(|v| matches!(v, Variable::U8(_))) =>
+        {
+            let bytes: Vec<u8> = var
+                .iter()
+                .map(|v| match v {
+                    Variable::U8(b) => *b,
+                    _ => unreachable!(),
+                })
+                .collect();
+            lua.create_string(bytes).map(Value::String)
+        }
         Variable::Null => Ok(Value::Nil),
         Variable::I8(var) => var.into_lua(lua),
         Variable::I16(var) => var.into_lua(lua),
@@ -54,11 +476,85 @@ pub fn plux_to_lua(variable: &Variable, lua: &Lua) -> mlua::Result<Value> {
         Variable::Bool(var) => var.into_lua(lua),
         Variable::Char(var) => var.to_string().into_lua(lua),
         Variable::String(var) => var.clone().into_lua(lua),
-        Variable::List(var) => var
+        Variable::List(var) => {
+            let table = lua.create_table_with_capacity(var.len(), 0)?;
+            for (index, v) in var.iter().enumerate() {
+                table.raw_set(index + 1, plux_to_lua_with(v, lua, policy)?)?;
+            }
+            if var.iter().any(|v| matches!(v, Variable::Null)) {
+                table.raw_set("n", var.len())?;
+            }
+            Ok(Value::Table(table))
+        }
+    }
+}
+
+/// Returns the conversion options of the plugin owning the Lua state
+pub fn conversion_options(lua: &Lua) -> ConversionOptions {
+    ConversionOptions {
+        strings: lua
+            .app_data_ref::<StringPolicy>()
+            .map(|policy| *policy)
+            .unwrap_or_default(),
+        numbers: lua
+            .app_data_ref::<NumberPolicy>()
+            .map(|policy| *policy)
+            .unwrap_or_default(),
+        metamethods: lua
+            .app_data_ref::<MetamethodGuard>()
+            .map(|guard| guard.clone()),
+        strict_nils: lua.app_data_ref::<StrictNils>().is_some(),
+    }
+}
+
+/// Converts the arguments of a call to `function` made from Lua
+///
+/// Errors name the function and the position of the failing argument.
+pub fn args_from_lua(
+    args: &MultiValue,
+    options: &ConversionOptions,
+    function: &str,
+) -> mlua::Result<Vec<Variable>> {
+    args.iter()
+        .enumerate()
+        .map(|(index, arg)| {
+            lua_to_plux_with(arg, options).map_err(|e| {
+                mlua::Error::RuntimeError(format!(
+                    "Function `{function}`: argument #{}: {e}",
+                    index + 1
+                ))
+            })
+        })
+        .collect()
+}
+
+/// Converts the values returned by the Lua function `function`
+///
+/// A single value converts as it is and several values to a
+/// [`Variable::List`], with `nil` values as [`Variable::Null`]. Errors name
+/// the function.
+pub fn output_from_lua(
+    values: &MultiValue,
+    options: &ConversionOptions,
+    function: &str,
+) -> mlua::Result<Option<Variable>> {
+    let convert = |value, position: String| {
+        lua_to_plux_with(value, options).map_err(|e| {
+            mlua::Error::RuntimeError(format!("Function `{function}`: output{position}: {e}"))
+        })
+    };
+    match values.len() {
+        0 => Ok(None),
+        1 => match &values[0] {
+            Value::Nil => Ok(None),
+            value => convert(value, String::new()).map(Some),
+        },
+        _ => values
             .iter()
-            .map(|v| plux_to_lua(v, lua))
-            .collect::<mlua::Result<Vec<_>>>()?
-            .into_lua(lua),
+            .enumerate()
+            .map(|(index, value)| convert(value, format!(" #{}", index + 1)))
+            .collect::<mlua::Result<_>>()
+            .map(|values| Some(Variable::List(values))),
     }
 }
 
@@ -86,9 +582,9 @@ mod tests {
         assert_eq!(lua_val, Value::Integer(42));
 
         // Test float
-        let var = Variable::F32(3.14);
+        let var = Variable::F32(2.5);
         let lua_val = plux_to_lua(&var, &lua).unwrap();
-        assert_eq!(lua_val, Value::Number(3.14));
+        assert_eq!(lua_val, Value::Number(2.5));
 
         // Test string
         let var = Variable::String("test".to_string());
@@ -96,6 +592,272 @@ mod tests {
         assert_eq!(lua_val, Value::String(lua.create_string("test").unwrap()));
     }
 
+    #[test]
+    fn test_lossy_conversion() {
+        let lua = Lua::new();
+
+        let value: Value = lua
+            .load("return { 1, function() end, { 'two' } }")
+            .eval()
+            .unwrap();
+
+        let mut warnings = vec![];
+        let var = lua_to_plux_lossy(&value, "state", &mut warnings);
+        assert_eq!(
+            var,
+            Variable::List(vec![
+                Variable::I64(1),
+                Variable::Null,
+                Variable::List(vec![Variable::String("two".to_string())]),
+            ])
+        );
+        assert_eq!(
+            warnings,
+            vec!["`state.2`: cannot convert function".to_string()]
+        );
+
+        // `n` is the length of the list, not one of its items
+        let value: Value = lua.load("return table.pack(1, 2)").eval().unwrap();
+        let mut warnings = vec![];
+        assert_eq!(
+            lua_to_plux_lossy(&value, "state", &mut warnings),
+            Variable::List(vec![Variable::I64(1), Variable::I64(2)])
+        );
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_number_policies() {
+        let lua = Lua::new();
+        let legacy = ConversionOptions {
+            numbers: NumberPolicy::Legacy,
+            ..Default::default()
+        };
+
+        let value: Value = lua.load("return 9007199254740993").eval().unwrap();
+        assert_eq!(
+            lua_to_plux(&value).unwrap(),
+            Variable::I64(9007199254740993)
+        );
+        assert_eq!(
+            lua_to_plux_with(&value, &legacy).unwrap(),
+            Variable::I32(9007199254740993_i64 as i32)
+        );
+
+        let value: Value = lua.load("return 0.1").eval().unwrap();
+        assert_eq!(lua_to_plux(&value).unwrap(), Variable::F64(0.1));
+        assert_eq!(
+            lua_to_plux_with(&value, &legacy).unwrap(),
+            Variable::F32(0.1)
+        );
+    }
+
+    #[test]
+    fn test_cyclic_conversion() {
+        let lua = Lua::new();
+
+        let value: Value = lua.load("local t = {}; t[1] = t; return t").eval().unwrap();
+        assert!(lua_to_plux(&value).is_err());
+    }
+
+    const PROXY: &str = r#"
+        touched = false
+        return setmetatable({ "raw" }, {
+            __pairs = function(t)
+                touched = true
+                return next, { "proxied" }, nil
+            end,
+        })
+    "#;
+
+    #[test]
+    fn test_raw_conversion_ignores_pairs() {
+        let lua = Lua::new();
+
+        let value: Value = lua.load(PROXY).eval().unwrap();
+        assert_eq!(
+            lua_to_plux(&value).unwrap(),
+            Variable::List(vec![Variable::String("raw".to_string())])
+        );
+        assert!(!lua.globals().get::<bool>("touched").unwrap());
+    }
+
+    #[test]
+    fn test_honored_pairs() {
+        let lua = Lua::new();
+        let guard = MetamethodGuard::default();
+        guard.install(&lua).unwrap();
+        let options = ConversionOptions {
+            metamethods: Some(guard),
+            ..Default::default()
+        };
+
+        let value: Value = lua.load(PROXY).eval().unwrap();
+        assert_eq!(
+            lua_to_plux_with(&value, &options).unwrap(),
+            Variable::List(vec![Variable::String("proxied".to_string())])
+        );
+        assert!(lua.globals().get::<bool>("touched").unwrap());
+    }
+
+    #[test]
+    fn test_honored_pairs_budget() {
+        let lua = Lua::new();
+        let guard = MetamethodGuard::default();
+        guard.install(&lua).unwrap();
+        let options = ConversionOptions {
+            metamethods: Some(guard),
+            ..Default::default()
+        };
+
+        let value: Value = lua
+            .load("return setmetatable({}, { __pairs = function() while true do end end })")
+            .eval()
+            .unwrap();
+        let error = lua_to_plux_with(&value, &options).unwrap_err();
+        assert!(error.to_string().contains("conversion budget"), "{error}");
+
+        // The budget is disarmed once the conversion is over
+        lua.load("for i = 1, 2000000 do end").exec().unwrap();
+    }
+
+    #[test]
+    fn test_nil_in_lists() {
+        let lua = Lua::new();
+
+        let value: Value = lua
+            .load("return table.pack(1, nil, 3, nil)")
+            .eval()
+            .unwrap();
+        let var = lua_to_plux(&value).unwrap();
+        assert_eq!(
+            var,
+            Variable::List(vec![
+                Variable::I64(1),
+                Variable::Null,
+                Variable::I64(3),
+                Variable::Null,
+            ])
+        );
+
+        lua.globals()
+            .set("packed", plux_to_lua(&var, &lua).unwrap())
+            .unwrap();
+        let (n, count, third): (usize, usize, i64) = lua
+            .load("return packed.n, select('#', table.unpack(packed, 1, packed.n)), packed[3]")
+            .eval()
+            .unwrap();
+        assert_eq!((n, count, third), (4, 4, 3));
+
+        // Holes without `n` stop at the largest key
+        let value: Value = lua.load("return { 1, nil, 3 }").eval().unwrap();
+        assert_eq!(
+            lua_to_plux(&value).unwrap(),
+            Variable::List(vec![Variable::I64(1), Variable::Null, Variable::I64(3)])
+        );
+    }
+
+    #[test]
+    fn test_strict_nils() {
+        let lua = Lua::new();
+        let options = ConversionOptions {
+            strict_nils: true,
+            ..Default::default()
+        };
+
+        let value: Value = lua.load("return table.pack(1, nil, 3)").eval().unwrap();
+        let error = lua_to_plux_with(&value, &options).unwrap_err();
+        assert!(error.to_string().contains("nil at index 2 of 3"), "{error}");
+
+        let value: Value = lua.load("return { 1, 2, 3 }").eval().unwrap();
+        assert!(lua_to_plux_with(&value, &options).is_ok());
+    }
+
+    #[test]
+    fn test_n_without_elements_is_not_a_list() {
+        let lua = Lua::new();
+
+        let value: Value = lua.load("return { n = 3 }").eval().unwrap();
+        assert_eq!(
+            lua_to_plux(&value).unwrap(),
+            map_variable([(Variable::String("n".to_string()), Variable::I64(3))])
+        );
+
+        let value: Value = lua.load("return { n = 0 }").eval().unwrap();
+        assert_eq!(
+            lua_to_plux(&value).unwrap(),
+            map_variable([(Variable::String("n".to_string()), Variable::I64(0))])
+        );
+
+        let value: Value = lua.load("return { 1, 2, n = 2 }").eval().unwrap();
+        assert_eq!(
+            lua_to_plux(&value).unwrap(),
+            Variable::List(vec![Variable::I64(1), Variable::I64(2)])
+        );
+    }
+
+    #[test]
+    fn test_sparse_table_is_not_a_list() {
+        let lua = Lua::new();
+
+        let value: Value = lua.load("return { [1000000] = true }").eval().unwrap();
+        assert_eq!(
+            lua_to_plux(&value).unwrap(),
+            map_variable([(Variable::I64(1000000), Variable::Bool(true))])
+        );
+    }
+
+    #[test]
+    fn test_map_round_trip() {
+        let lua = Lua::new();
+
+        let value: Value = lua
+            .load("return { name = 'plux', tags = { 'a', 'b' }, nested = { ok = true }, [10] = 'ten' }")
+            .eval()
+            .unwrap();
+        let var = lua_to_plux(&value).unwrap();
+        assert_eq!(
+            var,
+            map_variable([
+                (Variable::I64(10), Variable::String("ten".to_string())),
+                (
+                    Variable::String("name".to_string()),
+                    Variable::String("plux".to_string())
+                ),
+                (
+                    Variable::String("nested".to_string()),
+                    map_variable([(Variable::String("ok".to_string()), Variable::Bool(true))])
+                ),
+                (
+                    Variable::String("tags".to_string()),
+                    Variable::List(vec![
+                        Variable::String("a".to_string()),
+                        Variable::String("b".to_string()),
+                    ])
+                ),
+            ])
+        );
+
+        lua.globals()
+            .set("map", plux_to_lua(&var, &lua).unwrap())
+            .unwrap();
+        let (name, tag, ok, ten): (String, String, bool, String) = lua
+            .load("return map.name, map.tags[2], map.nested.ok, map[10]")
+            .eval()
+            .unwrap();
+        assert_eq!(
+            (name.as_str(), tag.as_str(), ok, ten.as_str()),
+            ("plux", "b", true, "ten")
+        );
+
+        let value: Value = lua.load("return { [{}] = 1 }").eval().unwrap();
+        let error = lua_to_plux(&value).unwrap_err();
+        assert!(
+            error.to_string().contains("unsupported table table key"),
+            "{error}"
+        );
+    }
+
     #[test]
     fn test_complex_conversion() {
         let lua = Lua::new();
@@ -111,7 +873,7 @@ mod tests {
         if let Value::Table(t) = lua_val {
             assert_eq!(t.get::<i64>(1).unwrap(), 1);
             assert_eq!(t.get::<String>(2).unwrap(), "two");
-            assert_eq!(t.get::<bool>(3).unwrap(), true);
+            assert!(t.get::<bool>(3).unwrap());
         } else {
             panic!("Expected table");
         }
@@ -0,0 +1,151 @@
+//! Per-plugin environment isolating plugin code from the host's globals
+//!
+//! The globals of a plugin state hold what the host provides: standard
+//! libraries, the vtable, `api`, `log` and the globals set with
+//! [`crate::LuaManager::with_global`]. Plugin chunks run in an environment
+//! table inheriting them, so the globals a plugin defines never shadow or
+//! replace host functions, and the host's globals are frozen.
+
+use mlua::{Lua, Table, Value};
+
+/// Name of the Lua registry value holding the plugin environment.
+pub const ENV_KEY: &str = "plux_env";
+
+/// Creates the plugin environment and freezes the globals.
+///
+/// Must run once the host has set all its globals. Their values move to a
+/// base table the globals inherit, so that assignments to the globals fail
+/// afterwards, only raw ones go through.
+pub fn create_env(lua: &Lua) -> mlua::Result<Table> {
+    let globals = lua.globals();
+    let base = lua.create_table()?;
+    for pair in globals.pairs::<Value, Value>() {
+        let (key, value) = pair?;
+        base.raw_set(key, value)?;
+    }
+    globals.clear()?;
+
+    let env = lua.create_table()?;
+    env.raw_set("_G", &env)?;
+    let meta = lua.create_table()?;
+    meta.raw_set("__index", &globals)?;
+    env.set_metatable(Some(meta))?;
+    lua.set_named_registry_value(ENV_KEY, &env)?;
+
+    let frozen = lua.create_table()?;
+    frozen.raw_set("__index", &base)?;
+    let deny = lua.create_function(|_, (_, key): (Table, Value)| -> mlua::Result<()> {
+        Err(mlua::Error::RuntimeError(format!(
+            "cannot assign '{}', host globals are read-only",
+            key.to_string()?
+        )))
+    })?;
+    frozen.raw_set("__newindex", deny)?;
+    frozen.raw_set("__metatable", false)?;
+    globals.set_metatable(Some(frozen))?;
+
+    Ok(env)
+}
+
+/// Makes `table` and the tables it holds read-only, assignments failing with
+/// an error naming the table `name`.
+///
+/// As with the globals, the values move to a base table `table` inherits, so
+/// that only raw assignments go through. `pairs` and the length operator
+/// still see the values.
+pub fn freeze(lua: &Lua, table: &Table, name: &str) -> mlua::Result<()> {
+    let base = lua.create_table()?;
+    for pair in table.pairs::<Value, Value>() {
+        let (key, value) = pair?;
+        if let Value::Table(nested) = &value {
+            freeze(lua, nested, name)?;
+        }
+        base.raw_set(key, value)?;
+    }
+    table.clear()?;
+
+    let frozen = lua.create_table()?;
+    frozen.raw_set("__index", &base)?;
+    let name = name.to_string();
+    let deny = lua.create_function(move |_, (_, key): (Table, Value)| -> mlua::Result<()> {
+        Err(mlua::Error::RuntimeError(format!(
+            "cannot assign '{}', {name} is read-only",
+            key.to_string()?
+        )))
+    })?;
+    frozen.raw_set("__newindex", deny)?;
+    let next: Value = lua.globals().raw_get("next")?;
+    let pairs = {
+        let base = base.clone();
+        lua.create_function(move |_, _: Table| Ok((next.clone(), base.clone(), Value::Nil)))?
+    };
+    frozen.raw_set("__pairs", pairs)?;
+    let len = {
+        let base = base.clone();
+        lua.create_function(move |_, _: Table| Ok(base.raw_len()))?
+    };
+    frozen.raw_set("__len", len)?;
+    frozen.raw_set("__metatable", false)?;
+    table.set_metatable(Some(frozen))?;
+
+    Ok(())
+}
+
+/// Returns the plugin environment, the globals in states without one.
+pub fn env(lua: &Lua) -> mlua::Result<Table> {
+    match lua.named_registry_value::<Option<Table>>(ENV_KEY)? {
+        Some(env) => Ok(env),
+        None => Ok(lua.globals()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_env_inherits_frozen_globals() {
+        let lua = Lua::new();
+        lua.globals().set("host_value", 1).unwrap();
+        let env = create_env(&lua).unwrap();
+
+        let (inherited, is_env): (i64, bool) = lua
+            .load("plugin_value = host_value + 1; return host_value, _G == _ENV")
+            .set_environment(env.clone())
+            .eval()
+            .unwrap();
+        assert_eq!((inherited, is_env), (1, true));
+        assert_eq!(env.get::<i64>("plugin_value").unwrap(), 2);
+        assert!(!lua.globals().contains_key("plugin_value").unwrap());
+
+        assert!(lua.load("host_value = 2").exec().is_err());
+        assert!(lua.load("setmetatable(_G, nil)").exec().is_err());
+    }
+
+    #[test]
+    fn test_frozen_tables_are_read_only() {
+        let lua = Lua::new();
+        let table: Table = lua
+            .load("return { 'a', 'b', nested = { x = 1 } }")
+            .eval()
+            .unwrap();
+        freeze(&lua, &table, "config").unwrap();
+        lua.globals().set("config", table).unwrap();
+
+        let (len, keys, x): (i64, i64, i64) = lua
+            .load(
+                r#"
+                local keys = 0
+                for _ in pairs(config) do keys = keys + 1 end
+                return #config, keys, config.nested.x
+            "#,
+            )
+            .eval()
+            .unwrap();
+        assert_eq!((len, keys, x), (2, 3, 1));
+
+        let error = lua.load("config.nested.x = 2").exec().unwrap_err();
+        assert!(error.to_string().contains("config is read-only"), "{error}");
+        assert!(lua.load("config[3] = 'c'").exec().is_err());
+    }
+}
@@ -0,0 +1,83 @@
+//! Structured errors raised to Lua plugins
+//!
+//! Some API failures are raised as tables `{ kind = ..., message = ... }`
+//! instead of plain strings, so that plugins can react to them:
+//!
+//! ```lua
+//! local ok, err = pcall(api.call_function_depend, "other", "1.0.0", "f")
+//! if not ok and err.kind == "missing_dependency" then
+//!     -- fall back
+//! end
+//! ```
+//!
+//! The tables convert to `"<kind>: <message>"` with `tostring`.
+
+use mlua::{Function, IntoLuaMulti, Lua, MultiValue, Table, Value};
+
+/// A required dependency is not loaded.
+pub const MISSING_DEPENDENCY: &str = "missing_dependency";
+
+/// The called plugin is paused.
+pub const PAUSED: &str = "paused";
+
+/// The called plugin is quarantined.
+pub const QUARANTINED: &str = "quarantined";
+
+/// The plugin calls the host faster than its rate limit allows.
+pub const RATE_LIMITED: &str = "rate_limited";
+
+/// The plugin is not allowed to access a resource.
+pub const ACCESS_DENIED: &str = "access_denied";
+
+/// Name of the Lua registry value holding the metatable of structured errors.
+const ERROR_META_KEY: &str = "plux_error_meta";
+
+/// Returns the values by which a raw function reports a structured failure
+pub fn failure(lua: &Lua, kind: &str, message: String) -> mlua::Result<MultiValue> {
+    (false, kind, message).into_lua_multi(lua)
+}
+
+/// Returns the values by which a raw function reports success
+pub fn success(lua: &Lua, value: Value) -> mlua::Result<MultiValue> {
+    (true, value).into_lua_multi(lua)
+}
+
+/// Wraps a raw function returning [`success`] or [`failure`] values into a
+/// function returning the value or raising the structured error.
+pub fn structured(lua: &Lua, raw: Function) -> mlua::Result<Function> {
+    let meta = match lua.named_registry_value::<Option<Table>>(ERROR_META_KEY)? {
+        Some(meta) => meta,
+        None => {
+            let meta: Table = lua
+                .load(
+                    r#"
+                        return {
+                            __tostring = function(err)
+                                return err.kind .. ": " .. err.message
+                            end,
+                        }
+                    "#,
+                )
+                .eval()?;
+            lua.set_named_registry_value(ERROR_META_KEY, &meta)?;
+            meta
+        }
+    };
+
+    lua.load(
+        r#"
+            local raw, meta = ...
+            local function check(ok, value, message)
+                if ok then
+                    return value
+                end
+                error(setmetatable({ kind = value, message = message }, meta))
+            end
+            return function(...)
+                return check(raw(...))
+            end
+        "#,
+    )
+    .set_name("=[plux api]")
+    .call((raw, meta))
+}
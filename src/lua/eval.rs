@@ -0,0 +1,41 @@
+//! Evaluation of Lua snippets inside a loaded plugin, see
+//! [`crate::LuaManager::eval`]
+
+use mlua::{Lua, MultiValue};
+use plux_rs::variable::Variable;
+
+use crate::lua::{
+    conversion::{conversion_options, lua_to_plux_with},
+    env,
+};
+
+/// Evaluates `source` in the plugin environment, as an expression or as a
+/// block if it is not one, naming the chunk `name`
+pub fn evaluate(lua: &Lua, name: &str, source: &str) -> mlua::Result<Option<Variable>> {
+    let env = env::env(lua)?;
+    let chunk = match lua
+        .load(format!("return {source}"))
+        .set_name(name)
+        .set_environment(env.clone())
+        .into_function()
+    {
+        Ok(chunk) => chunk,
+        Err(_) => lua
+            .load(source)
+            .set_name(name)
+            .set_environment(env)
+            .into_function()?,
+    };
+
+    let values = chunk.call::<MultiValue>(())?;
+    let options = conversion_options(lua);
+    let mut values = values
+        .iter()
+        .map(|value| lua_to_plux_with(value, &options))
+        .collect::<mlua::Result<Vec<_>>>()?;
+    Ok(match values.len() {
+        0 => None,
+        1 => values.pop(),
+        _ => Some(Variable::List(values)),
+    })
+}
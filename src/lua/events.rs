@@ -0,0 +1,165 @@
+//! The `api.events` table letting plugins exchange events
+//!
+//! ```lua
+//! api.events.on("resized", function(payload, source)
+//!     print(source .. " resized to " .. payload.width)
+//! end)
+//! api.events.emit("resized", { width = 640 })
+//! ```
+//!
+//! Handlers receive the payload and the id of the emitting plugin. Events are
+//! delivered by [`LuaManager::tick`](crate::LuaManager::tick), never from
+//! within `emit`.
+//!
+//! Plugins also learn when other plugins are loaded or unloaded, to wire up
+//! optional integrations, through `api.on_plugin_loaded` and
+//! `api.on_plugin_unloaded`:
+//!
+//! ```lua
+//! api.on_plugin_loaded(function(id, version)
+//!     if id == "metrics" then
+//!         reporter = deps.metrics
+//!     end
+//! end)
+//! ```
+//!
+//! Unlike events, these are called by the manager as soon as the plugin is
+//! loaded or unloaded. Plugins loaded before the subscription are listed by
+//! `api.list_plugins`.
+
+use mlua::{Function, Lua, Table, Value};
+use plux_rs::Bundle;
+
+use crate::error::ManagerError;
+use crate::events::{Event, EventBus};
+use crate::lua::conversion::{conversion_options, lua_to_plux_with, plux_to_lua_with};
+
+/// Name of the Lua registry value mapping event names to their handlers.
+pub const HANDLERS_KEY: &str = "plux_event_handlers";
+
+/// Name of the Lua registry value mapping `loaded` and `unloaded` to the
+/// handlers of the lifecycle events of other plugins.
+pub const LIFECYCLE_KEY: &str = "plux_lifecycle_handlers";
+
+/// Lifecycle event of a plugin, passed to the handlers of the others
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lifecycle {
+    Loaded,
+    Unloaded,
+}
+
+impl Lifecycle {
+    fn key(self) -> &'static str {
+        match self {
+            Self::Loaded => "loaded",
+            Self::Unloaded => "unloaded",
+        }
+    }
+}
+
+/// Registers `api.events.emit` and `api.events.on` for the plugin `id`
+pub fn register_events(lua: &Lua, id: &str, bus: EventBus) -> Result<(), ManagerError> {
+    lua.set_named_registry_value(HANDLERS_KEY, lua.create_table()?)?;
+    let events = lua.create_table()?;
+
+    let emit = {
+        let id = id.to_string();
+        lua.create_function(move |ctx, (name, payload): (String, Value)| {
+            let payload = lua_to_plux_with(&payload, &conversion_options(ctx))?;
+            bus.emit(Event {
+                name,
+                payload,
+                source: id.clone(),
+            });
+            Ok(())
+        })?
+    };
+    events.set("emit", emit)?;
+
+    let on = lua.create_function(|ctx, (name, handler): (String, Function)| {
+        let handlers: Table = ctx.named_registry_value(HANDLERS_KEY)?;
+        let list = match handlers.raw_get::<Option<Table>>(name.as_str())? {
+            Some(list) => list,
+            None => {
+                let list = ctx.create_table()?;
+                handlers.raw_set(name, &list)?;
+                list
+            }
+        };
+        list.raw_push(handler)
+    })?;
+    events.set("on", on)?;
+
+    let api_table: Table = lua.globals().get("api")?;
+    api_table.set("events", events)?;
+
+    Ok(())
+}
+
+/// Calls the handlers of `event`
+///
+/// A failing handler does not prevent the others from running. Returns
+/// `false` if the plugin has no handler for the event.
+pub fn deliver(lua: &Lua, event: &Event) -> mlua::Result<bool> {
+    let handlers: Table = lua.named_registry_value(HANDLERS_KEY)?;
+    let Some(list) = handlers.raw_get::<Option<Table>>(event.name.as_str())? else {
+        return Ok(false);
+    };
+
+    let payload = plux_to_lua_with(&event.payload, lua, conversion_options(lua).strings)?;
+    for handler in list.sequence_values::<Function>() {
+        if let Err(e) = handler?.call::<()>((payload.clone(), event.source.as_str())) {
+            log::error!("Handler of event `{}` failed: {}", event.name, e);
+        }
+    }
+
+    Ok(true)
+}
+
+/// Registers `api.on_plugin_loaded(handler)` and
+/// `api.on_plugin_unloaded(handler)`
+pub fn register_lifecycle(lua: &Lua) -> Result<(), ManagerError> {
+    let lifecycle = lua.create_table()?;
+    let api_table: Table = lua.globals().get("api")?;
+
+    for (event, name) in [
+        (Lifecycle::Loaded, "on_plugin_loaded"),
+        (Lifecycle::Unloaded, "on_plugin_unloaded"),
+    ] {
+        let list = lua.create_table()?;
+        lifecycle.raw_set(event.key(), &list)?;
+        // Looked up on every call, the registry value being per plugin in a
+        // shared state
+        let subscribe = lua.create_function(move |ctx, handler: Function| {
+            let lifecycle: Table = ctx.named_registry_value(LIFECYCLE_KEY)?;
+            lifecycle.raw_get::<Table>(event.key())?.raw_push(handler)
+        })?;
+        api_table.set(name, subscribe)?;
+    }
+
+    lua.set_named_registry_value(LIFECYCLE_KEY, lifecycle)?;
+    Ok(())
+}
+
+/// Calls the handlers of `event` with the id and version of the plugin
+/// `bundle`
+///
+/// A failing handler does not prevent the others from running.
+pub fn notify_lifecycle(lua: &Lua, event: Lifecycle, bundle: &Bundle) -> mlua::Result<()> {
+    let lifecycle: Table = lua.named_registry_value(LIFECYCLE_KEY)?;
+    let list: Table = lifecycle.raw_get(event.key())?;
+
+    let version = bundle.version.to_string();
+    for handler in list.sequence_values::<Function>() {
+        if let Err(e) = handler?.call::<()>((bundle.id.as_str(), version.as_str())) {
+            log::error!(
+                "Handler of plugin {} being {} failed: {}",
+                bundle,
+                event.key(),
+                e
+            );
+        }
+    }
+
+    Ok(())
+}
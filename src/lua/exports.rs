@@ -0,0 +1,90 @@
+//! Functions exported by a plugin's entry script
+
+use mlua::{Function, Lua, MultiValue, Table, Value};
+
+use crate::error::{ManagerError, PluginError};
+
+/// Name of the Lua registry value holding the functions exported by the plugin.
+pub const EXPORTS_KEY: &str = "plux_exports";
+
+/// Name of the Lua registry value holding the names of the exported functions
+/// declared `async = true`.
+pub const ASYNC_EXPORTS_KEY: &str = "plux_async_exports";
+
+/// Returns the export records of the table returned by an entry script
+///
+/// Entry scripts return either a list of `{ name, inputs, output, func }`
+/// records or a module table of `name = function` pairs, whose values may
+/// also be records without a `name`. Functions given directly declare no
+/// inputs and receive all the arguments of their calls. The functions of a
+/// module table are exported in name order.
+pub fn export_records(lua: &Lua, result: Table) -> mlua::Result<Vec<Table>> {
+    if result.raw_len() > 0 {
+        return result.sequence_values().collect();
+    }
+
+    let mut entries = result
+        .pairs::<Value, Value>()
+        .map(|pair| match pair? {
+            (Value::String(name), value) => Ok((name.to_str()?.to_string(), value)),
+            (key, _) => Err(mlua::Error::RuntimeError(format!(
+                "the entry script returned a {} key instead of a function name",
+                key.type_name()
+            ))),
+        })
+        .collect::<mlua::Result<Vec<_>>>()?;
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    entries
+        .into_iter()
+        .map(|(name, value)| match value {
+            Value::Function(func) => {
+                let record = lua.create_table()?;
+                record.set("func", func)?;
+                record.set("name", name)?;
+                Ok(record)
+            }
+            Value::Table(record) => {
+                if !record.contains_key("name")? {
+                    record.set("name", name)?;
+                }
+                Ok(record)
+            }
+            value => Err(mlua::Error::RuntimeError(format!(
+                "the entry script exported `{name}` as a {} instead of a function",
+                value.type_name()
+            ))),
+        })
+        .collect()
+}
+
+/// Looks up a function exported by the plugin
+pub fn get_export(lua: &Lua, name: &str) -> Result<Function, ManagerError> {
+    let exports: Table = lua.named_registry_value(EXPORTS_KEY)?;
+    exports
+        .get::<Option<Function>>(name)?
+        .ok_or_else(|| PluginError::FunctionNotFound(name.to_string()).into())
+}
+
+/// Returns `true` if the exported function `name` is declared `async = true`
+pub fn is_async_export(lua: &Lua, name: &str) -> mlua::Result<bool> {
+    let asynchronous: Option<Table> = lua.named_registry_value(ASYNC_EXPORTS_KEY)?;
+    asynchronous.map_or(Ok(false), |asynchronous| asynchronous.contains_key(name))
+}
+
+/// Calls an exported function, running `async` ones to completion on the
+/// calling thread, and returns all its results
+pub fn call_export(
+    function: &Function,
+    args: MultiValue,
+    asynchronous: bool,
+) -> mlua::Result<MultiValue> {
+    #[cfg(feature = "async")]
+    if asynchronous {
+        return futures_executor::block_on(function.call_async::<MultiValue>(args));
+    }
+    #[cfg(not(feature = "async"))]
+    let _ = asynchronous;
+
+    function.call::<MultiValue>(args)
+}
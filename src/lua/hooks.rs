@@ -0,0 +1,97 @@
+//! Instruction hook enforcing the budgets of a plugin's Lua state
+//!
+//! A state has a single hook, shared by the [`MetamethodGuard`] of the
+//! conversion layer and the [`Watchdog`] of calls. Each looks up its budget in
+//! the state's app data. With the `debug` feature, the hook also runs the
+//! [`Debugger`] of the plugin, see [`crate::LuaManager::set_debug_hook`], and
+//! records the lines it runs when coverage is enabled, see
+//! [`crate::LuaManager::with_coverage`].
+
+#[cfg(feature = "debug")]
+use mlua::DebugEvent;
+use mlua::{HookTriggers, Lua, VmState};
+
+use super::{conversion::MetamethodGuard, watchdog::Watchdog};
+#[cfg(feature = "debug")]
+use crate::{coverage::CoverageRecorder, manager::DebugHook};
+
+/// Number of instructions between two checks of the budgets
+pub const HOOK_INTERVAL: u32 = 1000;
+
+/// Debug hook of the plugin owning a Lua state and the events it handles
+#[cfg(feature = "debug")]
+#[derive(Clone)]
+pub struct Debugger {
+    pub triggers: HookTriggers,
+    pub hook: DebugHook,
+}
+
+/// Installs the hook on `lua`, replacing any previous one
+pub fn install(lua: &Lua) -> mlua::Result<()> {
+    #[cfg(feature = "debug")]
+    let triggers = lua
+        .app_data_ref::<Debugger>()
+        .map_or(HookTriggers::new(), |debugger| debugger.triggers);
+    #[cfg(feature = "debug")]
+    let triggers = match lua.app_data_ref::<CoverageRecorder>() {
+        Some(_) => triggers | HookTriggers::new().every_line(),
+        None => triggers,
+    };
+    #[cfg(not(feature = "debug"))]
+    let triggers = HookTriggers::new();
+
+    // The budgets are checked at the debugger's interval if it sets one
+    let triggers = triggers | HookTriggers::new().every_nth_instruction(HOOK_INTERVAL);
+    let interval = triggers.every_nth_instruction.unwrap_or(HOOK_INTERVAL);
+
+    lua.set_global_hook(triggers, move |lua, debug| {
+        #[cfg(feature = "debug")]
+        {
+            let count = debug.event() == DebugEvent::Count;
+            if debug.event() == DebugEvent::Line {
+                record_line(lua, debug);
+            }
+            let debugger = lua
+                .app_data_ref::<Debugger>()
+                .map(|debugger| debugger.clone());
+            if let Some(debugger) = debugger
+                && (!count || debugger.triggers.every_nth_instruction.is_some())
+                && let VmState::Yield = (debugger.hook)(lua, debug)?
+            {
+                return Ok(VmState::Yield);
+            }
+            // Line, call and return events may be left by the debugger of
+            // another plugin of a shared state
+            if !count {
+                return Ok(VmState::Continue);
+            }
+        }
+        #[cfg(not(feature = "debug"))]
+        let _ = debug;
+
+        if let Some(guard) = lua.app_data_ref::<MetamethodGuard>() {
+            guard.spend(interval as usize)?;
+        }
+        if let Some(watchdog) = lua.app_data_ref::<Watchdog>() {
+            watchdog.check()?;
+        }
+        Ok(VmState::Continue)
+    })
+}
+
+/// Records the line about to run in the coverage of the plugin, if its
+/// source is a file
+#[cfg(feature = "debug")]
+fn record_line(lua: &Lua, debug: &mlua::Debug<'_>) {
+    let Some(recorder) = lua.app_data_ref::<CoverageRecorder>() else {
+        return;
+    };
+    let source = debug.source();
+    let path = source
+        .source
+        .as_deref()
+        .and_then(|source| source.strip_prefix('@'));
+    if let (Some(path), Some(line)) = (path, debug.current_line()) {
+        recorder.coverage.record(&recorder.bundle, path, line);
+    }
+}
@@ -0,0 +1,47 @@
+//! The `log` table and `print` of plugins, routed through the `log` crate
+//!
+//! ```lua
+//! log.info("loaded", 3, "modules")
+//! log.warn("config missing, using defaults")
+//! print("goes to the host's logger at info level")
+//! ```
+//!
+//! Messages are logged with the plugin's bundle as target, their values
+//! joined by tabs as `print` does.
+
+use mlua::{Lua, MultiValue};
+
+use crate::error::ManagerError;
+
+/// Registers `log.debug`, `log.info`, `log.warn`, `log.error` and replaces
+/// `print` for the plugin `target`
+pub fn register_logging(lua: &Lua, target: &str) -> Result<(), ManagerError> {
+    let table = lua.create_table()?;
+    for (name, level) in [
+        ("debug", log::Level::Debug),
+        ("info", log::Level::Info),
+        ("warn", log::Level::Warn),
+        ("error", log::Level::Error),
+    ] {
+        table.set(name, create_logger(lua, target, level)?)?;
+    }
+
+    let globals = lua.globals();
+    globals.set("log", table)?;
+    globals.set("print", create_logger(lua, target, log::Level::Info)?)?;
+
+    Ok(())
+}
+
+fn create_logger(lua: &Lua, target: &str, level: log::Level) -> mlua::Result<mlua::Function> {
+    let target = target.to_string();
+    lua.create_function(move |_, values: MultiValue| {
+        let message = values
+            .iter()
+            .map(|value| value.to_string())
+            .collect::<mlua::Result<Vec<_>>>()?
+            .join("\t");
+        log::log!(target: &target, level, "{}", message);
+        Ok(())
+    })
+}
@@ -1,6 +1,25 @@
 //! Lua-specific functionality for the plugin manager.
 
 pub mod api;
+pub mod audit;
+pub mod capabilities;
 pub mod conversion;
+pub mod env;
+pub mod errors;
+pub mod eval;
+pub mod events;
+pub mod exports;
+pub mod hooks;
+pub mod logging;
+pub mod output;
+pub mod plugins;
 pub mod requests;
+pub mod require;
+pub mod settings;
+pub mod shared;
+pub mod state;
+pub mod storage;
+pub mod tasks;
+pub mod util;
 pub mod vtable;
+pub mod watchdog;
@@ -0,0 +1,104 @@
+//! Redirection of what plugins write to the standard streams
+//!
+//! `print`, `io.write`, `io.stdout:write` and `io.stderr:write` of a plugin
+//! are replaced with functions handing the text to the manager's
+//! [`OutputCapture`], tagged with the plugin's bundle, see
+//! [`crate::LuaManager::with_output_capture`]. The replaced streams only
+//! support `write`, `flush` and `setvbuf`.
+
+use std::sync::Arc;
+
+use mlua::{Lua, MultiValue, Table, Value};
+use plux_rs::Bundle;
+
+use crate::{
+    error::ManagerError,
+    output::{OutputCapture, OutputStream},
+};
+
+/// Replaces `print` and the standard output streams of the `io` library, if
+/// opened, so that what the plugin `bundle` writes goes to `capture`
+pub fn redirect_output(
+    lua: &Lua,
+    bundle: &Bundle,
+    capture: &Arc<OutputCapture>,
+) -> Result<(), ManagerError> {
+    let globals = lua.globals();
+
+    let print = {
+        let bundle = bundle.clone();
+        let capture = capture.clone();
+        lua.create_function(move |_, values: MultiValue| {
+            let mut line = values
+                .iter()
+                .map(|value| value.to_string())
+                .collect::<mlua::Result<Vec<_>>>()?
+                .join("\t");
+            line.push('\n');
+            capture.write(&bundle, OutputStream::Stdout, &line);
+            Ok(())
+        })?
+    };
+    globals.set("print", print)?;
+
+    let Some(io) = globals.get::<Option<Table>>("io")? else {
+        return Ok(());
+    };
+    let stdout = create_stream(lua, bundle, capture, OutputStream::Stdout)?;
+    let stderr = create_stream(lua, bundle, capture, OutputStream::Stderr)?;
+
+    // `io.write` may have been removed by the plugin's permissions
+    if io.get::<Option<mlua::Function>>("write")?.is_some() {
+        let stdout = stdout.clone();
+        io.set(
+            "write",
+            lua.create_function(move |_, args: MultiValue| {
+                let write: mlua::Function = stdout.get("write")?;
+                write.call::<Value>((stdout.clone(), args))
+            })?,
+        )?;
+    }
+    io.set("stdout", stdout)?;
+    io.set("stderr", stderr)?;
+
+    Ok(())
+}
+
+/// Creates a stream object writing to `stream`, whose `write` returns the
+/// stream itself like the file handles of the `io` library
+fn create_stream(
+    lua: &Lua,
+    bundle: &Bundle,
+    capture: &Arc<OutputCapture>,
+    stream: OutputStream,
+) -> mlua::Result<Table> {
+    let object = lua.create_table()?;
+
+    let bundle = bundle.clone();
+    let capture = capture.clone();
+    object.set(
+        "write",
+        lua.create_function(move |_, (this, args): (Table, MultiValue)| {
+            let mut text = String::new();
+            for (i, arg) in args.iter().enumerate() {
+                match arg {
+                    Value::String(s) => text.push_str(&s.to_string_lossy()),
+                    Value::Integer(_) | Value::Number(_) => text.push_str(&arg.to_string()?),
+                    _ => {
+                        return Err(mlua::Error::RuntimeError(format!(
+                            "bad argument #{} to 'write' (string expected, got {})",
+                            i + 1,
+                            arg.type_name()
+                        )));
+                    }
+                }
+            }
+            capture.write(&bundle, stream, &text);
+            Ok(this)
+        })?,
+    )?;
+    object.set("flush", lua.create_function(|_, this: Table| Ok(this))?)?;
+    object.set("setvbuf", lua.create_function(|_, _: MultiValue| Ok(true))?)?;
+
+    Ok(object)
+}
@@ -0,0 +1,196 @@
+//! Introspection of plugins: the `plugin` global describing the plugin
+//! itself, the `api.list_plugins` function enumerating the plugins of the
+//! host, the `api.describe_function` function documenting their functions
+//! and the `api.list_functions` function listing their signatures
+
+use std::{collections::HashMap, sync::Arc};
+
+use mlua::{Lua, LuaSerdeExt, SerializeOptions, Table, Value};
+use plux_rs::{Api, StdInfo, function::FunctionOutput};
+
+use crate::config::Config;
+use crate::error::ManagerError;
+use crate::lua::{api::VersionSpec, env};
+use crate::manager::{PluginVisibility, Registrations};
+use crate::sync::RwLockExt;
+
+/// Sets the global `plugin` table describing the plugin of `api`
+///
+/// The table holds the plugin's `id`, `version`, `format`, the `path` it was
+/// registered with, its parsed `config`, read-only, and whether each of its `features` is
+/// enabled.
+pub fn register_plugin_info(
+    lua: &Lua,
+    api: &Api<FunctionOutput, StdInfo>,
+    config: &Config,
+    features: &HashMap<String, bool>,
+) -> Result<(), ManagerError> {
+    let bundle = api.plugin();
+    let info = lua.create_table()?;
+    info.set("id", bundle.id.as_str())?;
+    info.set("version", bundle.version.to_string())?;
+    info.set("format", bundle.format.as_str())?;
+    if let Some(plugin) = api.get_plugin(&bundle.id, &bundle.version) {
+        info.set("path", plugin.info().path.to_string_lossy())?;
+    }
+    let options = SerializeOptions::new().serialize_none_to_null(false);
+    let config = match lua.to_value_with(config, options)? {
+        Value::Table(config) => config,
+        _ => unreachable!("configs serialize to tables"),
+    };
+    env::freeze(lua, &config, "plugin.config")?;
+    info.set("config", config)?;
+    info.set("features", lua.create_table_from(features.clone())?)?;
+
+    lua.globals().set("plugin", info)?;
+    Ok(())
+}
+
+/// Registers `api.list_plugins`, listing the plugins `visibility` lets the
+/// plugin of `api` see
+pub fn register_list_plugins(
+    lua: &Lua,
+    api: Arc<Api<FunctionOutput, StdInfo>>,
+    registered: Registrations,
+    visibility: Option<PluginVisibility>,
+) -> Result<(), ManagerError> {
+    let list_plugins = lua.create_function(move |ctx, ()| {
+        let viewer = api.plugin();
+        let registered = registered.read_unpoisoned();
+        let list = ctx.create_table()?;
+        for plugin in api.get_plugins() {
+            let bundle = &plugin.info().bundle;
+            if let Some(visible) = &visibility
+                && !visible(viewer, bundle)
+            {
+                continue;
+            }
+
+            let entry = ctx.create_table()?;
+            entry.set("id", bundle.id.as_str())?;
+            entry.set("version", bundle.version.to_string())?;
+            entry.set("format", bundle.format.as_str())?;
+            entry.set("loaded", plugin.is_load())?;
+            if let Some(registration) = registered.get(bundle) {
+                let metadata = &registration.metadata;
+                entry.set("name", metadata.name.as_str())?;
+                entry.set("description", metadata.description.as_str())?;
+                entry.set("author", metadata.author.as_str())?;
+                entry.set("license", metadata.license.as_deref())?;
+            }
+            list.push(entry)?;
+        }
+        Ok(list)
+    })?;
+
+    let api_table: Table = lua.globals().get("api")?;
+    api_table.set("list_plugins", list_plugins)?;
+
+    Ok(())
+}
+
+/// Registers `api.describe_function(plugin, name)`, returning the
+/// documentation of the function `name` of the newest loaded version of the
+/// plugin `plugin` that `visibility` lets the plugin of `api` see, or `nil`
+pub fn register_describe_function(
+    lua: &Lua,
+    api: Arc<Api<FunctionOutput, StdInfo>>,
+    registered: Registrations,
+    visibility: Option<PluginVisibility>,
+) -> Result<(), ManagerError> {
+    let describe_function = lua.create_function(move |ctx, (id, name): (String, String)| {
+        let viewer = api.plugin();
+        let registered = registered.read_unpoisoned();
+        let doc = registered
+            .iter()
+            .filter(|(bundle, _)| bundle.id == id)
+            .filter(|(bundle, _)| {
+                visibility
+                    .as_ref()
+                    .is_none_or(|visible| visible(viewer, bundle))
+            })
+            .filter_map(|(bundle, registration)| Some((bundle, registration.functions.get(&name)?)))
+            .max_by(|(a, _), (b, _)| a.version.cmp(&b.version))
+            .map(|(_, info)| &info.doc);
+        let Some(doc) = doc else {
+            return Ok(None);
+        };
+
+        let table = ctx.create_table()?;
+        table.set("name", name)?;
+        table.set("description", doc.description.as_deref())?;
+        table.set("examples", doc.examples.as_slice())?;
+        table.set("since", doc.since.as_deref())?;
+        Ok(Some(table))
+    })?;
+
+    let api_table: Table = lua.globals().get("api")?;
+    api_table.set("describe_function", describe_function)?;
+
+    Ok(())
+}
+
+/// Registers `api.list_functions(plugin, version)`, returning the signatures
+/// and documentation of the functions of the loaded plugin `plugin` that
+/// `visibility` lets the plugin of `api` see, or `nil`
+///
+/// The version is an exact version, a semver requirement resolved to the
+/// highest loaded version matching it, or `nil` for the newest loaded version.
+/// Each function is a table of its `name`, `inputs`, each with a `name`,
+/// `type`, `optional` and `variadic` field, `output` type, `description`,
+/// `examples` and `since`.
+pub fn register_list_functions(
+    lua: &Lua,
+    api: Arc<Api<FunctionOutput, StdInfo>>,
+    registered: Registrations,
+    visibility: Option<PluginVisibility>,
+) -> Result<(), ManagerError> {
+    let list_functions =
+        lua.create_function(move |ctx, (id, version): (String, Option<String>)| {
+            let spec = version.as_deref().map(VersionSpec::new).transpose()?;
+
+            let viewer = api.plugin();
+            let registered = registered.read_unpoisoned();
+            let functions = registered
+                .iter()
+                .filter(|(bundle, _)| {
+                    bundle.id == id
+                        && spec
+                            .as_ref()
+                            .is_none_or(|spec| spec.matches(&bundle.version))
+                })
+                .filter(|(bundle, _)| {
+                    api.get_plugin(&bundle.id, &bundle.version)
+                        .is_some_and(|plugin| plugin.is_load())
+                })
+                .filter(|(bundle, _)| {
+                    visibility
+                        .as_ref()
+                        .is_none_or(|visible| visible(viewer, bundle))
+                })
+                .max_by(|(a, _), (b, _)| a.version.cmp(&b.version))
+                .map(|(_, registration)| &registration.functions);
+            let Some(functions) = functions else {
+                return Ok(None);
+            };
+
+            let options = SerializeOptions::new().serialize_none_to_null(false);
+            let list = ctx.create_table()?;
+            for info in functions.values() {
+                let entry = ctx.create_table()?;
+                entry.set("name", info.name.as_str())?;
+                entry.set("inputs", ctx.to_value_with(&info.inputs, options)?)?;
+                entry.set("output", info.output.as_str())?;
+                entry.set("description", info.doc.description.as_deref())?;
+                entry.set("examples", info.doc.examples.as_slice())?;
+                entry.set("since", info.doc.since.as_deref())?;
+                list.push(entry)?;
+            }
+            Ok(Some(list))
+        })?;
+
+    let api_table: Table = lua.globals().get("api")?;
+    api_table.set("list_functions", list_functions)?;
+
+    Ok(())
+}
@@ -1,81 +1,212 @@
 //! Request handling for Lua plugins
 
-use std::sync::{Arc, Mutex};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
-use mlua::{Lua, MultiValue, Value};
+use mlua::{Function, Lua, MultiValue, Table, Value};
 use plux_rs::{
-    Requests,
+    Bundle, Requests,
     function::{Arg, DynamicFunction, Request},
+    variable::{Variable, VariableType},
 };
 
-use super::conversion::{lua_to_plux, plux_to_lua};
+use super::conversion::{conversion_options, output_from_lua, plux_to_lua_with};
+use super::env;
+use super::exports::EXPORTS_KEY;
+use super::state::StateSlot;
 use crate::error::{ManagerError, PluginError};
+use crate::health::CallGate;
+
+/// How the manager registers requests, see
+/// [`crate::LuaManager::with_optional_request`] and
+/// [`crate::LuaManager::with_request_inputs`]
+#[derive(Debug, Clone, Default)]
+pub struct RequestOptions {
+    /// Requests plugins may leave unhandled
+    pub optional: HashSet<String>,
+    /// Names of the arguments of requests
+    pub inputs: HashMap<String, Vec<String>>,
+}
 
 /// Registers functions that the plugin has requested
+///
+/// The functions do not keep the plugin's state alive, once the plugin is
+/// unloaded they fail with [`PluginError::NotLoaded`]. Calls go through `gate`.
+///
+/// Optional requests that the plugin does not handle are reported in
+/// `diagnostics` and return `nil`.
 pub fn register_requests(
-    lua: &Arc<Mutex<Lua>>,
+    lua: &Arc<StateSlot>,
+    bundle: &Bundle,
     requests: &Requests,
+    gate: &CallGate,
+    options: &RequestOptions,
+    diagnostics: &mut Vec<String>,
 ) -> Result<Vec<DynamicFunction>, ManagerError> {
     requests.iter().try_fold(vec![], |mut registered, request| {
-        let function = register_request(lua, request)?;
+        let optional = options.optional.contains(&request.name);
+        // Make sure the handler exists up front, unless the state is created lazily
+        if let Some(lua) = lua.peek()?
+            && get_request_handler(&lua, &request.name)?.is_none()
+        {
+            if !optional {
+                return Err(missing_handler(&request.name));
+            }
+            diagnostics.push(format!(
+                "optional request `{}` is not handled",
+                request.name
+            ));
+        }
+
+        let names = options.inputs.get(&request.name).map(Vec::as_slice);
+        let function = register_request(
+            lua,
+            bundle,
+            request,
+            names.unwrap_or_default(),
+            gate.clone(),
+            optional,
+        )?;
         registered.push(function);
         Ok(registered)
     })
 }
 
-/// Registers a single request
+/// Registers a single request, with its arguments named after `names`
+///
+/// A request whose last input is a `list` is variadic: when called with more
+/// arguments than declared, the surplus ones are collected with the last one
+/// into a list.
 fn register_request(
-    lua: &Arc<Mutex<Lua>>,
+    lua: &Arc<StateSlot>,
+    bundle: &Bundle,
     request: &Request,
+    names: &[String],
+    gate: CallGate,
+    optional: bool,
 ) -> Result<DynamicFunction, ManagerError> {
-    let lua_function = lua
-        .lock()
-        .unwrap()
-        .globals()
-        .get(request.name.clone())
-        .map(|value: Value| match value {
-            Value::Function(f) => Ok(f.clone()),
-            Value::Nil => Err(ManagerError::Plugin(PluginError::SourceError(format!(
-                "Request `{}` does not exist",
-                request.name
-            )))),
-            _ => Err(ManagerError::Plugin(PluginError::SourceError(format!(
-                "`{}` should be a function",
-                request.name
-            )))),
-        })
-        .map_err(|e| ManagerError::Lua(e))
-        .flatten()?;
+    let lua_weak = Arc::downgrade(lua);
+    let bundle = bundle.clone();
+    let name = request.name.clone();
+    let variadic = request
+        .inputs
+        .len()
+        .checked_sub(1)
+        .filter(|_| request.inputs.last() == Some(&VariableType::List));
 
-    let lua_clone = lua.clone();
+    let inputs: Vec<Arg> = request
+        .inputs
+        .iter()
+        .enumerate()
+        .map(|(index, ty)| {
+            let name = names
+                .get(index)
+                .cloned()
+                .unwrap_or_else(|| format!("arg_{}", index));
+            Arg::new(name, *ty)
+        })
+        .collect();
+    let arg_names: Vec<String> = inputs.iter().map(|input| input.name.clone()).collect();
 
     let function = DynamicFunction::new(
         request.name.clone(),
-        request
-            .inputs
-            .iter()
-            .enumerate()
-            .map(|(index, ty)| {
-                let name = format!("arg_{}", index);
-                Arg::new(name, ty.clone())
-            })
-            .collect(),
-        request
-            .output
-            .map(|output| Arg::new("output", output.clone())),
+        inputs,
+        request.output.map(|output| Arg::new("output", output)),
         move |args| {
-            let mut lua_args = vec![];
-            for arg in args {
-                lua_args.push(plux_to_lua(arg, &*lua_clone.lock().unwrap())?);
-            }
+            #[cfg(feature = "tracing")]
+            let _span = if log::Level::Debug <= gate.log_level {
+                tracing::debug_span!("request", bundle = %bundle, request = %name).entered()
+            } else {
+                tracing::Span::none().entered()
+            };
 
-            let result = match lua_function.call::<Value>(MultiValue::from_vec(lua_args))? {
-                Value::Nil => Ok(None),
-                value => Ok(Some(lua_to_plux(&value)?)),
+            let collected;
+            let args = match variadic {
+                Some(position) if args.len() > position + 1 => {
+                    collected = collect_surplus(args, position);
+                    &collected
+                }
+                _ => args,
             };
-            result
+
+            let output = gate.run(&bundle, &name, args, |args| {
+                // The handler is resolved on every call so that it follows plugin reloads
+                let lua = lua_weak
+                    .upgrade()
+                    .ok_or_else(|| PluginError::NotLoaded(bundle.to_string()))?
+                    .get()?;
+                let lua_function = match get_request_handler(&lua, &name)? {
+                    Some(lua_function) => lua_function,
+                    None if optional => return Ok(None),
+                    None => return Err(missing_handler(&name)),
+                };
+                let options = conversion_options(&lua);
+
+                let mut lua_args = Vec::with_capacity(args.len());
+                for (index, arg) in args.iter().enumerate() {
+                    let lua_arg = plux_to_lua_with(arg, &lua, options.strings).map_err(|e| {
+                        let arg_name = arg_names
+                            .get(index)
+                            .cloned()
+                            .unwrap_or_else(|| format!("arg_{}", index));
+                        mlua::Error::RuntimeError(format!(
+                            "Request `{name}`: argument `{arg_name}`: {e}"
+                        ))
+                    })?;
+                    lua_args.push(lua_arg);
+                }
+
+                let output = lua_function.call::<MultiValue>(MultiValue::from_vec(lua_args))?;
+                Ok(output_from_lua(&output, &options, &name)?)
+            })?;
+            Ok(output)
         },
     );
 
     Ok(function)
 }
+
+/// Collects the arguments of a call from `position` on into a list
+fn collect_surplus(args: &[Variable], position: usize) -> Vec<Variable> {
+    let mut collected = args[..position].to_vec();
+    collected.push(Variable::List(args[position..].to_vec()));
+    collected
+}
+
+/// Looks up the function handling a request, `None` if the plugin does not
+/// define it
+///
+/// The handler is the global named after the request, where a dotted name such
+/// as `handlers.on_tick` walks nested tables, or else the function of that
+/// name exported by the entry script.
+fn get_request_handler(lua: &Lua, name: &str) -> Result<Option<Function>, ManagerError> {
+    let mut value = Value::Table(env::env(lua)?);
+    for segment in name.split('.') {
+        value = match value {
+            Value::Table(table) => table.get(segment)?,
+            _ => Value::Nil,
+        };
+    }
+
+    match value {
+        Value::Function(f) => Ok(Some(f)),
+        Value::Nil => match lua.named_registry_value::<Option<Table>>(EXPORTS_KEY)? {
+            Some(exports) => Ok(exports.get(name)?),
+            None => Ok(None),
+        },
+        _ => Err(ManagerError::Plugin(PluginError::SourceError(format!(
+            "`{}` should be a function",
+            name
+        )))),
+    }
+}
+
+/// Error of a plugin not handling a request
+fn missing_handler(name: &str) -> ManagerError {
+    ManagerError::Plugin(PluginError::SourceError(format!(
+        "Request `{}` does not exist",
+        name
+    )))
+}
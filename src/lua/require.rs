@@ -0,0 +1,197 @@
+//! Module resolution for `require` in Lua plugins
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use mlua::{Function, Lua, Table, Value};
+use plux_rs::Bundle;
+
+use super::env;
+use crate::bytecode::BytecodeCache;
+use crate::error::ManagerError;
+use crate::source::{
+    FsSourceProvider, ModuleResolver, SourceProvider, SourceTransforms, transform_source,
+};
+
+/// Installs a package searcher resolving modules through the plugin's source provider
+///
+/// `require("a.b")` looks for `a/b.lua` and then `a/b/init.lua`, then for
+/// `a/b.<ext>` and `a/b/init.<ext>` for the extension of each of
+/// `transforms`, in registration order. The searcher
+/// runs right after `package.preload`, before the default searchers. Modules
+/// are compiled through `cache` when bytecode caching is enabled.
+///
+/// Only modules inside the plugin root are resolved: names that are not
+/// dot-separated lists of path components, such as `../other` or
+/// `/etc/module`, are an error.
+pub fn register_searcher(
+    lua: &Lua,
+    provider: Arc<dyn SourceProvider>,
+    cache: Option<Arc<BytecodeCache>>,
+    transforms: SourceTransforms,
+) -> Result<(), ManagerError> {
+    let searcher = lua.create_function(move |ctx, name: String| {
+        let base = module_path(&name)
+            .ok_or_else(|| mlua::Error::RuntimeError(format!("invalid module name '{name}'")))?;
+        let mut candidates = vec![format!("{base}.lua"), format!("{base}/init.lua")];
+        for extension in transforms.keys() {
+            candidates.extend([
+                format!("{base}.{extension}"),
+                format!("{base}/init.{extension}"),
+            ]);
+        }
+
+        for candidate in candidates {
+            if provider.exists(&candidate) {
+                let src = provider
+                    .read_source(&candidate)
+                    .map_err(mlua::Error::external)?;
+                let src = transform_source(&transforms, &candidate, src)
+                    .map_err(mlua::Error::external)?;
+                let cache_dir = provider.cache_dir();
+                let loader = load_module(ctx, &candidate, &src, &cache, cache_dir.as_deref())?;
+                return Ok(Value::Function(loader));
+            }
+        }
+
+        Ok(Value::String(ctx.create_string(format!(
+            "\n\tno module '{base}.lua' in plugin sources"
+        ))?))
+    })?;
+
+    insert_searcher(lua, searcher)
+}
+
+/// Installs a package searcher asking the host's resolvers for modules
+///
+/// The searcher runs after the one of the plugin's sources, so resolvers only
+/// see the modules the plugin does not ship. Resolvers are asked in order.
+pub fn register_resolvers(
+    lua: &Lua,
+    bundle: &Bundle,
+    resolvers: Arc<Vec<Arc<dyn ModuleResolver>>>,
+    cache: Option<Arc<BytecodeCache>>,
+) -> Result<(), ManagerError> {
+    if resolvers.is_empty() {
+        return Ok(());
+    }
+
+    let bundle = bundle.clone();
+    let searcher = lua.create_function(move |ctx, name: String| {
+        for resolver in resolvers.iter() {
+            if let Some(src) = resolver
+                .resolve(&bundle, &name)
+                .map_err(mlua::Error::external)?
+            {
+                let loader = load_module(ctx, &name, &src, &cache, None)?;
+                return Ok(Value::Function(loader));
+            }
+        }
+
+        Ok(Value::String(ctx.create_string(format!(
+            "\n\tno module '{name}' in host resolvers"
+        ))?))
+    })?;
+
+    insert_searcher(lua, searcher)
+}
+
+/// Compiles a module found by a searcher.
+fn load_module(
+    lua: &Lua,
+    path: &str,
+    src: &str,
+    cache: &Option<Arc<BytecodeCache>>,
+    cache_dir: Option<&Path>,
+) -> mlua::Result<Function> {
+    let name = format!("@{path}");
+    let chunk = match cache {
+        Some(cache) => cache.load(lua, &name, src, cache_dir)?,
+        None => lua.load(src).set_name(name),
+    };
+    chunk.set_environment(env::env(lua)?).into_function()
+}
+
+/// Inserts `searcher` right after `package.preload`.
+fn insert_searcher(lua: &Lua, searcher: Function) -> Result<(), ManagerError> {
+    let package: Table = lua.globals().get("package")?;
+    let searchers: Table = match package.get::<Option<Table>>("searchers")? {
+        Some(searchers) => searchers,
+        None => package.get("loaders")?,
+    };
+
+    // Shift the existing searchers to make room right after `package.preload`
+    let len = searchers.raw_len();
+    for index in (2..=len).rev() {
+        searchers.raw_set(index + 1, searchers.raw_get::<Value>(index)?)?;
+    }
+    searchers.raw_set(2, searcher)?;
+
+    Ok(())
+}
+
+/// Resolves modules from directories of Lua modules shared by all plugins.
+pub(crate) struct LibDirResolver {
+    dirs: Vec<FsSourceProvider>,
+}
+
+impl LibDirResolver {
+    pub(crate) fn new(dirs: Vec<PathBuf>) -> Self {
+        Self {
+            dirs: dirs.into_iter().map(FsSourceProvider::new).collect(),
+        }
+    }
+}
+
+impl ModuleResolver for LibDirResolver {
+    fn resolve(&self, _bundle: &Bundle, name: &str) -> io::Result<Option<String>> {
+        let Some(base) = module_path(name) else {
+            return Ok(None);
+        };
+        for dir in self.dirs.iter() {
+            for candidate in [format!("{base}.lua"), format!("{base}/init.lua")] {
+                if dir.exists(&candidate) {
+                    return dir.read_source(&candidate).map(Some);
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Returns the path of module `name` relative to the plugin root, without
+/// extension, or `None` if the name could point outside of the root.
+fn module_path(name: &str) -> Option<String> {
+    let valid = name.split('.').all(|component| {
+        !component.is_empty()
+            && !component
+                .chars()
+                .any(|c| matches!(c, '/' | '\\' | ':' | '\0'))
+    });
+    valid.then(|| name.replace('.', "/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_module_path() {
+        assert_eq!(module_path("util"), Some("util".to_string()));
+        assert_eq!(module_path("lib.util"), Some("lib/util".to_string()));
+        for name in [
+            "",
+            "..util",
+            "lib..util",
+            "lib.",
+            "../util",
+            "/etc/util",
+            "c:util",
+        ] {
+            assert_eq!(module_path(name), None, "{name}");
+        }
+    }
+}
@@ -0,0 +1,70 @@
+//! The `settings` global giving plugins their user settings
+
+use std::sync::Arc;
+
+use mlua::{Function, Lua, Table, Value};
+use plux_rs::variable::Variable;
+
+use crate::error::ManagerError;
+use crate::lua::conversion::{conversion_options, lua_to_plux_with, plux_to_lua_with};
+use crate::settings::Settings;
+
+/// Name of the Lua registry value holding the handlers of setting changes.
+pub const SETTINGS_KEY: &str = "plux_settings_handlers";
+
+/// Sets the global `settings` table with `settings.get(name)`,
+/// `settings.set(name, value)` and `settings.on_change(handler)`
+///
+/// Setting a value to `nil` resets it to its default. Handlers are called
+/// with the name of the setting, its new value and its previous one, whether
+/// the plugin or the host changed it.
+pub fn register_settings(lua: &Lua, settings: Arc<Settings>) -> Result<(), ManagerError> {
+    let table = lua.create_table()?;
+
+    let get = {
+        let settings = settings.clone();
+        lua.create_function(move |ctx, name: String| {
+            let value = settings.get(&name).map_err(mlua::Error::external)?;
+            plux_to_lua_with(&value, ctx, conversion_options(ctx).strings)
+        })?
+    };
+    table.set("get", get)?;
+
+    let set = lua.create_function(move |ctx, (name, value): (String, Value)| {
+        let value = lua_to_plux_with(&value, &conversion_options(ctx))?;
+        let (value, old) = settings.set(&name, value).map_err(mlua::Error::external)?;
+        notify_change(ctx, &name, &value, &old)
+    })?;
+    table.set("set", set)?;
+
+    // Looked up on every call, the registry value being per plugin in a
+    // shared state
+    let on_change = lua.create_function(|ctx, handler: Function| {
+        ctx.named_registry_value::<Table>(SETTINGS_KEY)?
+            .raw_push(handler)
+    })?;
+    table.set("on_change", on_change)?;
+
+    lua.set_named_registry_value(SETTINGS_KEY, lua.create_table()?)?;
+    lua.globals().set("settings", table)?;
+    Ok(())
+}
+
+/// Calls the handlers of setting changes with the name of the setting `name`,
+/// its new `value` and its `old` one
+///
+/// A failing handler does not prevent the others from running.
+pub fn notify_change(lua: &Lua, name: &str, value: &Variable, old: &Variable) -> mlua::Result<()> {
+    let handlers: Table = lua.named_registry_value(SETTINGS_KEY)?;
+    let strings = conversion_options(lua).strings;
+    let value = plux_to_lua_with(value, lua, strings)?;
+    let old = plux_to_lua_with(old, lua, strings)?;
+
+    for handler in handlers.sequence_values::<Function>() {
+        if let Err(e) = handler?.call::<()>((name, value.clone(), old.clone())) {
+            log::error!("Handler of setting `{name}` changing failed: {e}");
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,122 @@
+//! The `api.shared` and `api.store` tables giving plugins access to the
+//! host's shared channels and key-value store
+
+use mlua::{Lua, Table, Value};
+
+use crate::error::ManagerError;
+use crate::lua::conversion::{conversion_options, lua_to_plux_with, plux_to_lua_with};
+use crate::lua::errors;
+use crate::shared::{KeyValueStore, SharedStore};
+use crate::sync::RwLockExt;
+
+/// Registers `api.shared.get` and `api.shared.set` for the plugin `id`
+pub fn register_shared(lua: &Lua, id: &str, store: SharedStore) -> Result<(), ManagerError> {
+    let shared = lua.create_table()?;
+
+    let get = {
+        let id = id.to_string();
+        let store = store.clone();
+        lua.create_function(move |ctx, (channel, key): (String, String)| {
+            let channels = store.read_unpoisoned();
+            let Some(shared) = channels.get(&channel) else {
+                return errors::failure(ctx, errors::ACCESS_DENIED, unknown_channel(&channel));
+            };
+            if !shared.policy.can_read(&id) {
+                return errors::failure(ctx, errors::ACCESS_DENIED, denied(&id, "read", &channel));
+            }
+
+            let value = match shared.values.get(&key) {
+                Some(var) => plux_to_lua_with(var, ctx, conversion_options(ctx).strings)?,
+                None => Value::Nil,
+            };
+            errors::success(ctx, value)
+        })?
+    };
+    shared.set("get", errors::structured(lua, get)?)?;
+
+    let set = {
+        let id = id.to_string();
+        lua.create_function(move |ctx, (channel, key, value): (String, String, Value)| {
+            let var = lua_to_plux_with(&value, &conversion_options(ctx))?;
+
+            let mut channels = store.write_unpoisoned();
+            let Some(shared) = channels.get_mut(&channel) else {
+                return errors::failure(ctx, errors::ACCESS_DENIED, unknown_channel(&channel));
+            };
+            if !shared.policy.can_write(&id) {
+                return errors::failure(ctx, errors::ACCESS_DENIED, denied(&id, "write", &channel));
+            }
+
+            match value {
+                Value::Nil => shared.values.remove(&key),
+                _ => shared.values.insert(key, var),
+            };
+            errors::success(ctx, Value::Nil)
+        })?
+    };
+    shared.set("set", errors::structured(lua, set)?)?;
+
+    let api_table: Table = lua.globals().get("api")?;
+    api_table.set("shared", shared)?;
+
+    Ok(())
+}
+
+/// Registers `api.store.get`, `set`, `delete` and `keys`
+pub fn register_store(lua: &Lua, store: KeyValueStore) -> Result<(), ManagerError> {
+    let table = lua.create_table()?;
+
+    let get = {
+        let store = store.clone();
+        lua.create_function(
+            move |ctx, key: String| match store.read_unpoisoned().get(&key) {
+                Some(var) => plux_to_lua_with(var, ctx, conversion_options(ctx).strings),
+                None => Ok(Value::Nil),
+            },
+        )?
+    };
+    table.set("get", get)?;
+
+    let set = {
+        let store = store.clone();
+        lua.create_function(move |ctx, (key, value): (String, Value)| {
+            let mut values = store.write_unpoisoned();
+            match value {
+                Value::Nil => {
+                    values.shift_remove(&key);
+                }
+                _ => {
+                    values.insert(key, lua_to_plux_with(&value, &conversion_options(ctx))?);
+                }
+            }
+            Ok(())
+        })?
+    };
+    table.set("set", set)?;
+
+    let delete = {
+        let store = store.clone();
+        lua.create_function(move |_, key: String| {
+            Ok(store.write_unpoisoned().shift_remove(&key).is_some())
+        })?
+    };
+    table.set("delete", delete)?;
+
+    let keys = lua.create_function(move |_, ()| {
+        Ok(store.read_unpoisoned().keys().cloned().collect::<Vec<_>>())
+    })?;
+    table.set("keys", keys)?;
+
+    let api_table: Table = lua.globals().get("api")?;
+    api_table.set("store", table)?;
+
+    Ok(())
+}
+
+fn unknown_channel(channel: &str) -> String {
+    format!("shared channel `{channel}` does not exist")
+}
+
+fn denied(id: &str, access: &str, channel: &str) -> String {
+    format!("plugin `{id}` may not {access} shared channel `{channel}`")
+}
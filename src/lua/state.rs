@@ -0,0 +1,507 @@
+//! The Lua state of a loaded plugin
+//!
+//! A plugin keeps one state for its lifetime, replaced in place on reload.
+//! [`StateSlot`] only guards which state is current: its lock is held just
+//! long enough to clone the handle, never while Lua code runs.
+//!
+//! A call converts its arguments, runs the Lua function and converts its
+//! output on that handle. mlua serializes the use of a state behind its own
+//! reentrant lock, held for the whole run of a Lua function, so a call blocks
+//! calls into the same plugin from other threads until it returns, while a
+//! call from the same thread reenters the plugin. This is what lets a plugin
+//! call a dependency, or a host function, that calls back into it, up to
+//! [`crate::MAX_CALL_DEPTH`] nested calls.
+//!
+//! Plugins calling each other from several threads at once can still
+//! deadlock, each thread holding the state the other one waits for.
+//!
+//! The state of a lazily loaded plugin is only created by the first call
+//! needing it, see [`crate::LuaManager::with_lazy_loading`].
+//!
+//! # Shared state
+//!
+//! With [`crate::LuaManager::with_shared_state`], all plugins live in one
+//! [`SharedLua`]. Each plugin then owns a context: its own copy of the
+//! globals and standard library tables, its registry values (exports, event
+//! handlers, environment, ...) and its app data (conversion policies,
+//! capabilities, API scope, watchdog). Entering a plugin swaps its context
+//! in and holds a lock of the shared state, leaving restores the previous
+//! context. The lock is reentrant, so plugins still call each other on the
+//! same thread, and calls into any plugin from other threads wait for the
+//! outermost call.
+
+use std::{
+    marker::PhantomData,
+    ops::Deref,
+    sync::{Arc, Condvar, Mutex, PoisonError},
+    thread::{self, ThreadId},
+};
+
+use mlua::{Function, Lua, Table, Value};
+use plux_rs::Bundle;
+
+#[cfg(feature = "debug")]
+use super::hooks::Debugger;
+use super::{
+    capabilities::{ApiScope, Capabilities},
+    conversion::{MetamethodGuard, StrictNils},
+    env::ENV_KEY,
+    events::{HANDLERS_KEY, LIFECYCLE_KEY},
+    exports::{ASYNC_EXPORTS_KEY, EXPORTS_KEY},
+    settings::SETTINGS_KEY,
+    tasks::TASKS_KEY,
+    watchdog::Watchdog,
+};
+#[cfg(feature = "debug")]
+use crate::coverage::CoverageRecorder;
+use crate::{
+    config::{NumberPolicy, StringPolicy},
+    error::{ManagerError, PluginError},
+    sandbox::SandboxPolicy,
+    sync::{MutexExt, ReentrantGuard, ReentrantLock},
+};
+
+/// Registry values owned by each plugin of a shared state
+const PLUGIN_KEYS: &[&str] = &[
+    EXPORTS_KEY,
+    ASYNC_EXPORTS_KEY,
+    ENV_KEY,
+    HANDLERS_KEY,
+    LIFECYCLE_KEY,
+    SETTINGS_KEY,
+    TASKS_KEY,
+];
+
+/// Creates the globals of a new plugin from the pristine ones
+///
+/// Library tables are copied, so that plugins patching them, or losing some
+/// of their functions to their permissions, do not affect each other. The
+/// copy of `package` has its own `loaded` modules and searchers, and
+/// `require` is reimplemented on top of it: the standard one is bound to the
+/// `package` table of the state.
+const FRESH_GLOBALS: &str = r#"
+    local base = ...
+    local next, type, tostring, ipairs, error = next, type, tostring, ipairs, error
+
+    local function copy(t)
+        local c = {}
+        for k, v in next, t do
+            c[k] = v
+        end
+        return c
+    end
+
+    local function require_from(package)
+        return function(name)
+            local loaded = package.loaded
+            if loaded[name] ~= nil then
+                return loaded[name]
+            end
+            local message = ""
+            for _, searcher in ipairs(package.searchers or package.loaders) do
+                local loader, data = searcher(name)
+                if type(loader) == "function" then
+                    local module = loader(name, data)
+                    if module ~= nil then
+                        loaded[name] = module
+                    elseif loaded[name] == nil then
+                        loaded[name] = true
+                    end
+                    return loaded[name], data
+                elseif type(loader) == "string" then
+                    message = message .. loader
+                end
+            end
+            error("module '" .. tostring(name) .. "' not found:" .. message, 2)
+        end
+    end
+
+    return function()
+        local globals, copies = {}, {}
+        for name, value in next, base do
+            if type(value) == "table" and value ~= base then
+                copies[value] = copy(value)
+                globals[name] = copies[value]
+            else
+                globals[name] = value
+            end
+        end
+        globals._G = globals
+        copies[base] = globals
+
+        local package = globals.package
+        if package then
+            local loaded = {}
+            for name, module in next, package.loaded do
+                loaded[name] = copies[module] or module
+            end
+            package.loaded = loaded
+            package.searchers = package.searchers and copy(package.searchers)
+            package.loaders = package.loaders and copy(package.loaders)
+            globals.require = require_from(package)
+        end
+        return globals
+    end
+"#;
+
+/// One Lua state hosting every plugin of a manager
+pub struct SharedLua {
+    lua: Lua,
+    fresh_globals: Function,
+    lock: Arc<ReentrantLock>,
+}
+
+impl SharedLua {
+    /// Creates the state with the libraries of `sandbox`
+    pub fn new(sandbox: &SandboxPolicy) -> mlua::Result<Self> {
+        let lua = sandbox.create_lua()?;
+        let fresh_globals = lua
+            .load(FRESH_GLOBALS)
+            .set_name("=[plux state]")
+            .call(lua.globals())?;
+        Ok(Self {
+            lua,
+            fresh_globals,
+            lock: Arc::default(),
+        })
+    }
+
+    /// Enters a new plugin context, with fresh globals and without registry
+    /// values or app data
+    pub fn enter_new(self: &Arc<Self>) -> mlua::Result<ScopedLua> {
+        let guard = self.lock.lock();
+        let previous = Context::capture(&self.lua)?;
+        let context = Context::empty(self.fresh_globals.call(())?);
+        context.restore(&self.lua)?;
+        Ok(ScopedLua::shared(self.clone(), previous, guard))
+    }
+}
+
+/// What a plugin of a shared state owns in it
+struct Context {
+    globals: Table,
+    registry: Vec<Value>,
+    strings: Option<StringPolicy>,
+    numbers: Option<NumberPolicy>,
+    capabilities: Option<Capabilities>,
+    scope: Option<ApiScope>,
+    strict_nils: bool,
+    metamethods: Option<MetamethodGuard>,
+    watchdog: Option<Watchdog>,
+    #[cfg(feature = "debug")]
+    debugger: Option<Debugger>,
+    #[cfg(feature = "debug")]
+    coverage: Option<CoverageRecorder>,
+}
+
+impl Context {
+    fn empty(globals: Table) -> Self {
+        Self {
+            globals,
+            registry: vec![Value::Nil; PLUGIN_KEYS.len()],
+            strings: None,
+            numbers: None,
+            capabilities: None,
+            scope: None,
+            strict_nils: false,
+            metamethods: None,
+            watchdog: None,
+            #[cfg(feature = "debug")]
+            debugger: None,
+            #[cfg(feature = "debug")]
+            coverage: None,
+        }
+    }
+
+    /// Captures the context currently active in `lua`
+    fn capture(lua: &Lua) -> mlua::Result<Self> {
+        let mut registry = Vec::with_capacity(PLUGIN_KEYS.len());
+        for key in PLUGIN_KEYS {
+            registry.push(lua.named_registry_value(key)?);
+        }
+
+        Ok(Self {
+            globals: lua.globals(),
+            registry,
+            strings: lua.app_data_ref::<StringPolicy>().map(|policy| *policy),
+            numbers: lua.app_data_ref::<NumberPolicy>().map(|policy| *policy),
+            capabilities: lua
+                .app_data_ref::<Capabilities>()
+                .map(|capabilities| capabilities.clone()),
+            scope: lua.app_data_ref::<ApiScope>().map(|scope| scope.clone()),
+            strict_nils: lua.app_data_ref::<StrictNils>().is_some(),
+            metamethods: lua
+                .app_data_ref::<MetamethodGuard>()
+                .map(|guard| guard.clone()),
+            watchdog: lua
+                .app_data_ref::<Watchdog>()
+                .map(|watchdog| watchdog.clone()),
+            #[cfg(feature = "debug")]
+            debugger: lua
+                .app_data_ref::<Debugger>()
+                .map(|debugger| debugger.clone()),
+            #[cfg(feature = "debug")]
+            coverage: lua
+                .app_data_ref::<CoverageRecorder>()
+                .map(|recorder| recorder.clone()),
+        })
+    }
+
+    /// Makes this context the active one in `lua`
+    fn restore(&self, lua: &Lua) -> mlua::Result<()> {
+        lua.set_globals(self.globals.clone())?;
+        for (key, value) in PLUGIN_KEYS.iter().zip(self.registry.iter()) {
+            lua.set_named_registry_value(key, value)?;
+        }
+
+        set_app_data(lua, self.strings);
+        set_app_data(lua, self.numbers);
+        set_app_data(lua, self.capabilities.clone());
+        set_app_data(lua, self.scope.clone());
+        set_app_data(lua, self.strict_nils.then_some(StrictNils));
+        set_app_data(lua, self.metamethods.clone());
+        set_app_data(lua, self.watchdog.clone());
+        #[cfg(feature = "debug")]
+        set_app_data(lua, self.debugger.clone());
+        #[cfg(feature = "debug")]
+        set_app_data(lua, self.coverage.clone());
+        Ok(())
+    }
+}
+
+fn set_app_data<T: Send + 'static>(lua: &Lua, data: Option<T>) {
+    match data {
+        Some(data) => {
+            lua.set_app_data(data);
+        }
+        None => {
+            lua.remove_app_data::<T>();
+        }
+    }
+}
+
+/// A plugin's Lua state, in a state of its own or in a [`SharedLua`]
+#[derive(Clone)]
+pub struct PluginState {
+    lua: Lua,
+    shared: Option<(Arc<SharedLua>, Arc<Context>)>,
+}
+
+impl PluginState {
+    /// Returns the state, entered in the plugin's context until dropped
+    pub fn enter(&self) -> mlua::Result<ScopedLua> {
+        match &self.shared {
+            None => Ok(ScopedLua::isolated(self.lua.clone())),
+            Some((shared, context)) => {
+                let guard = shared.lock.lock();
+                let previous = Context::capture(&shared.lua)?;
+                let scoped = ScopedLua::shared(shared.clone(), previous, guard);
+                context.restore(&scoped)?;
+                Ok(scoped)
+            }
+        }
+    }
+
+    /// Returns the state, without entering the plugin's context
+    #[cfg(feature = "async")]
+    pub fn lua(&self) -> &Lua {
+        &self.lua
+    }
+
+    /// Runs `future`, entering the plugin's context for each of its polls
+    ///
+    /// A suspended call does not hold the shared state, and calls into other
+    /// plugins may run on the same thread in the meantime.
+    #[cfg(feature = "async")]
+    pub async fn scoped<T, E, F>(&self, future: F) -> Result<T, E>
+    where
+        E: From<mlua::Error>,
+        F: Future<Output = Result<T, E>>,
+    {
+        let mut future = std::pin::pin!(future);
+        std::future::poll_fn(|cx| {
+            let _lua = self.enter()?;
+            future.as_mut().poll(cx)
+        })
+        .await
+    }
+}
+
+/// A Lua state entered in the context of a plugin
+///
+/// For plugins of a [`SharedLua`], the shared state is locked and the
+/// plugin's context active until the handle is dropped. The lock belongs to
+/// the thread, so the handle cannot be sent to another one.
+pub struct ScopedLua {
+    lua: Lua,
+    scope: Option<Scope>,
+    _thread: PhantomData<*const ()>,
+}
+
+struct Scope {
+    shared: Arc<SharedLua>,
+    previous: Context,
+    _guard: ReentrantGuard,
+}
+
+impl ScopedLua {
+    /// Wraps a state of its own
+    pub fn isolated(lua: Lua) -> Self {
+        Self {
+            lua,
+            scope: None,
+            _thread: PhantomData,
+        }
+    }
+
+    fn shared(shared: Arc<SharedLua>, previous: Context, guard: ReentrantGuard) -> Self {
+        Self {
+            lua: shared.lua.clone(),
+            scope: Some(Scope {
+                shared,
+                previous,
+                _guard: guard,
+            }),
+            _thread: PhantomData,
+        }
+    }
+
+    /// Leaves the state, capturing the context built in it
+    pub fn into_state(self) -> mlua::Result<PluginState> {
+        let shared = match &self.scope {
+            None => None,
+            Some(scope) => Some((scope.shared.clone(), Arc::new(Context::capture(&self.lua)?))),
+        };
+        Ok(PluginState {
+            lua: self.lua.clone(),
+            shared,
+        })
+    }
+}
+
+impl Deref for ScopedLua {
+    type Target = Lua;
+
+    fn deref(&self) -> &Lua {
+        &self.lua
+    }
+}
+
+impl Drop for ScopedLua {
+    fn drop(&mut self) {
+        if let Some(scope) = &self.scope
+            && let Err(e) = scope.previous.restore(&self.lua)
+        {
+            log::error!("Cannot restore the previous plugin context: {}", e);
+        }
+    }
+}
+
+/// Creates the state of a lazily loaded plugin
+pub type CreateState = Box<dyn Fn() -> Result<PluginState, ManagerError> + Send + Sync>;
+
+enum Slot {
+    /// The state is created on first use
+    Lazy,
+    /// The state is being created by a thread
+    Creating(ThreadId),
+    Ready(PluginState),
+}
+
+/// The current Lua state of a plugin
+pub struct StateSlot {
+    slot: Mutex<Slot>,
+    created: Condvar,
+    lazy: Option<(Bundle, CreateState)>,
+}
+
+impl StateSlot {
+    pub fn new(state: PluginState) -> Self {
+        Self {
+            slot: Mutex::new(Slot::Ready(state)),
+            created: Condvar::new(),
+            lazy: None,
+        }
+    }
+
+    /// Creates a slot whose state is created by `create` on first use
+    ///
+    /// A failed creation is retried on the next use.
+    pub fn lazy(bundle: Bundle, create: CreateState) -> Self {
+        Self {
+            slot: Mutex::new(Slot::Lazy),
+            created: Condvar::new(),
+            lazy: Some((bundle, create)),
+        }
+    }
+
+    /// Returns the current state, creating it if needed
+    ///
+    /// Threads wait for the state another thread is creating, while the
+    /// thread creating it gets [`PluginError::StillLoading`].
+    pub fn current(&self) -> Result<PluginState, ManagerError> {
+        let current = thread::current().id();
+        let mut slot = self.slot.lock_unpoisoned();
+        loop {
+            match &*slot {
+                Slot::Ready(state) => return Ok(state.clone()),
+                Slot::Lazy => break,
+                Slot::Creating(thread) if *thread != current => {
+                    slot = self
+                        .created
+                        .wait(slot)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+                Slot::Creating(_) => break,
+            }
+        }
+        let Some((bundle, create)) = &self.lazy else {
+            unreachable!("only lazy slots lack a state");
+        };
+        if matches!(*slot, Slot::Creating(_)) {
+            return Err(PluginError::StillLoading(bundle.to_string()).into());
+        }
+        *slot = Slot::Creating(current);
+        drop(slot);
+
+        // Let the other threads retry if the creation fails or panics
+        let creating = Creating(self);
+        let state = create()?;
+        self.replace(state.clone());
+        std::mem::forget(creating);
+        Ok(state)
+    }
+
+    /// Returns the current state, entered in the plugin's context, creating
+    /// it if needed
+    pub fn get(&self) -> Result<ScopedLua, ManagerError> {
+        Ok(self.current()?.enter()?)
+    }
+
+    /// Returns the current state entered in the plugin's context, or `None`
+    /// if it has not been created yet
+    pub fn peek(&self) -> mlua::Result<Option<ScopedLua>> {
+        let state = match &*self.slot.lock_unpoisoned() {
+            Slot::Ready(state) => state.clone(),
+            _ => return Ok(None),
+        };
+        state.enter().map(Some)
+    }
+
+    /// Makes `state` the current state
+    pub fn replace(&self, state: PluginState) {
+        *self.slot.lock_unpoisoned() = Slot::Ready(state);
+        self.created.notify_all();
+    }
+}
+
+/// Puts a slot back to lazy when dropped, unless forgotten once the state
+/// is created
+struct Creating<'a>(&'a StateSlot);
+
+impl Drop for Creating<'_> {
+    fn drop(&mut self) {
+        *self.0.slot.lock_unpoisoned() = Slot::Lazy;
+        self.0.created.notify_all();
+    }
+}
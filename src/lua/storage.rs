@@ -0,0 +1,103 @@
+//! The `api.storage` table giving plugins a persistent data directory
+
+use std::{
+    io,
+    path::{Component, Path, PathBuf},
+};
+
+use mlua::{Lua, Table, Value};
+
+use crate::error::ManagerError;
+
+/// Registers `api.storage.read`, `write`, `list` and `delete`, limited to `dir`
+///
+/// Paths are relative to `dir`, which is created on the first write.
+pub fn register_storage(lua: &Lua, dir: PathBuf) -> Result<(), ManagerError> {
+    let table = lua.create_table()?;
+
+    let read = {
+        let dir = dir.clone();
+        lua.create_function(
+            move |ctx, path: String| match std::fs::read(resolve(&dir, &path)?) {
+                Ok(content) => Ok(Value::String(ctx.create_string(content)?)),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Value::Nil),
+                Err(e) => Err(mlua::Error::external(e)),
+            },
+        )?
+    };
+    table.set("read", read)?;
+
+    let write = {
+        let dir = dir.clone();
+        lua.create_function(move |_, (path, content): (String, mlua::String)| {
+            let path = resolve(&dir, &path)?;
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(path, content.as_bytes())?;
+            Ok(())
+        })?
+    };
+    table.set("write", write)?;
+
+    let list = {
+        let dir = dir.clone();
+        lua.create_function(move |_, prefix: Option<String>| {
+            let mut files = vec![];
+            if dir.is_dir() {
+                visit(&dir, &dir, &mut files)?;
+            }
+            let prefix = prefix.unwrap_or_default();
+            files.retain(|file| file.starts_with(&prefix));
+            files.sort();
+            Ok(files)
+        })?
+    };
+    table.set("list", list)?;
+
+    let delete = lua.create_function(move |_, path: String| {
+        match std::fs::remove_file(resolve(&dir, &path)?) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(mlua::Error::external(e)),
+        }
+    })?;
+    table.set("delete", delete)?;
+
+    let api_table: Table = lua.globals().get("api")?;
+    api_table.set("storage", table)?;
+
+    Ok(())
+}
+
+/// Returns the path of a file in `dir`, refusing paths leading outside of it.
+fn resolve(dir: &Path, path: &str) -> mlua::Result<PathBuf> {
+    let inside = !path.is_empty()
+        && Path::new(path)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+    match inside {
+        true => Ok(dir.join(path)),
+        false => Err(mlua::Error::RuntimeError(format!(
+            "storage path '{path}' is outside the data directory"
+        ))),
+    }
+}
+
+/// Collects the files under `dir` as `/`-separated paths relative to `root`.
+fn visit(dir: &Path, root: &Path, files: &mut Vec<String>) -> io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            visit(&path, root, files)?;
+        } else if let Ok(rel_path) = path.strip_prefix(root) {
+            let rel_path = rel_path
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            files.push(rel_path);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,71 @@
+//! Background tasks spawned by Lua plugins
+//!
+//! `api.spawn(func, ...)` wraps `func` in a coroutine that the host drives
+//! with [`LuaManager::tick`](crate::LuaManager::tick). A task runs until it
+//! yields, and is resumed again on a later tick until it returns. The first
+//! resume passes the extra arguments given to `api.spawn`.
+//!
+//! Spawning requires the `spawn` capability.
+
+use mlua::{Function, Lua, MultiValue, Table, Thread, ThreadStatus};
+
+use crate::error::ManagerError;
+use crate::lua::capabilities;
+
+/// Name of the Lua registry value holding the queue of spawned tasks.
+pub const TASKS_KEY: &str = "plux_tasks";
+
+/// Registers `api.spawn` in the API table
+pub fn register_spawn(lua: &Lua, api_table: &Table) -> Result<(), ManagerError> {
+    lua.set_named_registry_value(TASKS_KEY, lua.create_table()?)?;
+
+    let spawn = lua.create_function(|ctx, (func, args): (Function, MultiValue)| {
+        capabilities::require(ctx, "spawn")?;
+
+        let task = ctx.create_table()?;
+        task.set("thread", ctx.create_thread(func)?)?;
+        task.set("args", ctx.create_sequence_from(args)?)?;
+
+        let tasks: Table = ctx.named_registry_value(TASKS_KEY)?;
+        tasks.raw_push(task)
+    })?;
+    api_table.set("spawn", spawn)?;
+
+    Ok(())
+}
+
+/// Returns the number of spawned tasks that have not finished yet
+pub fn pending_tasks(lua: &Lua) -> mlua::Result<usize> {
+    let tasks: Option<Table> = lua.named_registry_value(TASKS_KEY)?;
+    Ok(tasks.map_or(0, |tasks| tasks.raw_len()))
+}
+
+/// Resumes the task at the front of the queue once
+///
+/// A task that yields goes to the back of the queue, a task that returns or
+/// fails is dropped. Returns `false` if there was no task to resume.
+pub fn resume_next(lua: &Lua) -> mlua::Result<bool> {
+    let tasks: Table = lua.named_registry_value(TASKS_KEY)?;
+    let Some(task) = tasks.raw_get::<Option<Table>>(1)? else {
+        return Ok(false);
+    };
+    tasks.raw_remove(1)?;
+
+    let thread: Thread = task.get("thread")?;
+    let args = match task.get::<Option<Table>>("args")? {
+        Some(args) => {
+            task.set("args", mlua::Nil)?;
+            args.sequence_values()
+                .collect::<mlua::Result<MultiValue>>()?
+        }
+        None => MultiValue::new(),
+    };
+
+    match thread.resume::<()>(args) {
+        Ok(()) if thread.status() == ThreadStatus::Resumable => tasks.raw_push(task)?,
+        Ok(()) => {}
+        Err(e) => log::error!("Spawned task failed: {}", e),
+    }
+
+    Ok(true)
+}
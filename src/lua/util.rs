@@ -0,0 +1,210 @@
+//! String and table utilities exposed to plugins as `api.util`
+//!
+//! The functions operate on Lua values directly, without converting through
+//! plux variables. Deep table operations share the depth limit of the
+//! conversion layer, so cyclic tables are rejected with an error.
+
+use mlua::{Lua, String as LuaString, Table, Value};
+
+use crate::error::ManagerError;
+use crate::lua::conversion::{MAX_DEPTH, depth_error};
+
+/// Registers the `util` table in the plugin API table
+pub fn register_util(lua: &Lua, api_table: &Table) -> Result<(), ManagerError> {
+    let util = lua.create_table()?;
+
+    util.set(
+        "split",
+        lua.create_function(|ctx, (s, sep): (LuaString, LuaString)| {
+            let sep = sep.as_bytes();
+            if sep.is_empty() {
+                return Err(mlua::Error::RuntimeError(
+                    "separator must not be empty".to_string(),
+                ));
+            }
+
+            let parts = ctx.create_table()?;
+            let s = s.as_bytes();
+            let mut start = 0;
+            let mut index = 0;
+            while index + sep.len() <= s.len() {
+                if s[index..index + sep.len()] == *sep {
+                    parts.raw_push(ctx.create_string(&s[start..index])?)?;
+                    index += sep.len();
+                    start = index;
+                } else {
+                    index += 1;
+                }
+            }
+            parts.raw_push(ctx.create_string(&s[start..])?)?;
+            Ok(parts)
+        })?,
+    )?;
+
+    util.set(
+        "trim",
+        lua.create_function(|ctx, s: LuaString| ctx.create_string(s.as_bytes().trim_ascii()))?,
+    )?;
+
+    util.set(
+        "starts_with",
+        lua.create_function(|_, (s, prefix): (LuaString, LuaString)| {
+            Ok(s.as_bytes().starts_with(&prefix.as_bytes()))
+        })?,
+    )?;
+
+    util.set(
+        "ends_with",
+        lua.create_function(|_, (s, suffix): (LuaString, LuaString)| {
+            Ok(s.as_bytes().ends_with(&suffix.as_bytes()))
+        })?,
+    )?;
+
+    util.set(
+        "deep_copy",
+        lua.create_function(|ctx, value: Value| deep_copy(ctx, value, 0))?,
+    )?;
+
+    util.set(
+        "merge",
+        lua.create_function(|ctx, (a, b): (Table, Table)| merge(ctx, &a, &b, 0))?,
+    )?;
+
+    util.set(
+        "keys",
+        lua.create_function(|ctx, t: Table| {
+            let keys = ctx.create_table()?;
+            for pair in t.pairs::<Value, Value>() {
+                keys.raw_push(pair?.0)?;
+            }
+            Ok(keys)
+        })?,
+    )?;
+
+    util.set(
+        "values",
+        lua.create_function(|ctx, t: Table| {
+            let values = ctx.create_table()?;
+            for pair in t.pairs::<Value, Value>() {
+                values.raw_push(pair?.1)?;
+            }
+            Ok(values)
+        })?,
+    )?;
+
+    api_table.set("util", util)?;
+
+    Ok(())
+}
+
+/// Recursively copies a value; non-table values are returned as is
+fn deep_copy(lua: &Lua, value: Value, depth: usize) -> mlua::Result<Value> {
+    match value {
+        Value::Table(table) => {
+            if depth >= MAX_DEPTH {
+                return Err(depth_error());
+            }
+
+            let copy = lua.create_table()?;
+            for pair in table.pairs::<Value, Value>() {
+                let (key, value) = pair?;
+                copy.raw_set(key, deep_copy(lua, value, depth + 1)?)?;
+            }
+            Ok(Value::Table(copy))
+        }
+        value => Ok(value),
+    }
+}
+
+/// Deeply merges `b` into a copy of `a`; values from `b` win
+fn merge(lua: &Lua, a: &Table, b: &Table, depth: usize) -> mlua::Result<Table> {
+    if depth >= MAX_DEPTH {
+        return Err(depth_error());
+    }
+
+    let result = match deep_copy(lua, Value::Table(a.clone()), depth)? {
+        Value::Table(result) => result,
+        _ => unreachable!(),
+    };
+
+    for pair in b.pairs::<Value, Value>() {
+        let (key, value) = pair?;
+        let merged = match (result.raw_get::<Value>(&key)?, value) {
+            (Value::Table(a), Value::Table(b)) => Value::Table(merge(lua, &a, &b, depth + 1)?),
+            (_, value) => deep_copy(lua, value, depth + 1)?,
+        };
+        result.raw_set(key, merged)?;
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn util_lua() -> Lua {
+        let lua = Lua::new();
+        let api_table = lua.create_table().unwrap();
+        register_util(&lua, &api_table).unwrap();
+        lua.globals().set("api", api_table).unwrap();
+        lua
+    }
+
+    #[test]
+    fn test_string_utils() {
+        let lua = util_lua();
+        lua.load(
+            r#"
+            local parts = api.util.split("a,b,,c", ",")
+            assert(#parts == 4 and parts[1] == "a" and parts[3] == "" and parts[4] == "c")
+            assert(#api.util.split("a--b", "--") == 2)
+            assert(not pcall(api.util.split, "abc", ""))
+
+            assert(api.util.trim("  padded \n") == "padded")
+            assert(api.util.starts_with("plugin", "plu"))
+            assert(not api.util.starts_with("plugin", "gin"))
+            assert(api.util.ends_with("plugin", "gin"))
+            "#,
+        )
+        .exec()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_table_utils() {
+        let lua = util_lua();
+        lua.load(
+            r#"
+            local original = { a = 1, nested = { b = 2 } }
+            local copy = api.util.deep_copy(original)
+            copy.nested.b = 3
+            assert(original.nested.b == 2 and copy.a == 1)
+
+            local merged = api.util.merge(
+                { a = 1, nested = { b = 2, c = 3 } },
+                { a = 10, nested = { c = 30 }, d = 4 }
+            )
+            assert(merged.a == 10 and merged.d == 4)
+            assert(merged.nested.b == 2 and merged.nested.c == 30)
+
+            local keys = api.util.keys({ x = true })
+            local values = api.util.values({ x = "value" })
+            assert(#keys == 1 and keys[1] == "x")
+            assert(#values == 1 and values[1] == "value")
+            "#,
+        )
+        .exec()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_deep_copy_cyclic_table() {
+        let lua = util_lua();
+        let error = lua
+            .load("local t = {}; t.self = t; return api.util.deep_copy(t)")
+            .exec()
+            .unwrap_err();
+        assert!(error.to_string().contains("maximum depth"));
+    }
+}
@@ -1,38 +1,138 @@
 //! Vtable handling for Lua plugins
+//!
+//! Host functions live in the global `host` table (`host.fn_name(...)`), and
+//! optionally as globals of the same name for plugins written against the
+//! flat namespace, see [`crate::LuaManager::with_flat_host_functions`].
+//!
+//! Every host function is exposed as an ordinary Lua function value. Each
+//! function value is created once per Lua state and cached, so it can be
+//! stored in tables, compared by identity and passed to `pcall`, and it
+//! stays the same value when the vtable is refreshed.
+//!
+//! Calls to host functions go through the middleware of the manager, see
+//! [`crate::LuaManager::with_middleware`], and calls beyond the rate limit of
+//! the plugin raise a structured `rate_limited` error, see
+//! [`crate::LuaManager::with_rate_limit`].
+//!
+//! Functions outside the [`ApiScope`](capabilities::ApiScope) of the plugin
+//! are not exposed, see [`crate::LuaManager::with_api_filter`].
 
-use mlua::{Lua, MultiValue, Value};
-use plux_rs::{Registry, function::FunctionOutput};
+use std::sync::Arc;
+
+use mlua::{Function, Lua, MultiValue, Table, Value};
+use plux_rs::{Bundle, Registry, function::FunctionOutput};
 
 use crate::{
-    error::ManagerError,
-    lua::conversion::{lua_to_plux, plux_to_lua},
+    audit::AuditLog,
+    error::{CallError, ManagerError, PluginError},
+    lua::conversion::{args_from_lua, conversion_options, plux_to_lua_with},
+    lua::{audit, capabilities, errors},
+    middleware::{CallInfo, CallTarget, MiddlewareChain, run_chain},
+    rate_limit::RateLimiter,
 };
 
-/// Register vtable functions.
-pub fn register_vtable(lua: &Lua, vtable: &Registry<FunctionOutput>) -> Result<(), ManagerError> {
+/// Name of the Lua registry value caching the host function values.
+const VTABLE_KEY: &str = "plux_vtable";
+
+/// Name of the global table holding the host functions.
+pub const HOST_TABLE: &str = "host";
+
+/// Register vtable functions in the `host` table, and as globals if `flat`.
+///
+/// Functions that were already registered in this state keep their existing
+/// Lua function value; only new registry functions are created. Calls made by
+/// the plugin `bundle` run through `middleware`, once admitted by `limiter`,
+/// and are recorded in `audit_log`. Functions the plugin's scope does not
+/// expose are skipped.
+pub fn register_vtable(
+    lua: &Lua,
+    bundle: &Bundle,
+    vtable: &Registry<FunctionOutput>,
+    middleware: &MiddlewareChain,
+    limiter: Option<&Arc<RateLimiter>>,
+    audit_log: Option<&Arc<AuditLog>>,
+    flat: bool,
+) -> Result<(), ManagerError> {
     let globals = lua.globals();
+    let host = match globals.get::<Option<Table>>(HOST_TABLE)? {
+        Some(host) => host,
+        None => {
+            let host = lua.create_table()?;
+            globals.raw_set(HOST_TABLE, &host)?;
+            host
+        }
+    };
+
+    let cache = match lua.named_registry_value::<Option<Table>>(VTABLE_KEY)? {
+        Some(cache) => cache,
+        None => {
+            let cache = lua.create_table()?;
+            lua.set_named_registry_value(VTABLE_KEY, &cache)?;
+            cache
+        }
+    };
 
     for function in vtable.iter() {
         let function_name = function.name();
+        if !capabilities::exposes(lua, &format!("{HOST_TABLE}.{function_name}")) {
+            continue;
+        }
+
+        if let Some(f) = cache.get::<Option<Function>>(function_name.as_str())? {
+            host.raw_set(function_name.as_str(), &f)?;
+            if flat {
+                globals.raw_set(function_name, f)?;
+            }
+            continue;
+        }
+
         let function = function.clone();
-        let f = lua.create_function(move |ctx, lua_args: MultiValue| {
-            let mut args = vec![];
-            for arg in lua_args.iter().map(lua_to_plux) {
-                args.push(arg?);
+        let caller = bundle.clone();
+        let middleware = middleware.clone();
+        let limiter = limiter.cloned();
+        let raw = lua.create_function(move |ctx, lua_args: MultiValue| {
+            if let Some(limiter) = &limiter
+                && !limiter.acquire()
+            {
+                return errors::failure(ctx, errors::RATE_LIMITED, limiter.exceeded());
             }
 
-            let output = function
-                .call(&args)
-                .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?
-                .map(|var| plux_to_lua(&var, ctx));
+            let options = conversion_options(ctx);
+            let args = args_from_lua(&lua_args, &options, &function.name())?;
+
+            let name = function.name();
+            let call = CallInfo {
+                bundle: &caller,
+                function: &name,
+                target: CallTarget::Host,
+            };
+            let output = run_chain(&middleware, &call, &args, &|args| {
+                function.call(args).map_err(|e| {
+                    let source = mlua::Error::ExternalError(Arc::from(e));
+                    PluginError::from(CallError::new(None, &name, args, None, source)).into()
+                })
+            })
+            .map_err(|e| match e {
+                ManagerError::Plugin(e) => mlua::Error::external(e),
+                e => mlua::Error::external(e),
+            })?
+            .map(|var| plux_to_lua_with(&var, ctx, options.strings));
 
             match output {
-                Some(out) => Ok(out?),
-                None => Ok(Value::Nil),
+                Some(out) => errors::success(ctx, out?),
+                None => errors::success(ctx, Value::Nil),
             }
         })?;
+        let mut f = errors::structured(lua, raw)?;
+        if let Some(log) = audit_log {
+            f = audit::audited(lua, bundle, format!("{HOST_TABLE}.{function_name}"), f, log)?;
+        }
 
-        globals.set(function_name, f)?;
+        cache.set(function_name.as_str(), &f)?;
+        host.raw_set(function_name.as_str(), &f)?;
+        if flat {
+            globals.raw_set(function_name, f)?;
+        }
     }
 
     Ok(())
@@ -0,0 +1,98 @@
+//! Time limit of the calls into a plugin
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use mlua::Lua;
+
+use super::hooks;
+use crate::sync::MutexExt;
+
+/// Deadline of the call running in a plugin's state, shared with its hook
+///
+/// The watchdog outlives reloads, each new state gets it installed.
+#[derive(Clone, Default)]
+pub struct Watchdog(Arc<Mutex<Option<Deadline>>>);
+
+struct Deadline {
+    at: Instant,
+    timeout: Duration,
+    expired: bool,
+}
+
+/// Disarms a [`Watchdog`] when dropped
+pub struct ArmedWatchdog<'a>(&'a Watchdog);
+
+impl ArmedWatchdog<'_> {
+    /// Returns `true` if the call ran past its deadline
+    pub fn expired(&self) -> bool {
+        self.0
+            .0
+            .lock_unpoisoned()
+            .as_ref()
+            .is_some_and(|deadline| deadline.expired)
+    }
+
+    /// Gives the next call of a batch the whole timeout again
+    ///
+    /// Returns `true` if the previous call ran past its deadline.
+    pub fn renew(&self) -> bool {
+        let mut deadline = self.0.0.lock_unpoisoned();
+        let Some(deadline) = deadline.as_mut() else {
+            return false;
+        };
+        let expired = deadline.expired;
+        deadline.at = Instant::now() + deadline.timeout;
+        deadline.expired = false;
+        expired
+    }
+}
+
+impl Drop for ArmedWatchdog<'_> {
+    fn drop(&mut self) {
+        *self.0.0.lock_unpoisoned() = None;
+    }
+}
+
+impl Watchdog {
+    /// Installs the watchdog on `lua`
+    pub fn install(&self, lua: &Lua) -> mlua::Result<()> {
+        lua.set_app_data(self.clone());
+        hooks::install(lua)
+    }
+
+    /// Gives the call about to run `timeout` to complete
+    ///
+    /// Returns `None` if a call is already running, nested calls share its deadline.
+    pub fn arm(&self, timeout: Duration) -> Option<ArmedWatchdog<'_>> {
+        let mut deadline = self.0.lock_unpoisoned();
+        if deadline.is_some() {
+            return None;
+        }
+        *deadline = Some(Deadline {
+            at: Instant::now() + timeout,
+            timeout,
+            expired: false,
+        });
+        Some(ArmedWatchdog(self))
+    }
+
+    /// Fails once the running call is past its deadline
+    pub fn check(&self) -> mlua::Result<()> {
+        let mut deadline = self.0.lock_unpoisoned();
+        let Some(deadline) = deadline.as_mut() else {
+            return Ok(());
+        };
+
+        if deadline.expired || Instant::now() > deadline.at {
+            deadline.expired = true;
+            return Err(mlua::Error::RuntimeError(format!(
+                "call exceeded its time limit of {:?}",
+                deadline.timeout
+            )));
+        }
+        Ok(())
+    }
+}
@@ -14,157 +14,3913 @@
 //!
 //! # Examples
 //!
-//! ```no_run
+//! ```ignore
 //! ...
-//! 
+//!
 //! use plux_lua_manager::prelude::*;
-//! 
+//!
 //! ...
-//! 
+//!
 //! loader.context(move |mut ctx| {
 //!     ctx.register_manager(LuaManager::new()).unwrap();
 //! });
-//! 
+//!
 //! ...
 //! ```
 
-use std::{
-    path::PathBuf,
-    sync::{Arc, Mutex},
-};
+use std::{
+    collections::HashMap,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex, RwLock,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use indexmap::IndexMap;
+use log::LevelFilter;
+use mlua::{Chunk, Function, IntoLua, Lua, MultiValue, Table, Value};
+#[cfg(feature = "debug")]
+use mlua::{HookTriggers, VmState};
+use plux_rs::{
+    Api, Bundle, Loader, Manager, Plugin, StdInfo,
+    context::LoadPluginContext,
+    function::{Arg, DynamicFunction, FunctionOutput},
+    utils::{ManagerResult, UnloadPluginError},
+    variable::{Variable, VariableType},
+};
+
+use crate::error::{ConfigError, ManagerError, PluginError};
+use crate::{
+    audit::{AuditLog, AuditRecord, AuditSink},
+    bytecode::BytecodeCache,
+    config::{
+        Config, FunctionDoc, InputDeclaration, KNOWN_CAPABILITIES, PluginMetadata,
+        dependency_mismatches, load_config_from,
+    },
+    events::EventBus,
+    graph::DependencyGraph,
+    health::{
+        CallGate, Fault, FaultLog, PluginHealth, QuarantineListener, QuarantinePolicy,
+        RestartAttempt, RestartListener, RestartPolicy,
+    },
+    lua::{
+        api::{self, DependencyApi},
+        audit,
+        capabilities::{ApiScope, Capabilities},
+        env, eval,
+        events::{self, Lifecycle},
+        exports::{
+            ASYNC_EXPORTS_KEY, EXPORTS_KEY, call_export, export_records, get_export,
+            is_async_export,
+        },
+        logging, output, plugins,
+        requests::{self, RequestOptions},
+        require, settings as lua_settings, shared,
+        state::{CreateState, ScopedLua, SharedLua, StateSlot},
+        storage, tasks, vtable,
+    },
+    metrics::FunctionMetrics,
+    middleware::{Middleware, MiddlewareChain},
+    output::{OutputCapture, OutputSink, OutputStream},
+    pack::{self, PackSourceProvider},
+    rate_limit::RateLimit,
+    runtime::{PluginRuntimeInfo, RuntimeInfo},
+    sandbox::{self, Permission, SandboxPolicy},
+    script,
+    settings::{SETTINGS_FILE, SettingInfo, Settings},
+    shared::{AccessPolicy, KeyValueStore, SharedStore},
+    source::{
+        FsSourceProvider, MemorySourceProvider, ModuleResolver, SourceProvider,
+        SourceProviderFactory, SourceTransform, SourceTransforms, transform_source,
+    },
+    sync::{MutexExt, RwLockExt},
+    typed::{
+        FunctionInfo, Input, InputInfo, TypedArgs, TypedFn, TypedOutput, check_args, check_output,
+        parse_input, parse_type, type_name,
+    },
+};
+
+#[cfg(feature = "archive")]
+use crate::archive::{self, ArchiveSourceProvider};
+#[cfg(feature = "fennel")]
+use crate::fennel::{FENNEL_EXTENSION, FennelCompiler};
+#[cfg(feature = "repl")]
+use crate::repl::Repl;
+#[cfg(feature = "teal")]
+use crate::teal::{TEAL_EXTENSION, TealCompiler};
+#[cfg(feature = "watch")]
+use crate::watch;
+#[cfg(feature = "debug")]
+use crate::{
+    coverage::{Coverage, CoverageRecorder, CoverageReport},
+    lua::hooks::{self, Debugger},
+};
+
+/// Logs through the `log` crate if the manager's verbosity allows `$level`.
+macro_rules! log_at {
+    ($manager:expr, $level:ident, $($arg:tt)+) => {
+        if log::Level::$level <= $manager.log_level {
+            log::log!(log::Level::$level, $($arg)+);
+        }
+    };
+}
+
+/// Enters the `tracing` span `$name` of the plugin `$bundle` until the end of
+/// the scope, or logs `$message` at `Info` without the `tracing` feature.
+macro_rules! operation {
+    ($manager:expr, $name:literal, $bundle:expr, $message:literal) => {
+        #[cfg(feature = "tracing")]
+        let _span = if log::Level::Info <= $manager.log_level {
+            tracing::info_span!($name, bundle = %$bundle).entered()
+        } else {
+            tracing::Span::none().entered()
+        };
+        #[cfg(not(feature = "tracing"))]
+        log_at!($manager, Info, concat!($message, ": {}"), $bundle);
+    };
+}
+
+use crate::lua::conversion::{
+    MetamethodGuard, StrictNils, conversion_options, lua_to_plux_lossy, output_from_lua,
+    plux_to_lua, plux_to_lua_with,
+};
+
+/// The main manager type for Lua plugins.
+///
+/// This struct is responsible for managing the lifecycle of Lua plugins,
+/// including loading, unloading, and interacting with them. It maintains
+/// a mapping of plugin bundles to their respective Lua states.
+///
+/// # Thread Safety
+///
+/// `LuaManager` is `Send` and `Sync`, allowing it to be used safely across
+/// thread boundaries. Each plugin's Lua state is protected by a mutex to
+/// ensure thread safety.
+///
+/// Cloning a `LuaManager` is cheap: all clones share the same plugin states,
+/// so the host can keep a handle after registering the manager with the loader.
+///
+/// # Ordering
+///
+/// Plugins are kept in load order. Every operation over several plugins
+/// ([`LuaManager::loaded_bundles`], [`LuaManager::refresh_vtable`],
+/// [`LuaManager::tick`]) visits them in that order. [`LuaManager::unload_all`]
+/// shuts plugins down in reverse load order, as does unregistering the manager
+/// for the plugins still loaded at that point.
+///
+/// # Lifecycle hooks
+///
+/// Plugins acquire and release resources by defining the global functions
+/// `on_load()` and `on_unload()`. `on_load` runs once the entry script has
+/// executed, and a failing `on_load` fails the load. `on_unload` runs before
+/// the plugin's state is dropped, its failures are only logged. Reloading a
+/// plugin runs both, see [`LuaManager::reload_plugin`].
+///
+/// # Introspection
+///
+/// Every plugin state has a global `plugin` table holding the plugin's own
+/// `id`, `version`, `format`, `path` and parsed `config`, so plugins need not
+/// hardcode their name. `api.list_plugins()` lists the other plugins, see
+/// [`LuaManager::with_plugin_visibility`].
+#[derive(Clone)]
+pub struct LuaManager {
+    /// Map of bundle identifiers to their loaded plugins, in load order
+    lua_refs: Arc<RwLock<IndexMap<Bundle, LuaPlugin>>>,
+    /// Creates the source provider of each plugin from its path
+    source_factory: SourceProviderFactory,
+    /// Sources of the plugins mounted by the manager, by the path they are
+    /// registered with
+    mounts: Arc<RwLock<IndexMap<PathBuf, Arc<dyn SourceProvider>>>>,
+    /// Plugins registered through this manager, in registration order
+    registered: Registrations,
+    /// Whether dependency versions are verified on registration and load
+    check_dependencies: bool,
+    /// Whether unknown capabilities are an error
+    strict_capabilities: bool,
+    /// Plugins waiting to be reloaded by the next [`LuaManager::tick`]
+    pending_reloads: Arc<Mutex<Vec<Bundle>>>,
+    /// Rotates the plugin served first by [`LuaManager::tick`]
+    tick_cursor: Arc<AtomicUsize>,
+    /// Memory usage above which [`LuaManager::tick`] runs a GC step
+    gc_watermark: Option<usize>,
+    /// Channels shared between plugins
+    shared: SharedStore,
+    /// Key-value store open to all plugins
+    store: KeyValueStore,
+    /// Events waiting to be delivered to plugins
+    events: EventBus,
+    /// When plugins whose calls keep failing are quarantined
+    quarantine_policy: Option<QuarantinePolicy>,
+    /// Notified when a plugin gets quarantined
+    quarantine_listener: Option<QuarantineListener>,
+    /// Whether plugins failing to load are marked as faulted instead of
+    /// failing the load
+    fault_isolation: bool,
+    /// When faulted plugins are restarted by [`LuaManager::tick`]
+    restart_policy: Option<RestartPolicy>,
+    /// Notified of every automatic restart of a faulted plugin
+    restart_listener: Option<RestartListener>,
+    /// How long calls to a paused plugin wait for it to resume
+    pause_timeout: Option<Duration>,
+    /// How fast each plugin may call host functions and dependencies
+    rate_limit: Option<RateLimit>,
+    /// Records the calls plugins make to host functions and the plugin API
+    audit: Option<Arc<AuditLog>>,
+    /// Receives what plugins print, instead of the logger
+    output: Option<Arc<OutputCapture>>,
+    /// Standard libraries opened in plugin states
+    sandbox: SandboxPolicy,
+    /// Creates the Lua state of each plugin, instead of the sandbox
+    state_factory: Option<StateFactory>,
+    /// Called with each plugin state before its entry script runs
+    before_load_hook: Option<StateHook>,
+    /// Called with each plugin state once it is loaded
+    after_load_hook: Option<StateHook>,
+    /// Called with each plugin state before it is dropped
+    unload_hook: Option<StateHook>,
+    /// Memory each plugin state may allocate
+    memory_limit: Option<usize>,
+    /// The state hosting all plugins, if they share one, created on first use
+    shared_lua: Option<Arc<Mutex<Option<Arc<SharedLua>>>>>,
+    /// Whether plugins declaring their exports are loaded on first use
+    lazy_loading: bool,
+    /// Plugins prepared by [`LuaManager::preload`], by path
+    preloaded: Arc<Mutex<HashMap<PathBuf, Preloaded>>>,
+    /// How long a call into a plugin may run
+    call_timeout: Option<Duration>,
+    /// Script executed to load a plugin
+    entry: String,
+    /// Resolve the modules plugins require but do not ship
+    resolvers: Arc<Vec<Arc<dyn ModuleResolver>>>,
+    /// Turn plugin sources into Lua, by file extension
+    transforms: SourceTransforms,
+    /// Wraps the calls between the host and plugins, outermost first
+    middleware: MiddlewareChain,
+    /// Directory holding the data directories of plugins
+    data_dir: Option<PathBuf>,
+    /// Compiled chunks reused across loads, if enabled
+    bytecode: Option<Arc<BytecodeCache>>,
+    /// Whether host functions are also set as globals
+    flat_host_functions: bool,
+    /// Which plugins each plugin sees in `api.list_plugins()`
+    plugin_visibility: Option<PluginVisibility>,
+    /// Which functions of the `api` and `host` tables each plugin gets
+    api_filter: Option<ApiFilter>,
+    /// Which features of their configs plugins get
+    feature_toggle: Option<FeatureToggle>,
+    /// Globals set in every plugin state
+    globals: Arc<IndexMap<String, Variable>>,
+    /// Chunks run in every plugin state before its entry script, by name
+    preludes: Arc<IndexMap<String, Vec<u8>>>,
+    /// Optional requests and names of request arguments
+    requests: Arc<RequestOptions>,
+    /// Most verbose level the manager logs at
+    log_level: LevelFilter,
+    /// Notified of the outcome of the reloads performed by [`LuaManager::tick`]
+    reload_listener: Option<ReloadListener>,
+    /// Whether plugin directories are watched for changes
+    #[cfg(feature = "watch")]
+    watch: bool,
+    /// Watchers of the directories of the loaded plugins
+    #[cfg(feature = "watch")]
+    watchers: Arc<Mutex<IndexMap<Bundle, notify::RecommendedWatcher>>>,
+    /// Lines run by the plugins, if coverage is enabled
+    #[cfg(feature = "debug")]
+    coverage: Option<Arc<Coverage>>,
+}
+
+/// Global function called by [`LuaManager::broadcast`].
+pub const BROADCAST_HANDLER: &str = "on_event";
+
+/// Script executed to load a plugin, unless configured otherwise.
+pub const DEFAULT_ENTRY: &str = "main.lua";
+
+/// Hooks saving the state of a plugin before a reload, by order of preference.
+const RELOAD_SAVE_HOOKS: [&str; 2] = ["on_reload_save", "on_save_state"];
+
+/// Hooks restoring the state of a plugin after a reload, by order of preference.
+const RELOAD_RESTORE_HOOKS: [&str; 2] = ["on_reload_restore", "on_restore_state"];
+
+/// Runtime state of a loaded Lua plugin.
+#[derive(Clone)]
+struct LuaPlugin {
+    /// The plugin's Lua state, swapped in place on reload
+    lua: Arc<StateSlot>,
+    /// The plugin API the state was created with
+    api: Arc<Api<FunctionOutput, StdInfo>>,
+    /// Where the plugin's sources are read from
+    source: Arc<dyn SourceProvider>,
+    /// Non-fatal problems found while loading the plugin
+    diagnostics: Vec<String>,
+    /// Pause flag, call failure counters and quarantine flag, kept across reloads
+    health: Arc<PluginHealth>,
+}
+
+/// A plugin prepared by [`LuaManager::preload`], waiting to be loaded.
+struct Preloaded {
+    config: Config,
+    /// A state created for the plugin and its entry scripts compiled in it,
+    /// by path, unless the plugins share a state
+    state: Option<(Lua, HashMap<String, Function>)>,
+}
+
+/// What the manager knows of a registered plugin.
+pub(crate) struct Registration {
+    /// The dependencies declared in the plugin's config
+    info: StdInfo,
+    /// The descriptive fields of the plugin's config
+    pub(crate) metadata: PluginMetadata,
+    /// The capabilities declared in the plugin's config or granted by its
+    /// permissions
+    capabilities: Vec<String>,
+    /// The permissions declared in the plugin's config
+    permissions: Option<Vec<Permission>>,
+    /// The sandbox policy narrowed to the libraries declared in the plugin's
+    /// config, the manager's if it declares none
+    sandbox: SandboxPolicy,
+    /// Whether each feature declared in the plugin's config is enabled
+    features: HashMap<String, bool>,
+    /// The user settings declared in the plugin's config and their values
+    settings: Arc<Settings>,
+    /// The signatures and documentation of the functions exported by the
+    /// plugin, while it is loaded
+    pub(crate) functions: IndexMap<String, FunctionInfo>,
+    /// The last failures of the plugin
+    faults: Arc<FaultLog>,
+    /// Whether the plugin failed to load, with fault isolation
+    faulted: bool,
+}
+
+/// Plugins registered through a manager, in registration order.
+pub(crate) type Registrations = Arc<RwLock<IndexMap<Bundle, Registration>>>;
+
+/// Decides whether the plugin given first may see the plugin given second in
+/// `api.list_plugins()`.
+pub type PluginVisibility = Arc<dyn Fn(&Bundle, &Bundle) -> bool + Send + Sync>;
+
+/// Decides whether a plugin gets the function at a path of the `api` or
+/// `host` table, see [`LuaManager::with_api_filter`].
+pub type ApiFilter = Arc<dyn Fn(&Bundle, &str) -> bool + Send + Sync>;
+
+/// Decides whether a feature of a plugin is enabled, given the plugin, the
+/// name of the feature and its default, see [`LuaManager::with_feature_toggle`].
+pub type FeatureToggle = Arc<dyn Fn(&Bundle, &str, bool) -> bool + Send + Sync>;
+
+/// Called on the debug events of a plugin, see [`LuaManager::set_debug_hook`].
+#[cfg(feature = "debug")]
+pub type DebugHook = Arc<dyn Fn(&Lua, &mlua::Debug<'_>) -> mlua::Result<VmState> + Send + Sync>;
+
+/// Creates the Lua state of the plugin `bundle`, see
+/// [`LuaManager::with_state_factory`].
+pub type StateFactory = Arc<dyn Fn(&Bundle) -> mlua::Result<Lua> + Send + Sync>;
+
+/// Called with the bundle and the Lua state of a plugin, see
+/// [`LuaManager::with_before_load_hook`], [`LuaManager::with_after_load_hook`]
+/// and [`LuaManager::with_unload_hook`].
+pub type StateHook = Arc<dyn Fn(&Bundle, &Lua) -> mlua::Result<()> + Send + Sync>;
+
+/// A function exported by a plugin's entry script.
+struct Export {
+    name: String,
+    inputs: Vec<Input>,
+    output: Arg,
+    doc: FunctionDoc,
+}
+
+impl Export {
+    /// Parses the declared types of a function's arguments and result.
+    fn parse(
+        name: String,
+        inputs: &[InputDeclaration],
+        output: Option<&str>,
+    ) -> Result<Self, PluginError> {
+        let invalid = |declaration: &dyn std::fmt::Display| {
+            PluginError::SourceError(format!(
+                "Function `{name}`: invalid type in `{declaration}`"
+            ))
+        };
+        let inputs = inputs
+            .iter()
+            .map(|input| parse_input(input).ok_or_else(|| invalid(input)))
+            .collect::<Result<Vec<_>, _>>()?;
+        if let Some(input) = inputs.iter().rev().skip(1).find(|input| input.variadic) {
+            return Err(PluginError::SourceError(format!(
+                "Function `{name}`: only the last argument may be variadic, not `{}`",
+                input.arg.name
+            )));
+        }
+        let output = match output {
+            Some(output) => Arg::new(
+                "output",
+                parse_type(output.trim()).ok_or_else(|| invalid(&output))?,
+            ),
+            None => Arg::new("output", VariableType::Let),
+        };
+
+        Ok(Self {
+            name,
+            inputs,
+            output,
+            doc: FunctionDoc::default(),
+        })
+    }
+
+    /// Returns the signature and documentation of the function.
+    fn info(&self) -> FunctionInfo {
+        FunctionInfo {
+            name: self.name.clone(),
+            inputs: self.inputs.iter().map(InputInfo::from).collect(),
+            output: type_name(self.output.ty),
+            doc: self.doc.clone(),
+        }
+    }
+}
+
+/// Outcome of a successful [`LuaManager::reload_plugin`].
+#[derive(Debug, Clone, Default)]
+pub struct ReloadReport {
+    /// Non-fatal problems encountered while carrying state over, e.g. values
+    /// returned by `on_reload_save` that could not be converted, or a failing
+    /// `on_unload` in the old state.
+    pub warnings: Vec<String>,
+    /// Functions exported by the new state that were registered with plux.
+    pub added: Vec<String>,
+    /// Functions registered with plux that the new state no longer exports.
+    ///
+    /// plux cannot unregister functions, calling them fails with
+    /// [`PluginError::FunctionNotFound`] until the plugin is loaded again.
+    pub removed: Vec<String>,
+}
+
+/// Called with the bundle of a plugin and the outcome of its reload, see
+/// [`LuaManager::with_reload_listener`].
+pub type ReloadListener = Arc<dyn Fn(&Bundle, Result<&ReloadReport, &ManagerError>) + Send + Sync>;
+
+/// Work performed by a [`LuaManager::tick`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TickReport {
+    /// Number of spawned task resumptions.
+    pub tasks_resumed: usize,
+    /// Number of events delivered to a plugin handling them.
+    pub events_delivered: usize,
+    /// Number of garbage collection steps.
+    pub gc_steps: usize,
+    /// Number of processed reload requests, successful or not.
+    pub reloads: usize,
+    /// Number of automatic restarts of faulted plugins, successful or not.
+    pub restarts: usize,
+    /// Whether the budget ran out before all pending work was done.
+    pub exhausted: bool,
+}
+
+impl TickReport {
+    fn work(&self) -> usize {
+        self.tasks_resumed + self.events_delivered + self.gc_steps + self.reloads + self.restarts
+    }
+}
+
+/// Discrepancies between the manager's plugin states and plux, found by
+/// [`LuaManager::verify_consistency`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConsistencyReport {
+    /// Plugins with a Lua state that plux does not consider loaded.
+    pub orphaned_states: Vec<Bundle>,
+    /// Plugins of this manager loaded by plux without a Lua state.
+    pub missing_states: Vec<Bundle>,
+    /// Functions the manager lists for a plugin, see [`LuaManager::functions`],
+    /// that are not registered with plux or no longer exported by its state.
+    pub orphaned_functions: Vec<(Bundle, String)>,
+}
+
+impl ConsistencyReport {
+    /// Returns `true` if no discrepancy was found.
+    pub fn is_consistent(&self) -> bool {
+        self.orphaned_states.is_empty()
+            && self.missing_states.is_empty()
+            && self.orphaned_functions.is_empty()
+    }
+}
+
+/// Options controlling [`LuaManager::call_batch_with`].
+#[derive(Debug, Clone, Default)]
+pub struct BatchOptions {
+    /// Stop at the first failing item instead of processing the whole batch.
+    pub fail_fast: bool,
+}
+
+impl Default for LuaManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Builds a [`LuaManager`] with manager-wide options.
+///
+/// The options are documented here. The `with_*` methods of [`LuaManager`]
+/// are shorthands for them, e.g. `LuaManager::new().with_entry("init.lua")`
+/// configures the manager through [`LuaManagerBuilder::entry`].
+///
+/// # Examples
+///
+/// ```
+/// use plux_lua_manager::{LuaManager, SandboxPolicy};
+/// use plux_rs::variable::Variable;
+///
+/// let manager = LuaManager::builder()
+///     .sandbox(SandboxPolicy::safe())
+///     .memory_limit(16 * 1024 * 1024)
+///     .entry("init.lua")
+///     .global("APP_NAME", Variable::String("demo".to_string()))
+///     .log_level(log::LevelFilter::Warn)
+///     .build();
+/// ```
+#[derive(Default)]
+pub struct LuaManagerBuilder {
+    manager: LuaManager,
+}
+
+impl LuaManagerBuilder {
+    /// Opens the standard libraries allowed by `policy` in plugin states,
+    /// [`SandboxPolicy::full`] by default.
+    ///
+    /// Plugins declaring `libs` in their config get the libraries both
+    /// declared and allowed.
+    pub fn sandbox(mut self, policy: SandboxPolicy) -> Self {
+        self.manager.sandbox = policy;
+        self
+    }
+
+    /// Limits the memory each plugin's Lua state may allocate to `bytes`.
+    ///
+    /// Allocations beyond the limit fail. Loading, reloading or calling a
+    /// plugin that runs out of memory fails with
+    /// [`PluginError::MemoryLimitExceeded`], and the plugin keeps working
+    /// for calls that fit in the limit.
+    pub fn memory_limit(mut self, bytes: usize) -> Self {
+        self.manager.memory_limit = Some(bytes);
+        self
+    }
+
+    /// Runs all plugins in a single Lua state instead of one state each.
+    ///
+    /// Meant for trusted plugin sets: a state per plugin costs memory and
+    /// keeps plugins from sharing Lua data cheaply. Each plugin still gets its
+    /// own globals, copies of the standard library tables, `require` cache,
+    /// exports and config-driven policies, but the plugins share:
+    ///
+    /// - the memory limit, which applies to the state as a whole, and the
+    ///   memory reported by [`LuaManager::plugin_runtime_info`];
+    /// - the metatables of strings and other builtin types;
+    /// - `package.preload` and the sandbox opened with
+    ///   [`LuaManagerBuilder::sandbox`], plugin configs can only narrow it.
+    ///
+    /// Calls into the plugins are serialized: a call from one thread waits for
+    /// a call into any plugin on another thread to return. Plugins stay
+    /// isolated in states of their own by default.
+    pub fn shared_state(mut self, shared: bool) -> Self {
+        self.manager.shared_lua = shared.then(Arc::default);
+        self
+    }
+
+    /// Defers creating the Lua state of plugins declaring their exports in
+    /// their config until it is needed.
+    ///
+    /// Loading such a plugin registers its declared functions and requests
+    /// right away, while its state is created and its entry script executed,
+    /// followed by `on_load`, by the first call to one of them,
+    /// [`LuaManager::call_batch`], [`LuaManager::call_async`] or
+    /// [`LuaManager::plugin_runtime_info`]. Until then, broadcasts and
+    /// [`LuaManager::tick`] skip the plugin, and unloading it runs no hook.
+    ///
+    /// A state failing to load fails the call that needed it, and the next call
+    /// tries again. Functions the entry script exports without declaring them
+    /// are not registered, and declared functions it does not export fail
+    /// when called. Plugins not declaring `exports` load eagerly.
+    pub fn lazy_loading(mut self, lazy: bool) -> Self {
+        self.manager.lazy_loading = lazy;
+        self
+    }
+
+    /// Aborts calls into a plugin's functions and request handlers that run
+    /// longer than `timeout`.
+    ///
+    /// The deadline is checked every thousand Lua instructions, so a
+    /// call blocked in a host function is only aborted once it returns to Lua.
+    /// Aborted calls fail with [`PluginError::Timeout`] and count as failures
+    /// towards the quarantine. Calls nested in a running call of the same
+    /// plugin share its deadline.
+    pub fn call_timeout(mut self, timeout: Duration) -> Self {
+        self.manager.call_timeout = Some(timeout);
+        self
+    }
+
+    /// Executes `entry` instead of `main.lua` to load plugins.
+    ///
+    /// The path is relative to the plugin directory. Plugins declaring an
+    /// `entry` in their config, and plugin packs, keep the entries declared
+    /// there.
+    pub fn entry(mut self, entry: impl Into<String>) -> Self {
+        self.manager.entry = entry.into();
+        self
+    }
+
+    /// Compiles entry scripts and required modules to bytecode the first time
+    /// they are loaded, so later loads and reloads of unchanged sources skip
+    /// parsing.
+    ///
+    /// Chunks are cached in memory, keyed by a hash of their source. With
+    /// `disk` set, they are also stored in the `.plux-cache` directory of
+    /// plugins read from the filesystem (see [`SourceProvider::cache_dir`]) and
+    /// survive restarts of the host.
+    pub fn bytecode_cache(mut self, disk: bool) -> Self {
+        self.manager.bytecode = Some(Arc::new(BytecodeCache::new(disk)));
+        self
+    }
+
+    /// Resolves the modules plugins `require` but do not ship with
+    /// `resolver`, after the resolvers registered before it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use plux_lua_manager::LuaManager;
+    ///
+    /// let manager = LuaManager::builder()
+    ///     .module_resolver(|_: &_, name: &str| {
+    ///         Ok((name == "host.version").then(|| "return '1.0.0'".to_string()))
+    ///     })
+    ///     .build();
+    /// ```
+    pub fn module_resolver(mut self, resolver: impl ModuleResolver + 'static) -> Self {
+        Arc::make_mut(&mut self.manager.resolvers).push(Arc::new(resolver));
+        self
+    }
+
+    /// Turns the entry scripts and modules of plugins ending in `.extension`
+    /// into Lua with `transform` when they are loaded, replacing the
+    /// transform registered for the same extension before.
+    ///
+    /// `require("a.b")` also looks for `a/b.<extension>` and
+    /// `a/b/init.<extension>`, after the Lua sources. Plugins written in
+    /// another language declare their entry, such as `entry = "main.moon"`,
+    /// in their config. Errors of the transform fail the load.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use plux_lua_manager::{LuaManager, PluginError};
+    ///
+    /// // Lua sources with `#define`-like constants
+    /// let manager = LuaManager::builder()
+    ///     .source_transform("plua", |_: &str, src: &str| {
+    ///         Ok::<_, PluginError>(src.replace("VERSION", "\"1.0.0\""))
+    ///     })
+    ///     .build();
+    /// ```
+    pub fn source_transform(
+        mut self,
+        extension: impl Into<String>,
+        transform: impl SourceTransform + 'static,
+    ) -> Self {
+        Arc::make_mut(&mut self.manager.transforms).insert(extension.into(), Arc::new(transform));
+        self
+    }
+
+    /// Wraps the calls between the host and plugins with `middleware`, inside
+    /// the middleware registered before it.
+    ///
+    /// Middleware sees the calls to plugin functions made through plux or
+    /// [`LuaManager::call_batch`] (once per item), to request handlers and to
+    /// [`BROADCAST_HANDLER`], as [`CallTarget::Plugin`](crate::CallTarget::Plugin)
+    /// calls, and the calls plugins make to host functions as
+    /// [`CallTarget::Host`](crate::CallTarget::Host) calls. It may inspect
+    /// or replace the arguments before passing them on, change the result,
+    /// or fail the call without running it, e.g. to log, scrub, rate limit or
+    /// authorize calls. Plugin calls rejected by a middleware do not count
+    /// towards the quarantine. [`LuaManager::call_async`] bypasses the chain.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use plux_lua_manager::{CallInfo, LuaManager, Next};
+    /// use plux_rs::variable::Variable;
+    ///
+    /// let manager = LuaManager::builder()
+    ///     .middleware(|call: &CallInfo<'_>, args: Vec<Variable>, next: Next<'_>| {
+    ///         log::debug!("{:?} call to `{}` of {}", call.target, call.function, call.bundle);
+    ///         next.run(args)
+    ///     })
+    ///     .build();
+    /// ```
+    pub fn middleware(mut self, middleware: impl Middleware + 'static) -> Self {
+        Arc::make_mut(&mut self.manager.middleware).push(Arc::new(middleware));
+        self
+    }
+
+    /// Makes the Lua modules in `dirs` requireable by every plugin.
+    ///
+    /// Modules are resolved like in plugin directories, `require("a.b")`
+    /// reading `a/b.lua` or `a/b/init.lua` from the first directory having
+    /// it. Plugins' own modules come first, and the directories are consulted
+    /// in the order of [`LuaManagerBuilder::module_resolver`] registrations.
+    pub fn shared_lib_dirs<I, P>(self, dirs: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        let dirs = dirs.into_iter().map(Into::into).collect();
+        self.module_resolver(require::LibDirResolver::new(dirs))
+    }
+
+    /// Gives every plugin a persistent data directory under `dir`, named after
+    /// the plugin's id so that it survives version upgrades.
+    ///
+    /// Plugins access it through `api.storage`:
+    ///
+    /// ```lua
+    /// api.storage.write("state/last_run.txt", tostring(os.time()))
+    /// local last_run = api.storage.read("state/last_run.txt") -- nil if missing
+    /// local files = api.storage.list("state/")
+    /// api.storage.delete("state/last_run.txt")
+    /// ```
+    ///
+    /// Paths are relative to the plugin's directory, paths leading outside of
+    /// it are an error. Without a data directory `api.storage` is not set.
+    pub fn data_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.manager.data_dir = Some(dir.into());
+        self
+    }
+
+    /// Also sets host functions as globals of the same name, for plugins
+    /// calling `fn_name(...)` rather than `host.fn_name(...)`. Disabled by
+    /// default.
+    pub fn flat_host_functions(mut self, flat: bool) -> Self {
+        self.manager.flat_host_functions = flat;
+        self
+    }
+
+    /// Limits the plugins listed by `api.list_plugins()` to those for which
+    /// `visible(viewer, plugin)` returns `true`. Plugins see every plugin by
+    /// default.
+    ///
+    /// `api.list_plugins()` returns a list of tables with the `id`, `version`
+    /// and `format` of each plugin, whether it is `loaded`, and the `name`,
+    /// `description`, `author` and `license` of the plugins registered
+    /// through this manager.
+    pub fn plugin_visibility<F>(mut self, visible: F) -> Self
+    where
+        F: Fn(&Bundle, &Bundle) -> bool + Send + Sync + 'static,
+    {
+        self.manager.plugin_visibility = Some(Arc::new(visible));
+        self
+    }
+
+    /// Exposes to each plugin only the functions of the `api` and `host`
+    /// tables for which `allow(plugin, path)` returns `true`, `path` being
+    /// e.g. `api.call_function_optional_depend` or `host.read_file`. Plugins
+    /// get every function by default.
+    ///
+    /// The filter applies on top of the `api` entry of the plugin config,
+    /// which lists the functions and tables of functions the plugin uses.
+    /// Hiding `api.call_function_depend` also disables the `deps` table.
+    pub fn api_filter<F>(mut self, allow: F) -> Self
+    where
+        F: Fn(&Bundle, &str) -> bool + Send + Sync + 'static,
+    {
+        self.manager.api_filter = Some(Arc::new(allow));
+        self
+    }
+
+    /// Enables the features declared in the `[features]` table of a plugin's
+    /// config for which `toggle(plugin, feature, default)` returns `true`,
+    /// instead of those enabled by default.
+    ///
+    /// Features are decided when the plugin is registered, and read by the
+    /// plugin from the `plugin.features` table.
+    pub fn feature_toggle<F>(mut self, toggle: F) -> Self
+    where
+        F: Fn(&Bundle, &str, bool) -> bool + Send + Sync + 'static,
+    {
+        self.manager.feature_toggle = Some(Arc::new(toggle));
+        self
+    }
+
+    /// Sets the global `name` to `value` in every plugin state, before the
+    /// plugin's entry script runs.
+    pub fn global(mut self, name: impl Into<String>, value: Variable) -> Self {
+        Arc::make_mut(&mut self.manager.globals).insert(name.into(), value);
+        self
+    }
+
+    /// Runs the chunk `name`, Lua source or precompiled bytecode, in every
+    /// plugin state before the plugin's entry script, e.g. to define helpers
+    /// or polyfills shared by all plugins.
+    ///
+    /// Preludes run in registration order, in the plugin's environment, so
+    /// the globals they set belong to the plugin. A failing prelude fails the
+    /// load. Registering a prelude under the name of another replaces it.
+    pub fn prelude(mut self, name: impl Into<String>, chunk: impl Into<Vec<u8>>) -> Self {
+        Arc::make_mut(&mut self.manager.preludes).insert(name.into(), chunk.into());
+        self
+    }
+
+    /// Lets plugins leave the request `name` unhandled.
+    ///
+    /// Plugins missing the global function handling a request fail to load,
+    /// unless the request is optional: the load then succeeds with a
+    /// diagnostic, see [`LuaManager::diagnostics`], and calls to the request
+    /// return `nil` while the plugin does not handle it.
+    pub fn optional_request(mut self, name: impl Into<String>) -> Self {
+        Arc::make_mut(&mut self.manager.requests)
+            .optional
+            .insert(name.into());
+        self
+    }
+
+    /// Names the arguments of the request `name`, in order.
+    ///
+    /// plux requests only declare the types of their arguments, which are
+    /// named `arg_0`, `arg_1`... unless named here. The names show in the
+    /// signature of the functions registered for the request and in the
+    /// errors of their arguments.
+    pub fn request_inputs<I, S>(mut self, name: impl Into<String>, inputs: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Arc::make_mut(&mut self.manager.requests)
+            .inputs
+            .insert(name.into(), inputs.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Creates the Lua state of each plugin with `factory` instead of
+    /// opening the libraries of the sandbox in a new state, so hosts choose
+    /// the opened libraries and the state options, or set globals up front.
+    ///
+    /// The sandbox still removes the libraries and functions it does not
+    /// allow from the created state, and the plugin API is registered in it as
+    /// usual. Plugins running in a shared state, see
+    /// [`LuaManagerBuilder::shared_state`], do not use the factory.
+    pub fn state_factory<F>(mut self, factory: F) -> Self
+    where
+        F: Fn(&Bundle) -> mlua::Result<Lua> + Send + Sync + 'static,
+    {
+        self.manager.state_factory = Some(Arc::new(factory));
+        self
+    }
+
+    /// Calls `hook` with each plugin state once the plugin API is registered
+    /// in it and before the plugin's entry script runs, e.g. to set globals
+    /// of the host's own, which are frozen with the other host globals right
+    /// after. The hook also runs for the state built by a reload.
+    ///
+    /// A failing hook fails the load.
+    pub fn before_load_hook<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&Bundle, &Lua) -> mlua::Result<()> + Send + Sync + 'static,
+    {
+        self.manager.before_load_hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Calls `hook` with each plugin state once the plugin's entry script and
+    /// `on_load` ran. The hook also runs for the state built by a reload, and
+    /// when a lazily loaded plugin creates its state.
+    ///
+    /// A failing hook fails the load, and only adds a warning to the report
+    /// of a reload.
+    pub fn after_load_hook<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&Bundle, &Lua) -> mlua::Result<()> + Send + Sync + 'static,
+    {
+        self.manager.after_load_hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Calls `hook` with each plugin state after the plugin's `on_unload`
+    /// and before the state is dropped, on unload and for the state replaced
+    /// by a reload. Lazily loaded plugins that never created their state do
+    /// not call it.
+    ///
+    /// Failures of the hook are logged.
+    pub fn unload_hook<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&Bundle, &Lua) -> mlua::Result<()> + Send + Sync + 'static,
+    {
+        self.manager.unload_hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Limits the messages the manager logs to `level` and more severe ones,
+    /// all levels by default.
+    ///
+    /// This only filters the manager's own messages, the logger installed by
+    /// the host still decides what gets written.
+    pub fn log_level(mut self, level: LevelFilter) -> Self {
+        self.manager.log_level = level;
+        self
+    }
+
+    /// Calls `listener` with the outcome of every reload performed by
+    /// [`LuaManager::tick`], whether requested with
+    /// [`LuaManager::request_reload`] or triggered by a change of the plugin's
+    /// sources.
+    pub fn reload_listener<F>(mut self, listener: F) -> Self
+    where
+        F: Fn(&Bundle, Result<&ReloadReport, &ManagerError>) + Send + Sync + 'static,
+    {
+        self.manager.reload_listener = Some(Arc::new(listener));
+        self
+    }
+
+    /// Watches the directory of every loaded plugin and schedules a reload
+    /// whenever one of its Lua sources is modified, created or removed.
+    ///
+    /// Reloads are performed by the next [`LuaManager::tick`], so the host
+    /// keeps control of the thread they run on. A plugin whose directory
+    /// cannot be watched still loads, with a diagnostic.
+    #[cfg(feature = "watch")]
+    pub fn watch(mut self, enabled: bool) -> Self {
+        self.manager.watch = enabled;
+        self
+    }
+
+    /// Records the lines of their sources plugins run, for
+    /// [`LuaManager::coverage_report`]. Disabled by default.
+    ///
+    /// Applies to the plugins loaded afterwards. Each line run calls the debug
+    /// hook, so plugins run noticeably slower with coverage enabled. Lines of
+    /// modules and shared libraries are recorded too, under their path.
+    #[cfg(feature = "debug")]
+    pub fn coverage(mut self, enabled: bool) -> Self {
+        self.manager.coverage = enabled.then(|| Arc::new(Coverage::default()));
+        self
+    }
+
+    /// Compiles the Teal sources of plugins, entry scripts and modules ending
+    /// in `.tl`, with the Teal compiler `compiler`, the source of its `tl`
+    /// module.
+    ///
+    /// Sources are type checked when loaded: syntax and type errors fail the
+    /// load with a [`PluginError::SourceError`] listing them with their file,
+    /// line and column. `require("a.b")` also looks for `a/b.tl` and
+    /// `a/b/init.tl`, after the Lua sources. Plugins written in Teal declare
+    /// their entry, such as `entry = "main.tl"`, in their config.
+    ///
+    /// The compiler is the [`SourceTransform`] of `.tl` files, see
+    /// [`LuaManagerBuilder::source_transform`].
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let manager = LuaManager::builder()
+    ///     .teal_compiler(include_bytes!("tl.lua"))?
+    ///     .build();
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `compiler` is not a valid `tl` module.
+    #[cfg(feature = "teal")]
+    pub fn teal_compiler(self, compiler: impl AsRef<[u8]>) -> Result<Self, ManagerError> {
+        let compiler = TealCompiler::new(compiler.as_ref())?;
+        Ok(self.source_transform(TEAL_EXTENSION, compiler))
+    }
+
+    /// Compiles the Fennel sources of plugins, entry scripts and modules
+    /// ending in `.fnl`, with the Fennel compiler `compiler`, the source of
+    /// its `fennel` module.
+    ///
+    /// Sources are compiled when loaded, and the generated Lua is kept for
+    /// the next loads of the same source, reloads included. Compile errors
+    /// fail the load with a [`PluginError::SourceError`]. `require("a.b")`
+    /// also looks for `a/b.fnl` and `a/b/init.fnl`, after the Lua sources.
+    /// Plugins written in Fennel declare their entry, such as
+    /// `entry = "main.fnl"`, in their config.
+    ///
+    /// The compiler is the [`SourceTransform`] of `.fnl` files, see
+    /// [`LuaManagerBuilder::source_transform`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `compiler` is not a valid `fennel` module.
+    #[cfg(feature = "fennel")]
+    pub fn fennel_compiler(self, compiler: impl AsRef<[u8]>) -> Result<Self, ManagerError> {
+        let compiler = FennelCompiler::new(compiler.as_ref())?;
+        Ok(self.source_transform(FENNEL_EXTENSION, compiler))
+    }
+
+    /// Makes [`LuaManager::tick`] run a garbage collection step on every
+    /// plugin whose Lua state uses more than `bytes` of memory.
+    pub fn gc_watermark(mut self, bytes: usize) -> Self {
+        self.manager.gc_watermark = Some(bytes);
+        self
+    }
+
+    /// Makes unknown capabilities in a plugin's config an error, instead of a
+    /// warning recorded in the plugin's [diagnostics](LuaManager::diagnostics).
+    pub fn strict_capabilities(mut self, strict: bool) -> Self {
+        self.manager.strict_capabilities = strict;
+        self
+    }
+
+    /// Enables or disables dependency version checks, enabled by default.
+    ///
+    /// When enabled, registering a plugin fails if one of its required
+    /// dependencies is registered only in versions it does not accept, and
+    /// loading a plugin records a diagnostic for every optional dependency in
+    /// the same situation. Hosts resolving dependencies themselves can disable
+    /// the checks.
+    pub fn dependency_check(mut self, enabled: bool) -> Self {
+        self.manager.check_dependencies = enabled;
+        self
+    }
+
+    /// Quarantines plugins whose calls fail repeatedly, according to `policy`.
+    ///
+    /// Calls to a quarantined plugin fail with [`PluginError::Quarantined`]
+    /// without entering Lua, and with a `quarantined` error kind when made
+    /// through `api.call_function_depend`. Failure counters reset on any
+    /// success. The quarantine is lifted by [`LuaManager::unquarantine`] or a
+    /// successful [`LuaManager::reload_plugin`].
+    pub fn quarantine(mut self, policy: QuarantinePolicy) -> Self {
+        self.manager.quarantine_policy = Some(policy);
+        self
+    }
+
+    /// Calls `listener` with the bundle of every plugin that gets quarantined.
+    pub fn quarantine_listener<F>(mut self, listener: F) -> Self
+    where
+        F: Fn(&Bundle) + Send + Sync + 'static,
+    {
+        self.manager.quarantine_listener = Some(Arc::new(listener));
+        self
+    }
+
+    /// Marks plugins failing to load as faulted instead of failing their
+    /// load, disabled by default.
+    ///
+    /// Without isolation, a plugin failing to load aborts the load of the
+    /// plugins loaded with it, such as by [`plux_rs::Loader::load_plugins`].
+    /// With it, the faulted plugin is loaded without any function and the
+    /// other plugins load and run as usual, until
+    /// [`LuaManager::reload_plugin`] brings it back. Together with
+    /// [`LuaManagerBuilder::quarantine`], this keeps a bad plugin from taking
+    /// the host down, whether it fails to load or its calls keep failing, see
+    /// [`LuaManager::is_faulted`] and [`LuaManager::faults`].
+    pub fn fault_isolation(mut self, enabled: bool) -> Self {
+        self.manager.fault_isolation = enabled;
+        self
+    }
+
+    /// Restarts faulted plugins according to `policy`, see
+    /// [`LuaManager::is_faulted`].
+    ///
+    /// [`LuaManager::tick`] reloads a faulted plugin once the backoff of the
+    /// policy has elapsed since it was first seen faulted or last restarted,
+    /// creating a new state and running its entry script again. A restart
+    /// that brings the plugin back lifts its quarantine and resets the count
+    /// and backoff of restarts, one that fails is recorded in
+    /// [`LuaManager::faults`]. After `max_restarts` restarts failing in a row,
+    /// the plugin stays faulted until [`LuaManager::unquarantine`] or a reload
+    /// by the host.
+    pub fn restart_policy(mut self, policy: RestartPolicy) -> Self {
+        self.manager.restart_policy = Some(policy);
+        self
+    }
+
+    /// Calls `listener` with the bundle of a faulted plugin and the outcome
+    /// of every automatic restart.
+    pub fn restart_listener<F>(mut self, listener: F) -> Self
+    where
+        F: Fn(&Bundle, &RestartAttempt) + Send + Sync + 'static,
+    {
+        self.manager.restart_listener = Some(Arc::new(listener));
+        self
+    }
+
+    /// Makes calls to a paused plugin wait up to `timeout` for it to resume,
+    /// instead of failing immediately.
+    pub fn pause_timeout(mut self, timeout: Duration) -> Self {
+        self.manager.pause_timeout = Some(timeout);
+        self
+    }
+
+    /// Limits how fast each plugin may call host functions, through the `host`
+    /// table, and its dependencies, through `api.call_function_depend` and
+    /// `deps`, to protect expensive host functions from tight plugin loops.
+    ///
+    /// Every plugin gets a bucket of `limit.burst` calls, refilled at
+    /// `limit.per_second` calls per second and kept across reloads. Calls
+    /// finding the bucket empty fail without running, raising a structured
+    /// error of kind `rate_limited` to the plugin.
+    pub fn rate_limit(mut self, limit: RateLimit) -> Self {
+        self.manager.rate_limit = Some(limit);
+        self
+    }
+
+    /// Records the calls plugins make to host functions, through the `host`
+    /// table, and to the functions of the `api` table, keeping the last
+    /// `capacity` records for [`LuaManager::audit_log`].
+    ///
+    /// Each [`AuditRecord`] holds the calling plugin, the function, a summary
+    /// of the arguments, and when and how long the call ran. Calls through
+    /// `deps` are recorded as calls to `api.call_function_depend`. Auditing
+    /// is disabled by default.
+    pub fn audit_log(mut self, capacity: usize) -> Self {
+        let sink = self.manager.audit.as_ref().and_then(|audit| audit.sink());
+        self.manager.audit = Some(Arc::new(AuditLog::new(capacity, sink)));
+        self
+    }
+
+    /// Hands every [`AuditRecord`] to `sink` as the call completes, see
+    /// [`LuaManagerBuilder::audit_log`]. Without an audit log, records are only
+    /// handed to the sink.
+    pub fn audit_sink<F>(mut self, sink: F) -> Self
+    where
+        F: Fn(&AuditRecord) + Send + Sync + 'static,
+    {
+        let capacity = self
+            .manager
+            .audit
+            .as_ref()
+            .map_or(0, |audit| audit.capacity());
+        let sink: AuditSink = Arc::new(sink);
+        self.manager.audit = Some(Arc::new(AuditLog::new(capacity, Some(sink))));
+        self
+    }
+
+    /// Captures what plugins write through `print`, `io.write`,
+    /// `io.stdout:write` and `io.stderr:write`, keeping the last `capacity`
+    /// bytes of each plugin for [`LuaManager::plugin_output`].
+    ///
+    /// Captured output no longer reaches the process's standard streams, nor
+    /// the logger for `print`. Capture is disabled by default.
+    pub fn output_capture(mut self, capacity: usize) -> Self {
+        let sink = self
+            .manager
+            .output
+            .as_ref()
+            .and_then(|output| output.sink());
+        self.manager.output = Some(Arc::new(OutputCapture::new(capacity, sink)));
+        self
+    }
+
+    /// Hands what plugins write to `sink`, with the writing plugin and the
+    /// stream written to, see [`LuaManagerBuilder::output_capture`]. Without
+    /// output capture, the output is only handed to the sink.
+    pub fn output_sink<F>(mut self, sink: F) -> Self
+    where
+        F: Fn(&Bundle, OutputStream, &str) + Send + Sync + 'static,
+    {
+        let capacity = self
+            .manager
+            .output
+            .as_ref()
+            .map_or(0, |output| output.capacity());
+        let sink: OutputSink = Arc::new(sink);
+        self.manager.output = Some(Arc::new(OutputCapture::new(capacity, Some(sink))));
+        self
+    }
+
+    /// Reads plugin sources through providers created by `factory`.
+    ///
+    /// The factory receives the plugin's path and is called once when the
+    /// plugin is registered and once when it is loaded. By default sources are
+    /// read from the plugin directory with [`FsSourceProvider`]. Plugins mounted
+    /// by the manager keep their own provider.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use plux_lua_manager::{FsSourceProvider, LuaManager};
+    ///
+    /// let manager = LuaManager::builder()
+    ///     .source_provider(|path| Arc::new(FsSourceProvider::new(path)))
+    ///     .build();
+    /// ```
+    pub fn source_provider<F>(mut self, factory: F) -> Self
+    where
+        F: Fn(&std::path::Path) -> Arc<dyn SourceProvider> + Send + Sync + 'static,
+    {
+        self.manager.source_factory = Arc::new(factory);
+        self
+    }
+
+    /// Returns the configured manager.
+    pub fn build(self) -> LuaManager {
+        self.manager
+    }
+}
+
+impl LuaManager {
+    /// Creates a new instance of `LuaManager`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use plux_lua_manager::LuaManager;
+    ///
+    /// let manager = LuaManager::new();
+    /// ```
+    pub fn new() -> Self {
+        Self {
+            lua_refs: Arc::new(RwLock::new(IndexMap::new())),
+            source_factory: Arc::new(|path| Arc::new(FsSourceProvider::new(path))),
+            mounts: Arc::new(RwLock::new(IndexMap::new())),
+            registered: Arc::new(RwLock::new(IndexMap::new())),
+            check_dependencies: true,
+            strict_capabilities: false,
+            pending_reloads: Arc::new(Mutex::new(vec![])),
+            tick_cursor: Arc::new(AtomicUsize::new(0)),
+            gc_watermark: None,
+            shared: SharedStore::default(),
+            store: KeyValueStore::default(),
+            events: EventBus::default(),
+            quarantine_policy: None,
+            quarantine_listener: None,
+            fault_isolation: false,
+            restart_policy: None,
+            restart_listener: None,
+            pause_timeout: None,
+            rate_limit: None,
+            audit: None,
+            output: None,
+            sandbox: SandboxPolicy::default(),
+            memory_limit: None,
+            shared_lua: None,
+            lazy_loading: false,
+            preloaded: Arc::new(Mutex::new(HashMap::new())),
+            call_timeout: None,
+            entry: DEFAULT_ENTRY.to_string(),
+            resolvers: Arc::new(vec![]),
+            transforms: Arc::new(IndexMap::new()),
+            middleware: Arc::new(vec![]),
+            data_dir: None,
+            bytecode: None,
+            flat_host_functions: false,
+            plugin_visibility: None,
+            api_filter: None,
+            feature_toggle: None,
+            state_factory: None,
+            before_load_hook: None,
+            after_load_hook: None,
+            unload_hook: None,
+            globals: Arc::new(IndexMap::new()),
+            preludes: Arc::new(IndexMap::new()),
+            requests: Arc::new(RequestOptions::default()),
+            log_level: LevelFilter::Trace,
+            reload_listener: None,
+            #[cfg(feature = "watch")]
+            watch: false,
+            #[cfg(feature = "watch")]
+            watchers: Arc::new(Mutex::new(IndexMap::new())),
+            #[cfg(feature = "debug")]
+            coverage: None,
+        }
+    }
+
+    /// Returns a [`LuaManagerBuilder`] to configure a new manager.
+    pub fn builder() -> LuaManagerBuilder {
+        LuaManagerBuilder::default()
+    }
+
+    /// Returns a builder going on with the configuration of this manager, to
+    /// which the `with_*` methods delegate.
+    fn into_builder(self) -> LuaManagerBuilder {
+        LuaManagerBuilder { manager: self }
+    }
+
+    /// See [`LuaManagerBuilder::gc_watermark`].
+    pub fn with_gc_watermark(self, bytes: usize) -> Self {
+        self.into_builder().gc_watermark(bytes).build()
+    }
+
+    /// See [`LuaManagerBuilder::strict_capabilities`].
+    pub fn with_strict_capabilities(self, strict: bool) -> Self {
+        self.into_builder().strict_capabilities(strict).build()
+    }
+
+    /// Returns the capabilities declared by a registered plugin, including
+    /// those granted by its permissions.
+    ///
+    /// This is available as soon as the plugin is registered, so that the
+    /// host can ask for consent before loading it.
+    pub fn capabilities(&self, bundle: &Bundle) -> Option<Vec<String>> {
+        self.registered
+            .read_unpoisoned()
+            .get(bundle)
+            .map(|registration| registration.capabilities.clone())
+    }
+
+    /// Returns the descriptive fields of a registered plugin's config.
+    pub fn metadata(&self, bundle: &Bundle) -> Option<PluginMetadata> {
+        self.registered
+            .read_unpoisoned()
+            .get(bundle)
+            .map(|registration| registration.metadata.clone())
+    }
+
+    /// Returns the documentation of the function `name` exported by a loaded
+    /// plugin, from the `description`, `examples` and `since` fields of its
+    /// declaration.
+    pub fn function_doc(&self, bundle: &Bundle, name: &str) -> Option<FunctionDoc> {
+        self.registered
+            .read_unpoisoned()
+            .get(bundle)
+            .and_then(|registration| Some(registration.functions.get(name)?.doc.clone()))
+    }
+
+    /// Returns the documentation of all the functions exported by a loaded
+    /// plugin, in declaration order.
+    pub fn function_docs(&self, bundle: &Bundle) -> Option<IndexMap<String, FunctionDoc>> {
+        self.registered
+            .read_unpoisoned()
+            .get(bundle)
+            .map(|registration| {
+                registration
+                    .functions
+                    .iter()
+                    .map(|(name, info)| (name.clone(), info.doc.clone()))
+                    .collect()
+            })
+    }
+
+    /// Returns the signatures and documentation of the functions exported by
+    /// a loaded plugin, in declaration order.
+    ///
+    /// Plugins list the functions of other plugins with
+    /// `api.list_functions(id, version)`.
+    pub fn functions(&self, bundle: &Bundle) -> Option<Vec<FunctionInfo>> {
+        self.registered
+            .read_unpoisoned()
+            .get(bundle)
+            .map(|registration| registration.functions.values().cloned().collect())
+    }
+
+    /// Returns whether each feature declared by a registered plugin is
+    /// enabled, see [`LuaManager::with_feature_toggle`].
+    pub fn features(&self, bundle: &Bundle) -> Option<HashMap<String, bool>> {
+        self.registered
+            .read_unpoisoned()
+            .get(bundle)
+            .map(|registration| registration.features.clone())
+    }
+
+    /// Returns the user settings declared by a registered plugin, by name.
+    ///
+    /// Plugins declare them as `[user_settings.<name>]` tables of their
+    /// config, see [`SettingDeclaration`](crate::SettingDeclaration).
+    pub fn settings_schema(&self, bundle: &Bundle) -> Option<Vec<SettingInfo>> {
+        self.registered
+            .read_unpoisoned()
+            .get(bundle)
+            .map(|registration| registration.settings.schema())
+    }
+
+    /// Returns the value of every user setting of a registered plugin, `Null`
+    /// for settings without a value or a default.
+    pub fn settings(&self, bundle: &Bundle) -> Option<IndexMap<String, Variable>> {
+        self.registered
+            .read_unpoisoned()
+            .get(bundle)
+            .map(|registration| registration.settings.values())
+    }
+
+    /// Returns the value of the user setting `name` of a registered plugin,
+    /// `Null` if it has no value or default.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plugin is not registered or does not declare
+    /// the setting.
+    pub fn setting(&self, bundle: &Bundle, name: &str) -> Result<Variable, ManagerError> {
+        Ok(self.plugin_settings(bundle)?.get(name)?)
+    }
+
+    /// Sets the user setting `name` of a registered plugin to `value`
+    /// converted to the declared type, or back to its default if `value` is
+    /// `Null`.
+    ///
+    /// With a data directory (see [`LuaManager::with_data_dir`]), the values
+    /// persist in the [`SETTINGS_FILE`] of the plugin's directory and are read
+    /// back when the plugin is registered again. The handlers the plugin set
+    /// with `settings.on_change(handler)` are called if it is loaded:
+    ///
+    /// ```lua
+    /// settings.on_change(function(name, value, old)
+    ///     log.info(name .. " changed from " .. tostring(old) .. " to " .. tostring(value))
+    /// end)
+    /// local volume = settings.get("volume")
+    /// settings.set("volume", volume / 2)
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plugin is not registered, does not declare the
+    /// setting, `value` is not of its type or the values cannot be written.
+    pub fn set_setting(
+        &self,
+        bundle: &Bundle,
+        name: &str,
+        value: Variable,
+    ) -> Result<(), ManagerError> {
+        log_at!(self, Debug, "Setting `{}` of plugin {}", name, bundle);
+        let (value, old) = self.plugin_settings(bundle)?.set(name, value)?;
+
+        let plugin = self.lua_refs.read_unpoisoned().get(bundle).cloned();
+        if let Some(plugin) = plugin {
+            let result = plugin.lua.peek().and_then(|lua| match lua {
+                Some(lua) => lua_settings::notify_change(&lua, name, &value, &old),
+                None => Ok(()),
+            });
+            if let Err(e) = result {
+                log_at!(
+                    self,
+                    Warn,
+                    "Notifying plugin {} of setting `{}`: {}",
+                    bundle,
+                    name,
+                    e
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the user settings of a registered plugin.
+    fn plugin_settings(&self, bundle: &Bundle) -> Result<Arc<Settings>, PluginError> {
+        self.registered
+            .read_unpoisoned()
+            .get(bundle)
+            .map(|registration| registration.settings.clone())
+            .ok_or_else(|| PluginError::NotRegistered(bundle.to_string()))
+    }
+
+    /// Returns the permissions declared by a registered plugin, `None` if the
+    /// plugin is not registered or declares no permissions and is therefore
+    /// unrestricted.
+    pub fn permissions(&self, bundle: &Bundle) -> Option<Vec<Permission>> {
+        self.registered
+            .read_unpoisoned()
+            .get(bundle)
+            .and_then(|registration| registration.permissions.clone())
+    }
+
+    /// See [`LuaManagerBuilder::quarantine`].
+    pub fn with_quarantine(self, policy: QuarantinePolicy) -> Self {
+        self.into_builder().quarantine(policy).build()
+    }
+
+    /// See [`LuaManagerBuilder::quarantine_listener`].
+    pub fn with_quarantine_listener<F>(self, listener: F) -> Self
+    where
+        F: Fn(&Bundle) + Send + Sync + 'static,
+    {
+        self.into_builder().quarantine_listener(listener).build()
+    }
+
+    /// See [`LuaManagerBuilder::fault_isolation`].
+    pub fn with_fault_isolation(self, enabled: bool) -> Self {
+        self.into_builder().fault_isolation(enabled).build()
+    }
+
+    /// See [`LuaManagerBuilder::restart_policy`].
+    pub fn with_restart_policy(self, policy: RestartPolicy) -> Self {
+        self.into_builder().restart_policy(policy).build()
+    }
+
+    /// See [`LuaManagerBuilder::restart_listener`].
+    pub fn with_restart_listener<F>(self, listener: F) -> Self
+    where
+        F: Fn(&Bundle, &RestartAttempt) + Send + Sync + 'static,
+    {
+        self.into_builder().restart_listener(listener).build()
+    }
+
+    /// See [`LuaManagerBuilder::pause_timeout`].
+    pub fn with_pause_timeout(self, timeout: Duration) -> Self {
+        self.into_builder().pause_timeout(timeout).build()
+    }
+
+    /// See [`LuaManagerBuilder::rate_limit`].
+    pub fn with_rate_limit(self, limit: RateLimit) -> Self {
+        self.into_builder().rate_limit(limit).build()
+    }
+
+    /// See [`LuaManagerBuilder::audit_log`].
+    pub fn with_audit_log(self, capacity: usize) -> Self {
+        self.into_builder().audit_log(capacity).build()
+    }
+
+    /// See [`LuaManagerBuilder::audit_sink`].
+    pub fn with_audit_sink<F>(self, sink: F) -> Self
+    where
+        F: Fn(&AuditRecord) + Send + Sync + 'static,
+    {
+        self.into_builder().audit_sink(sink).build()
+    }
+
+    /// Returns the calls recorded by the audit log, oldest first, see
+    /// [`LuaManager::with_audit_log`].
+    pub fn audit_log(&self) -> Vec<AuditRecord> {
+        self.audit
+            .as_ref()
+            .map(|audit| audit.records())
+            .unwrap_or_default()
+    }
+
+    /// See [`LuaManagerBuilder::output_capture`].
+    pub fn with_output_capture(self, capacity: usize) -> Self {
+        self.into_builder().output_capture(capacity).build()
+    }
+
+    /// See [`LuaManagerBuilder::output_sink`].
+    pub fn with_output_sink<F>(self, sink: F) -> Self
+    where
+        F: Fn(&Bundle, OutputStream, &str) + Send + Sync + 'static,
+    {
+        self.into_builder().output_sink(sink).build()
+    }
+
+    /// Returns the output captured for `bundle`, see
+    /// [`LuaManager::with_output_capture`].
+    pub fn plugin_output(&self, bundle: &Bundle) -> String {
+        self.output
+            .as_ref()
+            .map(|output| output.output(bundle, false))
+            .unwrap_or_default()
+    }
+
+    /// Returns and clears the output captured for `bundle`.
+    pub fn take_plugin_output(&self, bundle: &Bundle) -> String {
+        self.output
+            .as_ref()
+            .map(|output| output.output(bundle, true))
+            .unwrap_or_default()
+    }
+
+    /// See [`LuaManagerBuilder::sandbox`].
+    pub fn with_sandbox(self, policy: SandboxPolicy) -> Self {
+        self.into_builder().sandbox(policy).build()
+    }
+
+    /// See [`LuaManagerBuilder::memory_limit`].
+    pub fn with_memory_limit(self, bytes: usize) -> Self {
+        self.into_builder().memory_limit(bytes).build()
+    }
+
+    /// See [`LuaManagerBuilder::shared_state`].
+    pub fn with_shared_state(self, shared: bool) -> Self {
+        self.into_builder().shared_state(shared).build()
+    }
+
+    /// See [`LuaManagerBuilder::lazy_loading`].
+    pub fn with_lazy_loading(self, lazy: bool) -> Self {
+        self.into_builder().lazy_loading(lazy).build()
+    }
+
+    /// See [`LuaManagerBuilder::call_timeout`].
+    pub fn with_call_timeout(self, timeout: Duration) -> Self {
+        self.into_builder().call_timeout(timeout).build()
+    }
+
+    /// See [`LuaManagerBuilder::teal_compiler`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `compiler` is not a valid `tl` module.
+    #[cfg(feature = "teal")]
+    pub fn with_teal_compiler(self, compiler: impl AsRef<[u8]>) -> Result<Self, ManagerError> {
+        Ok(self.into_builder().teal_compiler(compiler)?.build())
+    }
+
+    /// See [`LuaManagerBuilder::fennel_compiler`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `compiler` is not a valid `fennel` module.
+    #[cfg(feature = "fennel")]
+    pub fn with_fennel_compiler(self, compiler: impl AsRef<[u8]>) -> Result<Self, ManagerError> {
+        Ok(self.into_builder().fennel_compiler(compiler)?.build())
+    }
+
+    /// See [`LuaManagerBuilder::entry`].
+    pub fn with_entry(self, entry: impl Into<String>) -> Self {
+        self.into_builder().entry(entry).build()
+    }
+
+    /// See [`LuaManagerBuilder::bytecode_cache`].
+    pub fn with_bytecode_cache(self, disk: bool) -> Self {
+        self.into_builder().bytecode_cache(disk).build()
+    }
+
+    /// See [`LuaManagerBuilder::module_resolver`].
+    pub fn with_module_resolver(self, resolver: impl ModuleResolver + 'static) -> Self {
+        self.into_builder().module_resolver(resolver).build()
+    }
+
+    /// See [`LuaManagerBuilder::source_transform`].
+    pub fn with_source_transform(
+        self,
+        extension: impl Into<String>,
+        transform: impl SourceTransform + 'static,
+    ) -> Self {
+        self.into_builder()
+            .source_transform(extension, transform)
+            .build()
+    }
+
+    /// See [`LuaManagerBuilder::middleware`].
+    pub fn with_middleware(self, middleware: impl Middleware + 'static) -> Self {
+        self.into_builder().middleware(middleware).build()
+    }
+
+    /// See [`LuaManagerBuilder::shared_lib_dirs`].
+    pub fn with_shared_lib_dirs<I, P>(self, dirs: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        self.into_builder().shared_lib_dirs(dirs).build()
+    }
+
+    /// See [`LuaManagerBuilder::data_dir`].
+    pub fn with_data_dir(self, dir: impl Into<PathBuf>) -> Self {
+        self.into_builder().data_dir(dir).build()
+    }
+
+    /// Returns the data directory of the plugin `id`, if the manager has a
+    /// data directory. See [`LuaManager::with_data_dir`].
+    pub fn plugin_data_dir(&self, id: &str) -> Option<PathBuf> {
+        self.data_dir.as_ref().map(|dir| dir.join(id))
+    }
+
+    /// See [`LuaManagerBuilder::flat_host_functions`].
+    pub fn with_flat_host_functions(self, flat: bool) -> Self {
+        self.into_builder().flat_host_functions(flat).build()
+    }
+
+    /// See [`LuaManagerBuilder::plugin_visibility`].
+    pub fn with_plugin_visibility<F>(self, visible: F) -> Self
+    where
+        F: Fn(&Bundle, &Bundle) -> bool + Send + Sync + 'static,
+    {
+        self.into_builder().plugin_visibility(visible).build()
+    }
+
+    /// See [`LuaManagerBuilder::api_filter`].
+    pub fn with_api_filter<F>(self, allow: F) -> Self
+    where
+        F: Fn(&Bundle, &str) -> bool + Send + Sync + 'static,
+    {
+        self.into_builder().api_filter(allow).build()
+    }
+
+    /// See [`LuaManagerBuilder::feature_toggle`].
+    pub fn with_feature_toggle<F>(self, toggle: F) -> Self
+    where
+        F: Fn(&Bundle, &str, bool) -> bool + Send + Sync + 'static,
+    {
+        self.into_builder().feature_toggle(toggle).build()
+    }
+
+    /// See [`LuaManagerBuilder::global`].
+    pub fn with_global(self, name: impl Into<String>, value: Variable) -> Self {
+        self.into_builder().global(name, value).build()
+    }
+
+    /// See [`LuaManagerBuilder::prelude`].
+    pub fn with_prelude(self, name: impl Into<String>, chunk: impl Into<Vec<u8>>) -> Self {
+        self.into_builder().prelude(name, chunk).build()
+    }
+
+    /// See [`LuaManagerBuilder::optional_request`].
+    pub fn with_optional_request(self, name: impl Into<String>) -> Self {
+        self.into_builder().optional_request(name).build()
+    }
+
+    /// See [`LuaManagerBuilder::request_inputs`].
+    pub fn with_request_inputs<I, S>(self, name: impl Into<String>, inputs: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.into_builder().request_inputs(name, inputs).build()
+    }
+
+    /// See [`LuaManagerBuilder::state_factory`].
+    pub fn with_state_factory<F>(self, factory: F) -> Self
+    where
+        F: Fn(&Bundle) -> mlua::Result<Lua> + Send + Sync + 'static,
+    {
+        self.into_builder().state_factory(factory).build()
+    }
+
+    /// See [`LuaManagerBuilder::before_load_hook`].
+    pub fn with_before_load_hook<F>(self, hook: F) -> Self
+    where
+        F: Fn(&Bundle, &Lua) -> mlua::Result<()> + Send + Sync + 'static,
+    {
+        self.into_builder().before_load_hook(hook).build()
+    }
+
+    /// See [`LuaManagerBuilder::after_load_hook`].
+    pub fn with_after_load_hook<F>(self, hook: F) -> Self
+    where
+        F: Fn(&Bundle, &Lua) -> mlua::Result<()> + Send + Sync + 'static,
+    {
+        self.into_builder().after_load_hook(hook).build()
+    }
+
+    /// See [`LuaManagerBuilder::unload_hook`].
+    pub fn with_unload_hook<F>(self, hook: F) -> Self
+    where
+        F: Fn(&Bundle, &Lua) -> mlua::Result<()> + Send + Sync + 'static,
+    {
+        self.into_builder().unload_hook(hook).build()
+    }
+
+    /// See [`LuaManagerBuilder::log_level`].
+    pub fn with_log_level(self, level: LevelFilter) -> Self {
+        self.into_builder().log_level(level).build()
+    }
+
+    /// See [`LuaManagerBuilder::reload_listener`].
+    pub fn with_reload_listener<F>(self, listener: F) -> Self
+    where
+        F: Fn(&Bundle, Result<&ReloadReport, &ManagerError>) + Send + Sync + 'static,
+    {
+        self.into_builder().reload_listener(listener).build()
+    }
+
+    /// See [`LuaManagerBuilder::watch`].
+    #[cfg(feature = "watch")]
+    pub fn with_watch(self, enabled: bool) -> Self {
+        self.into_builder().watch(enabled).build()
+    }
+
+    /// Pauses a plugin, keeping its state.
+    ///
+    /// Calls to the plugin's functions and request handlers fail with
+    /// [`PluginError::Paused`], or a `paused` error kind when made through
+    /// `api.call_function_depend`, and [`LuaManager::tick`] does not resume
+    /// its tasks until [`LuaManager::resume`] is called. A paused plugin can
+    /// still be reloaded and unloaded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plugin is not loaded.
+    pub fn pause(&self, bundle: &Bundle) -> Result<(), ManagerError> {
+        log_at!(self, Info, "Pausing plugin: {}", bundle);
+        self.get_plugin(bundle)?.health.pause();
+        Ok(())
+    }
+
+    /// Resumes a paused plugin.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plugin is not loaded.
+    pub fn resume(&self, bundle: &Bundle) -> Result<(), ManagerError> {
+        log_at!(self, Info, "Resuming plugin: {}", bundle);
+        self.get_plugin(bundle)?.health.resume();
+        Ok(())
+    }
+
+    /// Calls `hook` on the events of `triggers` while a plugin runs, with the
+    /// debug information of the running function, such as its source and
+    /// current line. Replaces the previous debug hook of the plugin.
+    ///
+    /// Meant for stepping debuggers, which may block in the hook at
+    /// breakpoints, and for coverage collection. The hook is kept across
+    /// reloads, and the budgets of the plugin are still enforced. With a
+    /// shared state, every plugin pays for the events of `triggers` while a
+    /// debug hook is set.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// use mlua::{HookTriggers, VmState};
+    ///
+    /// manager.set_debug_hook(&bundle, HookTriggers::new().every_line(), |_, debug| {
+    ///     println!("line {:?}", debug.current_line());
+    ///     Ok(VmState::Continue)
+    /// })?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plugin is not loaded.
+    #[cfg(feature = "debug")]
+    pub fn set_debug_hook<F>(
+        &self,
+        bundle: &Bundle,
+        triggers: HookTriggers,
+        hook: F,
+    ) -> Result<(), ManagerError>
+    where
+        F: Fn(&Lua, &mlua::Debug<'_>) -> mlua::Result<VmState> + Send + Sync + 'static,
+    {
+        let debugger = Debugger {
+            triggers,
+            hook: Arc::new(hook),
+        };
+        self.install_debugger(bundle, Some(debugger))
+    }
+
+    /// Removes the debug hook of a plugin, see [`LuaManager::set_debug_hook`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plugin is not loaded.
+    #[cfg(feature = "debug")]
+    pub fn remove_debug_hook(&self, bundle: &Bundle) -> Result<(), ManagerError> {
+        self.install_debugger(bundle, None)
+    }
+
+    /// See [`LuaManagerBuilder::coverage`].
+    #[cfg(feature = "debug")]
+    pub fn with_coverage(self, enabled: bool) -> Self {
+        self.into_builder().coverage(enabled).build()
+    }
+
+    /// Returns the lines run by each plugin since coverage was enabled or
+    /// reset, see [`LuaManager::with_coverage`].
+    #[cfg(feature = "debug")]
+    pub fn coverage_report(&self) -> CoverageReport {
+        self.coverage
+            .as_ref()
+            .map(|coverage| coverage.report())
+            .unwrap_or_default()
+    }
+
+    /// Forgets the lines run so far, e.g. between two test suites.
+    #[cfg(feature = "debug")]
+    pub fn reset_coverage(&self) {
+        if let Some(coverage) = &self.coverage {
+            coverage.reset();
+        }
+    }
+
+    /// Sets the debugger of a plugin, installed on its state if created.
+    #[cfg(feature = "debug")]
+    fn install_debugger(
+        &self,
+        bundle: &Bundle,
+        debugger: Option<Debugger>,
+    ) -> Result<(), ManagerError> {
+        let plugin = self.get_plugin(bundle)?;
+        *plugin.health.debugger.lock_unpoisoned() = debugger.clone();
+        if let Some(lua) = plugin.lua.peek()? {
+            match debugger {
+                Some(debugger) => lua.set_app_data(debugger),
+                None => lua.remove_app_data::<Debugger>(),
+            };
+            hooks::install(&lua)?;
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if the plugin is loaded and paused.
+    pub fn is_paused(&self, bundle: &Bundle) -> bool {
+        self.get_plugin(bundle)
+            .is_ok_and(|plugin| plugin.health.lock().is_paused())
+    }
+
+    /// Returns `true` if the plugin is loaded and quarantined.
+    pub fn is_quarantined(&self, bundle: &Bundle) -> bool {
+        self.get_plugin(bundle)
+            .is_ok_and(|plugin| plugin.health.lock().is_quarantined())
+    }
+
+    /// Returns `true` if the plugin failed to load with fault isolation, or is
+    /// loaded and quarantined.
+    pub fn is_faulted(&self, bundle: &Bundle) -> bool {
+        self.registered
+            .read_unpoisoned()
+            .get(bundle)
+            .is_some_and(|registration| registration.faulted)
+            || self.is_quarantined(bundle)
+    }
+
+    /// Returns the plugins that are faulted, see [`LuaManager::is_faulted`],
+    /// in registration order.
+    pub fn faulted_plugins(&self) -> Vec<Bundle> {
+        let bundles: Vec<_> = self.registered.read_unpoisoned().keys().cloned().collect();
+        bundles
+            .into_iter()
+            .filter(|bundle| self.is_faulted(bundle))
+            .collect()
+    }
+
+    /// Returns the last [`crate::FAULT_HISTORY`] failures of a registered plugin,
+    /// load failures and failed calls, oldest first.
+    ///
+    /// The history is kept across reloads, until the plugin is unregistered
+    /// or [`LuaManager::clear_faults`] is called.
+    pub fn faults(&self, bundle: &Bundle) -> Vec<Fault> {
+        self.registered
+            .read_unpoisoned()
+            .get(bundle)
+            .map(|registration| registration.faults.faults())
+            .unwrap_or_default()
+    }
+
+    /// Forgets the failures of a registered plugin.
+    pub fn clear_faults(&self, bundle: &Bundle) {
+        if let Some(registration) = self.registered.read_unpoisoned().get(bundle) {
+            registration.faults.clear();
+        }
+    }
+
+    /// Lifts the quarantine of a plugin and resets its failure counters.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plugin is not loaded.
+    pub fn unquarantine(&self, bundle: &Bundle) -> Result<(), ManagerError> {
+        log_at!(self, Info, "Lifting the quarantine of plugin: {}", bundle);
+        let plugin = self.get_plugin(bundle)?;
+        plugin.health.lock().clear();
+        plugin.health.restarts.lock_unpoisoned().reset();
+        Ok(())
+    }
+
+    /// See [`LuaManagerBuilder::dependency_check`].
+    pub fn with_dependency_check(self, enabled: bool) -> Self {
+        self.into_builder().dependency_check(enabled).build()
+    }
+
+    /// Returns the non-fatal problems found while loading a plugin, such as
+    /// optional dependencies available only in unaccepted versions or
+    /// unknown capabilities.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plugin is not loaded.
+    pub fn diagnostics(&self, bundle: &Bundle) -> Result<Vec<String>, ManagerError> {
+        Ok(self.get_plugin(bundle)?.diagnostics)
+    }
+
+    /// See [`LuaManagerBuilder::source_provider`].
+    pub fn with_source_provider<F>(self, factory: F) -> Self
+    where
+        F: Fn(&std::path::Path) -> Arc<dyn SourceProvider> + Send + Sync + 'static,
+    {
+        self.into_builder().source_provider(factory).build()
+    }
+
+    /// Mounts the plugin archive at `path` and returns the path to register
+    /// the plugin with.
+    ///
+    /// The archive is read in memory and holds the content of a plugin
+    /// directory. Since plux only registers directories, an empty directory
+    /// named after the archive stands for the plugin in the system temp
+    /// directory: `my_plugin-v1.0.0.zip` and `my_plugin-v1.0.0.lua.pluxpkg`
+    /// are both mounted as `my_plugin-v1.0.0.lua`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use plux_lua_manager::LuaManager;
+    /// use plux_rs::{Loader, StdInfo, function::FunctionOutput};
+    ///
+    /// let manager = LuaManager::new();
+    /// let path = manager.mount_archive("plugins/my_plugin-v1.0.0.zip").unwrap();
+    ///
+    /// let mut loader = Loader::<FunctionOutput, StdInfo>::new();
+    /// loader.context(|mut ctx| ctx.register_manager(manager)).unwrap();
+    /// loader.load_plugin_now(path.to_str().unwrap()).unwrap();
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file is not named like a plugin archive or
+    /// cannot be read as one.
+    #[cfg(feature = "archive")]
+    pub fn mount_archive(&self, path: impl AsRef<Path>) -> Result<PathBuf, ManagerError> {
+        let path = path.as_ref();
+        let dir_name = archive::plugin_dir_name(path).ok_or_else(|| {
+            PluginError::SourceError(format!("{} is not a plugin archive", path.display()))
+        })?;
+        let provider = ArchiveSourceProvider::open(path).map_err(PluginError::IoError)?;
+        self.mount(&dir_name, Arc::new(provider))
+    }
+
+    /// Mounts the single-file plugin at `path` and returns the path to
+    /// register the plugin with.
+    ///
+    /// The script is the plugin's entry script and its config is read from a
+    /// `--[[plux ... ]]` comment header opening the file, in the format of
+    /// `config.toml`. The plugin is named after the file, e.g. `greeter.lua`
+    /// with `version = "1.0.0"` in its header is mounted as
+    /// `greeter-v1.0.0.lua`, and the header may be left out for files
+    /// already named that way. Like [`LuaManager::mount_archive`], an empty
+    /// directory stands for the plugin in the system temp directory.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use plux_lua_manager::LuaManager;
+    /// use plux_rs::{Loader, StdInfo, function::FunctionOutput};
+    ///
+    /// let manager = LuaManager::new();
+    /// let path = manager.mount_script("plugins/greeter.lua").unwrap();
+    ///
+    /// let mut loader = Loader::<FunctionOutput, StdInfo>::new();
+    /// loader.context(|mut ctx| ctx.register_manager(manager)).unwrap();
+    /// loader.load_plugin_now(path.to_str().unwrap()).unwrap();
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read, is not a Lua script, has
+    /// an invalid header or declares no version.
+    pub fn mount_script(&self, path: impl AsRef<Path>) -> Result<PathBuf, ManagerError> {
+        let path = path.as_ref();
+        let src = std::fs::read_to_string(path).map_err(PluginError::IoError)?;
+        let (dir_name, config) = script::script_config(path, &src)?;
+        let provider = MemorySourceProvider::new()
+            .with_file("config.toml", config)
+            .with_file(script::SCRIPT_ENTRY, src);
+        self.mount(&dir_name, Arc::new(provider))
+    }
+
+    /// Mounts each sub-plugin of the plugin pack at `path` as a plugin of its
+    /// own and returns the paths to register them with, in dependency order.
+    ///
+    /// The pack's `config.toml` declares its sub-plugins as `[[plugins]]`
+    /// tables, and each is mounted as `<name>-v<version>.lua` with the config
+    /// of the pack and its own `name`, `version`, `entry` and `depends`, its
+    /// sources being read from the pack. Sub-plugins are thus loaded, called
+    /// and depended on like any other plugin. Like
+    /// [`LuaManager::mount_archive`], an empty directory stands for each of
+    /// them in the system temp directory.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use plux_lua_manager::LuaManager;
+    /// use plux_rs::{Loader, StdInfo, function::FunctionOutput};
+    ///
+    /// let manager = LuaManager::new();
+    /// let paths = manager.mount_pack("plugins/tools-v1.0.0.lua").unwrap();
+    ///
+    /// let mut loader = Loader::<FunctionOutput, StdInfo>::new();
+    /// loader.context(|mut ctx| ctx.register_manager(manager)).unwrap();
+    /// for path in paths {
+    ///     loader.load_plugin_now(path.to_str().unwrap()).unwrap();
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pack's `config.toml` cannot be read, declares
+    /// no sub-plugins, declares an `entry`, `version` or `exports` of its own,
+    /// or the dependencies between its sub-plugins cannot be satisfied.
+    pub fn mount_pack(&self, path: impl AsRef<Path>) -> Result<Vec<PathBuf>, ManagerError> {
+        let pack = self.source_provider(path.as_ref());
+        let src = pack.read_config().map_err(PluginError::IoError)?;
+        pack::sub_plugin_configs(&src)?
+            .into_iter()
+            .map(|(dir_name, config)| {
+                let provider = PackSourceProvider::new(pack.clone(), config);
+                self.mount(&dir_name, Arc::new(provider))
+            })
+            .collect()
+    }
+
+    /// Mounts a plugin compiled into the host and returns the path to register
+    /// the plugin with.
+    ///
+    /// `config` is the content of the plugin's `config.toml` and `sources`
+    /// maps the paths of its Lua files, relative to the plugin root, to their
+    /// content. Nothing is read from the filesystem, but like
+    /// [`LuaManager::mount_archive`] an empty directory named after `bundle`
+    /// stands for the plugin in the system temp directory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use plux_lua_manager::LuaManager;
+    /// use plux_rs::{Bundle, Loader, StdInfo, function::FunctionOutput};
+    ///
+    /// let manager = LuaManager::new();
+    /// let bundle = Bundle::from_filename("builtin-v1.0.0.lua").unwrap();
+    /// let path = manager
+    ///     .mount_embedded(
+    ///         &bundle,
+    ///         "name = \"Builtin\"\ndescription = \"\"\nauthor = \"Host\"",
+    ///         [("main.lua", "return {}")],
+    ///     )
+    ///     .unwrap();
+    ///
+    /// let mut loader = Loader::<FunctionOutput, StdInfo>::new();
+    /// loader.context(|mut ctx| ctx.register_manager(manager)).unwrap();
+    /// loader.load_plugin_now(path.to_str().unwrap()).unwrap();
+    /// # loader.stop().unwrap();
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `bundle` is not a Lua plugin or its directory
+    /// cannot be created.
+    pub fn mount_embedded<I, K, V>(
+        &self,
+        bundle: &Bundle,
+        config: &str,
+        sources: I,
+    ) -> Result<PathBuf, ManagerError>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        if bundle.format != "lua" {
+            return Err(PluginError::SourceError(format!("{bundle} is not a Lua plugin")).into());
+        }
+
+        let provider = sources
+            .into_iter()
+            .collect::<MemorySourceProvider>()
+            .with_file("config.toml", config);
+        self.mount(&bundle.to_string(), Arc::new(provider))
+    }
+
+    /// Creates the directory standing for a plugin whose sources are read
+    /// from `provider`, and returns its path.
+    fn mount(
+        &self,
+        dir_name: &str,
+        provider: Arc<dyn SourceProvider>,
+    ) -> Result<PathBuf, ManagerError> {
+        Bundle::from_filename(dir_name).map_err(|e| {
+            PluginError::SourceError(format!("Invalid plugin name `{dir_name}`: {e}"))
+        })?;
+
+        let path = std::env::temp_dir()
+            .join(format!("plux-mounts-{}", std::process::id()))
+            .join(dir_name);
+        std::fs::create_dir_all(&path).map_err(PluginError::IoError)?;
+        self.mounts
+            .write_unpoisoned()
+            .insert(path.clone(), provider);
+        Ok(path)
+    }
+
+    /// Returns the source provider of the plugin at `path`.
+    fn source_provider(&self, path: &Path) -> Arc<dyn SourceProvider> {
+        match self.mounts.read_unpoisoned().get(path) {
+            Some(provider) => provider.clone(),
+            None => (self.source_factory)(path),
+        }
+    }
+
+    /// Calls a plugin function once for every set of arguments in `batches`.
+    ///
+    /// The per-call overhead is paid once for the whole batch instead of once
+    /// per item as when calling the function through the registry: the
+    /// plugin's state is entered and the function resolved once, and the batch
+    /// is admitted, timed and recorded in the plugin's health and metrics as a
+    /// whole. Each item still goes through the middleware, and the call
+    /// timeout applies to each item. A failing item does not abort the rest
+    /// of the batch, and a batch quarantining the plugin runs to its end.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plugin is not loaded, is paused or quarantined,
+    /// or does not export `function_name`.
+    pub fn call_batch(
+        &self,
+        bundle: &Bundle,
+        function_name: &str,
+        batches: &[Vec<Variable>],
+    ) -> Result<Vec<Result<Option<Variable>, ManagerError>>, ManagerError> {
+        self.call_batch_with(bundle, function_name, batches, BatchOptions::default())
+    }
+
+    /// Same as [`call_batch`](Self::call_batch), with explicit [`BatchOptions`].
+    ///
+    /// With `fail_fast` set, the returned results end at the first failing item.
+    pub fn call_batch_with(
+        &self,
+        bundle: &Bundle,
+        function_name: &str,
+        batches: &[Vec<Variable>],
+        options: BatchOptions,
+    ) -> Result<Vec<Result<Option<Variable>, ManagerError>>, ManagerError> {
+        let plugin = self.get_plugin(bundle)?;
+        let gate = self.call_gate(&plugin.health);
+        let lua = plugin.lua.get()?;
+        let function = get_export(&lua, function_name)?;
+        let asynchronous = is_async_export(&lua, function_name)?;
+        let conversion = conversion_options(&lua);
+
+        gate.run_batch(bundle, function_name, batches, options.fail_fast, |args| {
+            let mut lua_args = Vec::with_capacity(args.len());
+            for arg in args {
+                lua_args.push(plux_to_lua_with(arg, &lua, conversion.strings)?);
+            }
+
+            let output = call_export(&function, MultiValue::from_vec(lua_args), asynchronous)?;
+            Ok(output_from_lua(&output, &conversion, function_name)?)
+        })
+    }
+
+    /// Calls a plugin function asynchronously.
+    ///
+    /// The function runs as a coroutine: every `coroutine.yield()` suspends
+    /// the call and hands control back to the executor, and the plugin's state
+    /// is not locked while the call is suspended. This works for any exported
+    /// function, functions declared `async = true` are the ones expected to
+    /// yield. [`LuaManager::with_call_timeout`] does not apply, wrap the future
+    /// in a timeout of the executor instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plugin is not loaded, is paused or quarantined,
+    /// does not export `function_name`, or if the call fails.
+    #[cfg(feature = "async")]
+    pub async fn call_async(
+        &self,
+        bundle: &Bundle,
+        function_name: &str,
+        args: &[Variable],
+    ) -> Result<Option<Variable>, ManagerError> {
+        let plugin = self.get_plugin(bundle)?;
+        let gate = self.call_gate(&plugin.health);
+        let state = plugin.lua.current()?;
+        gate.run_async(
+            bundle,
+            function_name,
+            args,
+            state.scoped(async {
+                let lua = state.lua();
+                let function = get_export(lua, function_name)?;
+                let options = conversion_options(lua);
+
+                let mut lua_args = Vec::with_capacity(args.len());
+                for arg in args {
+                    lua_args.push(plux_to_lua_with(arg, lua, options.strings)?);
+                }
+
+                let output = function
+                    .call_async::<MultiValue>(MultiValue::from_vec(lua_args))
+                    .await?;
+                Ok(output_from_lua(&output, &options, function_name)?)
+            }),
+        )
+        .await
+    }
+
+    /// Calls the global `on_event(name, ...)` handler of every loaded plugin
+    /// defining one, in load order, with `name` followed by `args`.
+    ///
+    /// Returns the outcome of each handler, plugins without a handler are
+    /// left out. Paused and quarantined plugins report the corresponding
+    /// [`PluginError`], and a failing handler does not stop the broadcast.
+    pub fn broadcast(
+        &self,
+        name: &str,
+        args: &[Variable],
+    ) -> Vec<(Bundle, Result<Option<Variable>, ManagerError>)> {
+        let plugins: Vec<_> = self
+            .lua_refs
+            .read_unpoisoned()
+            .iter()
+            .map(|(bundle, plugin)| (bundle.clone(), plugin.clone()))
+            .collect();
+
+        let mut results = vec![];
+        for (bundle, plugin) in plugins {
+            let lua = match plugin.lua.peek() {
+                Ok(Some(lua)) => lua,
+                Ok(None) => continue,
+                Err(e) => {
+                    results.push((bundle, Err(e.into())));
+                    continue;
+                }
+            };
+            let handler =
+                env::env(&lua).and_then(|env| env.get::<Option<Function>>(BROADCAST_HANDLER));
+            let handler = match handler {
+                Ok(Some(handler)) => handler,
+                Ok(None) => continue,
+                Err(e) => {
+                    results.push((bundle, Err(e.into())));
+                    continue;
+                }
+            };
+
+            let gate = self.call_gate(&plugin.health);
+            let result = gate.run(&bundle, BROADCAST_HANDLER, args, |args| {
+                let options = conversion_options(&lua);
+
+                let mut lua_args = Vec::with_capacity(args.len() + 1);
+                lua_args.push(name.into_lua(&lua)?);
+                for arg in args {
+                    lua_args.push(plux_to_lua_with(arg, &lua, options.strings)?);
+                }
+
+                let output = handler.call::<MultiValue>(MultiValue::from_vec(lua_args))?;
+                Ok(output_from_lua(&output, &options, BROADCAST_HANDLER)?)
+            });
+            if let Err(e) = &result {
+                log_at!(self, Warn, "Broadcast to plugin {} failed: {}", bundle, e);
+            }
+            results.push((bundle, result));
+        }
+
+        results
+    }
+
+    /// Prepares the plugins at `paths` for loading, on a pool of threads.
+    ///
+    /// Loading a plugin parses its config, creates its Lua state and compiles
+    /// its entry scripts before executing them, and the loader loads plugins
+    /// one after the other. Preloading does the first part for all the
+    /// plugins at once, on up to [`std::thread::available_parallelism`]
+    /// threads, so that loading them afterwards only executes the entry
+    /// scripts and registers their functions. Plugins in a
+    /// [shared state](LuaManager::with_shared_state) only get their config
+    /// parsed ahead of time.
+    ///
+    /// Returns the outcome for each path, in order. A plugin that fails to
+    /// preload is prepared again when loaded, reporting the same error, and
+    /// the preparation of a plugin is dropped once it is loaded.
+    pub fn preload<P>(&self, paths: &[P]) -> Vec<Result<(), ManagerError>>
+    where
+        P: AsRef<Path> + Sync,
+    {
+        let threads = std::thread::available_parallelism().map_or(1, NonZeroUsize::get);
+        let per_thread = paths.len().div_ceil(threads).max(1);
+
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = paths
+                .chunks(per_thread)
+                .map(|paths| {
+                    scope.spawn(move || {
+                        paths
+                            .iter()
+                            .map(|path| self.preload_plugin(path.as_ref()))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| {
+                    worker
+                        .join()
+                        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+                })
+                .collect()
+        })
+    }
+
+    /// Parses the config of the plugin at `path`, creates its state and
+    /// compiles its entry scripts in it, for [`LuaManager::preload`].
+    fn preload_plugin(&self, path: &Path) -> Result<(), ManagerError> {
+        let source = self.source_provider(path);
+        let (config, _) = load_config_from(source.as_ref())?;
+
+        let state = match self.shared_lua {
+            Some(_) => None,
+            None => {
+                let bundle = path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .and_then(|name| Bundle::from_filename(name).ok())
+                    .ok_or_else(|| {
+                        PluginError::SourceError(format!(
+                            "Invalid plugin path `{}`",
+                            path.display()
+                        ))
+                    })?;
+                let policy = self.plugin_sandbox(&config.name, &config);
+                let lua = self.new_lua(&bundle, policy.as_ref())?;
+                let entry = entry_path(config.entry.as_deref().unwrap_or(&self.entry));
+                let function = self
+                    .compile_entry(&lua, source.as_ref(), entry)?
+                    .into_function()?;
+                let compiled = HashMap::from([(entry.to_string(), function)]);
+                Some((lua, compiled))
+            }
+        };
+
+        self.preloaded
+            .lock_unpoisoned()
+            .insert(path.to_path_buf(), Preloaded { config, state });
+        Ok(())
+    }
+
+    /// Reloads a plugin's Lua state in place.
+    ///
+    /// A fresh state is created and the entry script is executed again; the old state
+    /// is only replaced once the new one has loaded successfully. The
+    /// `on_unload` hook of the old state runs before the swap and the
+    /// `on_load` hook of the new one after it. Functions already registered with plux keep
+    /// working and call into the new state, newly exported ones are registered
+    /// and the returned [`ReloadReport`] lists both kinds of changes.
+    ///
+    /// Plugins can carry in-memory state over by defining the global functions
+    /// `on_reload_save()`, called on the old state before it is replaced, and
+    /// `on_reload_restore(saved)`, called on the new state with the converted
+    /// result once its entry script has run. Values that cannot be converted
+    /// are skipped and reported in the returned [`ReloadReport`], as is a saved
+    /// value the new state has no hook to restore. The saved value only lives
+    /// for the duration of the reload. The former names `on_save_state` and
+    /// `on_restore_state` are still recognized.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plugin is not loaded, or if the new state fails
+    /// to load or restore, in which case the old state is kept.
+    pub fn reload_plugin(&self, bundle: &Bundle) -> Result<ReloadReport, ManagerError> {
+        operation!(self, "reload", bundle, "Reloading plugin");
+
+        let plugin = self.get_plugin(bundle)?;
+        let mut report = ReloadReport::default();
+
+        // Build the new state before touching the old one
+        let (config, _) = load_config_from(plugin.source.as_ref())?;
+        config.check_version(bundle)?;
+        let new_lua = self
+            .create_state(&plugin.api, &config, &plugin.health, None)
+            .map_err(|e| e.in_plugin(bundle))?;
+        let functions = self
+            .load_src(&new_lua, &plugin.source, config, HashMap::new())
+            .map_err(|e| e.in_plugin(bundle))?;
+
+        // Carry the in-memory state over
+        let saved = match plugin.lua.peek()? {
+            Some(old_lua) => match reload_hook(&old_lua, RELOAD_SAVE_HOOKS)? {
+                Some(on_reload_save) => Some(lua_to_plux_lossy(
+                    &on_reload_save.call::<Value>(())?,
+                    "state",
+                    &mut report.warnings,
+                )),
+                None => None,
+            },
+            None => None,
+        };
+
+        if let Some(saved) = saved {
+            match reload_hook(&new_lua, RELOAD_RESTORE_HOOKS)? {
+                Some(on_reload_restore) => {
+                    on_reload_restore.call::<()>(plux_to_lua(&saved, &new_lua)?)?;
+                }
+                None => report
+                    .warnings
+                    .push("the saved state was dropped: on_reload_restore is missing".to_string()),
+            }
+        }
+
+        let state = new_lua.into_state()?;
+
+        // Swap the state, letting the old one release its resources first
+        let result = plugin
+            .lua
+            .peek()
+            .and_then(|lua| lua.map_or(Ok(()), |lua| Self::call_hook(&lua, "on_unload")));
+        if let Err(e) = result {
+            report.warnings.push(format!("on_unload failed: {e}"));
+        }
+        if let Err(e) = self.call_host_hook(&self.unload_hook, bundle, &plugin.lua) {
+            report.warnings.push(format!("the unload hook failed: {e}"));
+        }
+        plugin.lua.replace(state.clone());
+        let result = state
+            .enter()
+            .and_then(|lua| Self::call_hook(&lua, "on_load"));
+        if let Err(e) = result {
+            report.warnings.push(format!("on_load failed: {e}"));
+        }
+        if let Err(e) = self.call_host_hook(&self.after_load_hook, bundle, &plugin.lua) {
+            report
+                .warnings
+                .push(format!("the after load hook failed: {e}"));
+        }
+        plugin.health.lock().clear();
+        plugin.health.restarts.lock_unpoisoned().reset();
+
+        for warning in report.warnings.iter() {
+            log_at!(self, Warn, "Reloading plugin {}: {}", bundle, warning);
+        }
+
+        // Register any newly exported functions
+        report.removed = plugin
+            .api
+            .get_plugin_by_bundle(bundle)
+            .map(|registered| {
+                registered
+                    .get_registry()
+                    .iter()
+                    .map(|f| f.name())
+                    .filter(|name| !functions.iter().any(|export| &export.name == name))
+                    .collect()
+            })
+            .unwrap_or_default();
+        self.document(bundle, &functions);
+        report.added =
+            self.register_functions(&plugin.lua, &plugin.api, &plugin.health, functions)?;
+        if let Some(registration) = self.registered.write_unpoisoned().get_mut(bundle) {
+            registration.faulted = false;
+        }
+        self.events.open(bundle);
+        self.debug_check_consistency(plugin.api.get_plugins(), None);
+
+        Ok(report)
+    }
+
+    /// Exposes host functions registered since the plugins were loaded.
+    ///
+    /// Host functions that were already visible to a plugin keep the same Lua
+    /// function value, so references stored by plugins remain valid and
+    /// compare equal to the refreshed `host` entries.
+    pub fn refresh_vtable(&self) -> Result<(), ManagerError> {
+        let plugins: Vec<_> = self.lua_refs.read_unpoisoned().values().cloned().collect();
+        for plugin in plugins {
+            // Lazily loaded plugins get the current vtable once created
+            if let Some(lua) = plugin.lua.peek()? {
+                vtable::register_vtable(
+                    &lua,
+                    plugin.api.plugin(),
+                    plugin.api.registry(),
+                    &self.middleware,
+                    plugin.health.rate_limiter.as_ref(),
+                    self.audit.as_ref(),
+                    self.flat_host_functions,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Schedules a reload of the plugin on the next [`LuaManager::tick`].
+    ///
+    /// Requesting a reload of a plugin that is already scheduled does nothing.
+    pub fn request_reload(&self, bundle: &Bundle) {
+        let mut pending = self.pending_reloads.lock_unpoisoned();
+        if !pending.contains(bundle) {
+            pending.push(bundle.clone());
+        }
+    }
+
+    /// Performs deferred work across the loaded plugins within `budget`.
+    ///
+    /// A tick processes pending reload requests, restarts faulted plugins
+    /// according to [`LuaManager::with_restart_policy`], runs a garbage collection
+    /// step on plugins over the GC watermark, resumes tasks spawned with
+    /// `api.spawn` and delivers the events emitted with `api.events.emit`.
+    /// Paused plugins keep their tasks and events. The plugin served first
+    /// rotates between ticks.
+    ///
+    /// A tick may run on any thread, but waits for the state of a plugin in use
+    /// by another thread until the call holding it returns. A tick must not
+    /// run while that call waits for the ticking thread, which would deadlock.
+    ///
+    /// The budget is checked before each unit of work, so a tick may exceed it
+    /// by at most one unit, plus the time spent waiting for a state. At least
+    /// one unit is always performed, so a tiny budget still makes progress.
+    /// Failures are logged and do not stop the tick.
+    pub fn tick(&self, budget: Duration) -> TickReport {
+        let start = Instant::now();
+        let mut report = TickReport::default();
+        let spent = |report: &TickReport| report.work() > 0 && start.elapsed() >= budget;
+
+        loop {
+            let bundle = {
+                let mut pending = self.pending_reloads.lock_unpoisoned();
+                if pending.is_empty() {
+                    break;
+                }
+                if spent(&report) {
+                    report.exhausted = true;
+                    return report;
+                }
+                pending.remove(0)
+            };
+
+            let result = self.reload_plugin(&bundle);
+            if let Err(e) = &result {
+                log_at!(self, Error, "Failed to reload plugin {}: {}", bundle, e);
+            }
+            if let Some(listener) = &self.reload_listener {
+                listener(&bundle, result.as_ref());
+            }
+            report.reloads += 1;
+        }
+
+        if let Some(policy) = &self.restart_policy {
+            let faulted: Vec<_> = self
+                .lua_refs
+                .read_unpoisoned()
+                .iter()
+                .map(|(bundle, plugin)| (bundle.clone(), plugin.health.clone()))
+                .collect();
+            for (bundle, health) in faulted {
+                if !self.is_faulted(&bundle) {
+                    continue;
+                }
+                let Some(attempt) = health
+                    .restarts
+                    .lock_unpoisoned()
+                    .next(policy, Instant::now())
+                else {
+                    continue;
+                };
+                if spent(&report) {
+                    report.exhausted = true;
+                    return report;
+                }
+
+                log_at!(self, Info, "Restarting faulted plugin {}", bundle);
+                let error = self.reload_plugin(&bundle).err();
+                if let Some(e) = &error {
+                    log_at!(self, Error, "Failed to restart plugin {}: {}", bundle, e);
+                    health.faults.record(None, e);
+                }
+                if let Some(listener) = &self.restart_listener {
+                    listener(
+                        &bundle,
+                        &RestartAttempt {
+                            attempt,
+                            last: attempt == policy.max_restarts,
+                            error: error.map(|e| e.to_string()),
+                        },
+                    );
+                }
+                report.restarts += 1;
+            }
+        }
+
+        let mut plugins: Vec<_> = self
+            .lua_refs
+            .read_unpoisoned()
+            .iter()
+            .map(|(bundle, plugin)| (bundle.clone(), plugin.clone()))
+            .collect();
+        if plugins.is_empty() {
+            return report;
+        }
+        let first = self.tick_cursor.fetch_add(1, Ordering::Relaxed) % plugins.len();
+        plugins.rotate_left(first);
+
+        for (bundle, plugin) in plugins {
+            let lua = match plugin.lua.peek() {
+                Ok(Some(lua)) => lua,
+                Ok(None) => continue,
+                Err(e) => {
+                    log_at!(self, Error, "Cannot enter plugin {}: {}", bundle, e);
+                    continue;
+                }
+            };
+            let paused = plugin.health.lock().is_paused();
+
+            if let Some(watermark) = self.gc_watermark
+                && lua.used_memory() > watermark
+            {
+                if spent(&report) {
+                    report.exhausted = true;
+                    return report;
+                }
+                if let Err(e) = lua.gc_step() {
+                    log_at!(self, Error, "GC step failed in plugin {}: {}", bundle, e);
+                }
+                report.gc_steps += 1;
+            }
+
+            if paused {
+                continue;
+            }
+            let pending = tasks::pending_tasks(&lua).unwrap_or_default();
+            for _ in 0..pending {
+                if spent(&report) {
+                    report.exhausted = true;
+                    return report;
+                }
+                match tasks::resume_next(&lua) {
+                    Ok(true) => report.tasks_resumed += 1,
+                    Ok(false) => break,
+                    Err(e) => {
+                        log_at!(
+                            self,
+                            Error,
+                            "Failed to resume a task in plugin {}: {}",
+                            bundle,
+                            e
+                        );
+                        break;
+                    }
+                }
+            }
+
+            let pending = self.events.pending(&bundle);
+            for _ in 0..pending {
+                if spent(&report) {
+                    report.exhausted = true;
+                    return report;
+                }
+                let Some(event) = self.events.next(&bundle) else {
+                    break;
+                };
+                match events::deliver(&lua, &event) {
+                    Ok(true) => report.events_delivered += 1,
+                    Ok(false) => {}
+                    Err(e) => log_at!(
+                        self,
+                        Error,
+                        "Failed to deliver event `{}` to plugin {}: {}",
+                        event.name,
+                        bundle,
+                        e
+                    ),
+                }
+            }
+        }
+
+        report
+    }
+
+    /// Returns the Lua implementation and version the manager was built with.
+    pub fn runtime_info(&self) -> RuntimeInfo {
+        RuntimeInfo::new()
+    }
+
+    /// Returns the runtime details of a plugin's Lua state.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plugin is not loaded.
+    pub fn plugin_runtime_info(&self, bundle: &Bundle) -> Result<PluginRuntimeInfo, ManagerError> {
+        let plugin = self.get_plugin(bundle)?;
+        let mut info = PluginRuntimeInfo::new(&*plugin.lua.get()?)?;
+        info.paused = plugin.health.lock().is_paused();
+        info.sandbox_profile = self
+            .registered
+            .read_unpoisoned()
+            .get(bundle)
+            .map_or(&self.sandbox, |registration| &registration.sandbox)
+            .profile()
+            .map(str::to_string);
+        info.memory_limit = self.memory_limit;
+        info.call_timeout = self.call_timeout;
+        Ok(info)
+    }
+
+    /// Returns the memory used by a plugin's Lua state in bytes, or 0 if the
+    /// state is created lazily and was not created yet.
+    ///
+    /// With a shared state, this is the memory used by all the plugins.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plugin is not loaded.
+    pub fn memory_usage(&self, bundle: &Bundle) -> Result<usize, ManagerError> {
+        let plugin = self.get_plugin(bundle)?;
+        let lua = plugin.lua.peek()?;
+        Ok(lua.map_or(0, |lua| lua.used_memory()))
+    }
+
+    /// Evaluates Lua `source` in the environment of a loaded plugin, and
+    /// returns its values converted for the host: nothing, the single value,
+    /// or a list of the values.
+    ///
+    /// `source` is either an expression, such as `count + 1`, or a block of
+    /// statements, returning values with `return` if any. It sees and may
+    /// change the plugin's globals, which makes it suitable for admin
+    /// consoles, tests and hot patches. The evaluation is not subject to the
+    /// call timeout, nor to pausing or quarantine.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// manager.eval(&bundle, "settings.verbose = true")?;
+    /// assert_eq!(manager.eval(&bundle, "settings.verbose")?, Some(Variable::Bool(true)));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plugin is not loaded, or if `source` does not
+    /// compile or fails.
+    pub fn eval(&self, bundle: &Bundle, source: &str) -> Result<Option<Variable>, ManagerError> {
+        let lua = self.plugin_state(bundle)?;
+        eval::evaluate(&lua, "=eval", source).map_err(|e| ManagerError::from(e).in_plugin(bundle))
+    }
+
+    /// Attaches a read-eval-print loop to the state of a loaded plugin.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let repl = manager.repl(&bundle)?;
+    /// repl.run(std::io::stdin().lock(), std::io::stdout())?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plugin is not loaded.
+    #[cfg(feature = "repl")]
+    pub fn repl(&self, bundle: &Bundle) -> Result<Repl, ManagerError> {
+        self.get_plugin(bundle)?;
+        Ok(Repl::new(self.clone(), bundle.clone()))
+    }
+
+    /// Runs a full garbage collection cycle in a plugin's Lua state, and
+    /// returns the memory it still uses in bytes.
+    ///
+    /// Meant for idle periods, as the cycle blocks the calls into the plugin
+    /// until it completes. With a shared state, the cycle collects the
+    /// garbage of all the plugins.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plugin is not loaded, or if a `__gc`
+    /// metamethod fails.
+    pub fn gc_collect(&self, bundle: &Bundle) -> Result<usize, ManagerError> {
+        let plugin = self.get_plugin(bundle)?;
+        let Some(lua) = plugin.lua.peek()? else {
+            return Ok(0);
+        };
+        lua.gc_collect()
+            .map_err(|e| ManagerError::from(e).in_plugin(bundle))?;
+        Ok(lua.used_memory())
+    }
+
+    /// Returns the call statistics of the functions of every loaded plugin, in
+    /// load order.
+    ///
+    /// Every call that ran is counted, whether made through plux, a request,
+    /// [`LuaManager::call_batch`] (once per item), [`LuaManager::call_async`]
+    /// or [`LuaManager::broadcast`] (as [`BROADCAST_HANDLER`]). Calls rejected
+    /// because the plugin is paused or quarantined are not. Statistics are kept
+    /// across reloads and dropped when the plugin is unloaded.
+    pub fn metrics(&self) -> IndexMap<Bundle, IndexMap<String, FunctionMetrics>> {
+        self.lua_refs
+            .read_unpoisoned()
+            .iter()
+            .map(|(bundle, plugin)| (bundle.clone(), plugin.health.metrics.snapshot()))
+            .collect()
+    }
+
+    /// Creates a channel shared between the plugins allowed by `policy`.
+    ///
+    /// If the channel already exists, its policy is replaced and its values
+    /// are kept.
+    pub fn create_shared(&self, name: &str, policy: AccessPolicy) {
+        let mut channels = self.shared.write_unpoisoned();
+        channels.entry(name.to_string()).or_default().policy = policy;
+    }
+
+    /// Returns a value of a shared channel.
+    pub fn shared_value(&self, name: &str, key: &str) -> Option<Variable> {
+        let channels = self.shared.read_unpoisoned();
+        channels.get(name)?.values.get(key).cloned()
+    }
+
+    /// Sets a value of a shared channel, returning `false` if the channel
+    /// does not exist.
+    pub fn set_shared_value(&self, name: &str, key: &str, value: Variable) -> bool {
+        let mut channels = self.shared.write_unpoisoned();
+        match channels.get_mut(name) {
+            Some(channel) => {
+                channel.values.insert(key.to_string(), value);
+                true
+            }
+            None => false,
+        }
+    }
 
-use hashbrown::HashMap;
-use log;
-use mlua::{Function, Lua, MultiValue, Table, Value};
-use plux_rs::{
-    Api, Bundle, Manager, Plugin, StdInfo,
-    context::LoadPluginContext,
-    function::{Arg, DynamicFunction, FunctionOutput},
-    utils::ManagerResult,
-    variable::VariableType,
-};
+    /// Returns a value of the key-value store plugins access as `api.store`.
+    pub fn store_value(&self, key: &str) -> Option<Variable> {
+        self.store.read_unpoisoned().get(key).cloned()
+    }
 
-use crate::error::{ManagerError, PluginError};
-use crate::{
-    config::load_config,
-    lua::{api, requests, vtable},
-};
+    /// Sets a value of the key-value store, returning the previous one.
+    pub fn set_store_value(&self, key: &str, value: Variable) -> Option<Variable> {
+        self.store.write_unpoisoned().insert(key.to_string(), value)
+    }
 
-use crate::lua::conversion::{lua_to_plux, plux_to_lua};
+    /// Removes a value of the key-value store, returning it.
+    pub fn remove_store_value(&self, key: &str) -> Option<Variable> {
+        self.store.write_unpoisoned().shift_remove(key)
+    }
 
-/// The main manager type for Lua plugins.
-///
-/// This struct is responsible for managing the lifecycle of Lua plugins,
-/// including loading, unloading, and interacting with them. It maintains
-/// a mapping of plugin bundles to their respective Lua states.
-///
-/// # Thread Safety
-///
-/// `LuaManager` is `Send` and `Sync`, allowing it to be used safely across
-/// thread boundaries. Each plugin's Lua state is protected by a mutex to
-/// ensure thread safety.
-pub struct LuaManager {
-    /// Map of bundle identifiers to their Lua states
-    lua_refs: HashMap<Bundle, Arc<Mutex<Lua>>>,
-}
+    /// Returns the keys of the key-value store, in insertion order.
+    pub fn store_keys(&self) -> Vec<String> {
+        self.store.read_unpoisoned().keys().cloned().collect()
+    }
 
-impl Default for LuaManager {
-    fn default() -> Self {
-        Self::new()
+    /// Returns the dependency graph of the plugins registered through this
+    /// manager, with the resolution of every declared dependency.
+    ///
+    /// The graph is exported with [`DependencyGraph::to_dot`] and
+    /// [`DependencyGraph::to_json`].
+    pub fn dependency_graph(&self) -> DependencyGraph {
+        let registered = self.registered.read_unpoisoned();
+        DependencyGraph::new(
+            registered
+                .iter()
+                .map(|(bundle, registration)| (bundle, &registration.info)),
+            &self.loaded_bundles(),
+        )
     }
-}
 
-impl LuaManager {
-    /// Creates a new instance of `LuaManager`.
+    /// Returns a handle calling the function `name` of a loaded plugin with
+    /// native Rust types.
     ///
     /// # Examples
     ///
+    /// ```ignore
+    /// let format_row = manager.typed_fn::<(i64, String), String>(&bundle, "format_row")?;
+    /// let row = format_row.call((7, "seven".to_string()))?;
     /// ```
-    /// use plux_lua_manager::LuaManager;
     ///
-    /// let manager = LuaManager::new();
-    /// ```
-    pub fn new() -> Self {
-        Self {
-            lua_refs: HashMap::new(),
+    /// # Errors
+    ///
+    /// Returns an error if the plugin is not loaded, does not export the
+    /// function, or declares a signature other than `A` and `R`.
+    pub fn typed_fn<A: TypedArgs, R: TypedOutput>(
+        &self,
+        bundle: &Bundle,
+        name: &str,
+    ) -> Result<TypedFn<A, R>, ManagerError> {
+        let plugin = self.get_plugin(bundle)?;
+        let function = plugin
+            .api
+            .get_plugin_by_bundle(bundle)
+            .and_then(|plugin| {
+                plugin
+                    .get_registry()
+                    .iter()
+                    .find(|function| function.name() == name)
+                    .cloned()
+            })
+            .ok_or_else(|| PluginError::FunctionNotFound(name.to_string()))?;
+
+        Ok(TypedFn::new(function)?)
+    }
+
+    /// Returns the bundles of the loaded plugins in load order.
+    pub fn loaded_bundles(&self) -> Vec<Bundle> {
+        self.lua_refs.read_unpoisoned().keys().cloned().collect()
+    }
+
+    /// Unloads the plugins of this manager from `loader` in reverse load order.
+    ///
+    /// `Loader::stop` unloads plugins in its own order, hosts that want
+    /// dependents to shut down before the plugins loaded earlier call this first.
+    ///
+    /// # Errors
+    ///
+    /// Stops at the first plugin that fails to unload.
+    pub fn unload_all(
+        &self,
+        loader: &mut Loader<'_, FunctionOutput, StdInfo>,
+    ) -> Result<(), Box<UnloadPluginError>> {
+        for bundle in self.loaded_bundles().iter().rev() {
+            loader.unload_plugin_by_bundle(bundle).map_err(Box::new)?;
+            self.debug_check_consistency(loader.get_plugins(), None);
+        }
+
+        Ok(())
+    }
+
+    /// Cross-checks the manager's plugin states against the plugins of `loader`.
+    ///
+    /// Every Lua state must belong to a plugin loaded by plux, every loaded
+    /// plugin registered through this manager must have a state, and every
+    /// function the manager lists for a plugin must be registered with plux
+    /// and exported by the state. States of lazily loaded plugins not created
+    /// yet are not inspected for functions, and inspecting a state in use by
+    /// another thread blocks until the call holding it returns.
+    ///
+    /// With `repair`, orphaned states are dropped without running their hooks
+    /// and orphaned functions are unregistered from the manager, which no
+    /// longer lists or documents them. plux keeps them in the plugin's
+    /// registry, as it does for the functions a reload drops, and calling them
+    /// fails with [`PluginError::FunctionNotFound`] until the plugin exports
+    /// them again. Missing states are only reported, the plugin must be loaded
+    /// again through the loader to get one.
+    ///
+    /// The returned report describes what was found, before any repair.
+    pub fn verify_consistency(
+        &self,
+        loader: &Loader<'_, FunctionOutput, StdInfo>,
+        repair: bool,
+    ) -> ConsistencyReport {
+        let report = self.check_consistency(loader.get_plugins(), None);
+        if repair && !report.is_consistent() {
+            let mut lua_refs = self.lua_refs.write_unpoisoned();
+            for bundle in report.orphaned_states.iter() {
+                log_at!(
+                    self,
+                    Warn,
+                    "Dropping the orphaned state of plugin {}",
+                    bundle
+                );
+                lua_refs.shift_remove(bundle);
+            }
+            drop(lua_refs);
+
+            let mut registered = self.registered.write_unpoisoned();
+            for (bundle, name) in report.orphaned_functions.iter() {
+                log_at!(
+                    self,
+                    Warn,
+                    "Unregistering the orphaned function `{}` of plugin {}",
+                    name,
+                    bundle
+                );
+                if let Some(registration) = registered.get_mut(bundle) {
+                    registration.functions.shift_remove(name);
+                }
+            }
+        }
+
+        report
+    }
+
+    fn check_consistency(
+        &self,
+        plugins: &[Plugin<'_, FunctionOutput, StdInfo>],
+        skip: Option<&Bundle>,
+    ) -> ConsistencyReport {
+        let mut report = ConsistencyReport::default();
+        let skipped = |bundle: &Bundle| skip == Some(bundle);
+        let loaded: Vec<_> = plugins
+            .iter()
+            .filter(|plugin| plugin.is_load())
+            .map(|plugin| &plugin.info().bundle)
+            .collect();
+
+        let lua_refs = self.lua_refs.read_unpoisoned();
+        let registered = self.registered.read_unpoisoned();
+        for (bundle, plugin) in lua_refs.iter().filter(|(bundle, _)| !skipped(bundle)) {
+            if !loaded.contains(&bundle) {
+                report.orphaned_states.push(bundle.clone());
+                continue;
+            }
+
+            let Ok(Some(lua)) = plugin.lua.peek() else {
+                continue;
+            };
+            let (Some(registry), Some(registration)) = (
+                plugins
+                    .iter()
+                    .find(|plugin| plugin.info().bundle == *bundle)
+                    .map(|plugin| plugin.get_registry()),
+                registered.get(bundle),
+            ) else {
+                continue;
+            };
+            for name in registration.functions.keys() {
+                if !registry.iter().any(|function| function.name() == *name)
+                    || get_export(&lua, name).is_err()
+                {
+                    report
+                        .orphaned_functions
+                        .push((bundle.clone(), name.clone()));
+                }
+            }
+        }
+
+        for bundle in loaded {
+            if !skipped(bundle) && registered.contains_key(bundle) && !lua_refs.contains_key(bundle)
+            {
+                report.missing_states.push(bundle.clone());
+            }
+        }
+
+        report
+    }
+
+    /// Asserts in debug builds that the manager agrees with plux, see
+    /// [`LuaManager::verify_consistency`], ignoring `transitioning`, the plugin
+    /// being loaded.
+    ///
+    /// The check is not run from `Manager::unload_plugin`, where the loader may
+    /// already be torn down.
+    fn debug_check_consistency(
+        &self,
+        plugins: &[Plugin<'_, FunctionOutput, StdInfo>],
+        transitioning: Option<&Bundle>,
+    ) {
+        if cfg!(debug_assertions) {
+            let report = self.check_consistency(plugins, transitioning);
+            debug_assert!(
+                report.is_consistent(),
+                "plugin states drifted from plux: {report:?}"
+            );
+        }
+    }
+
+    /// Runs the plugin's `on_unload` hook, if any, before its state is dropped.
+    fn shutdown_plugin(&self, bundle: &Bundle, plugin: &LuaPlugin) {
+        let result = plugin
+            .lua
+            .peek()
+            .and_then(|lua| lua.map_or(Ok(()), |lua| Self::call_hook(&lua, "on_unload")));
+        if let Err(e) = result {
+            log_at!(self, Warn, "on_unload failed in plugin {}: {}", bundle, e);
+        }
+        if let Err(e) = self.call_host_hook(&self.unload_hook, bundle, &plugin.lua) {
+            log_at!(
+                self,
+                Warn,
+                "The unload hook failed in plugin {}: {}",
+                bundle,
+                e
+            );
+        }
+    }
+
+    /// Calls a hook of the host with the state of a plugin, if both exist.
+    fn call_host_hook(
+        &self,
+        hook: &Option<StateHook>,
+        bundle: &Bundle,
+        lua: &StateSlot,
+    ) -> mlua::Result<()> {
+        match (hook, lua.peek()?) {
+            (Some(hook), Some(lua)) => hook(bundle, &lua),
+            _ => Ok(()),
+        }
+    }
+
+    /// Calls the global lifecycle hook `name` of a state, if defined.
+    fn call_hook(lua: &Lua, name: &str) -> mlua::Result<()> {
+        env::env(lua)?
+            .get::<Option<Function>>(name)
+            .and_then(|hook| hook.map_or(Ok(()), |f| f.call::<()>(())))
+    }
+
+    /// Calls the `on_dependency_unloaded(id, version)` hook of the loaded
+    /// plugins depending on `bundle`.
+    ///
+    /// Dependents are usually unloaded first, this only happens when the host
+    /// forces the dependency out.
+    fn notify_dependency_unloaded(&self, bundle: &Bundle) {
+        let dependents: Vec<_> = self
+            .lua_refs
+            .read_unpoisoned()
+            .iter()
+            .filter(|(_, plugin)| {
+                plugin.api.depends().contains(bundle)
+                    || plugin.api.optional_depends().contains(bundle)
+            })
+            .map(|(dependent, plugin)| (dependent.clone(), plugin.clone()))
+            .collect();
+
+        for (dependent, plugin) in dependents {
+            let result = plugin.lua.peek().and_then(|lua| {
+                let Some(lua) = lua else {
+                    return Ok(());
+                };
+                env::env(&lua)?
+                    .get::<Option<Function>>("on_dependency_unloaded")?
+                    .map_or(Ok(()), |f| {
+                        f.call::<()>((bundle.id.as_str(), bundle.version.to_string()))
+                    })
+            });
+            if let Err(e) = result {
+                log_at!(
+                    self,
+                    Warn,
+                    "on_dependency_unloaded failed in plugin {}: {}",
+                    dependent,
+                    e
+                );
+            }
+        }
+    }
+
+    /// Calls the `api.on_plugin_loaded` or `api.on_plugin_unloaded` handlers
+    /// of the other loaded plugins that may see `bundle`.
+    ///
+    /// Plugins whose state has not been created yet have no handlers.
+    fn notify_lifecycle(&self, bundle: &Bundle, event: Lifecycle) {
+        let plugins: Vec<_> = self
+            .lua_refs
+            .read_unpoisoned()
+            .iter()
+            .filter(|(other, _)| *other != bundle)
+            .filter(|(other, _)| {
+                self.plugin_visibility
+                    .as_ref()
+                    .is_none_or(|visible| visible(other, bundle))
+            })
+            .map(|(other, plugin)| (other.clone(), plugin.clone()))
+            .collect();
+
+        for (other, plugin) in plugins {
+            let result = plugin.lua.peek().and_then(|lua| match lua {
+                Some(lua) => events::notify_lifecycle(&lua, event, bundle),
+                None => Ok(()),
+            });
+            if let Err(e) = result {
+                log_at!(
+                    self,
+                    Warn,
+                    "Notifying plugin {} of plugin {}: {}",
+                    other,
+                    bundle,
+                    e
+                );
+            }
+        }
+    }
+
+    /// Returns the gate of the calls into a plugin with the given health.
+    fn call_gate(&self, health: &Arc<PluginHealth>) -> CallGate {
+        CallGate {
+            policy: self.quarantine_policy,
+            listener: self.quarantine_listener.clone(),
+            pause_timeout: self.pause_timeout,
+            call_timeout: self.call_timeout,
+            log_level: self.log_level,
+            middleware: self.middleware.clone(),
+            health: health.clone(),
+        }
+    }
+
+    /// Returns the Lua state hosting all plugins, created on first use, if the
+    /// manager runs them in a shared state.
+    fn shared_lua(&self) -> Result<Option<Arc<SharedLua>>, ManagerError> {
+        let Some(shared) = &self.shared_lua else {
+            return Ok(None);
+        };
+        let mut shared = shared.lock_unpoisoned();
+        if shared.is_none() {
+            *shared = Some(Arc::new(SharedLua::new(&self.sandbox)?));
+        }
+        Ok(shared.clone())
+    }
+
+    /// Returns the Lua state of a loaded plugin, created if it is loaded
+    /// lazily.
+    pub(crate) fn plugin_state(&self, bundle: &Bundle) -> Result<ScopedLua, ManagerError> {
+        self.get_plugin(bundle)?.lua.get()
+    }
+
+    /// Returns the runtime state of a loaded plugin.
+    fn get_plugin(&self, bundle: &Bundle) -> Result<LuaPlugin, ManagerError> {
+        self.lua_refs
+            .read_unpoisoned()
+            .get(bundle)
+            .cloned()
+            .ok_or_else(|| PluginError::NotLoaded(bundle.to_string()).into())
+    }
+
+    /// Returns the function creating the state of a lazily loaded plugin on
+    /// first use, as [`Manager::load_plugin`] would.
+    fn create_lazily(
+        &self,
+        bundle: &Bundle,
+        api: &Arc<Api<FunctionOutput, StdInfo>>,
+        source: &Arc<dyn SourceProvider>,
+        config: Config,
+        health: &Arc<PluginHealth>,
+    ) -> CreateState {
+        let manager = self.clone();
+        let bundle = bundle.clone();
+        let api = api.clone();
+        let source = source.clone();
+        let health = health.clone();
+        Box::new(move || {
+            log_at!(manager, Info, "Creating the state of plugin: {}", bundle);
+            let lua = manager
+                .create_state(&api, &config, &health, None)
+                .map_err(|e| e.in_plugin(&bundle))?;
+            let functions = manager
+                .load_src(&lua, &source, config.clone(), HashMap::new())
+                .map_err(|e| e.in_plugin(&bundle))?;
+            Self::call_hook(&lua, "on_load")
+                .map_err(|e| ManagerError::from(e).in_plugin(&bundle))?;
+            if let Some(hook) = &manager.after_load_hook {
+                hook(&bundle, &lua).map_err(|e| ManagerError::from(e).in_plugin(&bundle))?;
+            }
+
+            // Functions are registered with plux at load, from the config
+            let declared = config.exports.iter().flatten();
+            for export in functions {
+                if !declared
+                    .clone()
+                    .any(|declared| declared.name == export.name)
+                {
+                    log_at!(
+                        manager,
+                        Warn,
+                        "Plugin {} exports `{}`, which its config does not declare",
+                        bundle,
+                        export.name
+                    );
+                }
+            }
+
+            Ok(lua.into_state()?)
+        })
+    }
+
+    /// Returns the sandbox of a plugin, narrowed to the libraries it
+    /// declares, or `None` if it declares none.
+    fn plugin_sandbox(&self, id: &str, config: &Config) -> Option<SandboxPolicy> {
+        let declared = config.libs.as_ref()?;
+        let (policy, denied) = self.sandbox.restrict(declared);
+        for lib in denied {
+            log_at!(
+                self,
+                Warn,
+                "Plugin {} declares the {:?} library, which the sandbox does not allow",
+                id,
+                lib
+            );
+        }
+        Some(policy)
+    }
+
+    /// Creates a bare Lua state for the plugin `bundle` with the state
+    /// factory, or with the libraries of `policy`, the sandbox if `None`.
+    fn new_lua(
+        &self,
+        bundle: &Bundle,
+        policy: Option<&SandboxPolicy>,
+    ) -> Result<Lua, ManagerError> {
+        let policy = policy.unwrap_or(&self.sandbox);
+        match &self.state_factory {
+            Some(factory) => {
+                let lua = factory(bundle)?;
+                policy.strip(&lua.globals())?;
+                Ok(lua)
+            }
+            None => Ok(policy.create_lua()?),
+        }
+    }
+
+    /// Creates a new Lua state with the standard libraries allowed for the
+    /// plugin, the plugin API registered and the watchdog of the plugin
+    /// installed.
+    ///
+    /// `preloaded` is a state created for the plugin by [`LuaManager::preload`].
+    fn create_state(
+        &self,
+        api: &Arc<Api<FunctionOutput, StdInfo>>,
+        config: &Config,
+        health: &PluginHealth,
+        preloaded: Option<Lua>,
+    ) -> Result<ScopedLua, ManagerError> {
+        let lua = match (self.shared_lua()?, preloaded) {
+            (Some(shared), _) => {
+                let lua = shared.enter_new()?;
+                if let Some(policy) = self.plugin_sandbox(&api.plugin().id, config) {
+                    policy.strip(&lua.globals())?;
+                }
+                lua
+            }
+            (None, Some(lua)) => ScopedLua::isolated(lua),
+            (None, None) => {
+                let policy = self.plugin_sandbox(&api.plugin().id, config);
+                ScopedLua::isolated(self.new_lua(api.plugin(), policy.as_ref())?)
+            }
+        };
+        if let Some(permissions) = &config.permissions {
+            sandbox::enforce_permissions(&lua, permissions)?;
+        }
+        if let Some(limit) = self.memory_limit {
+            lua.set_memory_limit(limit)?;
         }
+        if self.call_timeout.is_some() {
+            health.watchdog.install(&lua)?;
+        }
+        #[cfg(feature = "debug")]
+        {
+            let debugger = health.debugger.lock_unpoisoned().clone();
+            let recorder = self.coverage.clone().map(|coverage| CoverageRecorder {
+                bundle: api.plugin().clone(),
+                coverage,
+            });
+            if debugger.is_some() || recorder.is_some() {
+                if let Some(debugger) = debugger {
+                    lua.set_app_data(debugger);
+                }
+                if let Some(recorder) = recorder {
+                    lua.set_app_data(recorder);
+                }
+                hooks::install(&lua)?;
+            }
+        }
+        let scope = ApiScope::new(
+            api.plugin().clone(),
+            config.api.clone(),
+            self.api_filter.clone(),
+        );
+        lua.set_app_data(scope.clone());
+
+        vtable::register_vtable(
+            &lua,
+            api.plugin(),
+            api.registry(),
+            &self.middleware,
+            health.rate_limiter.as_ref(),
+            self.audit.as_ref(),
+            self.flat_host_functions,
+        )?;
+        require::register_resolvers(
+            &lua,
+            api.plugin(),
+            self.resolvers.clone(),
+            self.bytecode.clone(),
+        )?;
+
+        let globals = lua.globals();
+        for (name, value) in self.globals.iter() {
+            globals.set(name.as_str(), plux_to_lua(value, &lua)?)?;
+        }
+
+        // Register the API
+        let dependencies: Arc<dyn DependencyApi> = api.clone();
+        api::register_api(&lua, &dependencies, config, health.rate_limiter.clone())?;
+        let (features, settings) = self
+            .registered
+            .read_unpoisoned()
+            .get(api.plugin())
+            .map(|registration| {
+                (
+                    registration.features.clone(),
+                    Some(registration.settings.clone()),
+                )
+            })
+            .unwrap_or_default();
+        plugins::register_plugin_info(&lua, api, config, &features)?;
+        plugins::register_list_plugins(
+            &lua,
+            api.clone(),
+            self.registered.clone(),
+            self.plugin_visibility.clone(),
+        )?;
+        plugins::register_describe_function(
+            &lua,
+            api.clone(),
+            self.registered.clone(),
+            self.plugin_visibility.clone(),
+        )?;
+        plugins::register_list_functions(
+            &lua,
+            api.clone(),
+            self.registered.clone(),
+            self.plugin_visibility.clone(),
+        )?;
+        shared::register_shared(&lua, &api.plugin().id, self.shared.clone())?;
+        shared::register_store(&lua, self.store.clone())?;
+        logging::register_logging(&lua, &api.plugin().to_string())?;
+        if let Some(capture) = &self.output {
+            output::redirect_output(&lua, api.plugin(), capture)?;
+        }
+        events::register_events(&lua, &api.plugin().id, self.events.clone())?;
+        events::register_lifecycle(&lua)?;
+        if let Some(settings) = settings {
+            lua_settings::register_settings(&lua, settings)?;
+        }
+        if let Some(dir) = self.plugin_data_dir(&api.plugin().id) {
+            storage::register_storage(&lua, dir)?;
+        }
+
+        if let Some(hook) = &self.before_load_hook {
+            hook(api.plugin(), &lua)?;
+        }
+        let api_table: Table = globals.get("api")?;
+        scope.restrict(&api_table, "api")?;
+        if let Some(log) = &self.audit {
+            audit::audit_table(&lua, api.plugin(), &api_table, "api", log)?;
+        }
+
+        // Keep the plugin's own globals apart from the host's
+        env::create_env(&lua)?;
+
+        Ok(lua)
     }
 
     /// Loads and executes the plugin's source code.
+    ///
+    /// The exported functions are stored in the state's registry and their
+    /// names and signatures are returned.
+    ///
+    /// Entry scripts found in `compiled` are not read and compiled again.
     fn load_src(
         &self,
-        lua: &Arc<Mutex<Lua>>,
-        api: Arc<Api<FunctionOutput, StdInfo>>,
-        path: PathBuf,
-    ) -> Result<(), ManagerError> {
-        let main_path = path.join("main.lua");
-        if !main_path.exists() {
-            return Err(ManagerError::Plugin(PluginError::SourceError(
-                "main.lua not found".to_string(),
-            )));
+        lua: &Lua,
+        source: &Arc<dyn SourceProvider>,
+        config: Config,
+        mut compiled: HashMap<String, Function>,
+    ) -> Result<Vec<Export>, ManagerError> {
+        // Resolve `require` through the plugin's sources
+        require::register_searcher(
+            lua,
+            source.clone(),
+            self.bytecode.clone(),
+            self.transforms.clone(),
+        )?;
+
+        let exports = lua.create_table()?;
+        lua.set_named_registry_value(EXPORTS_KEY, &exports)?;
+        let async_exports = lua.create_table()?;
+        lua.set_named_registry_value(ASYNC_EXPORTS_KEY, &async_exports)?;
+
+        lua.set_app_data(config.strings.unwrap_or_default());
+        lua.set_app_data(config.numbers.unwrap_or_default());
+        lua.set_app_data(Capabilities(config.granted_capabilities()));
+        if config.metamethods.unwrap_or(false) {
+            MetamethodGuard::default().install(lua)?;
+        }
+        if config.strict_nils.unwrap_or(false) {
+            lua.set_app_data(StrictNils);
+        }
+        for (name, chunk) in self.preludes.iter() {
+            lua.load(chunk.as_slice())
+                .set_name(format!("@{name}"))
+                .set_environment(env::env(lua)?)
+                .exec()?;
         }
 
-        let lua_guard = lua.lock().unwrap();
+        let mut functions = vec![];
+        let entry = config.entry.as_deref().unwrap_or(&self.entry);
+        let compiled = compiled.remove(entry_path(entry));
+        let result = self.exec_entry(lua, source.as_ref(), entry, env::env(lua)?, compiled)?;
+        Self::collect_exports(&exports, &async_exports, result, &mut functions)?;
 
-        // Set the package path to include the plugin's directory
-        let package_path = format!(
-            "{}/?.lua;{}/?/init.lua",
-            path.to_string_lossy(),
-            path.to_string_lossy()
-        );
+        Ok(functions)
+    }
 
-        lua_guard
-            .load(&format!(
-                r#"
-                package.path = '{};' .. package.path
-            "#,
-                package_path
-            ))
-            .exec()?;
+    /// Executes an entry script, optionally in a dedicated environment, and
+    /// returns the records of the functions it exports.
+    fn exec_entry(
+        &self,
+        lua: &Lua,
+        source: &dyn SourceProvider,
+        entry: &str,
+        env: Table,
+        compiled: Option<Function>,
+    ) -> Result<Vec<Table>, ManagerError> {
+        let result = match compiled {
+            Some(function) => {
+                function.set_environment(env)?;
+                function.call(())?
+            }
+            None => {
+                let chunk = self.compile_entry(lua, source, entry_path(entry))?;
+                chunk.set_environment(env).eval()?
+            }
+        };
+        Ok(export_records(lua, result)?)
+    }
+
+    /// Reads an entry script, compiled through the bytecode cache if enabled.
+    fn compile_entry<'lua>(
+        &self,
+        lua: &'lua Lua,
+        source: &dyn SourceProvider,
+        entry: &str,
+    ) -> Result<Chunk<'lua>, ManagerError> {
+        if !source.exists(entry) {
+            return Err(ManagerError::Plugin(PluginError::SourceError(format!(
+                "Entry script {entry} not found"
+            ))));
+        }
 
-        // Execute the main script
-        let src = std::fs::read_to_string(main_path)
+        let src = source
+            .read_source(entry)
             .map_err(|e| ManagerError::Plugin(PluginError::IoError(e)))?;
-        let result: Vec<Table> = lua_guard.load(&src).eval()?;
+        let src = transform_source(&self.transforms, entry, src)?;
+        let name = format!("@{entry}");
+        Ok(match &self.bytecode {
+            Some(cache) => cache.load(lua, &name, &src, source.cache_dir().as_deref())?,
+            None => lua.load(src).set_name(name),
+        })
+    }
 
-        // Register the plugin functions
-        let plugin = api.get_plugin_mut_by_bundle(api.plugin()).unwrap();
+    /// Stores the functions returned by an entry script in the exports table,
+    /// and the names of those declared `async = true` in `async_exports`.
+    fn collect_exports(
+        exports: &Table,
+        async_exports: &Table,
+        result: Vec<Table>,
+        functions: &mut Vec<Export>,
+    ) -> Result<(), ManagerError> {
         for info in result.into_iter() {
             let name: String = info.get("name")?;
-            let inputs: Vec<String> = info.get("inputs")?;
+            let inputs: Vec<InputDeclaration> =
+                info.get::<Option<Vec<_>>>("inputs")?.unwrap_or_default();
+            let output: Option<String> = info.get("output")?;
             let lua_function: Function = info.get("func")?;
+            let asynchronous = info.get::<Option<bool>>("async")?.unwrap_or(false);
+            let doc = FunctionDoc {
+                description: info.get("description")?,
+                examples: info
+                    .get::<Option<Vec<String>>>("examples")?
+                    .unwrap_or_default(),
+                since: info.get("since")?,
+            };
 
-            let lua_clone = lua.clone();
-            let function = DynamicFunction::new(
-                name.clone(),
-                inputs
-                    .iter()
-                    .map(|name| Arg::new(name, VariableType::Let))
-                    .collect(),
-                Some(Arg::new("output", VariableType::Let)),
-                move |args| {
-                    let mut lua_args = vec![];
+            if asynchronous && !cfg!(feature = "async") {
+                return Err(PluginError::SourceError(format!(
+                    "Function `{name}` is async, which requires the `async` feature"
+                ))
+                .into());
+            }
+            exports.set(name.as_str(), lua_function)?;
+            if asynchronous {
+                async_exports.set(name.as_str(), true)?;
+            }
+            functions.push(Export {
+                doc,
+                ..Export::parse(name, &inputs, output.as_deref())?
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Stores the signatures and documentation of the functions exported by a
+    /// plugin.
+    fn document(&self, bundle: &Bundle, functions: &[Export]) {
+        if let Some(registration) = self.registered.write_unpoisoned().get_mut(bundle) {
+            registration.functions = functions
+                .iter()
+                .map(|export| (export.name.clone(), export.info()))
+                .collect();
+        }
+    }
+
+    /// Registers the plugin functions that are not registered with plux yet
+    /// and returns their names.
+    ///
+    /// The functions hold the state weakly, so that functions handed out
+    /// before the plugin was unloaded fail cleanly instead of running it.
+    fn register_functions(
+        &self,
+        lua: &Arc<StateSlot>,
+        api: &Arc<Api<FunctionOutput, StdInfo>>,
+        health: &Arc<PluginHealth>,
+        functions: Vec<Export>,
+    ) -> Result<Vec<String>, ManagerError> {
+        let plugin = api
+            .get_plugin_mut_by_bundle(api.plugin())
+            .ok_or_else(|| PluginError::NotLoaded(api.plugin().to_string()))?;
+        let mut registered = vec![];
+        for Export {
+            name,
+            inputs,
+            output,
+            ..
+        } in functions.into_iter()
+        {
+            if plugin.get_registry().iter().any(|f| f.name() == name) {
+                continue;
+            }
+
+            registered.push(name.clone());
+            let lua_weak = Arc::downgrade(lua);
+            let bundle = api.plugin().clone();
+            let function_name = name.clone();
+            let gate = self.call_gate(health);
+            let args = inputs.iter().map(Input::declared).collect();
+            let signature = (inputs, output.ty);
+            let function = DynamicFunction::new(name, args, Some(output), move |args| {
+                let (inputs, output) = &signature;
+                let args = &check_args(&function_name, inputs, args).map_err(ManagerError::from)?;
+                let output = gate.run(&bundle, &function_name, args, |args| {
+                    // The state is gone once the plugin is unloaded
+                    let lua = lua_weak
+                        .upgrade()
+                        .ok_or_else(|| PluginError::NotLoaded(bundle.to_string()))?
+                        .get()?;
+                    let lua_function = get_export(&lua, &function_name)?;
+                    let asynchronous = is_async_export(&lua, &function_name)?;
+                    let options = conversion_options(&lua);
+
+                    let mut lua_args = Vec::with_capacity(args.len());
                     for arg in args {
-                        lua_args.push(plux_to_lua(arg, &*lua_clone.lock().unwrap())?);
+                        lua_args.push(plux_to_lua_with(arg, &lua, options.strings)?);
                     }
 
-                    let result = match lua_function.call::<Value>(MultiValue::from_vec(lua_args))? {
-                        Value::Nil => Ok(None),
-                        value => Ok(Some(lua_to_plux(&value)?)),
-                    };
-                    result
-                },
-            );
+                    let result =
+                        call_export(&lua_function, MultiValue::from_vec(lua_args), asynchronous)?;
+                    let result = output_from_lua(&result, &options, &function_name)?;
+                    Ok(check_output(&function_name, *output, result)?)
+                })?;
+                Ok(output)
+            });
 
             plugin
                 .register_function(function)
                 .map_err(|e| ManagerError::Plugin(PluginError::RegisterFunctionError(e)))?;
         }
 
+        Ok(registered)
+    }
+
+    /// Loads a plugin into memory and runs its `on_load` hook, see
+    /// [`Manager::load_plugin`].
+    fn try_load_plugin<'a>(
+        &mut self,
+        mut context: LoadPluginContext<'a, '_, FunctionOutput, StdInfo>,
+        api: Arc<Api<FunctionOutput, StdInfo>>,
+    ) -> ManagerResult<()> {
+        let bundle = context.plugin().info().bundle.clone();
+        operation!(self, "load", bundle, "Loading plugin");
+
+        let mut diagnostics = vec![];
+        if let Some(capabilities) = self.capabilities(&bundle) {
+            for capability in capabilities {
+                if !KNOWN_CAPABILITIES.contains(&capability.as_str()) {
+                    diagnostics.push(format!("unknown capability `{capability}`"));
+                }
+            }
+        }
+        if self.check_dependencies {
+            let info = &context.plugin().info().info;
+            let available = api.get_plugins().iter().map(|plugin| &plugin.info().bundle);
+
+            let mismatches = dependency_mismatches(&info.depends, available.clone());
+            if !mismatches.is_empty() {
+                return Err(PluginError::DependencyMismatch(mismatches).into());
+            }
+
+            for mismatch in dependency_mismatches(&info.optional_depends, available) {
+                log_at!(
+                    self,
+                    Warn,
+                    "Loading plugin {}: optional {}",
+                    bundle,
+                    mismatch
+                );
+                diagnostics.push(format!("optional {mismatch}"));
+            }
+        }
+
+        let source = self.source_provider(&context.plugin().info().path);
+
+        // Initialize the Lua environment and load the plugin's source code
+        let preloaded = self
+            .preloaded
+            .lock_unpoisoned()
+            .remove(&context.plugin().info().path);
+        let (config, state) = match preloaded {
+            Some(Preloaded { config, state }) => (config, state),
+            None => (load_config_from(source.as_ref())?.0, None),
+        };
+        let faults = self
+            .registered
+            .read_unpoisoned()
+            .get(&bundle)
+            .map(|registration| registration.faults.clone())
+            .unwrap_or_default();
+        let health = Arc::new(PluginHealth::new(self.rate_limit, faults));
+        let (lua, functions) = match &config.exports {
+            Some(declared) if self.lazy_loading => {
+                let functions = declared
+                    .iter()
+                    .map(|export| {
+                        Ok(Export {
+                            doc: export.doc(),
+                            ..Export::parse(
+                                export.name.clone(),
+                                &export.inputs,
+                                export.output.as_deref(),
+                            )?
+                        })
+                    })
+                    .collect::<Result<Vec<_>, PluginError>>()
+                    .map_err(|e| ManagerError::from(e).in_plugin(&bundle))?;
+                let create = self.create_lazily(&bundle, &api, &source, config, &health);
+                (Arc::new(StateSlot::lazy(bundle.clone(), create)), functions)
+            }
+            _ => {
+                let (lua, compiled) = state.unzip();
+                let lua = self
+                    .create_state(&api, &config, &health, lua)
+                    .map_err(|e| e.in_plugin(&bundle))?;
+                let functions = self
+                    .load_src(&lua, &source, config, compiled.unwrap_or_default())
+                    .map_err(|e| e.in_plugin(&bundle))?;
+                Self::call_hook(&lua, "on_load")
+                    .map_err(|e| ManagerError::from(e).in_plugin(&bundle))?;
+                if let Some(hook) = &self.after_load_hook {
+                    hook(&bundle, &lua).map_err(|e| ManagerError::from(e).in_plugin(&bundle))?;
+                }
+                (Arc::new(StateSlot::new(lua.into_state()?)), functions)
+            }
+        };
+        // Register any requested functions
+        let known = diagnostics.len();
+        let requests = requests::register_requests(
+            &lua,
+            &bundle,
+            context.requests(),
+            &self.call_gate(&health),
+            &self.requests,
+            &mut diagnostics,
+        )?;
+
+        // Functions stay bound to this state and health, even after a failed
+        // load, so they are registered once the plugin's code has loaded
+        self.document(&bundle, &functions);
+        self.register_functions(&lua, &api, &health, functions)?;
+        for request in requests {
+            context.register_request(request)?;
+        }
+        for diagnostic in &diagnostics[known..] {
+            log_at!(self, Warn, "Loading plugin {}: {}", bundle, diagnostic);
+        }
+
+        #[cfg(feature = "watch")]
+        if self.watch {
+            let path = &context.plugin().info().path;
+            match watch::watch_plugin(path, bundle.clone(), self.pending_reloads.clone()) {
+                Ok(watcher) => {
+                    self.watchers
+                        .lock_unpoisoned()
+                        .insert(bundle.clone(), watcher);
+                }
+                Err(e) => {
+                    log_at!(self, Warn, "Cannot watch plugin {}: {}", bundle, e);
+                    diagnostics.push(format!("cannot watch the plugin directory: {e}"));
+                }
+            }
+        }
+
+        // Store the Lua state
+        self.events.open(&bundle);
+        self.lua_refs.write_unpoisoned().insert(
+            bundle.clone(),
+            LuaPlugin {
+                lua,
+                api: api.clone(),
+                source,
+                diagnostics,
+                health,
+            },
+        );
+        self.debug_check_consistency(api.get_plugins(), Some(&bundle));
+        self.notify_lifecycle(&bundle, Lifecycle::Loaded);
+
+        Ok(())
+    }
+}
+
+/// Hooks used by the test suite to make the manager drift from plux.
+#[cfg(any(test, feature = "test-hooks"))]
+#[doc(hidden)]
+impl LuaManager {
+    /// Stores a copy of the state of `from` under `bundle`.
+    pub fn __duplicate_state(&self, from: &Bundle, bundle: Bundle) -> Result<(), ManagerError> {
+        let plugin = self.get_plugin(from)?;
+        self.lua_refs.write_unpoisoned().insert(bundle, plugin);
+        Ok(())
+    }
+
+    /// Drops the state of `bundle` without running its hooks.
+    pub fn __forget_state(&self, bundle: &Bundle) -> Result<(), ManagerError> {
+        self.lua_refs
+            .write_unpoisoned()
+            .shift_remove(bundle)
+            .map(|_| ())
+            .ok_or_else(|| PluginError::NotLoaded(bundle.to_string()).into())
+    }
+
+    /// Removes the export `name` from the state of `bundle`.
+    pub fn __forget_export(&self, bundle: &Bundle, name: &str) -> Result<(), ManagerError> {
+        let lua = self.get_plugin(bundle)?.lua.get()?;
+        let exports: Table = lua.named_registry_value(EXPORTS_KEY)?;
+        exports.set(name, Value::Nil)?;
         Ok(())
     }
 }
 
+/// Returns the first of the global functions `names` a plugin defines.
+fn reload_hook(lua: &Lua, names: [&str; 2]) -> mlua::Result<Option<Function>> {
+    let env = env::env(lua)?;
+    for name in names {
+        if let Some(hook) = env.get::<Option<Function>>(name)? {
+            return Ok(Some(hook));
+        }
+    }
+    Ok(None)
+}
+
+/// Returns the path of an entry script relative to the plugin directory.
+fn entry_path(entry: &str) -> &str {
+    entry.strip_prefix("./").unwrap_or(entry)
+}
+
 impl<'a> Manager<'a, FunctionOutput, StdInfo> for LuaManager {
     /// Returns the format identifier for this manager ("lua").
     fn format(&self) -> &'static str {
@@ -172,10 +3928,77 @@ impl<'a> Manager<'a, FunctionOutput, StdInfo> for LuaManager {
     }
 
     /// Registers a new plugin.
-    fn register_plugin(&mut self, context: plux_rs::RegisterPluginContext) -> ManagerResult<StdInfo> {
-        let (_, info) = load_config(&context.path).map_err(|e| ManagerError::Config(e))?;
+    fn register_plugin(
+        &mut self,
+        context: plux_rs::RegisterPluginContext,
+    ) -> ManagerResult<StdInfo> {
+        operation!(self, "register", context.bundle, "Registering plugin");
+        let source = self.source_provider(context.path);
+        let (config, info) = load_config_from(source.as_ref()).map_err(ManagerError::Config)?;
+        config
+            .check_version(context.bundle)
+            .map_err(ManagerError::Config)?;
+        config.check_platform().map_err(ManagerError::Config)?;
+
+        let unknown = config.unknown_capabilities();
+        if !unknown.is_empty() {
+            if self.strict_capabilities {
+                return Err(ManagerError::Config(ConfigError::UnknownCapabilities(unknown)).into());
+            }
+            log_at!(
+                self,
+                Warn,
+                "Registering plugin {}: unknown capabilities {}",
+                context.bundle,
+                unknown.join(", ")
+            );
+        }
+
+        if self.check_dependencies {
+            let mismatches =
+                dependency_mismatches(&info.depends, self.registered.read_unpoisoned().keys());
+            if !mismatches.is_empty() {
+                return Err(PluginError::DependencyMismatch(mismatches).into());
+            }
+        }
 
-        log::info!("Registering plugin: {}", context.bundle);
+        let features = config
+            .features
+            .iter()
+            .flatten()
+            .map(|(name, &default)| {
+                let enabled = match &self.feature_toggle {
+                    Some(toggle) => toggle(context.bundle, name, default),
+                    None => default,
+                };
+                (name.clone(), enabled)
+            })
+            .collect();
+        let settings = Settings::new(
+            &config.user_settings.clone().unwrap_or_default(),
+            self.plugin_data_dir(&context.bundle.id)
+                .map(|dir| dir.join(SETTINGS_FILE)),
+        )
+        .map_err(ManagerError::Config)?;
+
+        self.registered.write_unpoisoned().insert(
+            context.bundle.clone(),
+            Registration {
+                info: info.clone(),
+                metadata: config.metadata(),
+                capabilities: config.granted_capabilities(),
+                sandbox: match &config.libs {
+                    Some(declared) => self.sandbox.restrict(declared).0,
+                    None => self.sandbox.clone(),
+                },
+                permissions: config.permissions,
+                features,
+                settings: Arc::new(settings),
+                functions: IndexMap::new(),
+                faults: Arc::default(),
+                faulted: false,
+            },
+        );
         Ok(info)
     }
 
@@ -185,44 +4008,65 @@ impl<'a> Manager<'a, FunctionOutput, StdInfo> for LuaManager {
         plugin: &Plugin<'a, FunctionOutput, StdInfo>,
     ) -> ManagerResult<()> {
         let bundle = &plugin.info().bundle;
-        log::info!("Unregistering plugin: {}", bundle);
+        log_at!(self, Info, "Unregistering plugin: {}", bundle);
+        self.registered.write_unpoisoned().shift_remove(bundle);
         Ok(())
     }
 
-    /// Loads a plugin into memory.
+    /// Loads a plugin into memory and runs its `on_load` hook.
+    ///
+    /// With fault isolation, a plugin failing to load is marked as faulted
+    /// instead of failing the load.
     fn load_plugin(
         &mut self,
-        mut context: LoadPluginContext<'a, '_, FunctionOutput, StdInfo>,
+        context: LoadPluginContext<'a, '_, FunctionOutput, StdInfo>,
         api: Api<FunctionOutput, StdInfo>,
     ) -> ManagerResult<()> {
         let bundle = context.plugin().info().bundle.clone();
-        log::info!("Loading plugin: {}", bundle);
-
-        let lua = Arc::new(Mutex::new(Lua::new()));
+        let path = context.plugin().info().path.clone();
         let api = Arc::new(api);
+        let result = self.try_load_plugin(context, api.clone());
 
-        // Initialize the Lua environment
-        {
-            let lua_guard = lua.lock().unwrap();
-
-            vtable::register_vtable(&lua_guard, api.registry())?;
-
-            // Register the API
-            api::register_api(&lua_guard, &api)?;
-        }
-
-        // Load the plugin's source code
-        self.load_src(&lua, api.clone(), context.plugin().info().path.clone())?;
-
-        // Register any requested functions
-        let requests = requests::register_requests(&lua, context.requests())?;
-        for request in requests {
-            context.register_request(request)?;
+        let mut registered = self.registered.write_unpoisoned();
+        let Some(registration) = registered.get_mut(&bundle) else {
+            return result;
+        };
+        registration.faulted = false;
+        let Err(e) = result else {
+            return Ok(());
+        };
+        registration.faults.record(None, &e);
+        if !self.fault_isolation {
+            return Err(e);
         }
 
-        // Store the Lua state
-        self.lua_refs.insert(bundle, lua);
+        log_at!(
+            self,
+            Error,
+            "Plugin {} faulted while loading: {}",
+            bundle,
+            e
+        );
+        registration.faulted = true;
+        let faults = registration.faults.clone();
+        drop(registered);
 
+        // A state failing until the plugin is reloaded
+        let faulted = bundle.clone();
+        let lua = StateSlot::lazy(
+            bundle.clone(),
+            Box::new(move || Err(PluginError::Faulted(faulted.to_string()).into())),
+        );
+        self.lua_refs.write_unpoisoned().insert(
+            bundle,
+            LuaPlugin {
+                lua: Arc::new(lua),
+                api,
+                source: self.source_provider(&path),
+                diagnostics: vec![],
+                health: Arc::new(PluginHealth::new(self.rate_limit, faults)),
+            },
+        );
         Ok(())
     }
 
@@ -232,10 +4076,43 @@ impl<'a> Manager<'a, FunctionOutput, StdInfo> for LuaManager {
         plugin: &Plugin<'a, FunctionOutput, StdInfo>,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         let bundle = &plugin.info().bundle;
-        log::info!("Unloading plugin: {}", bundle);
+        operation!(self, "unload", bundle, "Unloading plugin");
+
+        #[cfg(feature = "watch")]
+        self.watchers.lock_unpoisoned().shift_remove(bundle);
+        self.events.close(bundle);
+        if let Some(registration) = self.registered.write_unpoisoned().get_mut(bundle) {
+            registration.functions.clear();
+            registration.faulted = false;
+        }
+
+        // Remove the Lua state, keeping the load order of the others
+        let plugin = self.lua_refs.write_unpoisoned().shift_remove(bundle);
+        if let Some(plugin) = plugin {
+            self.shutdown_plugin(bundle, &plugin);
+            // Calls waiting for a paused plugin fail as not loaded
+            plugin.health.resume();
+        }
+        self.notify_dependency_unloaded(bundle);
+        self.notify_lifecycle(bundle, Lifecycle::Unloaded);
+
+        Ok(())
+    }
 
-        // Remove the Lua state
-        self.lua_refs.remove(bundle);
+    /// Shuts down the plugins that are still loaded in reverse load order.
+    fn unregister_manager(&mut self) -> ManagerResult<()> {
+        #[cfg(feature = "watch")]
+        self.watchers.lock_unpoisoned().clear();
+
+        loop {
+            let plugin = self.lua_refs.write_unpoisoned().pop();
+            let Some((bundle, plugin)) = plugin else {
+                break;
+            };
+            operation!(self, "unload", bundle, "Unloading plugin");
+            self.events.close(&bundle);
+            self.shutdown_plugin(&bundle, &plugin);
+        }
 
         Ok(())
     }
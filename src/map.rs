@@ -0,0 +1,86 @@
+//! Encoding of key/value tables as plux variables.
+//!
+//! plux has no map variable, so Lua tables that are not lists cross the plugin
+//! boundary as a tagged list: [`MAP_TAG`] followed by one `[key, value]` list
+//! per entry, sorted by key.
+//!
+//! ```text
+//! { name = "plux", version = 1 }
+//!     <=> List([String("plux:map"), List([String("name"), String("plux")]), List([String("version"), I64(1)])])
+//! ```
+//!
+//! Tagged lists passed to a plugin become tables again, so dictionaries
+//! round-trip between plugins. Hosts build and read them with
+//! [`map_variable`] and [`map_entries`].
+
+use plux_rs::variable::Variable;
+
+/// First element of the lists encoding a map.
+pub const MAP_TAG: &str = "plux:map";
+
+/// Encodes key/value pairs as a map variable.
+///
+/// # Examples
+///
+/// ```
+/// use plux_lua_manager::{map_entries, map_variable};
+/// use plux_rs::variable::Variable;
+///
+/// let map = map_variable([("answer".into(), Variable::I64(42))]);
+/// assert_eq!(
+///     map_entries(&map),
+///     Some(vec![(Variable::String("answer".into()), Variable::I64(42))])
+/// );
+/// ```
+pub fn map_variable(entries: impl IntoIterator<Item = (Variable, Variable)>) -> Variable {
+    let mut list = vec![Variable::String(MAP_TAG.to_string())];
+    list.extend(
+        entries
+            .into_iter()
+            .map(|(key, value)| Variable::List(vec![key, value])),
+    );
+    Variable::List(list)
+}
+
+/// Decodes a map variable into its key/value pairs.
+///
+/// Returns `None` if `var` does not encode a map.
+pub fn map_entries(var: &Variable) -> Option<Vec<(Variable, Variable)>> {
+    let Variable::List(list) = var else {
+        return None;
+    };
+    let (Some(Variable::String(tag)), entries) = (list.first(), list.get(1..)?) else {
+        return None;
+    };
+    if tag != MAP_TAG {
+        return None;
+    }
+
+    entries
+        .iter()
+        .map(|entry| match entry {
+            Variable::List(pair) if pair.len() == 2 => Some((pair[0].clone(), pair[1].clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_entries() {
+        let map = map_variable([
+            (Variable::String("a".into()), Variable::Bool(true)),
+            (Variable::I64(1), Variable::Null),
+        ]);
+        assert_eq!(map_entries(&map).unwrap().len(), 2);
+
+        assert!(map_entries(&Variable::List(vec![])).is_none());
+        assert!(map_entries(&Variable::List(vec![Variable::String("a".into())])).is_none());
+
+        let malformed = Variable::List(vec![Variable::String(MAP_TAG.into()), Variable::I32(1)]);
+        assert!(map_entries(&malformed).is_none());
+    }
+}
@@ -0,0 +1,123 @@
+//! Call statistics of plugin functions, see [`crate::LuaManager::metrics`].
+
+use std::{collections::VecDeque, sync::Mutex, time::Duration};
+
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::sync::MutexExt;
+
+/// Number of recent calls the latency percentiles of a function are computed from.
+pub const LATENCY_SAMPLES: usize = 1024;
+
+/// Call statistics of a plugin function, see [`crate::LuaManager::metrics`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FunctionMetrics {
+    /// Number of calls that ran.
+    pub calls: u64,
+    /// Number of those calls that failed.
+    pub errors: u64,
+    /// Time spent in all the calls.
+    pub total_time: Duration,
+    /// Duration of the slowest call.
+    pub max_time: Duration,
+    /// Median duration of the last [`LATENCY_SAMPLES`] calls.
+    pub p50: Duration,
+    /// 90th percentile of the duration of the last [`LATENCY_SAMPLES`] calls.
+    pub p90: Duration,
+    /// 99th percentile of the duration of the last [`LATENCY_SAMPLES`] calls.
+    pub p99: Duration,
+}
+
+/// Statistics of a function, with the durations of its recent calls.
+#[derive(Default)]
+struct Recorder {
+    calls: u64,
+    errors: u64,
+    total_time: Duration,
+    max_time: Duration,
+    samples: VecDeque<Duration>,
+}
+
+impl Recorder {
+    fn snapshot(&self) -> FunctionMetrics {
+        let mut samples: Vec<_> = self.samples.iter().copied().collect();
+        samples.sort_unstable();
+        FunctionMetrics {
+            calls: self.calls,
+            errors: self.errors,
+            total_time: self.total_time,
+            max_time: self.max_time,
+            p50: percentile(&samples, 50),
+            p90: percentile(&samples, 90),
+            p99: percentile(&samples, 99),
+        }
+    }
+}
+
+/// Call statistics of the functions of a plugin, kept across reloads.
+#[derive(Default)]
+pub(crate) struct CallMetrics(Mutex<IndexMap<String, Recorder>>);
+
+impl CallMetrics {
+    /// Records a call to `function` that ran for `elapsed`.
+    pub(crate) fn record(&self, function: &str, elapsed: Duration, ok: bool) {
+        self.record_all(function, &[(elapsed, ok)]);
+    }
+
+    /// Records the calls to `function` of a batch, with how long each ran
+    /// and whether it succeeded.
+    pub(crate) fn record_all(&self, function: &str, calls: &[(Duration, bool)]) {
+        let mut functions = self.0.lock_unpoisoned();
+        if !functions.contains_key(function) {
+            functions.insert(function.to_string(), Recorder::default());
+        }
+        let recorder = &mut functions[function];
+
+        for &(elapsed, ok) in calls {
+            recorder.calls += 1;
+            if !ok {
+                recorder.errors += 1;
+            }
+            recorder.total_time += elapsed;
+            recorder.max_time = recorder.max_time.max(elapsed);
+            if recorder.samples.len() == LATENCY_SAMPLES {
+                recorder.samples.pop_front();
+            }
+            recorder.samples.push_back(elapsed);
+        }
+    }
+
+    /// Returns the statistics of every function called so far, in order of
+    /// first call.
+    pub(crate) fn snapshot(&self) -> IndexMap<String, FunctionMetrics> {
+        self.0
+            .lock_unpoisoned()
+            .iter()
+            .map(|(function, recorder)| (function.clone(), recorder.snapshot()))
+            .collect()
+    }
+}
+
+/// Returns the nearest-rank `rank`th percentile of sorted `samples`.
+fn percentile(samples: &[Duration], rank: usize) -> Duration {
+    if samples.is_empty() {
+        return Duration::ZERO;
+    }
+    let index = (samples.len() * rank).div_ceil(100).max(1) - 1;
+    samples[index]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentile() {
+        let samples: Vec<_> = (1..=100).map(Duration::from_millis).collect();
+        assert_eq!(percentile(&samples, 50), Duration::from_millis(50));
+        assert_eq!(percentile(&samples, 99), Duration::from_millis(99));
+        assert_eq!(percentile(&samples[..1], 90), Duration::from_millis(1));
+        assert_eq!(percentile(&[], 50), Duration::ZERO);
+    }
+}
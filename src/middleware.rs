@@ -0,0 +1,95 @@
+//! Middleware wrapping the calls between the host and plugins, see
+//! [`crate::LuaManager::with_middleware`].
+
+use std::sync::Arc;
+
+use plux_rs::{Bundle, variable::Variable};
+
+use crate::error::ManagerError;
+
+/// Which side of a call runs the called function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallTarget {
+    /// A function, request handler or event handler of a plugin, called by
+    /// the host or another plugin.
+    Plugin,
+    /// A host function, called by a plugin through the `host` table.
+    Host,
+}
+
+/// A call going through the middleware chain.
+#[derive(Debug, Clone, Copy)]
+pub struct CallInfo<'a> {
+    /// The plugin called, or calling the host function.
+    pub bundle: &'a Bundle,
+    /// The name of the called function.
+    pub function: &'a str,
+    /// Which side runs the called function.
+    pub target: CallTarget,
+}
+
+/// Wraps calls between the host and plugins, see
+/// [`crate::LuaManager::with_middleware`].
+pub trait Middleware: Send + Sync {
+    /// Handles `call` with `args`, usually by passing them, possibly changed,
+    /// to `next`, which runs the rest of the chain and then the call itself.
+    /// Returning without running `next` skips the call.
+    fn call(
+        &self,
+        call: &CallInfo<'_>,
+        args: Vec<Variable>,
+        next: Next<'_>,
+    ) -> Result<Option<Variable>, ManagerError>;
+}
+
+impl<F> Middleware for F
+where
+    F: Fn(&CallInfo<'_>, Vec<Variable>, Next<'_>) -> Result<Option<Variable>, ManagerError>
+        + Send
+        + Sync,
+{
+    fn call(
+        &self,
+        call: &CallInfo<'_>,
+        args: Vec<Variable>,
+        next: Next<'_>,
+    ) -> Result<Option<Variable>, ManagerError> {
+        self(call, args, next)
+    }
+}
+
+/// The middleware registered with a manager, outermost first.
+pub(crate) type MiddlewareChain = Arc<Vec<Arc<dyn Middleware>>>;
+
+/// A call run at the end of a middleware chain.
+pub(crate) type CallFn<'a> = dyn Fn(&[Variable]) -> Result<Option<Variable>, ManagerError> + 'a;
+
+/// The rest of a middleware chain, followed by the call itself.
+pub struct Next<'a> {
+    call: &'a CallInfo<'a>,
+    chain: &'a [Arc<dyn Middleware>],
+    inner: &'a CallFn<'a>,
+}
+
+impl Next<'_> {
+    /// Runs the rest of the chain and the call with `args`.
+    pub fn run(self, args: Vec<Variable>) -> Result<Option<Variable>, ManagerError> {
+        match self.chain.split_first() {
+            Some((middleware, chain)) => middleware.call(self.call, args, Next { chain, ..self }),
+            None => (self.inner)(&args),
+        }
+    }
+}
+
+/// Runs `inner` with `args` through every middleware of `chain`.
+pub(crate) fn run_chain(
+    chain: &[Arc<dyn Middleware>],
+    call: &CallInfo<'_>,
+    args: &[Variable],
+    inner: &CallFn<'_>,
+) -> Result<Option<Variable>, ManagerError> {
+    if chain.is_empty() {
+        return inner(args);
+    }
+    Next { call, chain, inner }.run(args.to_vec())
+}
@@ -0,0 +1,99 @@
+//! Capture of what plugins print and write to the standard streams, see
+//! [`crate::LuaManager::with_output_capture`].
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use plux_rs::Bundle;
+
+use crate::sync::MutexExt;
+
+/// The standard stream a plugin wrote to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OutputStream {
+    /// `print`, `io.write` and `io.stdout:write`.
+    Stdout,
+    /// `io.stderr:write`.
+    Stderr,
+}
+
+/// Receives what plugins write, see [`crate::LuaManager::with_output_sink`].
+pub type OutputSink = Arc<dyn Fn(&Bundle, OutputStream, &str) + Send + Sync>;
+
+/// The recent output of each plugin and the sink receiving it.
+pub(crate) struct OutputCapture {
+    /// Number of bytes kept per plugin, none if 0
+    capacity: usize,
+    buffers: Mutex<HashMap<Bundle, String>>,
+    sink: Option<OutputSink>,
+}
+
+impl OutputCapture {
+    pub(crate) fn new(capacity: usize, sink: Option<OutputSink>) -> Self {
+        Self {
+            capacity,
+            buffers: Mutex::new(HashMap::new()),
+            sink,
+        }
+    }
+
+    /// Returns the number of bytes kept per plugin.
+    pub(crate) fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the sink receiving the output.
+    pub(crate) fn sink(&self) -> Option<OutputSink> {
+        self.sink.clone()
+    }
+
+    /// Hands `text` to the sink and appends it to the buffer of `bundle`,
+    /// dropping its oldest output once the buffer is full.
+    pub(crate) fn write(&self, bundle: &Bundle, stream: OutputStream, text: &str) {
+        if let Some(sink) = &self.sink {
+            sink(bundle, stream, text);
+        }
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut buffers = self.buffers.lock_unpoisoned();
+        let buffer = buffers.entry(bundle.clone()).or_default();
+        buffer.push_str(text);
+        if buffer.len() > self.capacity {
+            let mut start = buffer.len() - self.capacity;
+            while !buffer.is_char_boundary(start) {
+                start += 1;
+            }
+            buffer.drain(..start);
+        }
+    }
+
+    /// Returns the output kept for `bundle`, and clears it if `take`.
+    pub(crate) fn output(&self, bundle: &Bundle, take: bool) -> String {
+        let mut buffers = self.buffers.lock_unpoisoned();
+        match take {
+            true => buffers.remove(bundle).unwrap_or_default(),
+            false => buffers.get(bundle).cloned().unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keeps_the_latest_output() {
+        let capture = OutputCapture::new(4, None);
+        let bundle = Bundle::from_filename("echo-v1.0.0.lua").unwrap();
+        capture.write(&bundle, OutputStream::Stdout, "abc");
+        capture.write(&bundle, OutputStream::Stderr, "dé");
+
+        assert_eq!(capture.output(&bundle, false), "cdé");
+        assert_eq!(capture.output(&bundle, true), "cdé");
+        assert_eq!(capture.output(&bundle, false), "");
+    }
+}
@@ -0,0 +1,146 @@
+//! Plugin packs, see [`crate::LuaManager::mount_pack`].
+//!
+//! Each sub-plugin is mounted as a plugin of its own, reading its sources
+//! from the pack directory. Its config is the one of the pack, with the
+//! `name`, `version`, `entry` and `depends` of the sub-plugin, the latter
+//! added to those of the pack.
+
+use std::{io, path::PathBuf, sync::Arc};
+
+use crate::{
+    config::{Config, pack_load_order},
+    error::ConfigError,
+    source::SourceProvider,
+};
+
+/// Returns the name of the plugin directory and the content of the
+/// `config.toml` of each sub-plugin of the pack whose config is `src`, in
+/// dependency order.
+pub(crate) fn sub_plugin_configs(src: &str) -> Result<Vec<(String, String)>, ConfigError> {
+    let pack: Config = toml::from_str(src)?;
+    let Some(plugins) = &pack.plugins else {
+        return Err(ConfigError::InvalidPack(
+            "no plugins are declared as `[[plugins]]`".to_string(),
+        ));
+    };
+    let mut base: toml::Table = src.parse()?;
+    for key in ["entry", "version", "exports"] {
+        if base.contains_key(key) {
+            return Err(ConfigError::InvalidPack(format!(
+                "`{key}` is declared by each of the `[[plugins]]`"
+            )));
+        }
+    }
+    base.remove("plugins");
+    let depends = base.remove("depends");
+
+    pack_load_order(plugins)?
+        .into_iter()
+        .map(|plugin| {
+            let mut config = base.clone();
+            let mut all_depends = match &depends {
+                Some(toml::Value::Table(depends)) => depends.clone(),
+                _ => toml::Table::new(),
+            };
+            for (id, version) in plugin.depends.iter().flatten() {
+                all_depends.insert(id.clone(), version.to_string().into());
+            }
+
+            config.insert("name".to_string(), plugin.name.clone().into());
+            config.insert("version".to_string(), plugin.version.to_string().into());
+            config.insert("entry".to_string(), plugin.entry.clone().into());
+            if !all_depends.is_empty() {
+                config.insert("depends".to_string(), all_depends.into());
+            }
+            let config =
+                toml::to_string(&config).map_err(|e| ConfigError::InvalidPack(e.to_string()))?;
+
+            Ok((format!("{}-v{}.lua", plugin.name, plugin.version), config))
+        })
+        .collect()
+}
+
+/// Reads the sources of a sub-plugin from its pack, with a config of its own.
+pub(crate) struct PackSourceProvider {
+    pack: Arc<dyn SourceProvider>,
+    config: String,
+}
+
+impl PackSourceProvider {
+    pub(crate) fn new(pack: Arc<dyn SourceProvider>, config: String) -> Self {
+        Self { pack, config }
+    }
+}
+
+impl SourceProvider for PackSourceProvider {
+    fn read_source(&self, rel_path: &str) -> io::Result<String> {
+        match rel_path {
+            "config.toml" => Ok(self.config.clone()),
+            _ => self.pack.read_source(rel_path),
+        }
+    }
+
+    fn exists(&self, rel_path: &str) -> bool {
+        rel_path == "config.toml" || self.pack.exists(rel_path)
+    }
+
+    fn list(&self, prefix: &str) -> io::Result<Vec<String>> {
+        self.pack.list(prefix)
+    }
+
+    fn cache_dir(&self) -> Option<PathBuf> {
+        self.pack.cache_dir()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACK: &str = r#"
+        name = "pack"
+        description = "Two plugins"
+        author = "Plux"
+        capabilities = ["spawn"]
+        depends = { external = "^2.0" }
+
+        [[plugins]]
+        name = "report"
+        version = "1.0.0"
+        entry = "report/main.lua"
+        depends = { math = "^1.0" }
+
+        [[plugins]]
+        name = "math"
+        version = "1.1.0"
+        entry = "math/main.lua"
+    "#;
+
+    #[test]
+    fn test_sub_plugin_configs() {
+        let configs = sub_plugin_configs(PACK).unwrap();
+        let names: Vec<_> = configs.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["math-v1.1.0.lua", "report-v1.0.0.lua"]);
+
+        let report: Config = toml::from_str(&configs[1].1).unwrap();
+        assert_eq!(report.name, "report");
+        assert_eq!(report.entry.as_deref(), Some("report/main.lua"));
+        assert_eq!(report.capabilities, Some(vec!["spawn".to_string()]));
+        assert_eq!(report.plugins, None);
+        let depends = report.depends.unwrap();
+        assert_eq!(depends.len(), 2);
+        assert!(depends.contains_key("math") && depends.contains_key("external"));
+    }
+
+    #[test]
+    fn test_invalid_packs() {
+        assert!(matches!(
+            sub_plugin_configs("name = \"a\"\ndescription = \"\"\nauthor = \"\""),
+            Err(ConfigError::InvalidPack(_))
+        ));
+        assert!(matches!(
+            sub_plugin_configs(&format!("entry = \"main.lua\"\n{PACK}")),
+            Err(ConfigError::InvalidPack(_))
+        ));
+    }
+}
@@ -0,0 +1,81 @@
+//! Rate limiting of the calls plugins make to the host, see
+//! [`crate::LuaManager::with_rate_limit`].
+
+use std::{sync::Mutex, time::Instant};
+
+use crate::sync::MutexExt;
+
+/// How many calls to host functions and dependencies a plugin may make, see
+/// [`crate::LuaManager::with_rate_limit`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    /// Number of calls per second a plugin may sustain.
+    pub per_second: f64,
+    /// Number of calls a plugin may make in a row after being idle.
+    pub burst: u32,
+}
+
+impl RateLimit {
+    /// Allows `per_second` calls per second on average, and up to `burst`
+    /// calls in a row.
+    pub fn new(per_second: f64, burst: u32) -> Self {
+        Self { per_second, burst }
+    }
+}
+
+/// Token bucket of a plugin, refilled at the rate of its [`RateLimit`].
+pub(crate) struct RateLimiter {
+    limit: RateLimit,
+    /// Available tokens and when they were last refilled
+    bucket: Mutex<(f64, Instant)>,
+}
+
+impl RateLimiter {
+    /// Creates a full bucket.
+    pub(crate) fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            bucket: Mutex::new((f64::from(limit.burst), Instant::now())),
+        }
+    }
+
+    /// Takes a token for a call, returning `false` if there is none left.
+    pub(crate) fn acquire(&self) -> bool {
+        let mut bucket = self.bucket.lock_unpoisoned();
+        let (tokens, refilled) = &mut *bucket;
+        let now = Instant::now();
+        let elapsed = now.duration_since(*refilled).as_secs_f64();
+        *tokens = (*tokens + elapsed * self.limit.per_second).min(f64::from(self.limit.burst));
+        *refilled = now;
+
+        if *tokens < 1.0 {
+            return false;
+        }
+        *tokens -= 1.0;
+        true
+    }
+
+    /// Describes the limit a call was rejected for.
+    pub(crate) fn exceeded(&self) -> String {
+        format!(
+            "rate limit of {} calls per second exceeded",
+            self.limit.per_second
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_burst_then_refill() {
+        let limiter = RateLimiter::new(RateLimit::new(1000.0, 2));
+        assert!(limiter.acquire());
+        assert!(limiter.acquire());
+        assert!(!limiter.acquire());
+
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        assert!(limiter.acquire());
+    }
+}
@@ -0,0 +1,97 @@
+//! Interactive evaluation of Lua inside a loaded plugin, see
+//! [`crate::LuaManager::repl`].
+
+use std::io::{self, BufRead, Write};
+
+use mlua::Lua;
+use plux_rs::{Bundle, variable::Variable};
+
+use crate::{error::ManagerError, manager::LuaManager};
+
+/// A read-eval-print loop attached to the state of a loaded plugin.
+///
+/// Input runs in the plugin's environment, with the globals and sandbox of
+/// the plugin, so it sees and may change the plugin's live state. Locals do
+/// not outlive the input declaring them. Meant for diagnosing plugins in
+/// development builds.
+pub struct Repl {
+    manager: LuaManager,
+    bundle: Bundle,
+}
+
+impl Repl {
+    pub(crate) fn new(manager: LuaManager, bundle: Bundle) -> Self {
+        Self { manager, bundle }
+    }
+
+    /// Returns the plugin the loop is attached to.
+    pub fn bundle(&self) -> &Bundle {
+        &self.bundle
+    }
+
+    /// Evaluates `input` in the plugin, see [`LuaManager::eval`].
+    pub fn eval(&self, input: &str) -> Result<Option<Variable>, ManagerError> {
+        self.manager.eval(&self.bundle, input)
+    }
+
+    /// Runs the loop, reading input from `input` and printing results and
+    /// errors to `output`, until `input` ends or reads `.exit`.
+    ///
+    /// Incomplete statements, such as an unterminated function, continue on
+    /// the next lines.
+    pub fn run(&self, mut input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+        let mut pending = String::new();
+        loop {
+            let prompt = match pending.is_empty() {
+                true => format!("{}> ", self.bundle.id),
+                false => ">> ".to_string(),
+            };
+            write!(output, "{prompt}")?;
+            output.flush()?;
+
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            if pending.is_empty() && line.trim() == ".exit" {
+                return Ok(());
+            }
+            pending.push_str(&line);
+
+            let incomplete = self
+                .manager
+                .plugin_state(&self.bundle)
+                .is_ok_and(|lua| is_incomplete(&lua, &pending));
+            if incomplete {
+                continue;
+            }
+            match self.eval(&pending) {
+                Ok(None) => {}
+                Ok(Some(value)) => writeln!(output, "{value:?}")?,
+                Err(e) => writeln!(output, "error: {e}")?,
+            }
+            pending.clear();
+        }
+    }
+}
+
+/// Returns `true` if `input` only fails to compile because it is
+/// incomplete, as an expression or as a block.
+fn is_incomplete(lua: &Lua, input: &str) -> bool {
+    let compile = |source: String| lua.load(source).into_function();
+    match (
+        compile(format!("return {input}")),
+        compile(input.to_string()),
+    ) {
+        (Err(expression), Err(block)) => [expression, block].iter().any(|e| {
+            matches!(
+                e,
+                mlua::Error::SyntaxError {
+                    incomplete_input: true,
+                    ..
+                }
+            )
+        }),
+        _ => false,
+    }
+}
@@ -0,0 +1,112 @@
+//! Information about the Lua runtime the manager was built with.
+
+use std::time::Duration;
+
+use mlua::Lua;
+use serde::{Deserialize, Serialize};
+
+/// Lua runtime the manager was built with, see [`LuaManager::runtime_info`].
+///
+/// [`LuaManager::runtime_info`]: crate::LuaManager::runtime_info
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuntimeInfo {
+    /// The Lua implementation, e.g. `PUC Lua 5.4`.
+    pub implementation: String,
+    /// The `_VERSION` reported by the Lua runtime.
+    pub version: String,
+    /// The enabled cargo features of this crate.
+    pub features: Vec<String>,
+    /// The version of this crate.
+    pub crate_version: String,
+}
+
+/// Runtime details of a plugin's Lua state, see [`LuaManager::plugin_runtime_info`].
+///
+/// [`LuaManager::plugin_runtime_info`]: crate::LuaManager::plugin_runtime_info
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PluginRuntimeInfo {
+    /// Information shared by all plugins of the manager.
+    pub runtime: RuntimeInfo,
+    /// The standard libraries available in the state.
+    pub stdlibs: Vec<String>,
+    /// The memory limit of the state in bytes, if any.
+    pub memory_limit: Option<usize>,
+    /// The time limit of the calls into the plugin, if any.
+    pub call_timeout: Option<Duration>,
+    /// The name of the sandbox profile applied to the state, if any.
+    pub sandbox_profile: Option<String>,
+    /// The memory currently used by the state in bytes.
+    pub used_memory: usize,
+    /// Whether the plugin is paused, see [`crate::LuaManager::pause`].
+    pub paused: bool,
+}
+
+/// Standard libraries looked up by [`PluginRuntimeInfo::stdlibs`].
+const STDLIBS: &[&str] = &[
+    "coroutine",
+    "table",
+    "io",
+    "os",
+    "string",
+    "utf8",
+    "math",
+    "package",
+    "debug",
+];
+
+impl RuntimeInfo {
+    pub(crate) fn new() -> Self {
+        let implementation = if cfg!(feature = "lua54") {
+            "PUC Lua 5.4"
+        } else if cfg!(feature = "lua53") {
+            "PUC Lua 5.3"
+        } else if cfg!(feature = "lua52") {
+            "PUC Lua 5.2"
+        } else {
+            "PUC Lua 5.1"
+        };
+
+        let features = [
+            ("lua54", cfg!(feature = "lua54")),
+            ("lua53", cfg!(feature = "lua53")),
+            ("lua52", cfg!(feature = "lua52")),
+            ("lua51", cfg!(feature = "lua51")),
+        ]
+        .into_iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(feature, _)| feature.to_string())
+        .collect();
+
+        Self {
+            implementation: implementation.to_string(),
+            version: Lua::new()
+                .globals()
+                .get::<String>("_VERSION")
+                .unwrap_or_default(),
+            features,
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+}
+
+impl PluginRuntimeInfo {
+    pub(crate) fn new(lua: &Lua) -> mlua::Result<Self> {
+        let globals = lua.globals();
+        let mut stdlibs = vec![];
+        for lib in STDLIBS {
+            if globals.contains_key(*lib)? {
+                stdlibs.push(lib.to_string());
+            }
+        }
+
+        Ok(Self {
+            runtime: RuntimeInfo::new(),
+            stdlibs,
+            memory_limit: None,
+            call_timeout: None,
+            sandbox_profile: None,
+            used_memory: lua.used_memory(),
+            paused: false,
+        })
+    }
+}
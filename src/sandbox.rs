@@ -0,0 +1,399 @@
+//! Selection of the Lua standard libraries opened in plugin states.
+//!
+//! The host chooses a [`SandboxPolicy`] with [`crate::LuaManager::with_sandbox`],
+//! and plugins may narrow it further by declaring the libraries they need in
+//! their config:
+//!
+//! ```toml
+//! libs = ["string", "table", "math"]
+//! ```
+//!
+//! The `debug` library is never opened. `require` always resolves the
+//! plugin's own modules; without the `package` library it resolves nothing
+//! else.
+//!
+//! Plugins declaring [`Permission`]s only get the parts of the opened
+//! libraries those permissions cover:
+//!
+//! ```toml
+//! permissions = ["fs"]
+//! ```
+
+use mlua::{Lua, LuaOptions, StdLib, Table};
+use serde::{Deserialize, Serialize};
+
+/// A Lua standard library.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum LuaLib {
+    /// `coroutine`, part of the base library in Lua 5.1.
+    Coroutine,
+    /// `table`.
+    Table,
+    /// `io`, filesystem and process access.
+    Io,
+    /// `os`, including `os.execute`, `os.remove` and `os.exit`.
+    Os,
+    /// `string`.
+    String,
+    /// `utf8`, Lua 5.3 and later.
+    Utf8,
+    /// `math`.
+    Math,
+    /// `package`, loading of Lua modules from `package.path`.
+    Package,
+}
+
+impl LuaLib {
+    /// Every library.
+    pub const ALL: &[LuaLib] = &[
+        LuaLib::Coroutine,
+        LuaLib::Table,
+        LuaLib::Io,
+        LuaLib::Os,
+        LuaLib::String,
+        LuaLib::Utf8,
+        LuaLib::Math,
+        LuaLib::Package,
+    ];
+
+    /// Name of the global holding the library.
+    fn name(self) -> &'static str {
+        match self {
+            LuaLib::Coroutine => "coroutine",
+            LuaLib::Table => "table",
+            LuaLib::Io => "io",
+            LuaLib::Os => "os",
+            LuaLib::String => "string",
+            LuaLib::Utf8 => "utf8",
+            LuaLib::Math => "math",
+            LuaLib::Package => "package",
+        }
+    }
+
+    fn std_lib(self) -> StdLib {
+        match self {
+            #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+            LuaLib::Coroutine => StdLib::COROUTINE,
+            LuaLib::Table => StdLib::TABLE,
+            LuaLib::Io => StdLib::IO,
+            LuaLib::Os => StdLib::OS,
+            LuaLib::String => StdLib::STRING,
+            #[cfg(any(feature = "lua54", feature = "lua53"))]
+            LuaLib::Utf8 => StdLib::UTF8,
+            LuaLib::Math => StdLib::MATH,
+            LuaLib::Package => StdLib::PACKAGE,
+            #[allow(unreachable_patterns)]
+            _ => StdLib::NONE,
+        }
+    }
+}
+
+/// Access to the host system a plugin asks for in its config.
+///
+/// Plugins declaring `permissions` lose the functions of the standard
+/// libraries their permissions do not cover. Each permission also grants the
+/// capability of the same name, see [`crate::KNOWN_CAPABILITIES`].
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Permission {
+    /// Files: `io.open`, `io.lines`, `io.input`, `io.output`, `io.tmpfile`,
+    /// `os.remove`, `os.rename`, `os.tmpname`, `loadfile`, `dofile` and the
+    /// `package` searchers reading modules from disk.
+    Fs,
+    /// Network access through the host's API.
+    Net,
+    /// Other processes: `io.popen`, `os.execute` and `os.exit`.
+    Subprocess,
+}
+
+impl Permission {
+    /// Returns the name of the permission in configs.
+    pub fn name(self) -> &'static str {
+        match self {
+            Permission::Fs => "fs",
+            Permission::Net => "net",
+            Permission::Subprocess => "subprocess",
+        }
+    }
+
+    /// Functions of the standard libraries covered by the permission, as
+    /// `(library, function)` with an empty library for base functions.
+    fn functions(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Permission::Fs => &[
+                ("io", "open"),
+                ("io", "lines"),
+                ("io", "input"),
+                ("io", "output"),
+                ("io", "tmpfile"),
+                ("os", "remove"),
+                ("os", "rename"),
+                ("os", "tmpname"),
+                ("", "loadfile"),
+                ("", "dofile"),
+            ],
+            Permission::Net => &[],
+            Permission::Subprocess => &[("io", "popen"), ("os", "execute"), ("os", "exit")],
+        }
+    }
+}
+
+/// Removes the functions of the permissions a plugin did not declare.
+pub(crate) fn enforce_permissions(lua: &Lua, granted: &[Permission]) -> mlua::Result<()> {
+    let globals = lua.globals();
+    for permission in [Permission::Fs, Permission::Net, Permission::Subprocess] {
+        if granted.contains(&permission) {
+            continue;
+        }
+        for (lib, name) in permission.functions() {
+            let table = match *lib {
+                "" => globals.clone(),
+                lib => match globals.raw_get::<Option<Table>>(lib)? {
+                    Some(table) => table,
+                    None => continue,
+                },
+            };
+            table.raw_set(*name, mlua::Value::Nil)?;
+        }
+    }
+    if !granted.contains(&Permission::Fs) {
+        restrict_package(&globals)?;
+    }
+    Ok(())
+}
+
+/// Leaves `package` able to resolve only `package.preload`.
+fn restrict_package(globals: &Table) -> mlua::Result<()> {
+    let package: Table = globals.get("package")?;
+    package.raw_set("path", "")?;
+    package.raw_set("cpath", "")?;
+    package.raw_set("loadlib", mlua::Value::Nil)?;
+
+    // Keep only `package.preload`
+    let searchers: Table = match package.get::<Option<Table>>("searchers")? {
+        Some(searchers) => searchers,
+        None => package.get("loaders")?,
+    };
+    for index in (2..=searchers.raw_len()).rev() {
+        searchers.raw_set(index, mlua::Value::Nil)?;
+    }
+    Ok(())
+}
+
+/// Which standard libraries and base functions plugin states get.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SandboxPolicy {
+    libs: Vec<LuaLib>,
+    load: bool,
+    profile: Option<&'static str>,
+}
+
+impl Default for SandboxPolicy {
+    fn default() -> Self {
+        Self::full()
+    }
+}
+
+impl SandboxPolicy {
+    /// Every library, and the `load`, `loadfile` and `dofile` functions.
+    pub fn full() -> Self {
+        Self {
+            libs: LuaLib::ALL.to_vec(),
+            load: true,
+            profile: Some("full"),
+        }
+    }
+
+    /// `coroutine`, `table`, `string`, `utf8` and `math`, without `load`,
+    /// `loadfile` and `dofile`.
+    ///
+    /// Suitable for untrusted plugins: they cannot reach the filesystem, other
+    /// processes or the host's Lua modules, nor compile code at runtime.
+    pub fn safe() -> Self {
+        Self {
+            libs: vec![
+                LuaLib::Coroutine,
+                LuaLib::Table,
+                LuaLib::String,
+                LuaLib::Utf8,
+                LuaLib::Math,
+            ],
+            load: false,
+            profile: Some("safe"),
+        }
+    }
+
+    /// Opens `lib` as well.
+    pub fn with_lib(mut self, lib: LuaLib) -> Self {
+        if !self.libs.contains(&lib) {
+            self.libs.push(lib);
+            self.profile = None;
+        }
+        self
+    }
+
+    /// Does not open `lib`.
+    pub fn without_lib(mut self, lib: LuaLib) -> Self {
+        if self.libs.contains(&lib) {
+            self.libs.retain(|l| *l != lib);
+            self.profile = None;
+        }
+        self
+    }
+
+    /// Enables or disables `load`, `loadfile` and `dofile`.
+    pub fn with_load(mut self, enabled: bool) -> Self {
+        if self.load != enabled {
+            self.load = enabled;
+            self.profile = None;
+        }
+        self
+    }
+
+    /// Returns `true` if `lib` is opened.
+    pub fn allows(&self, lib: LuaLib) -> bool {
+        self.libs.contains(&lib)
+    }
+
+    /// Returns `true` if `load`, `loadfile` and `dofile` are available.
+    pub fn allows_load(&self) -> bool {
+        self.load
+    }
+
+    /// Returns the name of the preset the policy was created from, `None`
+    /// once it has been customized.
+    pub fn profile(&self) -> Option<&str> {
+        self.profile
+    }
+
+    /// Restricts the policy to the libraries a plugin declared, no longer
+    /// named after a profile if that leaves libraries out.
+    ///
+    /// Declared libraries the policy does not allow are returned.
+    pub(crate) fn restrict(&self, declared: &[LuaLib]) -> (Self, Vec<LuaLib>) {
+        let denied = declared
+            .iter()
+            .copied()
+            .filter(|lib| !self.allows(*lib))
+            .collect();
+        let libs: Vec<_> = self
+            .libs
+            .iter()
+            .copied()
+            .filter(|lib| declared.contains(lib))
+            .collect();
+        let policy = Self {
+            profile: self.profile.filter(|_| libs.len() == self.libs.len()),
+            libs,
+            load: self.load,
+        };
+        (policy, denied)
+    }
+
+    /// Creates a Lua state following the policy.
+    pub(crate) fn create_lua(&self) -> mlua::Result<Lua> {
+        // `require` needs the package library
+        let libs = self
+            .libs
+            .iter()
+            .fold(StdLib::PACKAGE, |libs, lib| libs | lib.std_lib());
+        let lua = Lua::new_with(libs, LuaOptions::default())?;
+        self.strip(&lua.globals())?;
+
+        Ok(lua)
+    }
+
+    /// Removes from `globals` the libraries and functions the policy does not
+    /// allow, for states opened with more than the policy allows.
+    pub(crate) fn strip(&self, globals: &Table) -> mlua::Result<()> {
+        for lib in LuaLib::ALL.iter().filter(|lib| !self.allows(**lib)) {
+            match lib {
+                // `require` needs the package library
+                LuaLib::Package => restrict_package(globals)?,
+                lib if lib.std_lib() != StdLib::NONE => {
+                    globals.raw_set(lib.name(), mlua::Value::Nil)?
+                }
+                _ => {}
+            }
+        }
+        if !self.load {
+            for name in ["load", "loadfile", "dofile", "loadstring"] {
+                globals.raw_set(name, mlua::Value::Nil)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_safe_policy() {
+        let lua = SandboxPolicy::safe().create_lua().unwrap();
+        let (io, os, debug, load, math): (bool, bool, bool, bool, bool) = lua
+            .load("return io ~= nil, os ~= nil, debug ~= nil, load ~= nil, math ~= nil")
+            .eval()
+            .unwrap();
+        assert_eq!(
+            (io, os, debug, load, math),
+            (false, false, false, false, true)
+        );
+
+        let searchers: usize = lua.load("return #package.searchers").eval().unwrap();
+        assert_eq!(searchers, 1);
+    }
+
+    #[test]
+    fn test_restrict() {
+        let (policy, denied) = SandboxPolicy::safe().restrict(&[LuaLib::String, LuaLib::Io]);
+        assert!(policy.allows(LuaLib::String));
+        assert!(!policy.allows(LuaLib::Math));
+        assert_eq!(denied, vec![LuaLib::Io]);
+        assert_eq!(policy.profile(), None);
+
+        let (policy, _) = SandboxPolicy::safe().restrict(LuaLib::ALL);
+        assert_eq!(policy.profile(), Some("safe"));
+
+        assert_eq!(SandboxPolicy::safe().profile(), Some("safe"));
+        assert_eq!(SandboxPolicy::safe().with_lib(LuaLib::Os).profile(), None);
+    }
+
+    #[test]
+    fn test_strip() {
+        let lua = SandboxPolicy::full().create_lua().unwrap();
+        SandboxPolicy::safe().strip(&lua.globals()).unwrap();
+        let (io, os, load, string, searchers): (bool, bool, bool, bool, usize) = lua
+            .load("return io ~= nil, os ~= nil, load ~= nil, string ~= nil, #package.searchers")
+            .eval()
+            .unwrap();
+        assert_eq!(
+            (io, os, load, string, searchers),
+            (false, false, false, true, 1)
+        );
+    }
+
+    #[test]
+    fn test_enforce_permissions() {
+        let lua = SandboxPolicy::full().create_lua().unwrap();
+        enforce_permissions(&lua, &[Permission::Fs]).unwrap();
+        let (open, popen, execute, dofile, searchers): (bool, bool, bool, bool, usize) = lua
+            .load(
+                "return io.open ~= nil, io.popen ~= nil, os.execute ~= nil, dofile ~= nil, #package.searchers",
+            )
+            .eval()
+            .unwrap();
+        assert_eq!((open, popen, execute, dofile), (true, false, false, true));
+        assert!(searchers > 1);
+
+        let lua = SandboxPolicy::full().create_lua().unwrap();
+        enforce_permissions(&lua, &[Permission::Subprocess]).unwrap();
+        let (open, popen, clock, searchers): (bool, bool, bool, usize) = lua
+            .load("return io.open ~= nil, io.popen ~= nil, os.clock ~= nil, #package.searchers")
+            .eval()
+            .unwrap();
+        assert_eq!((open, popen, clock, searchers), (false, true, true, 1));
+    }
+}
@@ -0,0 +1,105 @@
+//! Plugins written as a single Lua file, see [`crate::LuaManager::mount_script`].
+//!
+//! The config of a script plugin is written in a comment header opening the
+//! file, in the format of `config.toml`:
+//!
+//! ```lua
+//! --[[plux
+//! version = "1.0.0"
+//! description = "Greets people"
+//! ]]
+//! return { greet = function(name) return "hello " .. name end }
+//! ```
+//!
+//! The header is optional for scripts named after their bundle, e.g.
+//! `greeter-v1.0.0.lua`. `name` defaults to the plugin id and `description`
+//! and `author` to empty strings, and the script itself is the entry script.
+
+use std::path::Path;
+
+use plux_rs::Bundle;
+
+use crate::error::PluginError;
+
+/// Opening of the comment header holding the config of a script plugin.
+const SCRIPT_HEADER: &str = "--[[plux";
+
+/// Name the script is mounted under in the plugin directory.
+pub(crate) const SCRIPT_ENTRY: &str = "main.lua";
+
+/// Returns the name of the plugin directory the script at `path` stands for,
+/// and the content of its `config.toml`.
+pub(crate) fn script_config(path: &Path, src: &str) -> Result<(String, String), PluginError> {
+    let invalid = |message: String| {
+        PluginError::SourceError(format!("Script plugin {}: {message}", path.display()))
+    };
+
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .filter(|name| name.ends_with(".lua"))
+        .ok_or_else(|| invalid("not a Lua script".to_string()))?;
+    let header = match src.trim_start().strip_prefix(SCRIPT_HEADER) {
+        Some(rest) => rest
+            .split_once("]]")
+            .map(|(header, _)| header)
+            .ok_or_else(|| invalid("the header is not closed".to_string()))?,
+        None => "",
+    };
+    let mut config: toml::Table =
+        toml::from_str(header).map_err(|e| invalid(format!("invalid header: {e}")))?;
+
+    let dir_name = match Bundle::from_filename(file_name) {
+        Ok(_) => file_name.to_string(),
+        Err(_) => {
+            let stem = file_name.trim_end_matches(".lua");
+            let version = config
+                .get("version")
+                .and_then(|version| version.as_str())
+                .ok_or_else(|| {
+                    invalid(format!(
+                        "no version, declare it in the header or name the file `{stem}-v<version>.lua`"
+                    ))
+                })?;
+            format!("{stem}-v{version}.lua")
+        }
+    };
+    let bundle = Bundle::from_filename(&dir_name)
+        .map_err(|e| invalid(format!("invalid plugin name `{dir_name}`: {e}")))?;
+
+    config
+        .entry("name")
+        .or_insert_with(|| bundle.id.clone().into());
+    config.entry("description").or_insert_with(|| "".into());
+    config.entry("author").or_insert_with(|| "".into());
+    config.insert("entry".to_string(), SCRIPT_ENTRY.into());
+    let config = toml::to_string(&config).map_err(|e| invalid(e.to_string()))?;
+
+    Ok((dir_name, config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script_config() {
+        let (dir_name, config) = script_config(
+            Path::new("scripts/greeter.lua"),
+            "--[[plux\nversion = \"1.2.0\"\nauthor = \"Plux\"\n]]\nreturn {}",
+        )
+        .unwrap();
+        assert_eq!(dir_name, "greeter-v1.2.0.lua");
+        let config: toml::Table = toml::from_str(&config).unwrap();
+        assert_eq!(config["name"].as_str(), Some("greeter"));
+        assert_eq!(config["author"].as_str(), Some("Plux"));
+        assert_eq!(config["entry"].as_str(), Some("main.lua"));
+
+        let (dir_name, _) = script_config(Path::new("tiny-v0.1.0.lua"), "return {}").unwrap();
+        assert_eq!(dir_name, "tiny-v0.1.0.lua");
+
+        assert!(script_config(Path::new("tiny.lua"), "return {}").is_err());
+        assert!(script_config(Path::new("tiny.txt"), "return {}").is_err());
+        assert!(script_config(Path::new("tiny.lua"), "--[[plux\nversion = \"1.0.0\"").is_err());
+    }
+}
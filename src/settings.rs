@@ -0,0 +1,207 @@
+//! User settings of plugins, see [`crate::LuaManager::set_setting`].
+
+use std::{collections::BTreeMap, collections::HashMap, io, path::PathBuf, sync::Mutex};
+
+use indexmap::IndexMap;
+use plux_rs::variable::{Variable, VariableType};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{SettingDeclaration, plain_variable},
+    error::{ConfigError, PluginError},
+    sync::MutexExt,
+    typed::{coerce, parse_type, type_name, type_of},
+};
+
+/// Name of the file holding the values of a plugin's settings, in its data
+/// directory.
+pub const SETTINGS_FILE: &str = "settings.toml";
+
+/// A setting declared by a plugin, see [`crate::LuaManager::settings_schema`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SettingInfo {
+    /// The name of the setting.
+    pub name: String,
+    /// The declared type, `let` if the value may be of any type.
+    pub ty: String,
+    /// The value of the setting until the user changes it.
+    pub default: Option<Variable>,
+    /// What the setting does.
+    pub description: Option<String>,
+}
+
+/// A value of the settings file
+#[derive(Deserialize, Serialize)]
+#[serde(transparent)]
+struct Stored(#[serde(with = "plain_variable")] Option<Variable>);
+
+/// A validated setting declaration
+struct Setting {
+    ty: VariableType,
+    default: Option<Variable>,
+    description: Option<String>,
+}
+
+/// The settings of a plugin and the values the user gave them.
+pub(crate) struct Settings {
+    /// The declared settings, by name
+    schema: IndexMap<String, Setting>,
+    /// The values differing from the defaults, by name
+    values: Mutex<IndexMap<String, Variable>>,
+    /// Where the values persist, if anywhere
+    file: Option<PathBuf>,
+}
+
+impl Settings {
+    /// Validates the `declarations` of a plugin and reads the values kept in
+    /// `file`.
+    ///
+    /// Values of settings the plugin no longer declares, or no longer of the
+    /// declared type, are dropped.
+    pub(crate) fn new(
+        declarations: &HashMap<String, SettingDeclaration>,
+        file: Option<PathBuf>,
+    ) -> Result<Self, ConfigError> {
+        let mut schema = IndexMap::new();
+        for (name, declaration) in declarations {
+            let invalid = |reason: String| ConfigError::InvalidSetting(name.clone(), reason);
+            let ty = declaration.ty.as_deref().unwrap_or("let");
+            let ty = parse_type(ty).ok_or_else(|| invalid(format!("unknown type `{ty}`")))?;
+            let default = match &declaration.default {
+                Some(default) => Some(coerce(default, ty).ok_or_else(|| {
+                    invalid(format!(
+                        "default is {}, expected {}",
+                        type_of(default),
+                        type_name(ty)
+                    ))
+                })?),
+                None => None,
+            };
+            schema.insert(
+                name.clone(),
+                Setting {
+                    ty,
+                    default,
+                    description: declaration.description.clone(),
+                },
+            );
+        }
+        schema.sort_unstable_keys();
+
+        let mut values = IndexMap::new();
+        if let Some(file) = file.as_ref().filter(|file| file.is_file()) {
+            let stored: BTreeMap<String, Stored> = toml::from_str(&std::fs::read_to_string(file)?)?;
+            for (name, Stored(value)) in stored {
+                let value = schema
+                    .get(&name)
+                    .zip(value)
+                    .and_then(|(setting, value)| coerce(&value, setting.ty));
+                match value {
+                    Some(value) => {
+                        values.insert(name, value);
+                    }
+                    None => log::warn!("Dropping the stored value of setting `{name}`"),
+                }
+            }
+        }
+
+        Ok(Self {
+            schema,
+            values: Mutex::new(values),
+            file,
+        })
+    }
+
+    /// Returns the declared settings, by name.
+    pub(crate) fn schema(&self) -> Vec<SettingInfo> {
+        self.schema
+            .iter()
+            .map(|(name, setting)| SettingInfo {
+                name: name.clone(),
+                ty: type_name(setting.ty),
+                default: setting.default.clone(),
+                description: setting.description.clone(),
+            })
+            .collect()
+    }
+
+    /// Returns the value of every setting, `Null` for those without a value.
+    pub(crate) fn values(&self) -> IndexMap<String, Variable> {
+        let values = self.values.lock_unpoisoned();
+        self.schema
+            .iter()
+            .map(|(name, setting)| (name.clone(), current(&values, name, setting)))
+            .collect()
+    }
+
+    /// Returns the value of the setting `name`, `Null` if it has none.
+    pub(crate) fn get(&self, name: &str) -> Result<Variable, PluginError> {
+        let setting = self.setting(name)?;
+        Ok(current(&self.values.lock_unpoisoned(), name, setting))
+    }
+
+    /// Sets the setting `name` to `value` converted to its type, or back to
+    /// its default if `value` is `Null`, and persists the values.
+    ///
+    /// Returns the new value and the previous one.
+    pub(crate) fn set(
+        &self,
+        name: &str,
+        value: Variable,
+    ) -> Result<(Variable, Variable), PluginError> {
+        let setting = self.setting(name)?;
+        let value = match value {
+            Variable::Null => None,
+            value => Some(coerce(&value, setting.ty).ok_or_else(|| {
+                PluginError::SettingTypeMismatch {
+                    setting: name.to_string(),
+                    expected: type_name(setting.ty),
+                    found: type_of(&value).to_string(),
+                }
+            })?),
+        };
+
+        let mut values = self.values.lock_unpoisoned();
+        let old = current(&values, name, setting);
+        let mut changed = values.clone();
+        match value {
+            Some(value) => changed.insert(name.to_string(), value),
+            None => changed.shift_remove(name),
+        };
+        self.save(&changed)?;
+        *values = changed;
+
+        Ok((current(&values, name, setting), old))
+    }
+
+    fn setting(&self, name: &str) -> Result<&Setting, PluginError> {
+        self.schema
+            .get(name)
+            .ok_or_else(|| PluginError::SettingNotFound(name.to_string()))
+    }
+
+    /// Writes `values` to the settings file, if any.
+    fn save(&self, values: &IndexMap<String, Variable>) -> io::Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        let stored: BTreeMap<&str, Stored> = values
+            .iter()
+            .map(|(name, value)| (name.as_str(), Stored(Some(value.clone()))))
+            .collect();
+        let content = toml::to_string(&stored).map_err(io::Error::other)?;
+        if let Some(parent) = file.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(file, content)
+    }
+}
+
+/// Returns the value of `setting` in `values`, its default if it has none.
+fn current(values: &IndexMap<String, Variable>, name: &str, setting: &Setting) -> Variable {
+    values
+        .get(name)
+        .or(setting.default.as_ref())
+        .cloned()
+        .unwrap_or(Variable::Null)
+}
@@ -0,0 +1,81 @@
+//! Host-managed memory shared between plugins.
+//!
+//! Every plugin can read and write the key-value store through `api.store`:
+//!
+//! ```lua
+//! api.store.set("theme", "dark")
+//! local theme = api.store.get("theme")
+//! api.store.delete("theme")
+//! for _, key in ipairs(api.store.keys()) do print(key) end
+//! ```
+//!
+//! For data only some plugins may access, the host creates named channels with [`LuaManager::create_shared`], each
+//! with an [`AccessPolicy`] listing the plugins allowed to read and write it.
+//! Plugins access the channels through `api.shared`:
+//!
+//! ```lua
+//! api.shared.set("layout", "width", 640)
+//! local width = api.shared.get("layout", "width")
+//! ```
+//!
+//! Values are stored host-side as [`Variable`]s and copied into the state of
+//! each reading plugin, so plugins never alias each other's Lua values.
+//!
+//! [`LuaManager::create_shared`]: crate::LuaManager::create_shared
+
+use std::sync::{Arc, RwLock};
+
+use hashbrown::HashMap;
+use indexmap::IndexMap;
+use plux_rs::variable::Variable;
+
+/// Plugins allowed to access a shared channel, identified by plugin id.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccessPolicy {
+    /// Plugins allowed to read the channel.
+    pub readers: Vec<String>,
+    /// Plugins allowed to read and write the channel.
+    pub writers: Vec<String>,
+}
+
+impl AccessPolicy {
+    /// Creates a policy denying access to every plugin.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allows the plugin to read the channel.
+    pub fn reader<S: Into<String>>(mut self, id: S) -> Self {
+        self.readers.push(id.into());
+        self
+    }
+
+    /// Allows the plugin to read and write the channel.
+    pub fn writer<S: Into<String>>(mut self, id: S) -> Self {
+        self.writers.push(id.into());
+        self
+    }
+
+    /// Returns `true` if the plugin may read the channel.
+    pub fn can_read(&self, id: &str) -> bool {
+        self.readers.iter().any(|r| r == id) || self.can_write(id)
+    }
+
+    /// Returns `true` if the plugin may write the channel.
+    pub fn can_write(&self, id: &str) -> bool {
+        self.writers.iter().any(|w| w == id)
+    }
+}
+
+/// A named shared channel.
+#[derive(Debug, Default)]
+pub(crate) struct SharedChannel {
+    pub policy: AccessPolicy,
+    pub values: HashMap<String, Variable>,
+}
+
+/// The shared channels of a manager.
+pub(crate) type SharedStore = Arc<RwLock<HashMap<String, SharedChannel>>>;
+
+/// The key-value store open to all plugins, keys in insertion order.
+pub(crate) type KeyValueStore = Arc<RwLock<IndexMap<String, Variable>>>;
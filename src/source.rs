@@ -0,0 +1,258 @@
+//! Storage abstraction for plugin sources.
+//!
+//! The manager reads a plugin's `config.toml`, entry scripts and required
+//! modules through a [`SourceProvider`]. By default plugins are read from
+//! their directory with [`FsSourceProvider`], but hosts can keep plugins in
+//! any storage (a database, an archive, memory) by supplying their own
+//! provider factory with [`LuaManager::with_source_provider`], or mount single
+//! plugins kept in memory with [`MemorySourceProvider`].
+//!
+//! Note that plux still identifies plugins by a directory named after the
+//! bundle (`my_plugin-v1.0.0.lua`), so a non-filesystem provider is keyed by
+//! that path even if the directory itself is empty.
+//!
+//! [`LuaManager::with_source_provider`]: crate::LuaManager::with_source_provider
+
+use std::{
+    io,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+use indexmap::IndexMap;
+use plux_rs::Bundle;
+
+use crate::{bytecode::CACHE_DIR, error::PluginError};
+
+/// A source of plugin files, addressed by paths relative to the plugin root.
+///
+/// Relative paths always use `/` as separator.
+pub trait SourceProvider: Send + Sync {
+    /// Reads the plugin's `config.toml`.
+    fn read_config(&self) -> io::Result<String> {
+        self.read_source("config.toml")
+    }
+
+    /// Reads a source file.
+    fn read_source(&self, rel_path: &str) -> io::Result<String>;
+
+    /// Returns `true` if the file exists.
+    fn exists(&self, rel_path: &str) -> bool;
+
+    /// Lists the files whose relative path starts with `prefix`.
+    fn list(&self, prefix: &str) -> io::Result<Vec<String>>;
+
+    /// Returns the directory where compiled chunks of the plugin are cached on
+    /// disk, see [`LuaManager::with_bytecode_cache`]. No directory by default.
+    ///
+    /// [`LuaManager::with_bytecode_cache`]: crate::LuaManager::with_bytecode_cache
+    fn cache_dir(&self) -> Option<PathBuf> {
+        None
+    }
+}
+
+/// Resolves modules plugins `require` but do not ship, from any backend.
+///
+/// Resolvers are registered with [`LuaManager::with_module_resolver`] and
+/// asked in registration order, after the plugin's own sources. Closures
+/// taking the requiring plugin and the module name are resolvers.
+///
+/// [`LuaManager::with_module_resolver`]: crate::LuaManager::with_module_resolver
+pub trait ModuleResolver: Send + Sync {
+    /// Returns the source of module `name` required by plugin `bundle`, or
+    /// `None` if the resolver does not know it.
+    fn resolve(&self, bundle: &Bundle, name: &str) -> io::Result<Option<String>>;
+}
+
+impl<F> ModuleResolver for F
+where
+    F: Fn(&Bundle, &str) -> io::Result<Option<String>> + Send + Sync,
+{
+    fn resolve(&self, bundle: &Bundle, name: &str) -> io::Result<Option<String>> {
+        self(bundle, name)
+    }
+}
+
+/// Turns plugin sources written in another language, or needing
+/// preprocessing, into Lua.
+///
+/// Transforms are registered for a file extension with
+/// [`LuaManager::with_source_transform`], and applied to the entry scripts
+/// and modules with that extension when they are loaded. Closures taking the
+/// path of the file and its source are transforms.
+///
+/// [`LuaManager::with_source_transform`]: crate::LuaManager::with_source_transform
+pub trait SourceTransform: Send + Sync {
+    /// Returns the Lua code of the source `src` of the file `path`, relative
+    /// to the plugin root.
+    ///
+    /// Errors are best reported as [`PluginError::SourceError`], with the
+    /// file and line they were found at.
+    fn transform(&self, path: &str, src: &str) -> Result<String, PluginError>;
+}
+
+impl<F> SourceTransform for F
+where
+    F: Fn(&str, &str) -> Result<String, PluginError> + Send + Sync,
+{
+    fn transform(&self, path: &str, src: &str) -> Result<String, PluginError> {
+        self(path, src)
+    }
+}
+
+/// Source transforms, by the extension of the files they apply to.
+pub(crate) type SourceTransforms = Arc<IndexMap<String, Arc<dyn SourceTransform>>>;
+
+/// Applies to `src` the transform registered for the extension of `path`, if
+/// any.
+pub(crate) fn transform_source(
+    transforms: &SourceTransforms,
+    path: &str,
+    src: String,
+) -> Result<String, PluginError> {
+    let transform = Path::new(path)
+        .extension()
+        .and_then(|extension| transforms.get(extension.to_str()?));
+    match transform {
+        Some(transform) => transform.transform(path, &src),
+        None => Ok(src),
+    }
+}
+
+/// Creates the [`SourceProvider`] of a plugin from its path.
+pub type SourceProviderFactory = Arc<dyn Fn(&Path) -> Arc<dyn SourceProvider> + Send + Sync>;
+
+/// Reads plugin sources from the plugin directory.
+///
+/// Paths leading outside the directory, through `..` or as absolute paths,
+/// are refused.
+#[derive(Debug, Clone)]
+pub struct FsSourceProvider {
+    root: PathBuf,
+}
+
+impl FsSourceProvider {
+    /// Creates a provider rooted at the plugin directory.
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Returns the plugin directory.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Returns the path of a file, refusing paths leading outside the plugin
+    /// directory.
+    fn resolve(&self, rel_path: &str) -> io::Result<PathBuf> {
+        let inside_root = Path::new(rel_path)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+        match inside_root {
+            true => Ok(self.root.join(rel_path)),
+            false => Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("{rel_path} is outside the plugin directory"),
+            )),
+        }
+    }
+}
+
+impl SourceProvider for FsSourceProvider {
+    fn read_source(&self, rel_path: &str) -> io::Result<String> {
+        std::fs::read_to_string(self.resolve(rel_path)?)
+    }
+
+    fn exists(&self, rel_path: &str) -> bool {
+        self.resolve(rel_path).is_ok_and(|path| path.is_file())
+    }
+
+    fn list(&self, prefix: &str) -> io::Result<Vec<String>> {
+        fn visit(dir: &Path, root: &Path, files: &mut Vec<String>) -> io::Result<()> {
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+                if path.ends_with(CACHE_DIR) {
+                    continue;
+                } else if path.is_dir() {
+                    visit(&path, root, files)?;
+                } else if let Ok(rel_path) = path.strip_prefix(root) {
+                    let rel_path = rel_path
+                        .components()
+                        .map(|c| c.as_os_str().to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join("/");
+                    files.push(rel_path);
+                }
+            }
+            Ok(())
+        }
+
+        let mut files = vec![];
+        visit(&self.root, &self.root, &mut files)?;
+        files.retain(|file| file.starts_with(prefix));
+        files.sort();
+        Ok(files)
+    }
+
+    fn cache_dir(&self) -> Option<PathBuf> {
+        Some(self.root.join(CACHE_DIR))
+    }
+}
+
+/// Keeps plugin sources in memory, for plugins compiled into the host.
+///
+/// See [`LuaManager::mount_embedded`].
+///
+/// [`LuaManager::mount_embedded`]: crate::LuaManager::mount_embedded
+#[derive(Debug, Clone, Default)]
+pub struct MemorySourceProvider {
+    files: IndexMap<String, String>,
+}
+
+impl MemorySourceProvider {
+    /// Creates a provider without files.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the file `rel_path` with the given content.
+    pub fn with_file(mut self, rel_path: impl Into<String>, content: impl Into<String>) -> Self {
+        self.files.insert(rel_path.into(), content.into());
+        self
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for MemorySourceProvider {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(files: I) -> Self {
+        Self {
+            files: files
+                .into_iter()
+                .map(|(rel_path, content)| (rel_path.into(), content.into()))
+                .collect(),
+        }
+    }
+}
+
+impl SourceProvider for MemorySourceProvider {
+    fn read_source(&self, rel_path: &str) -> io::Result<String> {
+        self.files
+            .get(rel_path)
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{rel_path} not found")))
+    }
+
+    fn exists(&self, rel_path: &str) -> bool {
+        self.files.contains_key(rel_path)
+    }
+
+    fn list(&self, prefix: &str) -> io::Result<Vec<String>> {
+        let mut files: Vec<String> = self
+            .files
+            .keys()
+            .filter(|file| file.starts_with(prefix))
+            .cloned()
+            .collect();
+        files.sort();
+        Ok(files)
+    }
+}
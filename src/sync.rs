@@ -0,0 +1,94 @@
+//! Locking that survives panics.
+//!
+//! A panic while a lock is held, such as one raised by a host function called
+//! from Lua, poisons it. The data behind the locks of the manager stays
+//! consistent across such panics, so instead of failing every later call the
+//! poison is ignored and the lock taken anyway.
+
+use std::{
+    sync::{
+        Arc, Condvar, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard,
+    },
+    thread::{self, ThreadId},
+};
+
+/// Locking of a [`Mutex`] ignoring poisoning.
+pub(crate) trait MutexExt<T> {
+    fn lock_unpoisoned(&self) -> MutexGuard<'_, T>;
+}
+
+impl<T> MutexExt<T> for Mutex<T> {
+    fn lock_unpoisoned(&self) -> MutexGuard<'_, T> {
+        self.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Locking of a [`RwLock`] ignoring poisoning.
+pub(crate) trait RwLockExt<T> {
+    fn read_unpoisoned(&self) -> RwLockReadGuard<'_, T>;
+    fn write_unpoisoned(&self) -> RwLockWriteGuard<'_, T>;
+}
+
+impl<T> RwLockExt<T> for RwLock<T> {
+    fn read_unpoisoned(&self) -> RwLockReadGuard<'_, T> {
+        self.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write_unpoisoned(&self) -> RwLockWriteGuard<'_, T> {
+        self.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// A lock the thread holding it can take again.
+///
+/// Held by the handles entering a plugin of a shared Lua state, see
+/// [`crate::lua::state`], so that nested calls on the same thread go through
+/// while other threads wait for the outermost call to return.
+#[derive(Default)]
+pub(crate) struct ReentrantLock {
+    owner: Mutex<Option<(ThreadId, usize)>>,
+    released: Condvar,
+}
+
+impl ReentrantLock {
+    /// Takes the lock, waiting for other threads to release it.
+    pub(crate) fn lock(self: &Arc<Self>) -> ReentrantGuard {
+        let current = thread::current().id();
+        let mut owner = self.owner.lock_unpoisoned();
+        loop {
+            match owner.as_mut() {
+                None => {
+                    *owner = Some((current, 1));
+                    break;
+                }
+                Some((thread, depth)) if *thread == current => {
+                    *depth += 1;
+                    break;
+                }
+                Some(_) => {
+                    owner = self
+                        .released
+                        .wait(owner)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+            }
+        }
+        ReentrantGuard(self.clone())
+    }
+}
+
+/// Releases a [`ReentrantLock`] once dropped as many times as it was taken.
+pub(crate) struct ReentrantGuard(Arc<ReentrantLock>);
+
+impl Drop for ReentrantGuard {
+    fn drop(&mut self) {
+        let mut owner = self.0.owner.lock_unpoisoned();
+        if let Some((_, depth)) = owner.as_mut() {
+            *depth -= 1;
+            if *depth == 0 {
+                *owner = None;
+                self.0.released.notify_one();
+            }
+        }
+    }
+}
@@ -0,0 +1,68 @@
+//! Compilation of Teal sources to Lua, see
+//! [`crate::LuaManager::with_teal_compiler`].
+
+use std::sync::Mutex;
+
+use mlua::{Function, Lua, Table};
+
+use crate::{error::PluginError, source::SourceTransform, sync::MutexExt};
+
+/// Extension of Teal sources.
+pub(crate) const TEAL_EXTENSION: &str = "tl";
+
+/// Type checks a Teal source and generates its Lua code, returning the code,
+/// or `nil` and the errors, one per line.
+const COMPILE: &str = r#"
+    local tl, src, path = ...
+    local result = tl.process_string(src, false, nil, path)
+    local errors = {}
+    for _, list in ipairs({ result.syntax_errors or {}, result.type_errors or {} }) do
+        for _, e in ipairs(list) do
+            errors[#errors + 1] = string.format("%s:%d:%d: %s", e.filename or path, e.y, e.x, e.msg)
+        end
+    end
+    if #errors > 0 then
+        return nil, table.concat(errors, "\n")
+    end
+    local generate = tl.generate or tl.pretty_print_ast
+    return generate(result.ast)
+"#;
+
+/// The Teal compiler, running in a Lua state of its own.
+pub(crate) struct TealCompiler {
+    lua: Mutex<(Lua, Table, Function)>,
+}
+
+impl TealCompiler {
+    /// Loads the compiler from the source of the `tl` module.
+    pub(crate) fn new(source: &[u8]) -> mlua::Result<Self> {
+        let lua = Lua::new();
+        let tl: Table = lua.load(source).set_name("=tl").eval()?;
+        let compile = lua.load(COMPILE).set_name("=[plux teal]").into_function()?;
+        Ok(Self {
+            lua: Mutex::new((lua, tl, compile)),
+        })
+    }
+}
+
+impl SourceTransform for TealCompiler {
+    /// Type checks the Teal source `src` of the file `path` and returns its
+    /// Lua code.
+    ///
+    /// Syntax and type errors fail with a [`PluginError::SourceError`]
+    /// listing them as `path:line:column: message`.
+    fn transform(&self, path: &str, src: &str) -> Result<String, PluginError> {
+        let lua = self.lua.lock_unpoisoned();
+        let (_, tl, compile) = &*lua;
+        let (code, errors): (Option<String>, Option<String>) = compile
+            .call((tl, src, path))
+            .map_err(|e| PluginError::SourceError(format!("cannot compile {path}: {e}")))?;
+        match code {
+            Some(code) => Ok(code),
+            None => Err(PluginError::SourceError(format!(
+                "type errors in {path}:\n{}",
+                errors.unwrap_or_default()
+            ))),
+        }
+    }
+}
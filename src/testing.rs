@@ -0,0 +1,443 @@
+//! Helpers to test Lua plugins and the hosts loading them.
+//!
+//! [`PluginFixture`] writes a plugin from inline sources to a temporary
+//! directory, and [`TestHost`] loads fixtures through a real [`LuaManager`]
+//! and plux loader and calls their functions:
+//!
+//! ```
+//! use plux_lua_manager::testing::{PluginFixture, TestHost};
+//! use plux_rs::variable::Variable;
+//!
+//! let mut host = TestHost::new();
+//! let bundle = host
+//!     .load(PluginFixture::new("adder").main(
+//!         r#"return {
+//!             { name = "add", inputs = { "a: i64", "b: i64" }, output = "i64",
+//!               func = function(a, b) return a + b end },
+//!         }"#,
+//!     ))
+//!     .unwrap();
+//!
+//! host.assert_call(&bundle, "add", &[Variable::I64(1), Variable::I64(2)], Some(Variable::I64(3)));
+//! ```
+//!
+//! [`MockApi`] registers the `api` and `deps` globals of a plugin in a bare
+//! Lua state, with canned dependencies instead of plugins loaded by plux:
+//!
+//! ```
+//! use std::sync::Arc;
+//!
+//! use plux_lua_manager::testing::MockApi;
+//! use plux_rs::variable::Variable;
+//!
+//! let api = Arc::new(MockApi::new().returns("math", "1.0.0", "pi", Some(Variable::F64(3.0))));
+//! let lua = mlua::Lua::new();
+//! api.register(&lua, r#"
+//!     name = "circle"
+//!     description = "Circle areas"
+//!     author = "Plux"
+//!     depends = { math = "^1.0" }
+//! "#).unwrap();
+//!
+//! let area: f64 = lua.load("return deps.math.pi() * 2 ^ 2").eval().unwrap();
+//! assert_eq!(area, 12.0);
+//! assert_eq!(api.calls()[0].function, "pi");
+//! ```
+
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use mlua::Lua;
+use plux_rs::{
+    Bundle, Loader, StdInfo, function::FunctionOutput, utils::CallFunctionDependError,
+    variable::Variable,
+};
+use semver::Version;
+
+use crate::{
+    config::Config,
+    error::{ConfigError, ManagerError, TestingError},
+    lua::api::{self, DependencyApi},
+    manager::LuaManager,
+    sync::MutexExt,
+};
+
+/// A plugin written from inline sources, see the [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct PluginFixture {
+    id: String,
+    version: String,
+    config: Option<String>,
+    files: Vec<(String, String)>,
+}
+
+impl PluginFixture {
+    /// Creates the fixture of the plugin `id` at version 1.0.0, with a minimal
+    /// config and an empty entry script.
+    pub fn new(id: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            version: "1.0.0".to_string(),
+            config: None,
+            files: vec![],
+        }
+    }
+
+    /// Sets the version of the plugin.
+    pub fn version(mut self, version: &str) -> Self {
+        self.version = version.to_string();
+        self
+    }
+
+    /// Replaces the generated config with `toml`.
+    pub fn config(mut self, toml: &str) -> Self {
+        self.config = Some(toml.to_string());
+        self
+    }
+
+    /// Sets the source of the entry script `main.lua`.
+    pub fn main(self, lua: &str) -> Self {
+        self.file("main.lua", lua)
+    }
+
+    /// Adds the file `path`, relative to the plugin directory.
+    pub fn file(mut self, path: &str, content: &str) -> Self {
+        self.files.retain(|(file, _)| file != path);
+        self.files.push((path.to_string(), content.to_string()));
+        self
+    }
+
+    /// Writes the plugin to a new temporary directory, removed when the
+    /// returned [`PluginDir`] is dropped.
+    pub fn create(&self) -> io::Result<PluginDir> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+        let root = std::env::temp_dir().join(format!(
+            "plux-lua-testing-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let path = root.join(format!("{}-v{}.lua", self.id, self.version));
+        let dir = PluginDir { root, path };
+
+        fs::create_dir_all(&dir.path)?;
+        let config = self.config.clone().unwrap_or_else(|| {
+            format!(
+                "name = \"{}\"\ndescription = \"Test plugin\"\nauthor = \"Plux\"\n",
+                self.id
+            )
+        });
+        fs::write(dir.path.join("config.toml"), config)?;
+        if !self.files.iter().any(|(file, _)| file == "main.lua") {
+            fs::write(dir.path.join("main.lua"), "return {}")?;
+        }
+        for (file, content) in self.files.iter() {
+            let file = dir.path.join(file);
+            if let Some(parent) = file.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(file, content)?;
+        }
+        Ok(dir)
+    }
+}
+
+/// The temporary directory of a [`PluginFixture`], removed on drop.
+#[derive(Debug)]
+pub struct PluginDir {
+    root: PathBuf,
+    path: PathBuf,
+}
+
+impl PluginDir {
+    /// Returns the path of the plugin directory.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for PluginDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+/// A plux loader with a [`LuaManager`], stopped on drop.
+pub struct TestHost {
+    manager: LuaManager,
+    // plux keeps pointers to the loader, boxed so that the host can move
+    loader: Box<Loader<'static, FunctionOutput, StdInfo>>,
+    dirs: Vec<PluginDir>,
+}
+
+impl TestHost {
+    /// Creates a host with a default [`LuaManager`].
+    pub fn new() -> Self {
+        Self::with_manager(LuaManager::new())
+    }
+
+    /// Creates a host with `manager`, configured beforehand.
+    pub fn with_manager(manager: LuaManager) -> Self {
+        let mut loader = Box::new(Loader::new());
+        let registered = manager.clone();
+        loader
+            .context(move |mut ctx| ctx.register_manager(registered))
+            .expect("registering the manager with a new loader cannot fail");
+        Self {
+            manager,
+            loader,
+            dirs: vec![],
+        }
+    }
+
+    /// Returns the manager of the host.
+    pub fn manager(&self) -> &LuaManager {
+        &self.manager
+    }
+
+    /// Returns the loader of the host.
+    pub fn loader(&mut self) -> &mut Loader<'static, FunctionOutput, StdInfo> {
+        &mut self.loader
+    }
+
+    /// Writes `fixture` to a temporary directory and loads it, which is
+    /// removed when the host is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plugin cannot be written or loaded.
+    pub fn load(&mut self, fixture: PluginFixture) -> Result<Bundle, TestingError> {
+        let dir = fixture.create()?;
+        let bundle = self.load_path(dir.path())?;
+        self.dirs.push(dir);
+        Ok(bundle)
+    }
+
+    /// Loads the plugin at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plugin cannot be loaded.
+    pub fn load_path<P: AsRef<Path>>(&mut self, path: P) -> Result<Bundle, TestingError> {
+        let path = path.as_ref().to_string_lossy();
+        self.loader
+            .load_plugin_now(&path)
+            .map_err(|(register, load)| {
+                let error = register
+                    .map(|e| e.to_string())
+                    .or_else(|| load.map(|e| e.to_string()));
+                TestingError::Load(path.to_string(), error.unwrap_or_default())
+            })
+    }
+
+    /// Calls the function `name` of the plugin `bundle` through plux.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plugin is not loaded, it does not export the
+    /// function, or the call fails.
+    pub fn call(
+        &self,
+        bundle: &Bundle,
+        name: &str,
+        args: &[Variable],
+    ) -> Result<Option<Variable>, TestingError> {
+        let plugin = self
+            .loader
+            .get_plugin_by_bundle(bundle)
+            .ok_or_else(|| TestingError::NotLoaded(bundle.to_string()))?;
+        let output = plugin
+            .call_function(name, args)
+            .map_err(|e| TestingError::Call(name.to_string(), e.to_string()))?;
+        output.map_err(|e| TestingError::Call(name.to_string(), e.to_string()))
+    }
+
+    /// Calls the function `name` of the plugin `bundle` and asserts that it
+    /// returns `expected`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the call fails or returns another value.
+    #[track_caller]
+    pub fn assert_call(
+        &self,
+        bundle: &Bundle,
+        name: &str,
+        args: &[Variable],
+        expected: Option<Variable>,
+    ) {
+        match self.call(bundle, name, args) {
+            Ok(output) => assert_eq!(output, expected, "unexpected output of `{name}`"),
+            Err(e) => panic!("{e}"),
+        }
+    }
+
+    /// Calls the function `name` of the plugin `bundle` and asserts that it
+    /// fails with an error containing `message`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the call succeeds or fails with another error.
+    #[track_caller]
+    pub fn assert_call_fails(&self, bundle: &Bundle, name: &str, args: &[Variable], message: &str) {
+        match self.call(bundle, name, args) {
+            Ok(output) => panic!("`{name}` returned {output:?} instead of failing"),
+            Err(e) => {
+                let e = e.to_string();
+                assert!(e.contains(message), "unexpected error of `{name}`: {e}");
+            }
+        }
+    }
+}
+
+impl Default for TestHost {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for TestHost {
+    fn drop(&mut self) {
+        // Stop the plugins before their directories are removed
+        let _ = self.loader.stop();
+    }
+}
+
+/// A canned function of a [`MockApi`].
+type MockFunction = Arc<dyn Fn(&[Variable]) -> FunctionOutput + Send + Sync>;
+
+/// A call received by a [`MockApi`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockCall {
+    /// The id of the called plugin.
+    pub id: String,
+    /// The version of the called plugin.
+    pub version: Version,
+    /// The name of the called function.
+    pub function: String,
+    /// The arguments of the call.
+    pub args: Vec<Variable>,
+}
+
+/// Canned dependencies of a plugin, see the [module documentation](self).
+///
+/// Every plugin of the mock is loaded and resolved as a dependency, and
+/// every call is recorded, including calls to functions the mock does not
+/// define, which fail.
+#[derive(Default)]
+pub struct MockApi {
+    plugins: HashMap<(String, Version), HashMap<String, MockFunction>>,
+    calls: Mutex<Vec<MockCall>>,
+}
+
+impl MockApi {
+    /// Creates a mock without plugins.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the plugin `id` v`version`, without functions.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `version` is not a valid semver version.
+    #[track_caller]
+    pub fn plugin(mut self, id: &str, version: &str) -> Self {
+        let version = Version::parse(version).expect("invalid plugin version");
+        self.plugins.entry((id.to_string(), version)).or_default();
+        self
+    }
+
+    /// Adds the function `name` of the plugin `id` v`version`, implemented by
+    /// `f`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `version` is not a valid semver version.
+    #[track_caller]
+    pub fn function<F>(mut self, id: &str, version: &str, name: &str, f: F) -> Self
+    where
+        F: Fn(&[Variable]) -> FunctionOutput + Send + Sync + 'static,
+    {
+        let version = Version::parse(version).expect("invalid plugin version");
+        self.plugins
+            .entry((id.to_string(), version))
+            .or_default()
+            .insert(name.to_string(), Arc::new(f));
+        self
+    }
+
+    /// Adds the function `name` of the plugin `id` v`version`, returning
+    /// `output` whatever its arguments.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `version` is not a valid semver version.
+    #[track_caller]
+    pub fn returns(self, id: &str, version: &str, name: &str, output: Option<Variable>) -> Self {
+        self.function(id, version, name, move |_| Ok(output.clone()))
+    }
+
+    /// Returns the calls received so far, in order.
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.calls.lock_unpoisoned().clone()
+    }
+
+    /// Registers the `api` and `deps` globals of the plugin whose
+    /// `config.toml` is `config` in `lua`, calling into the mock.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `config` is invalid or the globals cannot be set.
+    pub fn register(self: &Arc<Self>, lua: &Lua, config: &str) -> Result<(), ManagerError> {
+        let config: Config = toml::from_str(config).map_err(ConfigError::InvalidFormat)?;
+        let dependencies: Arc<dyn DependencyApi> = self.clone();
+        api::register_api(lua, &dependencies, &config, None)
+    }
+}
+
+impl DependencyApi for MockApi {
+    fn loaded_versions(&self, id: &str) -> Vec<Version> {
+        self.plugins
+            .keys()
+            .filter(|(plugin, _)| plugin == id)
+            .map(|(_, version)| version.clone())
+            .collect()
+    }
+
+    fn is_resolved_depend(&self, id: &str, version: &Version) -> bool {
+        self.plugins
+            .contains_key(&(id.to_string(), version.clone()))
+    }
+
+    fn call_function(
+        &self,
+        id: &str,
+        version: &Version,
+        name: &str,
+        args: &[Variable],
+    ) -> Result<FunctionOutput, CallFunctionDependError> {
+        self.calls.lock_unpoisoned().push(MockCall {
+            id: id.to_string(),
+            version: version.clone(),
+            function: name.to_string(),
+            args: args.to_vec(),
+        });
+
+        let functions = self
+            .plugins
+            .get(&(id.to_string(), version.clone()))
+            .ok_or(CallFunctionDependError::DependNotFound)?;
+        Ok(match functions.get(name) {
+            Some(f) => f(args),
+            None => Err(format!("function `{name}` is not mocked").into()),
+        })
+    }
+}
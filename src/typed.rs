@@ -0,0 +1,815 @@
+//! Typed handles to plugin functions.
+//!
+//! Plugins may declare the types of their exported functions, inputs as
+//! `"name: type"` or `{ name = "name", type = "type" }` and the output as
+//! `output = "type"`:
+//!
+//! ```lua
+//! return {
+//!     { name = "format_row", inputs = { "id: i64", { name = "name", type = "string" } }, output = "string", func = format_row },
+//! }
+//! ```
+//!
+//! Types are `let` (any, the default), `i8`, `i16`, `i32`, `i64`, `u8`, `u16`,
+//! `u32`, `u64`, `f32`, `f64`, `bool`, `char`, `string` and `list`. Calls
+//! through plux fail with [`PluginError::TypeMismatch`] when an argument or the
+//! result does not have its declared type, and numbers are converted to the
+//! declared type when they fit. The host may also get a [`TypedFn`] from
+//! [`crate::LuaManager::typed_fn`], which checks the declaration once and
+//! converts native Rust values on every call.
+//!
+//! An input declared as a table may be `optional`, or have a `default` value
+//! which makes it optional, e.g. `{ name = "retries", type = "i32", default = 3 }`.
+//! Optional arguments that callers leave out or pass as `nil` are replaced by
+//! their default, `nil` without one, and leaving out any other argument fails
+//! with [`PluginError::MissingArgument`].
+//!
+//! The last input may be variadic, `"...name"` or `{ name = "name", variadic = true }`,
+//! to receive all the remaining arguments of a call as a table. Its type, if
+//! declared, applies to each of them, and hosts see it as a `list` input: a
+//! single list passed in its position is taken as the remaining arguments.
+//!
+//! The declared signatures of a plugin's functions are listed by
+//! [`crate::LuaManager::functions`], and by `api.list_functions(id, version)`
+//! in Lua.
+
+use std::{marker::PhantomData, sync::Arc};
+
+use mlua::{FromLua, Lua, Value};
+use plux_rs::{
+    function::{Arg, Function, FunctionOutput},
+    variable::{Variable, VariableType},
+};
+
+use serde::Serialize;
+
+use crate::{
+    config::{FunctionDoc, InputDeclaration},
+    error::PluginError,
+    lua::conversion::lua_to_plux,
+};
+
+/// Parses a type name of an export declaration.
+pub(crate) fn parse_type(name: &str) -> Option<VariableType> {
+    Some(match name {
+        "let" => VariableType::Let,
+        "i8" => VariableType::I8,
+        "i16" => VariableType::I16,
+        "i32" => VariableType::I32,
+        "i64" => VariableType::I64,
+        "u8" => VariableType::U8,
+        "u16" => VariableType::U16,
+        "u32" => VariableType::U32,
+        "u64" => VariableType::U64,
+        "f32" => VariableType::F32,
+        "f64" => VariableType::F64,
+        "bool" => VariableType::Bool,
+        "char" => VariableType::Char,
+        "string" => VariableType::String,
+        "list" => VariableType::List,
+        _ => return None,
+    })
+}
+
+/// A declared input of a plugin function.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Input {
+    /// The argument, with the type of each value of a variadic one.
+    pub(crate) arg: Arg,
+    /// The value of a left out argument, `None` if the argument is required.
+    pub(crate) default: Option<Variable>,
+    /// Whether the argument collects the remaining arguments of a call.
+    pub(crate) variadic: bool,
+}
+
+impl Input {
+    /// Returns the argument as declared to plux, a `list` if variadic.
+    pub(crate) fn declared(&self) -> Arg {
+        match self.variadic {
+            true => Arg::new(self.arg.name.clone(), VariableType::List),
+            false => self.arg.clone(),
+        }
+    }
+}
+
+/// Returns the name of `ty` in export declarations, the inverse of
+/// [`parse_type`].
+pub(crate) fn type_name(ty: VariableType) -> String {
+    ty.to_string().to_lowercase()
+}
+
+/// The signature and documentation of a function exported by a plugin, see
+/// [`crate::LuaManager::functions`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FunctionInfo {
+    /// The name of the function.
+    pub name: String,
+    /// The declared arguments, in order.
+    pub inputs: Vec<InputInfo>,
+    /// The declared type of the result, `let` if it may be any value.
+    pub output: String,
+    /// The documentation of the function.
+    pub doc: FunctionDoc,
+}
+
+/// A declared argument of an exported function, see [`FunctionInfo`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InputInfo {
+    /// The name of the argument.
+    pub name: String,
+    /// The declared type, of each value if the argument is variadic.
+    #[serde(rename = "type")]
+    pub ty: String,
+    /// Whether callers may leave the argument out.
+    pub optional: bool,
+    /// Whether the argument collects the remaining arguments of a call.
+    pub variadic: bool,
+}
+
+impl From<&Input> for InputInfo {
+    fn from(input: &Input) -> Self {
+        Self {
+            name: input.arg.name.clone(),
+            ty: type_name(input.arg.ty),
+            optional: input.default.is_some(),
+            variadic: input.variadic,
+        }
+    }
+}
+
+/// Parses an input declaration, `"name"`, `"name: type"`, `"...name"` or
+/// `{ name = "name", type = "type", optional = true, default = value, variadic = true }`.
+///
+/// Returns `None` if the type is unknown, the default is not of that type or
+/// a variadic input has a default.
+pub(crate) fn parse_input(declaration: &InputDeclaration) -> Option<Input> {
+    match declaration {
+        InputDeclaration::Short(declaration) => {
+            let declaration = declaration.trim();
+            let (declaration, variadic) = match declaration.strip_prefix("...") {
+                Some(declaration) => (declaration, true),
+                None => (declaration, false),
+            };
+            let arg = match declaration.split_once(':') {
+                None => Arg::new(declaration.trim(), VariableType::Let),
+                Some((name, ty)) => Arg::new(name.trim(), parse_type(ty.trim())?),
+            };
+            Some(Input {
+                arg,
+                default: None,
+                variadic,
+            })
+        }
+        InputDeclaration::Full {
+            name,
+            ty,
+            optional,
+            default,
+            variadic,
+        } => {
+            let ty = ty
+                .as_deref()
+                .map_or(Some(VariableType::Let), |ty| parse_type(ty.trim()))?;
+            let default = match default {
+                Some(_) if *variadic => return None,
+                Some(default) => Some(coerce(default, ty)?),
+                None => optional.then_some(Variable::Null),
+            };
+            Some(Input {
+                arg: Arg::new(name.trim(), ty),
+                default,
+                variadic: *variadic,
+            })
+        }
+    }
+}
+
+impl FromLua for InputDeclaration {
+    fn from_lua(value: Value, lua: &Lua) -> mlua::Result<Self> {
+        match value {
+            Value::Table(table) => Ok(Self::Full {
+                name: table.get("name")?,
+                ty: table.get("type")?,
+                optional: table.get::<Option<bool>>("optional")?.unwrap_or_default(),
+                default: match table.get::<Value>("default")? {
+                    Value::Nil => None,
+                    default => Some(lua_to_plux(&default)?),
+                },
+                variadic: table.get::<Option<bool>>("variadic")?.unwrap_or_default(),
+            }),
+            value => String::from_lua(value, lua).map(Self::Short),
+        }
+    }
+}
+
+/// Converts `var` to the declared type `ty`, `None` if it is not a value of
+/// that type.
+///
+/// Integers convert to any integer type they fit in and to floats, floats
+/// with an integral value to integers, and one-character strings to `char`.
+pub(crate) fn coerce(var: &Variable, ty: VariableType) -> Option<Variable> {
+    fn int<T: TypedValue>(var: &Variable) -> Option<Variable> {
+        let var = match *var {
+            Variable::F32(x) => integral(x as f64)?,
+            Variable::F64(x) => integral(x)?,
+            ref var => var.clone(),
+        };
+        T::from_variable(var).map(T::into_variable)
+    }
+
+    fn integral(x: f64) -> Option<Variable> {
+        (x.fract() == 0.0 && x >= i64::MIN as f64 && x < i64::MAX as f64)
+            .then_some(Variable::I64(x as i64))
+    }
+
+    fn float(var: &Variable) -> Option<f64> {
+        Some(match *var {
+            Variable::I8(x) => x as f64,
+            Variable::I16(x) => x as f64,
+            Variable::I32(x) => x as f64,
+            Variable::I64(x) => x as f64,
+            Variable::U8(x) => x as f64,
+            Variable::U16(x) => x as f64,
+            Variable::U32(x) => x as f64,
+            Variable::U64(x) => x as f64,
+            Variable::F32(x) => x as f64,
+            Variable::F64(x) => x,
+            _ => return None,
+        })
+    }
+
+    match (ty, var) {
+        (VariableType::Let, var) => Some(var.clone()),
+        (VariableType::I8, var) => int::<i8>(var),
+        (VariableType::I16, var) => int::<i16>(var),
+        (VariableType::I32, var) => int::<i32>(var),
+        (VariableType::I64, var) => int::<i64>(var),
+        (VariableType::U8, var) => int::<u8>(var),
+        (VariableType::U16, var) => int::<u16>(var),
+        (VariableType::U32, var) => int::<u32>(var),
+        (VariableType::U64, var) => int::<u64>(var),
+        (VariableType::F32, var) => float(var).map(|x| Variable::F32(x as f32)),
+        (VariableType::F64, var) => float(var).map(Variable::F64),
+        (VariableType::Bool, Variable::Bool(_))
+        | (VariableType::Char, Variable::Char(_))
+        | (VariableType::String, Variable::String(_))
+        | (VariableType::List, Variable::List(_)) => Some(var.clone()),
+        (VariableType::Char, Variable::String(s)) => {
+            let mut chars = s.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Some(Variable::Char(c)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Returns the name of the type of `var`, as displayed for declared types.
+pub(crate) fn type_of(var: &Variable) -> &'static str {
+    match var {
+        Variable::Null => "Null",
+        Variable::I8(_) => "I8",
+        Variable::I16(_) => "I16",
+        Variable::I32(_) => "I32",
+        Variable::I64(_) => "I64",
+        Variable::U8(_) => "U8",
+        Variable::U16(_) => "U16",
+        Variable::U32(_) => "U32",
+        Variable::U64(_) => "U64",
+        Variable::F32(_) => "F32",
+        Variable::F64(_) => "F64",
+        Variable::Bool(_) => "Bool",
+        Variable::Char(_) => "Char",
+        Variable::String(_) => "String",
+        Variable::List(_) => "List",
+    }
+}
+
+/// Converts the arguments of a call to `function` to the declared types of
+/// `inputs`, filling in the defaults of left out optional arguments and
+/// collecting the remaining ones in a list for a variadic input.
+///
+/// Arguments beyond the declared ones are passed as they are.
+pub(crate) fn check_args(
+    function: &str,
+    inputs: &[Input],
+    args: &[Variable],
+) -> Result<Vec<Variable>, PluginError> {
+    let mismatch = |input: &Arg, value: String, arg: &Variable| PluginError::TypeMismatch {
+        function: function.to_string(),
+        value,
+        expected: input.ty.to_string(),
+        found: type_of(arg).to_string(),
+    };
+
+    let mut checked = Vec::with_capacity(args.len().max(inputs.len()));
+    for (index, input) in inputs.iter().enumerate() {
+        let Input {
+            arg: declared,
+            default,
+            variadic,
+        } = input;
+        if *variadic {
+            let rest = collect_varargs(&args[index.min(args.len())..]);
+            let rest = rest
+                .iter()
+                .enumerate()
+                .map(|(position, arg)| {
+                    coerce(arg, declared.ty).ok_or_else(|| {
+                        let value = format!("argument `{}[{}]`", declared.name, position + 1);
+                        mismatch(declared, value, arg)
+                    })
+                })
+                .collect::<Result<_, _>>()?;
+            checked.push(Variable::List(rest));
+            return Ok(checked);
+        }
+
+        let arg = match (args.get(index), default) {
+            (None | Some(Variable::Null), Some(default)) => default.clone(),
+            (Some(arg), _) => coerce(arg, declared.ty)
+                .ok_or_else(|| mismatch(declared, format!("argument `{}`", declared.name), arg))?,
+            (None, None) => {
+                return Err(PluginError::MissingArgument(
+                    function.to_string(),
+                    declared.name.clone(),
+                ));
+            }
+        };
+        checked.push(arg);
+    }
+    checked.extend(args.iter().skip(inputs.len()).cloned());
+    Ok(checked)
+}
+
+/// Returns the values of the variadic argument made of `rest`, the
+/// arguments from its position on.
+///
+/// A single list is taken as the values themselves, so that callers may pass
+/// them either one by one or as a list.
+pub(crate) fn collect_varargs(rest: &[Variable]) -> Vec<Variable> {
+    match rest {
+        [Variable::List(values)] => values.clone(),
+        rest => rest.to_vec(),
+    }
+}
+
+/// Converts the result of a call to `function` to the declared type
+/// `output`.
+///
+/// A function may return `nil` whatever its declared output.
+pub(crate) fn check_output(
+    function: &str,
+    output: VariableType,
+    result: Option<Variable>,
+) -> Result<Option<Variable>, PluginError> {
+    match result {
+        None | Some(Variable::Null) => Ok(result),
+        Some(var) => match coerce(&var, output) {
+            Some(var) => Ok(Some(var)),
+            None => Err(PluginError::TypeMismatch {
+                function: function.to_string(),
+                value: "the result".to_string(),
+                expected: output.to_string(),
+                found: type_of(&var).to_string(),
+            }),
+        },
+    }
+}
+
+/// A native Rust type that crosses the plugin boundary as a [`Variable`].
+pub trait TypedValue: Sized {
+    /// The declared type matching `Self`.
+    const TYPE: VariableType;
+
+    /// Converts the value to a variable.
+    fn into_variable(self) -> Variable;
+
+    /// Converts a variable back, `None` if it does not represent a `Self`.
+    fn from_variable(var: Variable) -> Option<Self>;
+}
+
+macro_rules! impl_typed_int {
+    ($($ty:ty => $var_type:ident),*) => {$(
+        impl TypedValue for $ty {
+            const TYPE: VariableType = VariableType::$var_type;
+
+            fn into_variable(self) -> Variable {
+                self.into()
+            }
+
+            // Lua has a single integer type, accept any integer that fits
+            fn from_variable(var: Variable) -> Option<Self> {
+                match var {
+                    Variable::I8(x) => x.try_into().ok(),
+                    Variable::I16(x) => x.try_into().ok(),
+                    Variable::I32(x) => x.try_into().ok(),
+                    Variable::I64(x) => x.try_into().ok(),
+                    Variable::U8(x) => x.try_into().ok(),
+                    Variable::U16(x) => x.try_into().ok(),
+                    Variable::U32(x) => x.try_into().ok(),
+                    Variable::U64(x) => x.try_into().ok(),
+                    _ => None,
+                }
+            }
+        }
+    )*};
+}
+
+impl_typed_int!(
+    i8 => I8, i16 => I16, i32 => I32, i64 => I64,
+    u8 => U8, u16 => U16, u32 => U32, u64 => U64
+);
+
+macro_rules! impl_typed_float {
+    ($($ty:ty => $var_type:ident),*) => {$(
+        impl TypedValue for $ty {
+            const TYPE: VariableType = VariableType::$var_type;
+
+            fn into_variable(self) -> Variable {
+                self.into()
+            }
+
+            fn from_variable(var: Variable) -> Option<Self> {
+                match var {
+                    Variable::F32(x) => Some(x as $ty),
+                    Variable::F64(x) => Some(x as $ty),
+                    Variable::I32(x) => Some(x as $ty),
+                    Variable::I64(x) => Some(x as $ty),
+                    _ => None,
+                }
+            }
+        }
+    )*};
+}
+
+impl_typed_float!(f32 => F32, f64 => F64);
+
+macro_rules! impl_typed_exact {
+    ($($ty:ty => $var_type:ident),*) => {$(
+        impl TypedValue for $ty {
+            const TYPE: VariableType = VariableType::$var_type;
+
+            fn into_variable(self) -> Variable {
+                self.into()
+            }
+
+            fn from_variable(var: Variable) -> Option<Self> {
+                match var {
+                    Variable::$var_type(x) => Some(x),
+                    _ => None,
+                }
+            }
+        }
+    )*};
+}
+
+impl_typed_exact!(bool => Bool, char => Char, String => String);
+
+impl<T: TypedValue> TypedValue for Vec<T> {
+    const TYPE: VariableType = VariableType::List;
+
+    fn into_variable(self) -> Variable {
+        Variable::List(self.into_iter().map(T::into_variable).collect())
+    }
+
+    fn from_variable(var: Variable) -> Option<Self> {
+        match var {
+            Variable::List(list) => list.into_iter().map(T::from_variable).collect(),
+            _ => None,
+        }
+    }
+}
+
+/// The arguments of a typed call, implemented for tuples of [`TypedValue`].
+pub trait TypedArgs {
+    /// The declared types of the arguments, in order.
+    fn types() -> Vec<VariableType>;
+
+    /// Converts the arguments to variables.
+    fn into_variables(self) -> Vec<Variable>;
+}
+
+macro_rules! impl_typed_args {
+    ($($name:ident),*) => {
+        impl<$($name: TypedValue),*> TypedArgs for ($($name,)*) {
+            fn types() -> Vec<VariableType> {
+                vec![$($name::TYPE),*]
+            }
+
+            #[allow(non_snake_case)]
+            fn into_variables(self) -> Vec<Variable> {
+                let ($($name,)*) = self;
+                vec![$($name.into_variable()),*]
+            }
+        }
+    };
+}
+
+impl_typed_args!();
+impl_typed_args!(A);
+impl_typed_args!(A, B);
+impl_typed_args!(A, B, C);
+impl_typed_args!(A, B, C, D);
+impl_typed_args!(A, B, C, D, E);
+impl_typed_args!(A, B, C, D, E, F);
+
+/// The result of a typed call.
+///
+/// Implemented for [`TypedValue`] types, `Option` of them for functions that
+/// may return `nil`, and `()` to ignore the output.
+pub trait TypedOutput: Sized {
+    /// The declared output type matching `Self`, `None` to accept any.
+    const TYPE: Option<VariableType>;
+
+    /// Converts the output of a call, `None` if it does not represent a `Self`.
+    fn from_output(output: Option<Variable>) -> Option<Self>;
+}
+
+macro_rules! impl_typed_output {
+    ($($ty:ty),*) => {$(
+        impl TypedOutput for $ty {
+            const TYPE: Option<VariableType> = Some(<$ty as TypedValue>::TYPE);
+
+            fn from_output(output: Option<Variable>) -> Option<Self> {
+                output.and_then(<$ty as TypedValue>::from_variable)
+            }
+        }
+    )*};
+}
+
+impl_typed_output!(
+    i8, i16, i32, i64, u8, u16, u32, u64, f32, f64, bool, char, String
+);
+
+impl<T: TypedValue> TypedOutput for Vec<T> {
+    const TYPE: Option<VariableType> = Some(VariableType::List);
+
+    fn from_output(output: Option<Variable>) -> Option<Self> {
+        output.and_then(Self::from_variable)
+    }
+}
+
+impl<T: TypedValue> TypedOutput for Option<T> {
+    const TYPE: Option<VariableType> = Some(T::TYPE);
+
+    fn from_output(output: Option<Variable>) -> Option<Self> {
+        match output {
+            None | Some(Variable::Null) => Some(None),
+            Some(var) => T::from_variable(var).map(Some),
+        }
+    }
+}
+
+impl TypedOutput for () {
+    const TYPE: Option<VariableType> = None;
+
+    fn from_output(_: Option<Variable>) -> Option<Self> {
+        Some(())
+    }
+}
+
+/// A plugin function called with native Rust types.
+///
+/// Created by [`crate::LuaManager::typed_fn`].
+pub struct TypedFn<A, R> {
+    function: Arc<dyn Function<Output = FunctionOutput>>,
+    _signature: PhantomData<fn(A) -> R>,
+}
+
+impl<A: TypedArgs, R: TypedOutput> TypedFn<A, R> {
+    /// Wraps `function` after checking its declaration against `A` and `R`.
+    ///
+    /// Inputs and outputs declared as `let` accept any type.
+    pub(crate) fn new(
+        function: Arc<dyn Function<Output = FunctionOutput>>,
+    ) -> Result<Self, PluginError> {
+        let mismatch = |message: String| PluginError::SignatureMismatch(function.name(), message);
+
+        let inputs = function.inputs();
+        let types = A::types();
+        if inputs.len() != types.len() {
+            return Err(mismatch(format!(
+                "it takes {} arguments, got {}",
+                inputs.len(),
+                types.len()
+            )));
+        }
+        for (index, (input, ty)) in inputs.iter().zip(types).enumerate() {
+            if input.ty != VariableType::Let && input.ty != ty {
+                return Err(mismatch(format!(
+                    "argument #{} `{}` is declared as {}, got {}",
+                    index + 1,
+                    input.name,
+                    input.ty,
+                    ty
+                )));
+            }
+        }
+
+        let declared = function
+            .output()
+            .map_or(VariableType::Let, |output| output.ty);
+        if let Some(ty) = R::TYPE
+            && declared != VariableType::Let
+            && declared != ty
+        {
+            return Err(mismatch(format!(
+                "the output is declared as {declared}, got {ty}"
+            )));
+        }
+
+        Ok(Self {
+            function,
+            _signature: PhantomData,
+        })
+    }
+
+    /// Calls the function.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the call, or [`PluginError::SignatureMismatch`]
+    /// if the returned value does not convert to `R`.
+    pub fn call(&self, args: A) -> Result<R, Box<dyn std::error::Error + Send + Sync>> {
+        let output = self.function.call(&args.into_variables())?;
+        let description = format!("{output:?}");
+        R::from_output(output).ok_or_else(|| {
+            PluginError::SignatureMismatch(
+                self.function.name(),
+                format!("it returned {description}"),
+            )
+            .into()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_input() {
+        let short = |declaration: &str| InputDeclaration::Short(declaration.to_string());
+        let full = |ty: &str, optional, default| InputDeclaration::Full {
+            name: "id".to_string(),
+            ty: Some(ty.to_string()),
+            optional,
+            default,
+            variadic: false,
+        };
+
+        let input = parse_input(&short("id: i64")).unwrap();
+        assert_eq!(input.arg.name, "id");
+        assert_eq!(input.arg.ty, VariableType::I64);
+        assert_eq!(input.default, None);
+
+        let input = parse_input(&short("name")).unwrap();
+        assert_eq!(input.arg.name, "name");
+        assert_eq!(input.arg.ty, VariableType::Let);
+
+        assert!(parse_input(&short("id: integer")).is_none());
+
+        let input = parse_input(&full("u8", false, None)).unwrap();
+        assert_eq!(input.arg.ty, VariableType::U8);
+        assert_eq!(input.default, None);
+
+        let input = parse_input(&full("u8", true, None)).unwrap();
+        assert_eq!(input.default, Some(Variable::Null));
+
+        let input = parse_input(&full("u8", false, Some(Variable::I64(3)))).unwrap();
+        assert_eq!(input.default, Some(Variable::U8(3)));
+
+        assert!(parse_input(&full("u8", true, Some(Variable::Bool(true)))).is_none());
+
+        let input = parse_input(&short("...rest: i64")).unwrap();
+        assert_eq!(input.arg.name, "rest");
+        assert_eq!(input.arg.ty, VariableType::I64);
+        assert!(input.variadic);
+        assert_eq!(input.declared().ty, VariableType::List);
+    }
+
+    #[test]
+    fn test_check_args() {
+        let inputs = [
+            Input {
+                arg: Arg::new("id", VariableType::I64),
+                default: None,
+                variadic: false,
+            },
+            Input {
+                arg: Arg::new("retries", VariableType::U8),
+                default: Some(Variable::U8(3)),
+                variadic: false,
+            },
+            Input {
+                arg: Arg::new("label", VariableType::String),
+                default: Some(Variable::Null),
+                variadic: false,
+            },
+        ];
+
+        assert_eq!(
+            check_args("f", &inputs, &[Variable::I32(1)]).unwrap(),
+            [Variable::I64(1), Variable::U8(3), Variable::Null]
+        );
+        assert_eq!(
+            check_args(
+                "f",
+                &inputs,
+                &[Variable::I32(1), Variable::Null, Variable::Null]
+            )
+            .unwrap(),
+            [Variable::I64(1), Variable::U8(3), Variable::Null]
+        );
+        assert_eq!(
+            check_args("f", &inputs, &[Variable::I32(1), Variable::I32(5)]).unwrap(),
+            [Variable::I64(1), Variable::U8(5), Variable::Null]
+        );
+        assert!(matches!(
+            check_args("f", &inputs, &[]),
+            Err(PluginError::MissingArgument(_, name)) if name == "id"
+        ));
+
+        let inputs = [
+            Input {
+                arg: Arg::new("format", VariableType::String),
+                default: None,
+                variadic: false,
+            },
+            Input {
+                arg: Arg::new("values", VariableType::I64),
+                default: None,
+                variadic: true,
+            },
+        ];
+        let format = || Variable::String("%d".to_string());
+        assert_eq!(
+            check_args("f", &inputs, &[format()]).unwrap(),
+            [format(), Variable::List(vec![])]
+        );
+        assert_eq!(
+            check_args("f", &inputs, &[format(), Variable::I32(1), Variable::U8(2)]).unwrap(),
+            [
+                format(),
+                Variable::List(vec![Variable::I64(1), Variable::I64(2)])
+            ]
+        );
+        assert_eq!(
+            check_args(
+                "f",
+                &inputs,
+                &[format(), Variable::List(vec![Variable::I32(1)])]
+            )
+            .unwrap(),
+            [format(), Variable::List(vec![Variable::I64(1)])]
+        );
+        assert!(matches!(
+            check_args("f", &inputs, &[format(), Variable::I32(1), Variable::Bool(true)]),
+            Err(PluginError::TypeMismatch { value, .. }) if value == "argument `values[2]`"
+        ));
+    }
+
+    #[test]
+    fn test_typed_values() {
+        assert_eq!(i64::from_variable(Variable::I32(7)), Some(7));
+        assert_eq!(u8::from_variable(Variable::I32(-1)), None);
+        assert_eq!(
+            Vec::<String>::from_variable(Variable::List(vec![Variable::String("a".into())])),
+            Some(vec!["a".to_string()])
+        );
+        assert_eq!(Option::<bool>::from_output(None), Some(None));
+    }
+
+    #[test]
+    fn test_coerce() {
+        assert_eq!(
+            coerce(&Variable::I64(7), VariableType::U8),
+            Some(Variable::U8(7))
+        );
+        assert_eq!(coerce(&Variable::I64(-1), VariableType::U8), None);
+        assert_eq!(
+            coerce(&Variable::F64(2.0), VariableType::I32),
+            Some(Variable::I32(2))
+        );
+        assert_eq!(coerce(&Variable::F64(2.5), VariableType::I32), None);
+        assert_eq!(
+            coerce(&Variable::U16(2), VariableType::F64),
+            Some(Variable::F64(2.0))
+        );
+        assert_eq!(
+            coerce(&Variable::String("x".to_string()), VariableType::Char),
+            Some(Variable::Char('x'))
+        );
+        assert_eq!(
+            coerce(&Variable::String("xy".to_string()), VariableType::Char),
+            None
+        );
+        assert_eq!(coerce(&Variable::Null, VariableType::Bool), None);
+        assert_eq!(
+            coerce(&Variable::Null, VariableType::Let),
+            Some(Variable::Null)
+        );
+    }
+}
@@ -0,0 +1,51 @@
+//! Watching of plugin directories, see [`crate::LuaManager::with_watch`].
+
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use plux_rs::Bundle;
+
+use crate::sync::MutexExt;
+
+/// Watches the directory of the plugin `bundle` and schedules a reload in
+/// `pending` whenever one of its Lua sources changes.
+pub(crate) fn watch_plugin(
+    path: &Path,
+    bundle: Bundle,
+    pending: Arc<Mutex<Vec<Bundle>>>,
+) -> notify::Result<RecommendedWatcher> {
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+        let event = match event {
+            Ok(event) => event,
+            Err(e) => {
+                log::warn!("Watching plugin {} failed: {}", bundle, e);
+                return;
+            }
+        };
+        if !is_source_change(&event) {
+            return;
+        }
+
+        log::debug!("Sources of plugin {} changed", bundle);
+        let mut pending = pending.lock_unpoisoned();
+        if !pending.contains(&bundle) {
+            pending.push(bundle.clone());
+        }
+    })?;
+    watcher.watch(path, RecursiveMode::Recursive)?;
+    Ok(watcher)
+}
+
+/// Returns `true` if `event` modifies, creates or removes a Lua source.
+fn is_source_change(event: &Event) -> bool {
+    matches!(
+        event.kind,
+        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+    ) && event
+        .paths
+        .iter()
+        .any(|path| path.extension().is_some_and(|ext| ext == "lua"))
+}
@@ -0,0 +1,91 @@
+use plux_lua_manager::{
+    LuaManager,
+    testing::{PluginFixture, TestHost},
+};
+use plux_rs::{
+    function::{Arg, DynamicFunction},
+    variable::{Variable, VariableType},
+};
+
+fn host_with_functions(manager: LuaManager) -> TestHost {
+    let mut host = TestHost::with_manager(manager);
+    host.loader().context(|mut ctx| {
+        for name in ["read_file", "write_file"] {
+            ctx.register_function(DynamicFunction::new(
+                name,
+                vec![],
+                Some(Arg::new("output", VariableType::String)),
+                move |_| Ok(Some(Variable::String(name.to_string()))),
+            ));
+        }
+    });
+    host
+}
+
+const PROBE: &str = r#"return {
+    { name = "probe", inputs = {}, output = "string", func = function()
+        return table.concat({
+            tostring(api.call_function_optional_depend ~= nil),
+            tostring(api.call_function_depend ~= nil),
+            tostring(api.util.split ~= nil),
+            tostring(host.read_file ~= nil),
+            tostring(host.write_file ~= nil),
+        }, " ")
+    end },
+}"#;
+
+#[test]
+fn api_filter_hides_functions_per_plugin() {
+    let mut host = host_with_functions(LuaManager::new().with_api_filter(|plugin, path| {
+        plugin.id != "restricted"
+            || !matches!(
+                path,
+                "api.call_function_optional_depend" | "host.write_file"
+            )
+    }));
+    let restricted = host
+        .load(PluginFixture::new("restricted").main(PROBE))
+        .unwrap();
+    let trusted = host
+        .load(PluginFixture::new("trusted").main(PROBE))
+        .unwrap();
+
+    host.assert_call(
+        &restricted,
+        "probe",
+        &[],
+        Some(Variable::String("false true true true false".to_string())),
+    );
+    host.assert_call(
+        &trusted,
+        "probe",
+        &[],
+        Some(Variable::String("true true true true true".to_string())),
+    );
+}
+
+#[test]
+fn config_declares_the_exposed_functions() {
+    let mut host = host_with_functions(LuaManager::new());
+    let bundle = host
+        .load(
+            PluginFixture::new("declared")
+                .config(
+                    r#"
+                    name = "declared"
+                    description = ""
+                    author = ""
+                    api = ["api.util", "host.read_file"]
+                    "#,
+                )
+                .main(PROBE),
+        )
+        .unwrap();
+
+    host.assert_call(
+        &bundle,
+        "probe",
+        &[],
+        Some(Variable::String("false false true true false".to_string())),
+    );
+}
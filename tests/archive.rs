@@ -0,0 +1,81 @@
+#![cfg(feature = "archive")]
+
+mod utils;
+
+use std::{fs, io::Write, path::Path};
+
+use plux_lua_manager::LuaManager;
+use plux_rs::variable::Variable;
+use zip::{ZipWriter, write::SimpleFileOptions};
+
+use crate::utils::loader_init;
+
+const CONFIG: &str = r#"
+name = "packaged"
+description = "Plugin distributed as an archive"
+author = "Plux"
+"#;
+
+const MAIN_LUA: &str = r#"
+local greeting = require("lib.greeting")
+return { { name = "greet", inputs = {}, output = "string", func = function() return greeting end } }
+"#;
+
+fn write_archive(path: &Path, root: &str) {
+    let mut archive = ZipWriter::new(fs::File::create(path).unwrap());
+    for (name, content) in [
+        ("config.toml", CONFIG),
+        ("main.lua", MAIN_LUA),
+        ("lib/greeting.lua", "return 'hello from the archive'"),
+    ] {
+        archive
+            .start_file(format!("{root}{name}"), SimpleFileOptions::default())
+            .unwrap();
+        archive.write_all(content.as_bytes()).unwrap();
+    }
+    archive.finish().unwrap();
+}
+
+#[test]
+fn archives_are_loaded_in_memory() {
+    let dir = std::env::temp_dir().join(format!("plux-archive-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    for (archive, root, mounted) in [
+        ("packaged-v1.0.0.zip", "", "packaged-v1.0.0.lua"),
+        (
+            "packaged-v2.0.0.lua.pluxpkg",
+            "packaged-v2.0.0.lua/",
+            "packaged-v2.0.0.lua",
+        ),
+    ] {
+        let path = dir.join(archive);
+        write_archive(&path, root);
+
+        let manager = LuaManager::new();
+        let mount = manager.mount_archive(&path).unwrap();
+        assert!(mount.ends_with(mounted));
+
+        let mut loader = loader_init(manager);
+        let bundle = loader.load_plugin_now(mount.to_str().unwrap()).unwrap();
+        let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+        assert_eq!(
+            plugin.call_function("greet", &[]).unwrap().unwrap(),
+            Some(Variable::String("hello from the archive".to_string()))
+        );
+        loader.stop().unwrap();
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn non_archives_are_rejected() {
+    let manager = LuaManager::new();
+    assert!(
+        manager
+            .mount_archive("plugins/packaged-v1.0.0.tar")
+            .is_err()
+    );
+    assert!(manager.mount_archive("plugins/missing-v1.0.0.zip").is_err());
+}
@@ -0,0 +1,59 @@
+mod utils;
+
+use plux_lua_manager::LuaManager;
+#[cfg(feature = "async")]
+use plux_rs::variable::Variable;
+
+use crate::utils::{get_plugin_path, loader_init};
+
+#[cfg(feature = "async")]
+#[test]
+fn async_functions_run_through_plux() {
+    let mut loader = loader_init(LuaManager::new());
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("async_counter", "1.0.0").to_str().unwrap())
+        .unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+
+    assert_eq!(
+        plugin
+            .call_function("count_to", &[Variable::I64(3)])
+            .unwrap()
+            .unwrap(),
+        Some(Variable::I64(3))
+    );
+
+    loader.stop().unwrap();
+}
+
+#[cfg(feature = "async")]
+#[tokio::test(flavor = "current_thread")]
+async fn async_calls_interleave_on_the_executor() {
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("async_counter", "1.0.0").to_str().unwrap())
+        .unwrap();
+
+    let (a, b) = tokio::join!(
+        manager.call_async(&bundle, "count_to", &[Variable::I64(100)]),
+        manager.call_async(&bundle, "count_to", &[Variable::I64(1)]),
+    );
+    assert_eq!(a.unwrap(), Some(Variable::I64(100)));
+    assert_eq!(b.unwrap(), Some(Variable::I64(1)));
+
+    loader.stop().unwrap();
+}
+
+#[cfg(not(feature = "async"))]
+#[test]
+fn async_functions_require_the_feature() {
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+    let result =
+        loader.load_plugin_now(get_plugin_path("async_counter", "1.0.0").to_str().unwrap());
+    assert!(result.is_err());
+    assert!(manager.loaded_bundles().is_empty());
+
+    loader.stop().unwrap();
+}
@@ -0,0 +1,62 @@
+use std::sync::{Arc, Mutex};
+
+use plux_lua_manager::{
+    LuaManager,
+    testing::{PluginFixture, TestHost},
+};
+use plux_rs::{
+    function::{Arg, DynamicFunction},
+    variable::{Variable, VariableType},
+};
+
+#[test]
+fn audit_log_records_host_and_api_calls() {
+    let sunk = Arc::new(Mutex::new(vec![]));
+    let sink = sunk.clone();
+    let manager = LuaManager::new()
+        .with_audit_log(2)
+        .with_audit_sink(move |record| sink.lock().unwrap().push(record.function.clone()));
+
+    let mut host = TestHost::with_manager(manager);
+    host.loader().context(|mut ctx| {
+        ctx.register_function(DynamicFunction::new(
+            "double",
+            vec![Arg::new("x", VariableType::I64)],
+            Some(Arg::new("output", VariableType::I64)),
+            |args| Ok(Some(Variable::I64(args[0].parse_ref::<i64>() * 2))),
+        ))
+    });
+    let bundle = host
+        .load(PluginFixture::new("audited").main(
+            r#"return {
+                { name = "run", inputs = {}, output = "string", func = function()
+                    local parts = api.util.split("a,b", ",")
+                    local doubled = host.double(21)
+                    local ok, err = pcall(api.call_function_depend, "missing", "1.0.0", "f")
+                    return #parts .. " " .. doubled .. " " .. err.kind
+                end },
+            }"#,
+        ))
+        .unwrap();
+
+    host.assert_call(
+        &bundle,
+        "run",
+        &[],
+        Some(Variable::String("2 42 missing_dependency".to_string())),
+    );
+    assert_eq!(
+        *sunk.lock().unwrap(),
+        ["api.util.split", "host.double", "api.call_function_depend"]
+    );
+
+    // Only the last records are kept
+    let log = host.manager().audit_log();
+    assert_eq!(log.len(), 2);
+    assert_eq!(log[0].plugin, bundle);
+    assert_eq!(log[0].function, "host.double");
+    assert_eq!(log[0].args, "21");
+    assert!(log[0].ok);
+    assert_eq!(log[1].args, r#""missing", "1.0.0", "f""#);
+    assert!(!log[1].ok);
+}
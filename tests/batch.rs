@@ -0,0 +1,155 @@
+mod utils;
+
+use plux_lua_manager::{BatchOptions, LuaManager};
+use plux_rs::variable::Variable;
+
+use crate::utils::{benchmark, get_plugin_path, loader_init};
+
+#[test]
+fn call_batch_returns_every_output() {
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("batch", "1.0.0").to_str().unwrap())
+        .unwrap();
+
+    let batches: Vec<Vec<Variable>> = (0..1000).map(|i| vec![Variable::I32(i)]).collect();
+    let results = manager.call_batch(&bundle, "transform", &batches).unwrap();
+
+    assert_eq!(results.len(), 1000);
+    for (i, result) in results.into_iter().enumerate() {
+        assert_eq!(result.unwrap(), Some(Variable::I64(i as i64 * 2)));
+    }
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn call_batch_collects_failures() {
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("batch", "1.0.0").to_str().unwrap())
+        .unwrap();
+
+    let batches = vec![
+        vec![Variable::I32(1)],
+        vec![Variable::I32(-1)],
+        vec![Variable::I32(3)],
+    ];
+
+    let results = manager.call_batch(&bundle, "checked", &batches).unwrap();
+    assert_eq!(results.len(), 3);
+    assert!(results[0].is_ok());
+    assert!(results[1].is_err());
+    assert_eq!(*results[2].as_ref().unwrap(), Some(Variable::I64(3)));
+
+    let options = BatchOptions { fail_fast: true };
+    let results = manager
+        .call_batch_with(&bundle, "checked", &batches, options)
+        .unwrap();
+    assert_eq!(results.len(), 2);
+    assert!(results[1].is_err());
+
+    assert!(manager.call_batch(&bundle, "missing", &batches).is_err());
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn paused_plugins_reject_whole_batches() {
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("batch", "1.0.0").to_str().unwrap())
+        .unwrap();
+    let batches = vec![vec![Variable::I32(1)]; 3];
+
+    manager.pause(&bundle).unwrap();
+    let error = manager
+        .call_batch(&bundle, "transform", &batches)
+        .unwrap_err();
+    assert!(error.to_string().contains("paused"), "{error}");
+    assert!(!manager.metrics()[&bundle].contains_key("transform"));
+
+    manager.resume(&bundle).unwrap();
+    assert_eq!(
+        manager
+            .call_batch(&bundle, "transform", &batches)
+            .unwrap()
+            .len(),
+        3
+    );
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn call_batch_matches_individual_calls() {
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("batch", "1.0.0").to_str().unwrap())
+        .unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+
+    let batches: Vec<Vec<Variable>> = (-50..50).map(|i| vec![Variable::I32(i)]).collect();
+    for function in ["transform", "checked"] {
+        let individual: Vec<_> = batches
+            .iter()
+            .map(|args| plugin.call_function(function, args).unwrap().ok())
+            .collect();
+        let batched: Vec<_> = manager
+            .call_batch(&bundle, function, &batches)
+            .unwrap()
+            .into_iter()
+            .map(Result::ok)
+            .collect();
+        assert_eq!(batched, individual);
+
+        let metrics = &manager.metrics()[&bundle][function];
+        assert_eq!(metrics.calls, 2 * batches.len() as u64);
+    }
+
+    loader.stop().unwrap();
+}
+
+#[test]
+#[ignore = "benchmark, run with `cargo test --release -- --ignored`"]
+fn benchmark_call_batch() {
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("batch", "1.0.0").to_str().unwrap())
+        .unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+
+    let batches: Vec<Vec<Variable>> = (0..1000).map(|i| vec![Variable::I32(i)]).collect();
+
+    let mut individual = std::time::Duration::MAX;
+    let mut batched = std::time::Duration::MAX;
+    for _ in 0..5 {
+        let (elapsed, _) = benchmark(|| {
+            for args in batches.iter() {
+                plugin.call_function("transform", args).unwrap().unwrap();
+            }
+        });
+        individual = individual.min(elapsed);
+
+        let (elapsed, _) = benchmark(|| manager.call_batch(&bundle, "transform", &batches));
+        batched = batched.min(elapsed);
+    }
+
+    println!("individual: {individual:?}, batched: {batched:?}");
+    assert!(
+        batched < individual,
+        "individual: {individual:?}, batched: {batched:?}"
+    );
+
+    loader.stop().unwrap();
+}
@@ -0,0 +1,32 @@
+mod utils;
+
+use plux_lua_manager::LuaManager;
+use plux_rs::variable::Variable;
+
+use crate::utils::{get_plugin_path, loader_init};
+
+#[test]
+fn broadcast_reaches_every_handler() {
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+    let [ok, fail, _silent] = ["broadcast_ok", "broadcast_fail", "events_emitter"].map(|id| {
+        loader
+            .load_plugin_now(get_plugin_path(id, "1.0.0").to_str().unwrap())
+            .unwrap()
+    });
+
+    let results = manager.broadcast("theme", &[Variable::String("dark".to_string())]);
+    assert_eq!(results.len(), 2);
+
+    assert_eq!(results[0].0, ok);
+    assert_eq!(
+        results[0].1.as_ref().unwrap(),
+        &Some(Variable::String("theme:dark".to_string()))
+    );
+
+    assert_eq!(results[1].0, fail);
+    let error = results[1].1.as_ref().unwrap_err().to_string();
+    assert!(error.contains("cannot handle theme"), "{error}");
+
+    loader.stop().unwrap();
+}
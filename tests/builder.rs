@@ -0,0 +1,37 @@
+mod utils;
+
+use std::time::Duration;
+
+use plux_lua_manager::{LuaManager, SandboxPolicy};
+use plux_rs::variable::Variable;
+
+use crate::utils::{get_plugin_path, loader_init};
+
+#[test]
+fn builder_applies_manager_options() {
+    let manager = LuaManager::builder()
+        .sandbox(SandboxPolicy::safe())
+        .memory_limit(16 * 1024 * 1024)
+        .call_timeout(Duration::from_secs(1))
+        .entry("init.lua")
+        .global("APP_NAME", Variable::String("host".to_string()))
+        .log_level(log::LevelFilter::Off)
+        .build();
+    let mut loader = loader_init(manager.clone());
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("builder", "1.0.0").to_str().unwrap())
+        .unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+
+    assert_eq!(
+        plugin.call_function("greeting", &[]).unwrap().unwrap(),
+        Some(Variable::String("hello from host".to_string()))
+    );
+
+    let info = manager.plugin_runtime_info(&bundle).unwrap();
+    assert_eq!(info.memory_limit, Some(16 * 1024 * 1024));
+    assert_eq!(info.call_timeout, Some(Duration::from_secs(1)));
+    assert_eq!(info.sandbox_profile.as_deref(), Some("safe"));
+
+    loader.stop().unwrap();
+}
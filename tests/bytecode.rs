@@ -0,0 +1,80 @@
+mod utils;
+
+use std::fs;
+
+use plux_lua_manager::LuaManager;
+use plux_rs::{Bundle, Loader, StdInfo, function::FunctionOutput, variable::Variable};
+
+use crate::utils::loader_init;
+
+const CONFIG: &str = r#"
+name = "compiled"
+description = "Plugin loaded through the bytecode cache"
+author = "Plux"
+"#;
+
+const MAIN_LUA: &str = r#"
+local answer = require("answer")
+return { { name = "answer", inputs = {}, output = "i64", func = function() return answer end } }
+"#;
+
+fn answer(loader: &Loader<'static, FunctionOutput, StdInfo>, bundle: &Bundle) -> Option<Variable> {
+    let plugin = loader.get_plugin_by_bundle(bundle).unwrap();
+    plugin.call_function("answer", &[]).unwrap().unwrap()
+}
+
+#[test]
+fn compiled_chunks_are_cached_on_disk() {
+    let dir = std::env::temp_dir()
+        .join(format!("plux-bytecode-{}", std::process::id()))
+        .join("compiled-v1.0.0.lua");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("config.toml"), CONFIG).unwrap();
+    fs::write(dir.join("main.lua"), MAIN_LUA).unwrap();
+    fs::write(dir.join("answer.lua"), "return 1").unwrap();
+
+    let manager = LuaManager::new().with_bytecode_cache(true);
+    let mut loader = loader_init(manager.clone());
+    let bundle = loader.load_plugin_now(dir.to_str().unwrap()).unwrap();
+    assert_eq!(answer(&loader, &bundle), Some(Variable::I64(1)));
+    assert_eq!(fs::read_dir(dir.join(".plux-cache")).unwrap().count(), 2);
+
+    // An edited module is compiled again
+    fs::write(dir.join("answer.lua"), "return 2").unwrap();
+    manager.reload_plugin(&bundle).unwrap();
+    assert_eq!(answer(&loader, &bundle), Some(Variable::I64(2)));
+    assert_eq!(fs::read_dir(dir.join(".plux-cache")).unwrap().count(), 3);
+
+    // Cached chunks are picked up by a new manager
+    loader.stop().unwrap();
+    let mut loader = loader_init(LuaManager::new().with_bytecode_cache(true));
+    let bundle = loader.load_plugin_now(dir.to_str().unwrap()).unwrap();
+    assert_eq!(answer(&loader, &bundle), Some(Variable::I64(2)));
+    assert_eq!(fs::read_dir(dir.join(".plux-cache")).unwrap().count(), 3);
+
+    loader.stop().unwrap();
+    fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn memory_cache_leaves_plugin_directory_untouched() {
+    let dir = std::env::temp_dir()
+        .join(format!("plux-bytecode-memory-{}", std::process::id()))
+        .join("compiled-v1.0.0.lua");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("config.toml"), CONFIG).unwrap();
+    fs::write(dir.join("main.lua"), MAIN_LUA).unwrap();
+    fs::write(dir.join("answer.lua"), "return 3").unwrap();
+
+    let mut loader = loader_init(LuaManager::new().with_bytecode_cache(false));
+    let bundle = loader.load_plugin_now(dir.to_str().unwrap()).unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+    assert_eq!(
+        plugin.call_function("answer", &[]).unwrap().unwrap(),
+        Some(Variable::I64(3))
+    );
+    assert!(!dir.join(".plux-cache").exists());
+
+    loader.stop().unwrap();
+    fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+}
@@ -0,0 +1,70 @@
+mod utils;
+
+use plux_lua_manager::LuaManager;
+use plux_rs::utils::RegisterPluginError;
+
+use crate::utils::{get_plugin_path, loader_init};
+
+fn plugin_path(id: &str) -> String {
+    get_plugin_path(id, "1.0.0").to_str().unwrap().to_string()
+}
+
+#[test]
+fn undeclared_capability_fails_at_call() {
+    let mut loader = loader_init(LuaManager::new());
+    let bundle = loader
+        .load_plugin_now(&plugin_path("spawn_undeclared"))
+        .unwrap();
+
+    let error = loader
+        .get_plugin_by_bundle(&bundle)
+        .unwrap()
+        .call_function("start", &[])
+        .unwrap()
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("capability 'spawn' not declared"), "{error}");
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn declared_capability_allows_call() {
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+
+    let bundle = loader
+        .register_plugin(&plugin_path("spawn_declared"))
+        .unwrap();
+    assert_eq!(
+        manager.capabilities(&bundle),
+        Some(vec!["spawn".to_string(), "teleport".to_string()])
+    );
+
+    loader.load_plugin(&bundle.id, &bundle.version).unwrap();
+    loader
+        .get_plugin_by_bundle(&bundle)
+        .unwrap()
+        .call_function("start", &[])
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        manager.diagnostics(&bundle).unwrap(),
+        vec!["unknown capability `teleport`".to_string()]
+    );
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn unknown_capability_is_an_error_in_strict_mode() {
+    let mut loader = loader_init(LuaManager::new().with_strict_capabilities(true));
+
+    let error = match loader.register_plugin(&plugin_path("spawn_declared")) {
+        Err(RegisterPluginError::RegisterPluginByManager(error)) => error.to_string(),
+        result => panic!("unexpected result: {result:?}"),
+    };
+    assert!(error.contains("Unknown capabilities: teleport"), "{error}");
+
+    loader.stop().unwrap();
+}
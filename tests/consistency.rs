@@ -0,0 +1,86 @@
+mod utils;
+
+use plux_lua_manager::{ConsistencyReport, LuaManager};
+use plux_rs::Bundle;
+
+use crate::utils::{PinnedLoader, get_plugin_path};
+
+fn load(manager: &LuaManager) -> (PinnedLoader, Bundle) {
+    let mut loader = PinnedLoader::new(manager.clone());
+    let bundle = loader.load(&get_plugin_path("counter", "1.0.0"));
+    (loader, bundle)
+}
+
+#[test]
+fn consistent_after_load() {
+    let manager = LuaManager::new();
+    let (loader, _) = load(&manager);
+
+    assert!(manager.verify_consistency(&loader, false).is_consistent());
+}
+
+#[test]
+fn orphaned_state_is_dropped_on_repair() {
+    let manager = LuaManager::new();
+    let (loader, bundle) = load(&manager);
+
+    let ghost = Bundle::from_filename("ghost-v1.0.0.lua").unwrap();
+    manager.__duplicate_state(&bundle, ghost.clone()).unwrap();
+
+    let report = manager.verify_consistency(&loader, false);
+    assert_eq!(
+        report,
+        ConsistencyReport {
+            orphaned_states: vec![ghost.clone()],
+            ..Default::default()
+        }
+    );
+    assert!(manager.loaded_bundles().contains(&ghost));
+
+    assert_eq!(manager.verify_consistency(&loader, true), report);
+    assert_eq!(manager.loaded_bundles(), vec![bundle]);
+    assert!(manager.verify_consistency(&loader, false).is_consistent());
+}
+
+#[test]
+fn missing_state_is_reported() {
+    let manager = LuaManager::new();
+    let (loader, bundle) = load(&manager);
+
+    manager.__forget_state(&bundle).unwrap();
+    assert!(manager.__forget_state(&bundle).is_err());
+
+    let report = manager.verify_consistency(&loader, true);
+    assert_eq!(report.missing_states, vec![bundle]);
+    assert_eq!(manager.verify_consistency(&loader, false), report);
+}
+
+#[test]
+fn orphaned_function_is_unregistered_on_repair() {
+    let manager = LuaManager::new();
+    let (loader, bundle) = load(&manager);
+
+    manager.__forget_export(&bundle, "increment").unwrap();
+
+    let report = manager.verify_consistency(&loader, true);
+    assert_eq!(
+        report.orphaned_functions,
+        vec![(bundle.clone(), "increment".to_string())]
+    );
+    assert!(
+        !manager
+            .functions(&bundle)
+            .unwrap()
+            .iter()
+            .any(|function| function.name == "increment")
+    );
+    assert!(manager.verify_consistency(&loader, false).is_consistent());
+
+    let error = loader
+        .get_plugin_by_bundle(&bundle)
+        .unwrap()
+        .call_function("increment", &[])
+        .unwrap()
+        .unwrap_err();
+    assert!(error.to_string().contains("increment"), "{error}");
+}
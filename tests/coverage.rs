@@ -0,0 +1,56 @@
+#![cfg(feature = "debug")]
+
+use plux_lua_manager::{
+    LuaManager,
+    testing::{PluginFixture, TestHost},
+};
+use plux_rs::variable::Variable;
+
+#[test]
+fn coverage_records_the_lines_run_per_file() {
+    let mut host = TestHost::with_manager(LuaManager::new().with_coverage(true));
+    let bundle = host
+        .load(
+            PluginFixture::new("covered")
+                .file(
+                    "util.lua",
+                    "return {\n  half = function(x)\n    return x // 2\n  end,\n}\n",
+                )
+                .main(
+                    r#"local util = require("util")
+return {
+    { name = "half", inputs = { "i64" }, output = "i64", func = function(x)
+        if x < 0 then
+            error("negative")
+        end
+        return util.half(x)
+    end },
+}"#,
+                ),
+        )
+        .unwrap();
+    host.manager().reset_coverage();
+
+    host.assert_call(&bundle, "half", &[Variable::I64(8)], Some(Variable::I64(4)));
+    host.assert_call(&bundle, "half", &[Variable::I64(6)], Some(Variable::I64(3)));
+
+    let report = host.manager().coverage_report();
+    let main = report
+        .files
+        .iter()
+        .find(|file| file.path == "main.lua")
+        .unwrap();
+    assert_eq!(main.plugin, bundle.to_string());
+    assert_eq!(main.lines.get(&4), Some(&2));
+    assert_eq!(main.lines.get(&5), None);
+    assert_eq!(main.lines.get(&7), Some(&2));
+    let util = report
+        .files
+        .iter()
+        .find(|file| file.path == "util.lua")
+        .unwrap();
+    assert_eq!(util.lines.get(&3), Some(&2));
+
+    let lcov = report.to_lcov();
+    assert!(lcov.contains("SF:main.lua\nDA:4,2\nDA:7,2\n"), "{lcov}");
+}
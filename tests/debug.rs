@@ -0,0 +1,76 @@
+#![cfg(feature = "debug")]
+
+use std::sync::{Arc, Mutex};
+
+use mlua::{DebugEvent, HookTriggers, VmState};
+use plux_lua_manager::testing::{PluginFixture, TestHost};
+use plux_rs::variable::Variable;
+
+const STEPPER: &str = r#"return {
+    { name = "sum", inputs = {}, output = "i64", func = function()
+        local total = 0
+        for i = 1, 3 do
+            total = total + i
+        end
+        return total
+    end },
+}"#;
+
+#[test]
+fn debug_hook_sees_the_lines_run_by_a_plugin() {
+    let mut host = TestHost::new();
+    let bundle = host
+        .load(PluginFixture::new("stepper").main(STEPPER))
+        .unwrap();
+
+    let lines = Arc::new(Mutex::new(vec![]));
+    let seen = lines.clone();
+    host.manager()
+        .set_debug_hook(
+            &bundle,
+            HookTriggers::new().every_line(),
+            move |_, debug| {
+                if debug.event() == DebugEvent::Line {
+                    seen.lock().unwrap().push(debug.current_line().unwrap());
+                }
+                Ok(VmState::Continue)
+            },
+        )
+        .unwrap();
+
+    host.assert_call(&bundle, "sum", &[], Some(Variable::I64(6)));
+    let stepped = lines.lock().unwrap().clone();
+    assert_eq!(stepped.iter().filter(|line| **line == 5).count(), 3);
+    assert_eq!(stepped.last(), Some(&7));
+
+    host.manager().remove_debug_hook(&bundle).unwrap();
+    lines.lock().unwrap().clear();
+    host.assert_call(&bundle, "sum", &[], Some(Variable::I64(6)));
+    assert!(lines.lock().unwrap().is_empty());
+}
+
+#[test]
+fn debug_hook_errors_stop_the_plugin() {
+    let mut host = TestHost::new();
+    let bundle = host
+        .load(PluginFixture::new("stepper").main(STEPPER))
+        .unwrap();
+
+    host.manager()
+        .set_debug_hook(
+            &bundle,
+            HookTriggers::new().every_line(),
+            |_, debug| match debug.current_line() {
+                Some(5) if debug.source().what == "Lua" => {
+                    Err(mlua::Error::RuntimeError("breakpoint".to_string()))
+                }
+                _ => Ok(VmState::Continue),
+            },
+        )
+        .unwrap();
+    host.assert_call_fails(&bundle, "sum", &[], "breakpoint");
+
+    // The hook is kept across reloads
+    host.manager().reload_plugin(&bundle).unwrap();
+    host.assert_call_fails(&bundle, "sum", &[], "breakpoint");
+}
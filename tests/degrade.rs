@@ -0,0 +1,58 @@
+mod utils;
+
+use plux_lua_manager::LuaManager;
+use plux_rs::variable::Variable;
+
+use crate::utils::{get_plugin_path, loader_init};
+
+#[test]
+fn dependent_survives_dependency_unload() {
+    let mut loader = loader_init(LuaManager::new());
+    let b = loader
+        .load_plugin_now(get_plugin_path("degrade_b", "1.0.0").to_str().unwrap())
+        .unwrap();
+    let a = loader
+        .load_plugin_now(get_plugin_path("degrade_a", "1.0.0").to_str().unwrap())
+        .unwrap();
+
+    let call = |loader: &plux_rs::Loader<'static, _, _>, bundle, name| {
+        loader
+            .get_plugin_by_bundle(bundle)
+            .unwrap()
+            .call_function(name, &[])
+            .unwrap()
+    };
+    assert_eq!(
+        call(&loader, &a, "use_b").unwrap(),
+        Some(Variable::String("ok 42".to_string()))
+    );
+
+    // The loader refuses to unload a dependency in use, force it out
+    let index = loader
+        .get_plugins()
+        .iter()
+        .position(|plugin| plugin.info().bundle == b)
+        .unwrap();
+    unsafe { loader.forced_unload_plugin(index) }.unwrap();
+
+    assert_eq!(
+        call(&loader, &a, "use_b").unwrap(),
+        Some(Variable::String(
+            "missing_dependency / missing_dependency: dependency `degrade_b` v1.0.0 is not loaded"
+                .to_string()
+        ))
+    );
+
+    // Functions handed out before the unload fail cleanly
+    let error = call(&loader, &b, "value").unwrap_err().to_string();
+    assert!(error.contains("is not loaded"), "{error}");
+
+    assert_eq!(
+        call(&loader, &a, "get_events").unwrap(),
+        Some(Variable::List(vec![Variable::String(
+            "degrade_b 1.0.0".to_string()
+        )]))
+    );
+
+    loader.stop().unwrap();
+}
@@ -0,0 +1,190 @@
+mod utils;
+
+use plux_lua_manager::LuaManager;
+use plux_rs::{utils::RegisterPluginError, variable::Variable};
+
+use crate::utils::{get_plugin_path, loader_init};
+
+fn plugin_path(id: &str, version: &str) -> String {
+    get_plugin_path(id, version).to_str().unwrap().to_string()
+}
+
+#[test]
+fn required_version_mismatch_fails_registration() {
+    let mut loader = loader_init(LuaManager::new());
+    loader
+        .register_plugin(&plugin_path("dep_ok", "1.0.0"))
+        .unwrap();
+    loader
+        .register_plugin(&plugin_path("dep_old", "1.9.0"))
+        .unwrap();
+    loader
+        .register_plugin(&plugin_path("dep_opt", "1.0.0"))
+        .unwrap();
+
+    let error = match loader.register_plugin(&plugin_path("dep_check", "1.0.0")) {
+        Err(RegisterPluginError::RegisterPluginByManager(error)) => error.to_string(),
+        result => panic!("unexpected result: {result:?}"),
+    };
+    assert!(error.contains("`dep_old` ^2.0 is required, but only 1.9.0 is available"));
+    assert!(!error.contains("dep_ok"));
+    assert!(!error.contains("dep_opt"));
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn optional_version_mismatch_is_a_diagnostic() {
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+    loader
+        .load_plugin_now(&plugin_path("dep_ok", "1.0.0"))
+        .unwrap();
+    loader
+        .load_plugin_now(&plugin_path("dep_opt", "1.0.0"))
+        .unwrap();
+
+    let bundle = loader
+        .load_plugin_now(&plugin_path("dep_optional", "1.0.0"))
+        .unwrap();
+    assert_eq!(
+        manager.diagnostics(&bundle).unwrap(),
+        vec!["optional `dep_opt` ^3.0 is required, but only 1.0.0 is available".to_string()]
+    );
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn dependency_check_can_be_disabled() {
+    let manager = LuaManager::new().with_dependency_check(false);
+    let mut loader = loader_init(manager.clone());
+    loader
+        .register_plugin(&plugin_path("dep_old", "1.9.0"))
+        .unwrap();
+    loader
+        .register_plugin(&plugin_path("dep_check", "1.0.0"))
+        .unwrap();
+    loader.stop().unwrap();
+
+    let mut loader = loader_init(manager.clone());
+    loader
+        .load_plugin_now(&plugin_path("dep_ok", "1.0.0"))
+        .unwrap();
+    loader
+        .load_plugin_now(&plugin_path("dep_opt", "1.0.0"))
+        .unwrap();
+    let bundle = loader
+        .load_plugin_now(&plugin_path("dep_optional", "1.0.0"))
+        .unwrap();
+    assert!(manager.diagnostics(&bundle).unwrap().is_empty());
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn declared_version_must_match_the_bundle() {
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+    let bundle = plux_rs::Bundle::from_filename("versioned-v1.0.0.lua").unwrap();
+    let path = manager
+        .mount_embedded(
+            &bundle,
+            "name = \"versioned\"\ndescription = \"\"\nauthor = \"Plux\"\nversion = \"2.0.0\"",
+            [("main.lua", "return {}")],
+        )
+        .unwrap();
+
+    let error = match loader.register_plugin(path.to_str().unwrap()) {
+        Err(RegisterPluginError::RegisterPluginByManager(error)) => error.to_string(),
+        result => panic!("unexpected result: {result:?}"),
+    };
+    assert!(error.contains("Version 2.0.0 does not match the plugin version 1.0.0"));
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn plugins_check_which_dependencies_are_loaded() {
+    let mut loader = loader_init(LuaManager::new());
+    loader
+        .load_plugin_now(&plugin_path("dep_opt", "1.0.0"))
+        .unwrap();
+    loader
+        .register_plugin(&plugin_path("dep_ok", "1.0.0"))
+        .unwrap();
+    let bundle = loader
+        .load_plugin_now(&plugin_path("has_depend", "1.0.0"))
+        .unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+
+    let has = |id: &str, requirement: &str| {
+        plugin
+            .call_function(
+                "has",
+                &[
+                    Variable::String(id.to_string()),
+                    Variable::String(requirement.to_string()),
+                ],
+            )
+            .unwrap()
+            .unwrap()
+    };
+    assert_eq!(has("dep_opt", "^1.0"), Some(Variable::Bool(true)));
+    assert_eq!(has("dep_opt", "^2.0"), Some(Variable::Bool(false)));
+    // Registered but not loaded
+    assert_eq!(has("dep_ok", "^1.0"), Some(Variable::Bool(false)));
+    assert_eq!(
+        plugin
+            .call_function("has_any", &[Variable::String("missing".to_string())])
+            .unwrap()
+            .unwrap(),
+        Some(Variable::Bool(false))
+    );
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn dependencies_are_called_by_version_requirement() {
+    let mut loader = loader_init(LuaManager::new());
+    loader
+        .load_plugin_now(&plugin_path("degrade_b", "1.0.0"))
+        .unwrap();
+    let bundle = loader
+        .load_plugin_now(&plugin_path("dep_caller", "1.0.0"))
+        .unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+    let call = |name: &str| plugin.call_function(name, &[]).unwrap().unwrap();
+
+    assert_eq!(call("by_requirement"), Some(Variable::I64(42)));
+    assert_eq!(call("by_declared"), Some(Variable::I64(42)));
+    assert_eq!(
+        call("unmatched"),
+        Some(Variable::String(
+            "missing_dependency: dependency `degrade_b` ^2.0 is not loaded".to_string()
+        ))
+    );
+    assert_eq!(call("optional_unmatched"), Some(Variable::Bool(false)));
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn dependencies_are_called_through_deps() {
+    let mut loader = loader_init(LuaManager::new());
+    loader
+        .load_plugin_now(&plugin_path("degrade_b", "1.0.0"))
+        .unwrap();
+    let bundle = loader
+        .load_plugin_now(&plugin_path("dep_caller", "1.0.0"))
+        .unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+
+    assert_eq!(
+        plugin.call_function("via_deps", &[]).unwrap().unwrap(),
+        Some(Variable::String("42 true".to_string()))
+    );
+
+    loader.stop().unwrap();
+}
@@ -0,0 +1,117 @@
+use plux_lua_manager::{
+    FunctionDoc, LuaManager,
+    testing::{PluginFixture, TestHost},
+};
+use plux_rs::variable::Variable;
+
+#[test]
+fn functions_are_documented() {
+    let mut host = TestHost::new();
+    let documented = host
+        .load(PluginFixture::new("documented").main(
+            r#"
+            return {
+                {
+                    name = "mul",
+                    inputs = { "a", "b" },
+                    func = function(a, b) return a * b end,
+                    description = "Multiplies two numbers",
+                    examples = { "mul(2, 3) == 6" },
+                    since = "1.0.0",
+                },
+                { name = "plain", inputs = {}, func = function() end },
+            }
+            "#,
+        ))
+        .unwrap();
+    let reader = host
+        .load(PluginFixture::new("reader").main(
+            r#"
+            return {
+                { name = "help", inputs = { "plugin", "name" }, func = function(plugin, name)
+                    local doc = api.describe_function(plugin, name)
+                    if doc == nil then
+                        return "undocumented"
+                    end
+                    return string.format("%s: %s (%d examples, since %s)",
+                        doc.name, doc.description, #doc.examples, doc.since)
+                end },
+            }
+            "#,
+        ))
+        .unwrap();
+
+    let manager = host.manager();
+    assert_eq!(
+        manager.function_doc(&documented, "mul"),
+        Some(FunctionDoc {
+            description: Some("Multiplies two numbers".to_string()),
+            examples: vec!["mul(2, 3) == 6".to_string()],
+            since: Some("1.0.0".to_string()),
+        })
+    );
+    assert_eq!(
+        manager.function_doc(&documented, "plain"),
+        Some(FunctionDoc::default())
+    );
+    assert_eq!(
+        manager
+            .function_docs(&documented)
+            .unwrap()
+            .keys()
+            .collect::<Vec<_>>(),
+        ["mul", "plain"]
+    );
+
+    let help = |name: &str| {
+        host.call(
+            &reader,
+            "help",
+            &[
+                Variable::String("documented".to_string()),
+                Variable::String(name.to_string()),
+            ],
+        )
+        .unwrap()
+    };
+    assert_eq!(
+        help("mul"),
+        Some(Variable::String(
+            "mul: Multiplies two numbers (1 examples, since 1.0.0)".to_string()
+        ))
+    );
+    assert_eq!(
+        help("missing"),
+        Some(Variable::String("undocumented".to_string()))
+    );
+
+    host.loader().unload_plugin_by_bundle(&documented).unwrap();
+    assert_eq!(host.manager().function_doc(&documented, "mul"), None);
+}
+
+#[test]
+fn declared_exports_are_documented() {
+    let mut host = TestHost::with_manager(LuaManager::new().with_lazy_loading(true));
+    let bundle = host
+        .load(
+            PluginFixture::new("lazy_documented")
+                .config(
+                    r#"
+                    name = "lazy_documented"
+                    description = "Documents its declared exports"
+                    author = "Plux"
+
+                    [[exports]]
+                    name = "double"
+                    inputs = ["x: i64"]
+                    output = "i64"
+                    description = "Doubles a number"
+                    "#,
+                )
+                .main(r#"return { { name = "double", inputs = { "x: i64" }, output = "i64", func = function(x) return x * 2 end } }"#),
+        )
+        .unwrap();
+
+    let doc = host.manager().function_doc(&bundle, "double").unwrap();
+    assert_eq!(doc.description.as_deref(), Some("Doubles a number"));
+}
@@ -0,0 +1,60 @@
+mod utils;
+
+use plux_lua_manager::LuaManager;
+use plux_rs::{Bundle, variable::Variable};
+
+use crate::utils::loader_init;
+
+const CONFIG: &str = r#"
+name = "builtin"
+description = "Plugin compiled into the host"
+author = "Plux"
+"#;
+
+const MAIN_LUA: &str = r#"
+local math = require("util.math")
+return { { name = "double", inputs = { "i64" }, output = "i64", func = math.double } }
+"#;
+
+const MATH_LUA: &str = "return { double = function(n) return n * 2 end }";
+
+#[test]
+fn embedded_plugins_are_loaded_from_memory() {
+    let manager = LuaManager::new();
+    let bundle = Bundle::from_filename("builtin-v1.0.0.lua").unwrap();
+    let path = manager
+        .mount_embedded(
+            &bundle,
+            CONFIG,
+            [("main.lua", MAIN_LUA), ("util/math.lua", MATH_LUA)],
+        )
+        .unwrap();
+    assert_eq!(std::fs::read_dir(&path).unwrap().count(), 0);
+
+    let mut loader = loader_init(manager);
+    assert_eq!(
+        loader.load_plugin_now(path.to_str().unwrap()).unwrap(),
+        bundle
+    );
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+    assert_eq!(
+        plugin
+            .call_function("double", &[Variable::I64(21)])
+            .unwrap()
+            .unwrap(),
+        Some(Variable::I64(42))
+    );
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn embedded_plugins_must_be_lua() {
+    let manager = LuaManager::new();
+    let bundle = Bundle::from_filename("builtin-v1.0.0.py").unwrap();
+    assert!(
+        manager
+            .mount_embedded(&bundle, CONFIG, [("main.lua", MAIN_LUA)])
+            .is_err()
+    );
+}
@@ -0,0 +1,71 @@
+mod utils;
+
+use plux_lua_manager::LuaManager;
+use plux_rs::{Bundle, variable::Variable};
+
+use crate::utils::{get_plugin_path, loader_init};
+
+#[test]
+fn entry_is_read_from_config() {
+    // The config's entry wins over the manager's
+    let mut loader = loader_init(LuaManager::new().with_entry("init.lua"));
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("custom_entry", "1.0.0").to_str().unwrap())
+        .unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+    assert_eq!(
+        plugin.call_function("entry", &[]).unwrap().unwrap(),
+        Some(Variable::String("src/init.lua".to_string()))
+    );
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn invalid_entries_fail_to_load() {
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+
+    for (id, entry) in [
+        ("missing_entry", "src/init.lua"),
+        ("escaping_entry", "../main.lua"),
+    ] {
+        let bundle = Bundle::from_filename(&format!("{id}-v1.0.0.lua")).unwrap();
+        let config =
+            format!("name = \"{id}\"\ndescription = \"\"\nauthor = \"Plux\"\nentry = \"{entry}\"");
+        let path = manager
+            .mount_embedded(&bundle, &config, [("main.lua", "return {}")])
+            .unwrap();
+        assert!(loader.load_plugin_now(path.to_str().unwrap()).is_err());
+    }
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn entry_may_return_a_module_table() {
+    let mut loader = loader_init(LuaManager::new());
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("module_entry", "1.0.0").to_str().unwrap())
+        .unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+
+    let functions: Vec<_> = plugin.get_registry().iter().map(|f| f.name()).collect();
+    assert_eq!(functions, ["add", "greet"]);
+    assert_eq!(
+        plugin
+            .call_function("add", &[Variable::I64(2), Variable::I64(3)])
+            .unwrap()
+            .unwrap(),
+        Some(Variable::I64(5))
+    );
+    assert_eq!(
+        plugin
+            .call_function("greet", &[Variable::String("plux".to_string())])
+            .unwrap()
+            .unwrap(),
+        Some(Variable::String("hello plux".to_string()))
+    );
+
+    loader.stop().unwrap();
+}
@@ -0,0 +1,48 @@
+mod utils;
+
+use plux_lua_manager::LuaManager;
+use plux_rs::{
+    function::{Arg, DynamicFunction},
+    variable::{Variable, VariableType},
+};
+
+use crate::utils::{get_plugin_path, loader_init};
+
+#[test]
+fn plugin_globals_do_not_collide_with_host_globals() {
+    let manager = LuaManager::new().with_flat_host_functions(true);
+    let mut loader = loader_init(manager.clone());
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("env_shadow", "1.0.0").to_str().unwrap())
+        .unwrap();
+
+    // A host function registered later does not replace the plugin's global,
+    // even with flat host functions
+    loader.context(|mut ctx| {
+        ctx.register_function(DynamicFunction::new(
+            "add",
+            vec![
+                Arg::new("a", VariableType::I64),
+                Arg::new("b", VariableType::I64),
+            ],
+            Some(Arg::new("c", VariableType::I64)),
+            |args| {
+                Ok(Some(
+                    (args[0].parse_ref::<i64>() + args[1].parse_ref::<i64>()).into(),
+                ))
+            },
+        ))
+    });
+    manager.refresh_vtable().unwrap();
+
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+    let call = |name: &str| plugin.call_function(name, &[]).unwrap().unwrap();
+    assert_eq!(
+        call("own_add"),
+        Some(Variable::String("plugin add".to_string()))
+    );
+    assert_eq!(call("host_api"), Some(Variable::Bool(true)));
+    assert_eq!(call("assign_host_global"), Some(Variable::Bool(false)));
+
+    loader.stop().unwrap();
+}
@@ -0,0 +1,108 @@
+mod utils;
+
+use plux_lua_manager::{LuaManager, ManagerError, PluginError};
+use plux_rs::{
+    function::{Arg, DynamicFunction},
+    utils::LoadPluginError,
+    variable::{Variable, VariableType},
+};
+
+use crate::utils::{get_plugin_path, loader_init};
+
+#[test]
+fn call_errors_carry_the_plugin_arguments_and_traceback() {
+    let mut loader = loader_init(LuaManager::new());
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("batch", "1.0.0").to_str().unwrap())
+        .unwrap();
+
+    let error = loader
+        .get_plugin_by_bundle(&bundle)
+        .unwrap()
+        .call_function("checked", &[Variable::I32(-1)])
+        .unwrap()
+        .unwrap_err();
+    match error.downcast_ref::<ManagerError>() {
+        Some(ManagerError::Plugin(PluginError::Call(call))) => {
+            assert_eq!(call.plugin.as_ref(), Some(&bundle));
+            assert_eq!(call.function, "checked");
+            assert_eq!(call.args, "I32(-1)");
+            assert!(
+                call.source
+                    .to_string()
+                    .ends_with("main.lua:7: negative input")
+            );
+            assert!(!call.source.to_string().contains("stack traceback"));
+            assert!(call.traceback.as_deref().unwrap().contains("main.lua:7"));
+        }
+        _ => panic!("unexpected error: {error}"),
+    }
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn load_errors_carry_the_plugin() {
+    let mut loader = loader_init(LuaManager::new());
+    let error = match loader
+        .load_plugin_now(get_plugin_path("lifecycle_fail", "1.0.0").to_str().unwrap())
+    {
+        Err((None, Some(LoadPluginError::LoadPluginByManager(error)))) => error,
+        result => panic!("unexpected result: {result:?}"),
+    };
+    match error.downcast_ref::<ManagerError>() {
+        Some(ManagerError::Plugin(PluginError::Runtime {
+            plugin,
+            traceback,
+            source,
+        })) => {
+            assert!(plugin.starts_with("lifecycle_fail"));
+            assert!(
+                source
+                    .to_string()
+                    .ends_with("main.lua:2: resource unavailable")
+            );
+            assert!(traceback.as_deref().unwrap().contains("main.lua:2"));
+        }
+        _ => panic!("unexpected error: {error}"),
+    }
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn host_function_errors_name_the_function() {
+    let mut loader = loader_init(LuaManager::new());
+    loader.context(|mut ctx| {
+        ctx.register_function(DynamicFunction::new(
+            "add",
+            vec![
+                Arg::new("a", VariableType::I64),
+                Arg::new("b", VariableType::I64),
+            ],
+            Some(Arg::new("c", VariableType::I64)),
+            |_| Err("host failure".into()),
+        ))
+    });
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("host_refs", "1.0.0").to_str().unwrap())
+        .unwrap();
+
+    let error = loader
+        .get_plugin_by_bundle(&bundle)
+        .unwrap()
+        .call_function("call_stored", &[Variable::I32(2), Variable::I32(3)])
+        .unwrap()
+        .unwrap_err()
+        .to_string();
+    assert!(
+        error.contains("Call to `call_stored(I32(2), I32(3))` of plugin"),
+        "{error}"
+    );
+    assert!(
+        error.contains("Call to `add(I64(2), I64(3))` failed: host failure"),
+        "{error}"
+    );
+
+    loader.stop().unwrap();
+}
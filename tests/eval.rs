@@ -0,0 +1,62 @@
+use plux_lua_manager::{
+    LuaManager,
+    testing::{PluginFixture, TestHost},
+};
+use plux_rs::{Bundle, variable::Variable};
+
+const GREETER: &str = r#"
+    greeting = "hello"
+    return {
+        { name = "greet", inputs = {}, output = "string", func = function()
+            return greeting
+        end },
+    }"#;
+
+#[test]
+fn eval_runs_in_the_plugin_environment() {
+    let mut host = TestHost::new();
+    let bundle = host
+        .load(PluginFixture::new("greeter").main(GREETER))
+        .unwrap();
+    let manager = host.manager();
+
+    assert_eq!(
+        manager.eval(&bundle, "greeting .. '!'").unwrap(),
+        Some(Variable::String("hello!".to_string()))
+    );
+    assert_eq!(manager.eval(&bundle, "greeting = 'patched'").unwrap(), None);
+    assert_eq!(
+        manager
+            .eval(&bundle, "local n = 2\nreturn n, n * 2")
+            .unwrap(),
+        Some(Variable::List(vec![Variable::I64(2), Variable::I64(4)]))
+    );
+    host.assert_call(
+        &bundle,
+        "greet",
+        &[],
+        Some(Variable::String("patched".to_string())),
+    );
+
+    assert!(manager.eval(&bundle, "error('boom')").is_err());
+    assert!(manager.eval(&bundle, "return (").is_err());
+    let missing = Bundle::from_filename("missing-v1.0.0.lua").unwrap();
+    assert!(manager.eval(&missing, "1").is_err());
+}
+
+#[test]
+fn eval_sees_each_plugin_of_a_shared_state() {
+    let mut host = TestHost::with_manager(LuaManager::new().with_shared_state(true));
+    let first = host
+        .load(PluginFixture::new("first").main(GREETER))
+        .unwrap();
+    let second = host
+        .load(PluginFixture::new("second").main(GREETER))
+        .unwrap();
+
+    host.manager().eval(&first, "greeting = 'first'").unwrap();
+    assert_eq!(
+        host.manager().eval(&second, "greeting").unwrap(),
+        Some(Variable::String("hello".to_string()))
+    );
+}
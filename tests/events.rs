@@ -0,0 +1,42 @@
+mod utils;
+
+use std::time::Duration;
+
+use plux_lua_manager::LuaManager;
+use plux_rs::variable::Variable;
+
+use crate::utils::{get_plugin_path, loader_init};
+
+#[test]
+fn events_are_delivered_on_tick() {
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+    let [emitter, listener] = ["events_emitter", "events_listener"].map(|id| {
+        loader
+            .load_plugin_now(get_plugin_path(id, "1.0.0").to_str().unwrap())
+            .unwrap()
+    });
+    let call = |bundle, name, args: &[Variable]| {
+        loader
+            .get_plugin_by_bundle(bundle)
+            .unwrap()
+            .call_function(name, args)
+            .unwrap()
+            .unwrap()
+    };
+
+    call(&emitter, "resize", &[Variable::I64(640)]);
+    assert_eq!(call(&listener, "last_event", &[]), None);
+
+    let report = manager.tick(Duration::from_secs(1));
+    assert_eq!(report.events_delivered, 1);
+    assert_eq!(
+        call(&listener, "last_event", &[]),
+        Some(Variable::String("events_emitter:640".to_string()))
+    );
+
+    // Delivered events are not delivered again
+    assert_eq!(manager.tick(Duration::from_secs(1)).events_delivered, 0);
+
+    loader.stop().unwrap();
+}
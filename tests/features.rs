@@ -0,0 +1,68 @@
+use plux_lua_manager::{
+    LuaManager,
+    testing::{PluginFixture, TestHost},
+};
+use plux_rs::variable::Variable;
+
+fn featured(id: &str) -> PluginFixture {
+    PluginFixture::new(id)
+        .config(&format!(
+            "name = \"{id}\"\ndescription = \"\"\nauthor = \"\"\n\n[features]\nfast = true\nfancy = false\ntelemetry = true\n"
+        ))
+        .main(
+            r#"return {
+                { name = "enabled", inputs = {}, output = "string", func = function()
+                    local enabled = {}
+                    for _, name in ipairs({ "fast", "fancy", "telemetry", "undeclared" }) do
+                        if plugin.features[name] then
+                            table.insert(enabled, name)
+                        end
+                    end
+                    return table.concat(enabled, " ")
+                end },
+            }"#,
+        )
+}
+
+#[test]
+fn declared_features_are_enabled_by_default() {
+    let mut host = TestHost::new();
+    let bundle = host.load(featured("defaults")).unwrap();
+
+    host.assert_call(
+        &bundle,
+        "enabled",
+        &[],
+        Some(Variable::String("fast telemetry".to_string())),
+    );
+    let features = host.manager().features(&bundle).unwrap();
+    assert_eq!(features.len(), 3);
+    assert!(!features["fancy"]);
+}
+
+#[test]
+fn the_host_toggles_features_at_registration() {
+    let manager = LuaManager::new().with_feature_toggle(|bundle, feature, default| {
+        match (bundle.id.as_str(), feature) {
+            ("toggled", "fancy") => true,
+            (_, "telemetry") => false,
+            _ => default,
+        }
+    });
+    let mut host = TestHost::with_manager(manager);
+    let toggled = host.load(featured("toggled")).unwrap();
+    let other = host.load(featured("other")).unwrap();
+
+    host.assert_call(
+        &toggled,
+        "enabled",
+        &[],
+        Some(Variable::String("fast fancy".to_string())),
+    );
+    host.assert_call(
+        &other,
+        "enabled",
+        &[],
+        Some(Variable::String("fast".to_string())),
+    );
+}
@@ -0,0 +1,74 @@
+#![cfg(feature = "fennel")]
+
+use plux_lua_manager::{
+    LuaManager, ManagerError, PluginError,
+    testing::{PluginFixture, TestHost},
+};
+use plux_rs::variable::Variable;
+
+/// Stands in for the Fennel compiler: sources are Lua with `;` comments, and
+/// the generated code records how many compilations ran before it
+const FAKE_FENNEL: &str = r#"
+    local fennel = { compilations = 0 }
+    function fennel.compileString(src, options)
+        if src:find("(unbalanced", 1, true) then
+            error(options.filename .. ":1:0 Parse error: expected closing delimiter )", 0)
+        end
+        fennel.compilations = fennel.compilations + 1
+        return "local compilations = " .. fennel.compilations .. "\n" .. src:gsub(";[^\n]*", "")
+    end
+    return fennel
+"#;
+
+fn fixture() -> PluginFixture {
+    PluginFixture::new("lisp")
+        .config("name = \"lisp\"\ndescription = \"\"\nauthor = \"\"\nentry = \"main.fnl\"\n")
+        .file(
+            "helpers.fnl",
+            "; helpers\nreturn { inc = function(x) return x + 1 end }\n",
+        )
+        .file(
+            "main.fnl",
+            r#"; entry point
+local helpers = require("helpers")
+return {
+    { name = "run", inputs = {}, output = "string", func = function()
+        return helpers.inc(1) .. " " .. compilations
+    end },
+}"#,
+        )
+}
+
+#[test]
+fn fennel_sources_are_compiled_once() {
+    let manager = LuaManager::new().with_fennel_compiler(FAKE_FENNEL).unwrap();
+    let mut host = TestHost::with_manager(manager);
+    let bundle = host.load(fixture()).unwrap();
+    host.assert_call(
+        &bundle,
+        "run",
+        &[],
+        Some(Variable::String("2 1".to_string())),
+    );
+
+    // The compiled code is reused on reload
+    host.manager().reload_plugin(&bundle).unwrap();
+    host.assert_call(
+        &bundle,
+        "run",
+        &[],
+        Some(Variable::String("2 1".to_string())),
+    );
+}
+
+#[test]
+fn fennel_compile_errors_fail_the_load() {
+    let manager = LuaManager::new().with_fennel_compiler(FAKE_FENNEL).unwrap();
+    let plugin = fixture().file("main.fnl", "(unbalanced").create().unwrap();
+
+    let results = manager.preload(&[plugin.path()]);
+    let Err(ManagerError::Plugin(PluginError::SourceError(message))) = &results[0] else {
+        panic!("expected a source error, got {:?}", results[0]);
+    };
+    assert!(message.contains("main.fnl:1:0 Parse error"), "{message}");
+}
@@ -0,0 +1,82 @@
+mod utils;
+
+use plux_lua_manager::{EdgeStatus, LuaManager};
+use semver::Version;
+
+use crate::utils::{get_plugin_path, loader_init};
+
+#[test]
+fn dependency_graph_follows_load_state() {
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+    let [c, _, _] = ["graph_c", "graph_b", "graph_a"].map(|id| {
+        loader
+            .register_plugin(get_plugin_path(id, "1.0.0").to_str().unwrap())
+            .unwrap()
+    });
+    loader.load_plugin_by_bundle(&c).unwrap();
+
+    let graph = manager.dependency_graph();
+    let nodes: Vec<_> = graph
+        .nodes
+        .iter()
+        .map(|node| (node.bundle.id.as_str(), node.loaded))
+        .collect();
+    assert_eq!(
+        nodes,
+        vec![("graph_c", true), ("graph_b", false), ("graph_a", false)]
+    );
+
+    let edges: Vec<_> = graph
+        .edges
+        .iter()
+        .map(|edge| {
+            (
+                edge.from.id.as_str(),
+                edge.to.as_str(),
+                edge.optional,
+                edge.resolved.clone(),
+                edge.status,
+            )
+        })
+        .collect();
+    let v1 = Some(Version::new(1, 0, 0));
+    assert_eq!(
+        edges,
+        vec![
+            ("graph_c", "graph_extra", true, None, EdgeStatus::Missing),
+            ("graph_b", "graph_c", false, v1.clone(), EdgeStatus::Loaded),
+            ("graph_a", "graph_b", false, v1, EdgeStatus::Registered),
+        ]
+    );
+
+    let dot = graph.to_dot();
+    assert!(dot.starts_with("digraph dependencies {"));
+    assert!(dot.contains("\"graph_c v1.0.0\" [label=\"graph_c v1.0.0\", style=filled];"));
+    assert!(
+        dot.contains("\"graph_extra (missing)\" [label=\"graph_extra (missing)\", style=dotted];")
+    );
+    assert!(dot.contains("\"graph_b v1.0.0\" -> \"graph_c v1.0.0\" [label=\"^1.0\"];"));
+    assert!(dot.contains(
+        "\"graph_c v1.0.0\" -> \"graph_extra (missing)\" [label=\"^1.0\", style=dashed];"
+    ));
+
+    let json = graph.to_json();
+    assert!(json.starts_with(
+        r#"{"nodes":[{"id":"graph_c","version":"1.0.0","format":"lua","loaded":true},"#
+    ));
+    assert!(json.contains(
+        r#"{"from":{"id":"graph_c","version":"1.0.0"},"to":"graph_extra","requirement":"^1.0","optional":true,"resolved":null,"status":"missing"}"#
+    ));
+    assert!(json.ends_with(
+        r#"{"from":{"id":"graph_a","version":"1.0.0"},"to":"graph_b","requirement":"^1.0","optional":false,"resolved":"1.0.0","status":"registered"}]}"#
+    ));
+
+    // Unloading updates the graph
+    loader.unload_plugin_by_bundle(&c).unwrap();
+    let graph = manager.dependency_graph();
+    assert!(graph.nodes.iter().all(|node| !node.loaded));
+    assert_eq!(graph.edges[1].status, EdgeStatus::Registered);
+
+    loader.stop().unwrap();
+}
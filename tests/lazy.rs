@@ -0,0 +1,70 @@
+mod utils;
+
+use std::sync::{
+    Arc,
+    atomic::{AtomicUsize, Ordering},
+};
+
+use plux_lua_manager::LuaManager;
+use plux_rs::{
+    Bundle, Loader, StdInfo,
+    function::{DynamicFunction, FunctionOutput},
+    variable::Variable,
+};
+
+use crate::utils::{get_plugin_path, loader_init};
+
+/// Loads the `lazy` plugin, counting the executions of its entry script.
+///
+/// plux keeps pointers to the loader, which must not move once plugins are
+/// loaded.
+fn load_lazy(loader: &mut Loader<'static, FunctionOutput, StdInfo>) -> (Bundle, Arc<AtomicUsize>) {
+    let loads = Arc::new(AtomicUsize::new(0));
+    let counter = loads.clone();
+    loader.context(move |mut ctx| {
+        ctx.register_function(DynamicFunction::new("loaded", vec![], None, move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(None)
+        }))
+    });
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("lazy", "1.0.0").to_str().unwrap())
+        .unwrap();
+    (bundle, loads)
+}
+
+#[test]
+fn lazy_plugins_load_on_first_call() {
+    let mut loader = loader_init(LuaManager::new().with_lazy_loading(true));
+    let (bundle, loads) = load_lazy(&mut loader);
+    assert_eq!(loads.load(Ordering::SeqCst), 0);
+
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+    for _ in 0..2 {
+        assert_eq!(
+            plugin
+                .call_function("double", &[Variable::I64(21)])
+                .unwrap()
+                .unwrap(),
+            Some(Variable::I64(42))
+        );
+    }
+    assert_eq!(loads.load(Ordering::SeqCst), 1);
+
+    // Declared functions the entry script does not export fail when called
+    assert!(plugin.call_function("missing", &[]).unwrap().is_err());
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn plugins_load_eagerly_by_default() {
+    let mut loader = loader_init(LuaManager::new());
+    let (bundle, loads) = load_lazy(&mut loader);
+    assert_eq!(loads.load(Ordering::SeqCst), 1);
+
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+    assert!(plugin.call_function("missing", &[]).is_err());
+
+    loader.stop().unwrap();
+}
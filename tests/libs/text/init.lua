@@ -0,0 +1,7 @@
+local text = {}
+
+function text.shout(s)
+    return string.upper(s) .. "!"
+end
+
+return text
@@ -0,0 +1,135 @@
+mod utils;
+
+use std::sync::{Arc, Mutex};
+
+use plux_lua_manager::{
+    LuaManager,
+    testing::{PluginFixture, TestHost},
+};
+use plux_rs::variable::Variable;
+
+use crate::utils::{get_plugin_path, loader_init};
+
+#[test]
+fn on_load_runs_before_calls() {
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("lifecycle", "1.0.0").to_str().unwrap())
+        .unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+
+    assert_eq!(
+        plugin.call_function("status", &[]).unwrap().unwrap(),
+        Some(Variable::String("open".to_string()))
+    );
+
+    // The new state acquires the resource again
+    manager.reload_plugin(&bundle).unwrap();
+    assert_eq!(
+        plugin.call_function("status", &[]).unwrap().unwrap(),
+        Some(Variable::String("open".to_string()))
+    );
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn failing_on_load_fails_the_load() {
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+    let result =
+        loader.load_plugin_now(get_plugin_path("lifecycle_fail", "1.0.0").to_str().unwrap());
+    assert!(result.is_err());
+    assert!(manager.loaded_bundles().is_empty());
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn host_hooks_see_every_state() {
+    let dir = PluginFixture::new("hooked")
+        .main(
+            "return { { name = \"greeting\", inputs = {}, func = function() return host_name end } }",
+        )
+        .create()
+        .unwrap();
+
+    let events = Arc::new(Mutex::new(vec![]));
+    let (before, after, unload) = (events.clone(), events.clone(), events.clone());
+    let manager = LuaManager::new()
+        .with_before_load_hook(move |bundle, lua| {
+            before.lock().unwrap().push(format!("before {bundle}"));
+            lua.globals().set("host_name", "embedder")
+        })
+        .with_after_load_hook(move |bundle, _| {
+            after.lock().unwrap().push(format!("after {bundle}"));
+            Ok(())
+        })
+        .with_unload_hook(move |bundle, _| {
+            unload.lock().unwrap().push(format!("unload {bundle}"));
+            Ok(())
+        });
+    let mut loader = loader_init(manager.clone());
+    let bundle = loader
+        .load_plugin_now(dir.path().to_str().unwrap())
+        .unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+    assert_eq!(
+        plugin.call_function("greeting", &[]).unwrap().unwrap(),
+        Some(Variable::String("embedder".to_string()))
+    );
+
+    manager.reload_plugin(&bundle).unwrap();
+    loader.stop().unwrap();
+    assert_eq!(
+        *events.lock().unwrap(),
+        [
+            "before hooked-v1.0.0.lua",
+            "after hooked-v1.0.0.lua",
+            "before hooked-v1.0.0.lua",
+            "unload hooked-v1.0.0.lua",
+            "after hooked-v1.0.0.lua",
+            "unload hooked-v1.0.0.lua",
+        ]
+    );
+}
+
+#[test]
+fn plugins_are_notified_of_other_plugins() {
+    let manager = LuaManager::new().with_plugin_visibility(|_, other| other.id != "hidden");
+    let mut host = TestHost::with_manager(manager);
+    let watcher = host
+        .load(PluginFixture::new("watcher").main(
+            r#"
+            local seen = {}
+            api.on_plugin_loaded(function(id, version)
+                table.insert(seen, "loaded " .. id .. " " .. version)
+            end)
+            api.on_plugin_unloaded(function(id, version)
+                table.insert(seen, "unloaded " .. id .. " " .. version)
+            end)
+            api.on_plugin_loaded(function(id)
+                error("failing handlers do not stop the others")
+            end)
+            return {
+                { name = "seen", inputs = {}, output = "string", func = function()
+                    return table.concat(seen, ", ")
+                end },
+            }
+            "#,
+        ))
+        .unwrap();
+    let extra = host.load(PluginFixture::new("extra")).unwrap();
+    host.load(PluginFixture::new("hidden")).unwrap();
+    host.loader().unload_plugin_by_bundle(&extra).unwrap();
+
+    host.assert_call(
+        &watcher,
+        "seen",
+        &[],
+        Some(Variable::String(
+            "loaded extra 1.0.0, unloaded extra 1.0.0".to_string(),
+        )),
+    );
+}
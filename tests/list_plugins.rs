@@ -0,0 +1,61 @@
+mod utils;
+
+use plux_lua_manager::{LuaManager, PluginMetadata};
+use plux_rs::{Loader, StdInfo, function::FunctionOutput, variable::Variable};
+
+use crate::utils::{get_plugin_path, loader_init};
+
+fn listed(manager: LuaManager) -> (Loader<'static, FunctionOutput, StdInfo>, Option<Variable>) {
+    let mut loader = loader_init(manager);
+    loader
+        .register_plugin(get_plugin_path("dep_ok", "1.0.0").to_str().unwrap())
+        .unwrap();
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("lister", "1.0.0").to_str().unwrap())
+        .unwrap();
+    let output = loader
+        .get_plugin_by_bundle(&bundle)
+        .unwrap()
+        .call_function("plugins", &[])
+        .unwrap()
+        .unwrap();
+    (loader, output)
+}
+
+#[test]
+fn plugins_list_the_plugins_of_the_host() {
+    let manager = LuaManager::new();
+    let (mut loader, output) = listed(manager.clone());
+    assert_eq!(
+        output,
+        Some(Variable::List(vec![
+            Variable::String("dep_ok 1.0.0 Plux false".to_string()),
+            Variable::String("lister 1.0.0 Plux true".to_string()),
+        ]))
+    );
+
+    let bundle = loader.get_plugins()[1].info().bundle.clone();
+    assert_eq!(
+        manager.metadata(&bundle),
+        Some(PluginMetadata {
+            name: "Lister".to_string(),
+            description: "Lists the plugins it can see".to_string(),
+            author: "Plux".to_string(),
+            license: Some("MIT".to_string()),
+        })
+    );
+    loader.stop().unwrap();
+}
+
+#[test]
+fn visibility_hides_plugins() {
+    let manager = LuaManager::new().with_plugin_visibility(|viewer, plugin| viewer.id == plugin.id);
+    let (mut loader, output) = listed(manager);
+    assert_eq!(
+        output,
+        Some(Variable::List(vec![Variable::String(
+            "lister 1.0.0 Plux true".to_string()
+        )]))
+    );
+    loader.stop().unwrap();
+}
@@ -0,0 +1,60 @@
+mod utils;
+
+use std::sync::Mutex;
+
+use log::{Level, Log, Metadata, Record};
+use plux_lua_manager::LuaManager;
+use plux_rs::variable::Variable;
+
+use crate::utils::{get_plugin_path, loader_init};
+
+/// Records the messages logged with a plugin target.
+struct Recorder(Mutex<Vec<(String, Level, String)>>);
+
+impl Log for Recorder {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        if record.target().starts_with("logger") {
+            self.0.lock().unwrap().push((
+                record.target().to_string(),
+                record.level(),
+                record.args().to_string(),
+            ));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static RECORDER: Recorder = Recorder(Mutex::new(vec![]));
+
+#[test]
+fn plugin_output_goes_to_the_host_logger() {
+    log::set_logger(&RECORDER).unwrap();
+    log::set_max_level(log::LevelFilter::Trace);
+
+    let mut loader = loader_init(LuaManager::new());
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("logger", "1.0.0").to_str().unwrap())
+        .unwrap();
+    loader
+        .get_plugin_by_bundle(&bundle)
+        .unwrap()
+        .call_function("greet", &[Variable::String("bob".to_string())])
+        .unwrap()
+        .unwrap();
+
+    let target = bundle.to_string();
+    assert_eq!(
+        *RECORDER.0.lock().unwrap(),
+        vec![
+            (target.clone(), Level::Info, "hello\tbob".to_string()),
+            (target, Level::Warn, "greeted\t1\tuser".to_string()),
+        ]
+    );
+
+    loader.stop().unwrap();
+}
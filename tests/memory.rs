@@ -0,0 +1,65 @@
+mod utils;
+
+use plux_lua_manager::LuaManager;
+use plux_rs::variable::Variable;
+
+use crate::utils::{get_plugin_path, loader_init};
+
+const LIMIT: usize = 4 * 1024 * 1024;
+
+#[test]
+fn memory_limit_fails_calls_gracefully() {
+    let manager = LuaManager::new().with_memory_limit(LIMIT);
+    let mut loader = loader_init(manager.clone());
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("memory_hog", "1.0.0").to_str().unwrap())
+        .unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+
+    let error = plugin
+        .call_function("allocate", &[Variable::I64(2 * LIMIT as i64)])
+        .unwrap()
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("exceeded its memory limit"), "{error}");
+
+    // The state survives and serves calls within the limit
+    assert_eq!(
+        plugin
+            .call_function("allocate", &[Variable::I64(1024)])
+            .unwrap()
+            .unwrap(),
+        Some(Variable::I64(1024))
+    );
+    assert_eq!(
+        manager.plugin_runtime_info(&bundle).unwrap().memory_limit,
+        Some(LIMIT)
+    );
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn gc_collect_frees_garbage() {
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("memory_hog", "1.0.0").to_str().unwrap())
+        .unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+
+    plugin
+        .call_function("allocate", &[Variable::I64(LIMIT as i64)])
+        .unwrap()
+        .unwrap();
+    let used = manager.memory_usage(&bundle).unwrap();
+    assert!(used > 0);
+
+    let collected = manager.gc_collect(&bundle).unwrap();
+    assert!(collected <= used);
+    assert!(collected < LIMIT / 4);
+    assert_eq!(manager.memory_usage(&bundle).unwrap(), collected);
+
+    loader.stop().unwrap();
+    assert!(manager.memory_usage(&bundle).is_err());
+}
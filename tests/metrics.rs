@@ -0,0 +1,67 @@
+mod utils;
+
+use plux_lua_manager::LuaManager;
+use plux_rs::variable::Variable;
+
+use crate::utils::{get_plugin_path, loader_init};
+
+#[test]
+fn metrics_count_calls_and_errors() {
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("batch", "1.0.0").to_str().unwrap())
+        .unwrap();
+    assert!(manager.metrics()[&bundle].is_empty());
+
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+    plugin
+        .call_function("transform", &[Variable::I32(1)])
+        .unwrap()
+        .unwrap();
+    let batches = vec![vec![Variable::I32(2)], vec![Variable::I32(3)]];
+    manager.call_batch(&bundle, "transform", &batches).unwrap();
+    let results = manager
+        .call_batch(&bundle, "checked", &[vec![Variable::I32(-1)]])
+        .unwrap();
+    assert!(results[0].is_err());
+
+    let metrics = manager.metrics();
+    let functions = &metrics[&bundle];
+    assert_eq!(
+        functions.keys().collect::<Vec<_>>(),
+        vec!["transform", "checked"]
+    );
+
+    let transform = &functions["transform"];
+    assert_eq!((transform.calls, transform.errors), (3, 0));
+    assert!(transform.p50 <= transform.p90);
+    assert!(transform.p90 <= transform.p99);
+    assert!(transform.p99 <= transform.max_time);
+    assert!(transform.max_time <= transform.total_time);
+
+    let checked = &functions["checked"];
+    assert_eq!((checked.calls, checked.errors), (1, 1));
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn metrics_survive_reload() {
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("batch", "1.0.0").to_str().unwrap())
+        .unwrap();
+    manager
+        .call_batch(&bundle, "transform", &[vec![Variable::I32(1)]])
+        .unwrap();
+
+    manager.reload_plugin(&bundle).unwrap();
+    assert_eq!(manager.metrics()[&bundle]["transform"].calls, 1);
+
+    loader.stop().unwrap();
+    assert!(manager.metrics().is_empty());
+}
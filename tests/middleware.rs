@@ -0,0 +1,75 @@
+use std::sync::{Arc, Mutex};
+
+use plux_lua_manager::{
+    CallInfo, CallTarget, LuaManager, ManagerError, Next, PluginError,
+    testing::{PluginFixture, TestHost},
+};
+use plux_rs::{
+    function::{Arg, DynamicFunction},
+    variable::{Variable, VariableType},
+};
+
+#[test]
+fn middleware_wraps_plugin_and_host_calls() {
+    let calls = Arc::new(Mutex::new(vec![]));
+    let log = calls.clone();
+    let manager = LuaManager::new()
+        .with_middleware(
+            move |call: &CallInfo<'_>, args: Vec<Variable>, next: Next<'_>| {
+                log.lock().unwrap().push(format!(
+                    "{:?} {} {}",
+                    call.target, call.bundle.id, call.function
+                ));
+                next.run(args)
+            },
+        )
+        .with_middleware(|call: &CallInfo<'_>, args: Vec<Variable>, next: Next<'_>| {
+            // Scrub the secrets the plugin passes to the host
+            match call.target {
+                CallTarget::Host => next.run(vec![Variable::String("***".to_string())]),
+                CallTarget::Plugin if call.function == "forbidden" => Err(ManagerError::from(
+                    PluginError::SourceError("not authorized".to_string()),
+                )),
+                CallTarget::Plugin => next.run(args),
+            }
+        });
+
+    let mut host = TestHost::with_manager(manager);
+    host.loader().context(|mut ctx| {
+        ctx.register_function(DynamicFunction::new(
+            "echo",
+            vec![Arg::new("text", VariableType::String)],
+            Some(Arg::new("output", VariableType::String)),
+            |args| Ok(Some(args[0].clone())),
+        ))
+    });
+    let bundle = host
+        .load(PluginFixture::new("wrapped").main(
+            r#"return {
+                { name = "leak", inputs = { "secret: string" }, output = "string",
+                  func = function(secret) return host.echo(secret) end },
+                { name = "forbidden", inputs = {}, func = function() return 1 end },
+            }"#,
+        ))
+        .unwrap();
+
+    host.assert_call(
+        &bundle,
+        "leak",
+        &[Variable::String("hunter2".to_string())],
+        Some(Variable::String("***".to_string())),
+    );
+    host.assert_call_fails(&bundle, "forbidden", &[], "not authorized");
+    assert_eq!(
+        *calls.lock().unwrap(),
+        [
+            "Plugin wrapped leak",
+            "Host wrapped echo",
+            "Plugin wrapped forbidden"
+        ]
+    );
+
+    // Rejected calls never reach the plugin
+    let metrics = host.manager().metrics();
+    assert!(!metrics[&bundle].contains_key("forbidden"));
+}
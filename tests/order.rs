@@ -0,0 +1,65 @@
+mod utils;
+
+use std::sync::{Arc, Mutex};
+
+use plux_lua_manager::LuaManager;
+use plux_rs::{
+    function::{Arg, DynamicFunction},
+    variable::VariableType,
+};
+
+use crate::utils::{get_plugin_path, loader_init};
+
+const LOAD_ORDER: [&str; 3] = ["order_c", "order_a", "order_b"];
+
+/// Loads the fixtures, then unloads them through the manager's shutdown and
+/// returns the loaded bundles and the recorded `on_unload` events.
+fn run() -> (Vec<String>, Vec<String>) {
+    let events = Arc::new(Mutex::new(vec![]));
+    let record = {
+        let events = events.clone();
+        DynamicFunction::new(
+            "record",
+            vec![Arg::new("id", VariableType::String)],
+            None,
+            move |args| {
+                events
+                    .lock()
+                    .unwrap()
+                    .push(args[0].parse_ref::<String>().clone());
+                Ok(None)
+            },
+        )
+    };
+
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+    loader.context(|mut ctx| ctx.register_function(record));
+    for id in LOAD_ORDER {
+        loader
+            .load_plugin_now(get_plugin_path(id, "1.0.0").to_str().unwrap())
+            .unwrap();
+    }
+
+    let bundles = manager
+        .loaded_bundles()
+        .iter()
+        .map(|bundle| bundle.id.clone())
+        .collect();
+
+    manager.unload_all(&mut loader).unwrap();
+    loader.stop().unwrap();
+    let events = events.lock().unwrap().clone();
+    (bundles, events)
+}
+
+#[test]
+fn plugins_iterate_in_load_order() {
+    let expected_shutdown: Vec<_> = LOAD_ORDER.iter().rev().map(|id| id.to_string()).collect();
+
+    for _ in 0..3 {
+        let (bundles, events) = run();
+        assert_eq!(bundles, LOAD_ORDER);
+        assert_eq!(events, expected_shutdown);
+    }
+}
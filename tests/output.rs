@@ -0,0 +1,53 @@
+use std::sync::{Arc, Mutex};
+
+use plux_lua_manager::{
+    LuaManager, OutputStream,
+    testing::{PluginFixture, TestHost},
+};
+use plux_rs::variable::Variable;
+
+const NOISY: &str = r#"return {
+    { name = "run", inputs = {}, output = "string", func = function()
+        print("hello", 1)
+        io.write("a", 2, "\n"):write("b\n")
+        io.stderr:write("oops\n")
+        local ok = pcall(io.write, {})
+        return tostring(ok)
+    end },
+}"#;
+
+#[test]
+fn output_is_captured_per_plugin() {
+    let sunk = Arc::new(Mutex::new(vec![]));
+    let sink = sunk.clone();
+    let manager = LuaManager::new()
+        .with_output_capture(1024)
+        .with_output_sink(move |bundle, stream, text| {
+            sink.lock()
+                .unwrap()
+                .push((bundle.id.clone(), stream, text.to_string()))
+        });
+
+    let mut host = TestHost::with_manager(manager);
+    let first = host.load(PluginFixture::new("first").main(NOISY)).unwrap();
+    let second = host.load(PluginFixture::new("second").main(NOISY)).unwrap();
+
+    host.assert_call(&first, "run", &[], Some(Variable::String("false".into())));
+    assert_eq!(
+        host.manager().plugin_output(&first),
+        "hello\t1\na2\nb\noops\n"
+    );
+    assert_eq!(host.manager().plugin_output(&second), "");
+    assert!(sunk.lock().unwrap().contains(&(
+        "first".to_string(),
+        OutputStream::Stderr,
+        "oops\n".to_string()
+    )));
+
+    host.assert_call(&second, "run", &[], Some(Variable::String("false".into())));
+    assert_eq!(
+        host.manager().take_plugin_output(&second),
+        "hello\t1\na2\nb\noops\n"
+    );
+    assert_eq!(host.manager().plugin_output(&second), "");
+}
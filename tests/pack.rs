@@ -0,0 +1,60 @@
+mod utils;
+
+use plux_lua_manager::LuaManager;
+use plux_rs::variable::Variable;
+
+use crate::utils::{get_plugin_path, loader_init};
+
+#[test]
+fn pack_mounts_sub_plugins_as_plugins() {
+    let manager = LuaManager::new();
+    let paths = manager
+        .mount_pack(get_plugin_path("pack", "1.0.0"))
+        .unwrap();
+    let names: Vec<_> = paths
+        .iter()
+        .map(|path| path.file_name().unwrap().to_str().unwrap())
+        .collect();
+    assert_eq!(names, ["math-v1.0.0.lua", "report-v1.0.0.lua"]);
+
+    let mut loader = loader_init(manager);
+    let bundles: Vec<_> = paths
+        .iter()
+        .map(|path| loader.load_plugin_now(path.to_str().unwrap()).unwrap())
+        .collect();
+
+    let math = loader.get_plugin_by_bundle(&bundles[0]).unwrap();
+    let doubled = math
+        .call_function("double", &[Variable::I32(21)])
+        .unwrap()
+        .unwrap();
+    assert_eq!(doubled, Some(Variable::I64(42)));
+
+    let report = loader.get_plugin_by_bundle(&bundles[1]).unwrap();
+    assert!(
+        report
+            .info()
+            .info
+            .depends
+            .iter()
+            .any(|depend| depend.id == "math")
+    );
+    let described = report
+        .call_function("describe", &[Variable::I32(4)])
+        .unwrap()
+        .unwrap();
+    assert_eq!(described, Some(Variable::String("double = 8".to_string())));
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn packs_cannot_be_loaded_directly() {
+    let mut loader = loader_init(LuaManager::new());
+    assert!(
+        loader
+            .load_plugin_now(get_plugin_path("pack", "1.0.0").to_str().unwrap())
+            .is_err()
+    );
+    loader.stop().unwrap();
+}
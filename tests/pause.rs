@@ -0,0 +1,120 @@
+mod utils;
+
+use std::{thread, time::Duration};
+
+use plux_lua_manager::LuaManager;
+use plux_rs::{Bundle, Loader, StdInfo, function::FunctionOutput, variable::Variable};
+
+use crate::utils::{get_plugin_path, loader_init};
+
+fn load(loader: &mut Loader<'static, FunctionOutput, StdInfo>, id: &str) -> Bundle {
+    loader
+        .load_plugin_now(get_plugin_path(id, "1.0.0").to_str().unwrap())
+        .unwrap()
+}
+
+fn call(
+    loader: &Loader<'static, FunctionOutput, StdInfo>,
+    bundle: &Bundle,
+    name: &str,
+) -> FunctionOutput {
+    loader
+        .get_plugin_by_bundle(bundle)
+        .unwrap()
+        .call_function(name, &[])
+        .unwrap()
+}
+
+#[test]
+fn paused_plugin_keeps_its_state() {
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+    let counter = &load(&mut loader, "counter");
+
+    assert_eq!(
+        call(&loader, counter, "increment").unwrap(),
+        Some(Variable::I64(1))
+    );
+
+    manager.pause(counter).unwrap();
+    assert!(manager.is_paused(counter));
+    assert!(manager.plugin_runtime_info(counter).unwrap().paused);
+
+    let error = call(&loader, counter, "increment").unwrap_err().to_string();
+    assert!(error.contains("is paused"), "{error}");
+
+    manager.resume(counter).unwrap();
+    assert!(!manager.is_paused(counter));
+    assert_eq!(
+        call(&loader, counter, "increment").unwrap(),
+        Some(Variable::I64(2))
+    );
+
+    // A paused plugin can still be unloaded
+    manager.pause(counter).unwrap();
+    loader.unload_plugin_by_bundle(counter).unwrap();
+    assert!(manager.loaded_bundles().is_empty());
+}
+
+#[test]
+fn paused_dependency_fails_with_kind() {
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+    let b = &load(&mut loader, "degrade_b");
+    let a = &load(&mut loader, "degrade_a");
+
+    manager.pause(b).unwrap();
+    let result = call(&loader, a, "use_b").unwrap();
+    assert!(
+        matches!(&result, Some(Variable::String(s)) if s.starts_with("paused / ")),
+        "{result:?}"
+    );
+
+    manager.resume(b).unwrap();
+    assert_eq!(
+        call(&loader, a, "use_b").unwrap(),
+        Some(Variable::String("ok 42".to_string()))
+    );
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn tick_skips_tasks_of_paused_plugins() {
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+    let ticker = load(&mut loader, "ticker_a");
+
+    manager.pause(&ticker).unwrap();
+    assert_eq!(manager.tick(Duration::from_secs(1)).tasks_resumed, 0);
+
+    manager.resume(&ticker).unwrap();
+    assert_eq!(manager.tick(Duration::from_secs(1)).tasks_resumed, 1);
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn calls_wait_for_resume_within_timeout() {
+    let manager = LuaManager::new().with_pause_timeout(Duration::from_secs(5));
+    let mut loader = loader_init(manager.clone());
+    let counter = load(&mut loader, "counter");
+
+    manager.pause(&counter).unwrap();
+    let resumer = {
+        let manager = manager.clone();
+        let counter = counter.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            manager.resume(&counter).unwrap();
+        })
+    };
+
+    assert_eq!(
+        call(&loader, &counter, "increment").unwrap(),
+        Some(Variable::I64(1))
+    );
+    resumer.join().unwrap();
+
+    loader.stop().unwrap();
+}
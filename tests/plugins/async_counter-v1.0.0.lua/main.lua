@@ -0,0 +1,10 @@
+local function count_to(n)
+    for _ = 1, n do
+        coroutine.yield()
+    end
+    return n
+end
+
+return {
+    { name = "count_to", inputs = { "n: i64" }, output = "i64", async = true, func = count_to },
+}
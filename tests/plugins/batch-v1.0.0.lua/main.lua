@@ -0,0 +1,15 @@
+local function transform(x)
+    return x * 2
+end
+
+local function checked(x)
+    if x < 0 then
+        error("negative input")
+    end
+    return x
+end
+
+return {
+    { name = "transform", inputs = { "x" }, func = transform },
+    { name = "checked", inputs = { "x" }, func = checked },
+}
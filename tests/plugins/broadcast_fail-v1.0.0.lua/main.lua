@@ -0,0 +1,5 @@
+function on_event(name)
+    error("cannot handle " .. name)
+end
+
+return {}
@@ -0,0 +1,5 @@
+function on_event(name, value)
+    return name .. ":" .. value
+end
+
+return {}
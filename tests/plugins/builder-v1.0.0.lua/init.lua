@@ -0,0 +1,7 @@
+local function greeting()
+    return "hello from " .. APP_NAME
+end
+
+return {
+    { name = "greeting", inputs = {}, output = "string", func = greeting },
+}
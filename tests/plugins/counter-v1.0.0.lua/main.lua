@@ -0,0 +1,18 @@
+local count = 0
+
+function on_save_state()
+    return { count }
+end
+
+function on_restore_state(saved)
+    count = saved[1]
+end
+
+local function increment()
+    count = count + 1
+    return count
+end
+
+return {
+    { name = "increment", inputs = {}, func = increment },
+}
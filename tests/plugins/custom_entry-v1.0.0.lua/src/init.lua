@@ -0,0 +1,7 @@
+local function entry()
+    return "src/init.lua"
+end
+
+return {
+    { name = "entry", inputs = {}, output = "string", func = entry },
+}
@@ -0,0 +1,22 @@
+local events = {}
+
+function on_dependency_unloaded(id, version)
+    table.insert(events, id .. " " .. version)
+end
+
+local function use_b()
+    local ok, result = pcall(api.call_function_depend, "degrade_b", "1.0.0", "value")
+    if ok then
+        return "ok " .. result
+    end
+    return result.kind .. " / " .. tostring(result)
+end
+
+local function get_events()
+    return events
+end
+
+return {
+    { name = "use_b", inputs = {}, func = use_b },
+    { name = "get_events", inputs = {}, func = get_events },
+}
@@ -0,0 +1,7 @@
+local function value()
+    return 42
+end
+
+return {
+    { name = "value", inputs = {}, func = value },
+}
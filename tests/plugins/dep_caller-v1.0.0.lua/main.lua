@@ -0,0 +1,29 @@
+local function by_requirement()
+    return api.call_function_depend("degrade_b", "^1.0", "value")
+end
+
+local function by_declared()
+    return api.call_function_depend("degrade_b", nil, "value")
+end
+
+local function unmatched()
+    local ok, err = pcall(api.call_function_depend, "degrade_b", "^2.0", "value")
+    return not ok and tostring(err)
+end
+
+local function optional_unmatched()
+    local found = api.call_function_optional_depend("degrade_b", "~1.1", "value")
+    return found
+end
+
+local function via_deps()
+    return deps.degrade_b.value() .. " " .. tostring(deps.undeclared == nil)
+end
+
+return {
+    { name = "by_requirement", inputs = {}, func = by_requirement },
+    { name = "by_declared", inputs = {}, func = by_declared },
+    { name = "unmatched", inputs = {}, func = unmatched },
+    { name = "optional_unmatched", inputs = {}, func = optional_unmatched },
+    { name = "via_deps", inputs = {}, func = via_deps },
+}
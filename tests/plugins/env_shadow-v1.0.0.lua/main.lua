@@ -0,0 +1,22 @@
+-- Shadows the host function of the same name
+function add(a, b)
+    return "plugin add"
+end
+
+local function own_add()
+    return add(1, 2)
+end
+
+local function host_api()
+    return api ~= nil and rawget(_G, "api") == nil
+end
+
+local function assign_host_global()
+    return (pcall(load("string = nil")))
+end
+
+return {
+    { name = "own_add", inputs = {}, func = own_add },
+    { name = "host_api", inputs = {}, func = host_api },
+    { name = "assign_host_global", inputs = {}, func = assign_host_global },
+}
@@ -0,0 +1,7 @@
+local function resize(width)
+    api.events.emit("resized", { width = width })
+end
+
+return {
+    { name = "resize", inputs = { "width: i64" }, func = resize },
+}
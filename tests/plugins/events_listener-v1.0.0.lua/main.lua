@@ -0,0 +1,13 @@
+local last = nil
+
+api.events.on("resized", function(payload, source)
+    last = source .. ":" .. payload.width
+end)
+
+local function last_event()
+    return last
+end
+
+return {
+    { name = "last_event", inputs = {}, func = last_event },
+}
@@ -0,0 +1,12 @@
+local function has(id, requirement)
+    return api.has_depend(id, requirement)
+end
+
+local function has_any(id)
+    return api.has_depend(id)
+end
+
+return {
+    { name = "has", inputs = { "string", "string" }, output = "bool", func = has },
+    { name = "has_any", inputs = { "string" }, output = "bool", func = has_any },
+}
@@ -0,0 +1,12 @@
+local function call_flat(a, b)
+    return add(a, b)
+end
+
+local function same_function()
+    return rawequal(add, host.add)
+end
+
+return {
+    { name = "call_flat", inputs = { "a", "b" }, func = call_flat },
+    { name = "same_function", inputs = {}, func = same_function },
+}
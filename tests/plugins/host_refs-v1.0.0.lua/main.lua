@@ -0,0 +1,22 @@
+-- Host functions stored at load time
+local handlers = { add = host.add }
+
+local function same_identity()
+    return handlers.add == host.add and rawequal(handlers.add, host.add)
+end
+
+local function call_stored(a, b)
+    local ok, result = pcall(handlers.add, a, b)
+    assert(ok, result)
+    return result
+end
+
+local function call_late(a, b)
+    return host.mul(a, b)
+end
+
+return {
+    { name = "same_identity", inputs = {}, func = same_identity },
+    { name = "call_stored", inputs = { "a", "b" }, func = call_stored },
+    { name = "call_late", inputs = { "a", "b" }, func = call_late },
+}
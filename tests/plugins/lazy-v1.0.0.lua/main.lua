@@ -0,0 +1,9 @@
+host.loaded()
+
+local function double(x)
+    return x * 2
+end
+
+return {
+    { name = "double", inputs = { "x: i64" }, output = "i64", func = double },
+}
@@ -0,0 +1,5 @@
+local text = require("text")
+
+return {
+    { name = "shout", inputs = { "string" }, output = "string", func = text.shout },
+}
@@ -0,0 +1,13 @@
+local resource = nil
+
+function on_load()
+    resource = "open"
+end
+
+local function status()
+    return resource or "closed"
+end
+
+return {
+    { name = "status", inputs = {}, output = "string", func = status },
+}
@@ -0,0 +1,5 @@
+function on_load()
+    error("resource unavailable")
+end
+
+return {}
@@ -0,0 +1,17 @@
+local function plugins()
+    local found = {}
+    for _, plugin in ipairs(api.list_plugins()) do
+        table.insert(found, string.format(
+            "%s %s %s %s",
+            plugin.id,
+            plugin.version,
+            tostring(plugin.author),
+            tostring(plugin.loaded)
+        ))
+    end
+    return found
+end
+
+return {
+    { name = "plugins", inputs = {}, func = plugins },
+}
@@ -0,0 +1,8 @@
+local function greet(name)
+    print("hello", name)
+    log.warn("greeted", 1, "user")
+end
+
+return {
+    { name = "greet", inputs = { "name: string" }, func = greet },
+}
@@ -0,0 +1,7 @@
+local function allocate(bytes)
+    return #string.rep("x", bytes)
+end
+
+return {
+    { name = "allocate", inputs = { "bytes: i64" }, output = "i64", func = allocate },
+}
@@ -0,0 +1,15 @@
+local M = {}
+
+function M.add(a, b)
+    return a + b
+end
+
+M.greet = {
+    inputs = { "name: string" },
+    output = "string",
+    func = function(name)
+        return "hello " .. name
+    end,
+}
+
+return M
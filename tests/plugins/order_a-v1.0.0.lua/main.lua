@@ -0,0 +1,5 @@
+function on_unload()
+    host.record("order_a")
+end
+
+return {}
@@ -0,0 +1,7 @@
+local util = {}
+
+function util.label(name, value)
+    return name .. " = " .. value
+end
+
+return util
@@ -0,0 +1,7 @@
+local function double(x)
+    return x * 2
+end
+
+return {
+    { name = "double", inputs = { "x" }, func = double },
+}
@@ -0,0 +1,10 @@
+local util = require("lib.util")
+
+local function describe(x)
+    local doubled = api.call_function_depend("math", "1.0.0", "double", x)
+    return util.label("double", doubled)
+end
+
+return {
+    { name = "describe", inputs = { "x" }, func = describe },
+}
@@ -0,0 +1,10 @@
+local function ping(n)
+    if n <= 0 then
+        return 0
+    end
+    return deps.pong_b.pong(n - 1) + 1
+end
+
+return {
+    { name = "ping", inputs = { "n" }, output = "i64", func = ping },
+}
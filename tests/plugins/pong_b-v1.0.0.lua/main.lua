@@ -0,0 +1,10 @@
+local function pong(n)
+    if n <= 0 then
+        return 0
+    end
+    return deps.ping_a.ping(n - 1) + 1
+end
+
+return {
+    { name = "pong", inputs = { "n" }, output = "i64", func = pong },
+}
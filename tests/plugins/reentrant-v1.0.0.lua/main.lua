@@ -0,0 +1,12 @@
+local function outer()
+    return host.reenter() + 1
+end
+
+local function leaf()
+    return 41
+end
+
+return {
+    { name = "outer", inputs = {}, func = outer },
+    { name = "leaf", inputs = {}, func = leaf },
+}
@@ -0,0 +1,11 @@
+local names = { "io", "os", "string", "table", "math", "load", "dofile" }
+
+return function()
+    local found = {}
+    for _, name in ipairs(names) do
+        if _G[name] ~= nil then
+            table.insert(found, name)
+        end
+    end
+    return found
+end
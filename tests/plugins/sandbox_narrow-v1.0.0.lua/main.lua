@@ -0,0 +1,5 @@
+local libs = require("libs")
+
+return {
+    { name = "libs", inputs = {}, func = libs },
+}
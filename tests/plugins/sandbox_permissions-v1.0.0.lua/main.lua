@@ -0,0 +1,21 @@
+local names = {
+    { "io", "open" },
+    { "io", "popen" },
+    { "os", "remove" },
+    { "os", "execute" },
+    { "os", "time" },
+}
+
+local function functions()
+    local found = {}
+    for _, name in ipairs(names) do
+        if _G[name[1]][name[2]] ~= nil then
+            table.insert(found, name[1] .. "." .. name[2])
+        end
+    end
+    return found
+end
+
+return {
+    { name = "functions", inputs = {}, func = functions },
+}
@@ -0,0 +1,20 @@
+local function describe()
+    return {
+        plugin.id,
+        plugin.version,
+        plugin.format,
+        plugin.config.name,
+        plugin.config.capabilities[1],
+        plugin.config.optional_depends.dep_opt,
+        tostring(plugin.config.license),
+    }
+end
+
+local function path()
+    return plugin.path
+end
+
+return {
+    { name = "describe", inputs = {}, func = describe },
+    { name = "path", inputs = {}, func = path },
+}
@@ -0,0 +1,11 @@
+local function read()
+    local ok, err = pcall(api.shared.get, "layout", "width")
+    if ok then
+        return "read " .. tostring(err)
+    end
+    return err.kind
+end
+
+return {
+    { name = "read", inputs = {}, func = read },
+}
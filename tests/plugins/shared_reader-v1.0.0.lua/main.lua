@@ -0,0 +1,7 @@
+local function read()
+    return api.shared.get("layout", "width")
+end
+
+return {
+    { name = "read", inputs = {}, func = read },
+}
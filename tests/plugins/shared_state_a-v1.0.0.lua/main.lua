@@ -0,0 +1,11 @@
+owner = plugin.id
+string.owner = plugin.id
+local helper = require("helper")
+
+local function whoami()
+    return owner .. " " .. string.owner .. " " .. helper.name
+end
+
+return {
+    { name = "whoami", inputs = {}, func = whoami },
+}
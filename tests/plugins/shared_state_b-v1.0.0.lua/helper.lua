@@ -0,0 +1 @@
+return { name = "helper of shared_state_b" }
@@ -0,0 +1,7 @@
+local function publish(width)
+    api.shared.set("layout", "width", width)
+end
+
+return {
+    { name = "publish", inputs = { "width" }, func = publish },
+}
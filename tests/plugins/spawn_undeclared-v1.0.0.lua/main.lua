@@ -0,0 +1,7 @@
+local function start()
+    api.spawn(function() end)
+end
+
+return {
+    { name = "start", inputs = {}, func = start },
+}
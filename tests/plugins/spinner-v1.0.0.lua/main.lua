@@ -0,0 +1,11 @@
+local function spin(forever)
+    local n = 0
+    while forever or n < 1000 do
+        n = n + 1
+    end
+    return n
+end
+
+return {
+    { name = "spin", inputs = { "forever: bool" }, output = "i64", func = spin },
+}
@@ -0,0 +1,27 @@
+local function save(path, content)
+    api.storage.write(path, content)
+end
+
+local function load(path)
+    return api.storage.read(path)
+end
+
+local function files()
+    return api.storage.list()
+end
+
+local function remove(path)
+    return api.storage.delete(path)
+end
+
+local function escape(path)
+    return (pcall(api.storage.write, path, "escaped"))
+end
+
+return {
+    { name = "save", inputs = { "string", "string" }, func = save },
+    { name = "load", inputs = { "string" }, func = load },
+    { name = "files", inputs = {}, func = files },
+    { name = "remove", inputs = { "string" }, output = "bool", func = remove },
+    { name = "escape", inputs = { "string" }, output = "bool", func = escape },
+}
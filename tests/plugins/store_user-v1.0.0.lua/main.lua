@@ -0,0 +1,22 @@
+local function put(key, value)
+    api.store.set(key, value)
+end
+
+local function get(key)
+    return api.store.get(key)
+end
+
+local function delete(key)
+    return api.store.delete(key)
+end
+
+local function keys()
+    return table.concat(api.store.keys(), ",")
+end
+
+return {
+    { name = "put", inputs = { "key: string", "value" }, func = put },
+    { name = "get", inputs = { "key: string" }, func = get },
+    { name = "delete", inputs = { "key: string" }, output = "bool", func = delete },
+    { name = "keys", inputs = {}, output = "string", func = keys },
+}
@@ -0,0 +1,15 @@
+-- Latin-1 encoded "café" followed by another byte, not valid UTF-8
+local payload = "caf\xE9\x80"
+
+local function get_payload()
+    return payload
+end
+
+local function echo(value)
+    return value
+end
+
+return {
+    { name = "get_payload", inputs = {}, func = get_payload },
+    { name = "echo", inputs = { "value" }, func = echo },
+}
@@ -0,0 +1,21 @@
+local steps = 0
+
+-- Burns some time on every step, then waits for the next tick
+api.spawn(function(work)
+    while true do
+        local sum = 0
+        for i = 1, work do
+            sum = sum + i
+        end
+        steps = steps + 1
+        coroutine.yield()
+    end
+end, 100000)
+
+local function progress()
+    return steps
+end
+
+return {
+    { name = "progress", inputs = {}, func = progress },
+}
@@ -0,0 +1,41 @@
+local function format_row(id, name)
+    return string.format("%d: %s", id, name)
+end
+
+local function describe(value)
+    return tostring(value)
+end
+
+local function scale(x, factor)
+    return x * factor
+end
+
+local function count(text)
+    return text
+end
+
+local function fetch(url, retries, label)
+    return string.format("%s x%d %s", url, retries, label or "-")
+end
+
+return {
+    { name = "format_row", inputs = { "id: i64", "name: string" }, output = "string", func = format_row },
+    { name = "describe", inputs = { "value" }, func = describe },
+    {
+        name = "scale",
+        inputs = { { name = "x", type = "f64" }, { name = "factor", type = "i32" } },
+        output = "i32",
+        func = scale,
+    },
+    { name = "count", inputs = { "text: string" }, output = "i64", func = count },
+    {
+        name = "fetch",
+        inputs = {
+            "url: string",
+            { name = "retries", type = "i32", default = 3 },
+            { name = "label", type = "string", optional = true },
+        },
+        output = "string",
+        func = fetch,
+    },
+}
@@ -0,0 +1,61 @@
+mod utils;
+
+use std::panic::{AssertUnwindSafe, catch_unwind};
+
+use plux_lua_manager::LuaManager;
+use plux_rs::{
+    function::{Arg, DynamicFunction},
+    variable::{Variable, VariableType},
+};
+
+use crate::utils::{get_plugin_path, loader_init};
+
+fn host_function(name: &str, f: fn(i64, i64) -> i64) -> DynamicFunction {
+    DynamicFunction::new(
+        name,
+        vec![
+            Arg::new("a", VariableType::I64),
+            Arg::new("b", VariableType::I64),
+        ],
+        Some(Arg::new("c", VariableType::I64)),
+        move |args| {
+            let a = args[0].parse_ref::<i64>();
+            let b = args[1].parse_ref::<i64>();
+            Ok(Some(f(*a, *b).into()))
+        },
+    )
+}
+
+#[test]
+fn plugin_survives_a_panicking_host_function() {
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+    loader.context(|mut ctx| {
+        ctx.register_function(host_function("add", |a, b| a + b));
+        ctx.register_function(host_function("mul", |_, _| panic!("host bug")));
+    });
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("host_refs", "1.0.0").to_str().unwrap())
+        .unwrap();
+
+    let args = vec![vec![Variable::I64(2), Variable::I64(3)]];
+    let panicked = catch_unwind(AssertUnwindSafe(|| {
+        manager.call_batch(&bundle, "call_late", &args)
+    }));
+    assert!(panicked.is_err());
+
+    // Neither the state nor the manager stay locked out after the panic
+    let results = manager.call_batch(&bundle, "call_stored", &args).unwrap();
+    assert_eq!(*results[0].as_ref().unwrap(), Some(Variable::I64(5)));
+    assert_eq!(
+        loader
+            .get_plugin_by_bundle(&bundle)
+            .unwrap()
+            .call_function("call_stored", &args[0])
+            .unwrap()
+            .unwrap(),
+        Some(Variable::I64(5))
+    );
+
+    loader.stop().unwrap();
+}
@@ -0,0 +1,48 @@
+mod utils;
+
+use std::fs;
+
+use plux_lua_manager::LuaManager;
+use plux_rs::variable::Variable;
+
+use crate::utils::{get_plugin_path, loader_init};
+
+#[test]
+fn preloaded_plugins_load_from_their_compiled_entries() {
+    let dir = std::env::temp_dir()
+        .join(format!("plux-preload-{}", std::process::id()))
+        .join("batch-v1.0.0.lua");
+    fs::create_dir_all(&dir).unwrap();
+    for file in ["config.toml", "main.lua"] {
+        fs::copy(get_plugin_path("batch", "1.0.0").join(file), dir.join(file)).unwrap();
+    }
+
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+    let paths = [
+        dir.clone(),
+        get_plugin_path("counter", "1.0.0"),
+        get_plugin_path("missing", "1.0.0"),
+    ];
+    let results = manager.preload(&paths);
+    assert!(results[0].is_ok() && results[1].is_ok());
+    assert!(results[2].is_err());
+
+    // The entry script was compiled by the preload
+    fs::remove_file(dir.join("main.lua")).unwrap();
+    let batch = loader.load_plugin_now(dir.to_str().unwrap()).unwrap();
+    let counter = loader.load_plugin_now(paths[1].to_str().unwrap()).unwrap();
+
+    let plugin = loader.get_plugin_by_bundle(&batch).unwrap();
+    assert_eq!(
+        plugin
+            .call_function("transform", &[Variable::I32(21)])
+            .unwrap()
+            .unwrap(),
+        Some(Variable::I64(42))
+    );
+    assert!(loader.get_plugin_by_bundle(&counter).is_some());
+
+    loader.stop().unwrap();
+    fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+}
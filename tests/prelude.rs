@@ -0,0 +1,45 @@
+use mlua::Lua;
+use plux_lua_manager::{
+    LuaManager,
+    testing::{PluginFixture, TestHost},
+};
+use plux_rs::variable::Variable;
+
+#[test]
+fn preludes_run_before_the_entry_script() {
+    let bytecode = Lua::new()
+        .load("function triple(x) return x * 3 end")
+        .into_function()
+        .unwrap()
+        .dump(false);
+    let manager = LuaManager::new()
+        .with_prelude("helpers.lua", "function double(x) return x * 2 end")
+        .with_prelude("compiled.lua", bytecode);
+
+    let mut host = TestHost::with_manager(manager);
+    let bundle = host
+        .load(PluginFixture::new("uses_helpers").main(
+            r#"
+            return {
+                { name = "six_times", inputs = { "x" }, func = function(x) return triple(double(x)) end },
+            }
+            "#,
+        ))
+        .unwrap();
+    host.assert_call(
+        &bundle,
+        "six_times",
+        &[Variable::I64(2)],
+        Some(Variable::I64(12)),
+    );
+}
+
+#[test]
+fn failing_prelude_fails_the_load() {
+    let manager = LuaManager::new().with_prelude("broken.lua", "error('no polyfill')");
+    let mut host = TestHost::with_manager(manager);
+    assert!(
+        host.load(PluginFixture::new("plain").main("return {}"))
+            .is_err()
+    );
+}
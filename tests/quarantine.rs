@@ -0,0 +1,292 @@
+mod utils;
+
+use std::{
+    io,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use plux_lua_manager::{
+    LuaManager, QuarantinePolicy, RestartAttempt, RestartPolicy, SourceProvider,
+    testing::{PluginFixture, TestHost},
+};
+use plux_rs::{
+    Loader,
+    function::Request,
+    variable::{Variable, VariableType},
+};
+
+use crate::utils::{get_plugin_path, loader_init};
+
+const CONFIG: &str = r#"
+    name = "virtual"
+    description = "Plugin whose implementation is swapped by the test"
+    author = "Plux"
+"#;
+
+const BROKEN: &str = r#"
+    return {
+        { name = "work", inputs = {}, func = function() error("broken") end },
+    }
+"#;
+
+const FIXED: &str = r#"
+    return {
+        { name = "work", inputs = {}, func = function() return "done" end },
+    }
+"#;
+
+/// Serves `main.lua` from a string the test can replace.
+struct SwapProvider {
+    main: Mutex<String>,
+}
+
+impl SourceProvider for SwapProvider {
+    fn read_source(&self, rel_path: &str) -> io::Result<String> {
+        match rel_path {
+            "config.toml" => Ok(CONFIG.to_string()),
+            "main.lua" => Ok(self.main.lock().unwrap().clone()),
+            _ => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                rel_path.to_string(),
+            )),
+        }
+    }
+
+    fn exists(&self, rel_path: &str) -> bool {
+        matches!(rel_path, "config.toml" | "main.lua")
+    }
+
+    fn list(&self, _prefix: &str) -> io::Result<Vec<String>> {
+        Ok(vec![])
+    }
+}
+
+#[test]
+fn failing_plugin_is_quarantined_until_fixed() {
+    let provider = Arc::new(SwapProvider {
+        main: Mutex::new(BROKEN.to_string()),
+    });
+    let quarantined = Arc::new(AtomicUsize::new(0));
+
+    let manager = {
+        let provider = provider.clone();
+        let quarantined = quarantined.clone();
+        LuaManager::new()
+            .with_source_provider(move |_| provider.clone())
+            .with_quarantine(QuarantinePolicy::new(3))
+            .with_quarantine_listener(move |_| {
+                quarantined.fetch_add(1, Ordering::Relaxed);
+            })
+    };
+    let mut loader = loader_init(manager.clone());
+
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("virtual", "1.0.0").to_str().unwrap())
+        .unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+    let work = || plugin.call_function("work", &[]).unwrap();
+
+    for _ in 0..3 {
+        assert!(!manager.is_quarantined(&bundle));
+        let error = work().unwrap_err().to_string();
+        assert!(error.contains("broken"), "{error}");
+    }
+    assert!(manager.is_quarantined(&bundle));
+    assert_eq!(quarantined.load(Ordering::Relaxed), 1);
+
+    // Quarantined calls fail without entering Lua
+    let error = work().unwrap_err().to_string();
+    assert!(error.contains("is quarantined"), "{error}");
+
+    manager.unquarantine(&bundle).unwrap();
+    let error = work().unwrap_err().to_string();
+    assert!(error.contains("broken"), "{error}");
+
+    *provider.main.lock().unwrap() = FIXED.to_string();
+    manager.reload_plugin(&bundle).unwrap();
+    assert!(!manager.is_quarantined(&bundle));
+    assert_eq!(work().unwrap(), Some(Variable::String("done".to_string())));
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn faulted_plugins_do_not_abort_the_load() {
+    let broken = PluginFixture::new("broken")
+        .main("return {")
+        .create()
+        .unwrap();
+    let flaky = PluginFixture::new("flaky")
+        .main(
+            r#"return {
+                { name = "work", inputs = {}, func = function() error("flaky") end },
+            }"#,
+        )
+        .create()
+        .unwrap();
+
+    let mut host = TestHost::with_manager(
+        LuaManager::new()
+            .with_fault_isolation(true)
+            .with_quarantine(QuarantinePolicy::new(2)),
+    );
+    let bundles = host
+        .loader()
+        .load_plugins([
+            broken.path().to_str().unwrap(),
+            flaky.path().to_str().unwrap(),
+        ])
+        .unwrap();
+    let (broken, flaky) = (&bundles[0], &bundles[1]);
+
+    let manager = host.manager();
+    assert!(manager.is_faulted(broken));
+    assert!(!manager.is_faulted(flaky));
+    let faults = manager.faults(broken);
+    assert_eq!(faults.len(), 1);
+    assert_eq!(faults[0].function, None);
+    assert!(faults[0].error.contains("main.lua"), "{}", faults[0].error);
+
+    for _ in 0..2 {
+        host.assert_call_fails(flaky, "work", &[], "flaky");
+    }
+    let manager = host.manager();
+    assert_eq!(manager.faulted_plugins(), [broken.clone(), flaky.clone()]);
+    let faults = manager.faults(flaky);
+    assert_eq!(faults.len(), 2);
+    assert_eq!(faults[1].function.as_deref(), Some("work"));
+
+    manager.clear_faults(flaky);
+    assert!(manager.faults(flaky).is_empty());
+}
+
+#[test]
+fn faulted_plugins_are_restarted() {
+    let provider = Arc::new(SwapProvider {
+        main: Mutex::new("return {".to_string()),
+    });
+    let attempts = Arc::new(Mutex::new(vec![]));
+
+    let manager = {
+        let provider = provider.clone();
+        let attempts = attempts.clone();
+        LuaManager::new()
+            .with_source_provider(move |_| provider.clone())
+            .with_fault_isolation(true)
+            .with_restart_policy(RestartPolicy::new(3, Duration::ZERO))
+            .with_restart_listener(move |_, attempt| {
+                attempts.lock().unwrap().push(attempt.clone());
+            })
+    };
+    let mut loader = loader_init(manager.clone());
+
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("virtual", "1.0.0").to_str().unwrap())
+        .unwrap();
+    assert!(manager.is_faulted(&bundle));
+
+    // Still broken
+    assert_eq!(manager.tick(Duration::from_secs(1)).restarts, 1);
+    assert!(manager.is_faulted(&bundle));
+
+    *provider.main.lock().unwrap() = FIXED.to_string();
+    assert_eq!(manager.tick(Duration::from_secs(1)).restarts, 1);
+    assert!(!manager.is_faulted(&bundle));
+    assert_eq!(manager.tick(Duration::from_secs(1)).restarts, 0);
+
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+    assert_eq!(
+        plugin.call_function("work", &[]).unwrap().unwrap(),
+        Some(Variable::String("done".to_string()))
+    );
+
+    let attempts = attempts.lock().unwrap();
+    assert_eq!(attempts.len(), 2);
+    assert_eq!(attempts[0].attempt, 1);
+    assert!(attempts[0].error.is_some());
+    assert_eq!(
+        attempts[1],
+        RestartAttempt {
+            attempt: 2,
+            last: false,
+            error: None,
+        }
+    );
+    assert_eq!(manager.faults(&bundle).len(), 2);
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn functions_of_plugins_failing_on_a_missing_request_work_once_loaded_again() {
+    let provider = Arc::new(SwapProvider {
+        main: Mutex::new(FIXED.to_string()),
+    });
+    let manager = {
+        let provider = provider.clone();
+        LuaManager::new()
+            .with_source_provider(move |_| provider.clone())
+            .with_fault_isolation(true)
+    };
+    let mut loader = Loader::new();
+    let registered = manager.clone();
+    loader
+        .context(move |mut ctx| {
+            ctx.register_request(Request::new("describe", vec![], Some(VariableType::String)));
+            ctx.register_manager(registered)
+        })
+        .unwrap();
+
+    // The plugin does not handle the required request
+    let bundle = loader
+        .register_plugin(get_plugin_path("virtual", "1.0.0").to_str().unwrap())
+        .unwrap();
+    assert!(loader.load_plugin_by_bundle(&bundle).is_err());
+
+    *provider.main.lock().unwrap() = format!("function describe() return 'virtual' end\n{FIXED}");
+    loader.load_plugin_by_bundle(&bundle).unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+    assert_eq!(
+        plugin.call_function("work", &[]).unwrap().unwrap(),
+        Some(Variable::String("done".to_string()))
+    );
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn plugins_back_from_a_restart_are_restarted_again() {
+    let mut host = TestHost::with_manager(
+        LuaManager::new()
+            .with_quarantine(QuarantinePolicy::new(1))
+            .with_restart_policy(RestartPolicy::new(1, Duration::ZERO)),
+    );
+    let bundle = host
+        .load(PluginFixture::new("unsteady").main(
+            r#"return {
+                { name = "work", inputs = { "fail" }, func = function(fail)
+                    if fail then error("unsteady") end
+                    return "done"
+                end },
+            }"#,
+        ))
+        .unwrap();
+
+    for _ in 0..2 {
+        host.assert_call_fails(&bundle, "work", &[Variable::Bool(true)], "unsteady");
+        let manager = host.manager();
+        assert!(manager.is_faulted(&bundle));
+        assert_eq!(manager.tick(Duration::from_secs(1)).restarts, 1);
+        assert!(!manager.is_faulted(&bundle));
+        host.assert_call(
+            &bundle,
+            "work",
+            &[Variable::Bool(false)],
+            Some(Variable::String("done".to_string())),
+        );
+    }
+}
@@ -0,0 +1,45 @@
+use plux_lua_manager::{
+    LuaManager, RateLimit,
+    testing::{PluginFixture, TestHost},
+};
+use plux_rs::{
+    function::{Arg, DynamicFunction},
+    variable::{Variable, VariableType},
+};
+
+#[test]
+fn host_calls_beyond_the_rate_limit_fail() {
+    let mut host =
+        TestHost::with_manager(LuaManager::new().with_rate_limit(RateLimit::new(0.001, 3)));
+    host.loader().context(|mut ctx| {
+        ctx.register_function(DynamicFunction::new(
+            "expensive",
+            vec![],
+            Some(Arg::new("output", VariableType::I64)),
+            |_| Ok(Some(Variable::I64(1))),
+        ))
+    });
+    let bundle = host
+        .load(PluginFixture::new("looper").main(
+            r#"return {
+                { name = "spin", inputs = {}, output = "string", func = function()
+                    local calls = 0
+                    while true do
+                        local ok, err = pcall(host.expensive)
+                        if not ok then
+                            return calls .. " " .. err.kind
+                        end
+                        calls = calls + 1
+                    end
+                end },
+            }"#,
+        ))
+        .unwrap();
+
+    host.assert_call(
+        &bundle,
+        "spin",
+        &[],
+        Some(Variable::String("3 rate_limited".to_string())),
+    );
+}
@@ -0,0 +1,79 @@
+mod utils;
+
+use plux_lua_manager::{LuaManager, MAX_CALL_DEPTH};
+use plux_rs::{
+    Bundle,
+    function::{Arg, DynamicFunction},
+    variable::{Variable, VariableType},
+};
+
+use crate::utils::{get_plugin_path, loader_init};
+
+#[test]
+fn host_function_calls_back_into_the_calling_plugin() {
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+    let bundle = Bundle::from_filename("reentrant-v1.0.0.lua").unwrap();
+
+    let reenter = {
+        let manager = manager.clone();
+        let bundle = bundle.clone();
+        DynamicFunction::new(
+            "reenter",
+            vec![],
+            Some(Arg::new("value", VariableType::I64)),
+            move |_| {
+                let mut results = manager.call_batch(&bundle, "leaf", &[vec![]])?;
+                Ok(results.remove(0)?)
+            },
+        )
+    };
+    loader.context(|mut ctx| ctx.register_function(reenter));
+    loader
+        .load_plugin_now(get_plugin_path("reentrant", "1.0.0").to_str().unwrap())
+        .unwrap();
+
+    // Through plux, then through the manager, which used to hold the state locked
+    assert_eq!(
+        loader
+            .get_plugin_by_bundle(&bundle)
+            .unwrap()
+            .call_function("outer", &[])
+            .unwrap()
+            .unwrap(),
+        Some(Variable::I64(42))
+    );
+    let results = manager.call_batch(&bundle, "outer", &[vec![]]).unwrap();
+    assert_eq!(*results[0].as_ref().unwrap(), Some(Variable::I64(42)));
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn plugins_call_each_other_recursively() {
+    let mut loader = loader_init(LuaManager::new());
+    loader
+        .load_plugin_now(get_plugin_path("pong_b", "1.0.0").to_str().unwrap())
+        .unwrap();
+    let a = loader
+        .load_plugin_now(get_plugin_path("ping_a", "1.0.0").to_str().unwrap())
+        .unwrap();
+
+    let ping = |n: i64| {
+        loader
+            .get_plugin_by_bundle(&a)
+            .unwrap()
+            .call_function("ping", &[Variable::I64(n)])
+            .unwrap()
+    };
+    assert_eq!(ping(10).unwrap(), Some(Variable::I64(10)));
+    // Runaway recursion fails instead of overflowing the stack
+    let error = ping(100_000).unwrap_err().to_string();
+    assert!(
+        error.contains(&format!("maximum call depth of {MAX_CALL_DEPTH}")),
+        "{error}"
+    );
+    assert_eq!(ping(3).unwrap(), Some(Variable::I64(3)));
+
+    loader.stop().unwrap();
+}
@@ -0,0 +1,95 @@
+use plux_lua_manager::{
+    FunctionInfo, InputInfo,
+    testing::{PluginFixture, TestHost},
+};
+use plux_rs::variable::Variable;
+
+#[test]
+fn functions_are_listed_from_rust_and_lua() {
+    let mut host = TestHost::new();
+    let math = host
+        .load(PluginFixture::new("math").main(
+            r#"
+            return {
+                {
+                    name = "add",
+                    inputs = { "a: i64", { name = "b", type = "i64", default = 0 } },
+                    output = "i64",
+                    func = function(a, b) return a + b end,
+                    description = "Adds two numbers",
+                },
+                { name = "sum", inputs = { "...values: f64" }, func = function(values) end },
+            }
+            "#,
+        ))
+        .unwrap();
+    let dispatcher = host
+        .load(PluginFixture::new("dispatcher").main(
+            r#"
+            return {
+                { name = "describe", inputs = { "plugin", "version" }, output = "string", func = function(plugin, version)
+                    local functions = api.list_functions(plugin, version)
+                    if functions == nil then
+                        return "unknown"
+                    end
+                    local lines = {}
+                    for _, f in ipairs(functions) do
+                        local inputs = {}
+                        for _, input in ipairs(f.inputs) do
+                            table.insert(inputs, input.name .. ": " .. input.type
+                                .. (input.optional and "?" or "") .. (input.variadic and "..." or ""))
+                        end
+                        table.insert(lines, f.name .. "(" .. table.concat(inputs, ", ") .. ") -> " .. f.output)
+                    end
+                    return table.concat(lines, "; ")
+                end },
+            }
+            "#,
+        ))
+        .unwrap();
+
+    let functions = host.manager().functions(&math).unwrap();
+    assert_eq!(functions.len(), 2);
+    assert_eq!(functions[0].name, "add");
+    assert_eq!(
+        functions[0].inputs,
+        [
+            InputInfo {
+                name: "a".to_string(),
+                ty: "i64".to_string(),
+                optional: false,
+                variadic: false,
+            },
+            InputInfo {
+                name: "b".to_string(),
+                ty: "i64".to_string(),
+                optional: true,
+                variadic: false,
+            },
+        ]
+    );
+    assert_eq!(functions[0].output, "i64");
+    assert_eq!(
+        functions[0].doc.description.as_deref(),
+        Some("Adds two numbers")
+    );
+    assert!(matches!(
+        &functions[1],
+        FunctionInfo { name, output, .. } if name == "sum" && output == "let"
+    ));
+
+    let listed = "add(a: i64, b: i64?) -> i64; sum(values: f64...) -> let";
+    for (plugin, version, expected) in [
+        ("math", Variable::Null, listed),
+        ("math", Variable::String("^1".to_string()), listed),
+        ("math", Variable::String("2.0.0".to_string()), "unknown"),
+        ("missing", Variable::Null, "unknown"),
+    ] {
+        host.assert_call(
+            &dispatcher,
+            "describe",
+            &[Variable::String(plugin.to_string()), version],
+            Some(Variable::String(expected.to_string())),
+        );
+    }
+}
@@ -0,0 +1,219 @@
+mod utils;
+
+use std::{
+    io,
+    sync::{Arc, Mutex},
+};
+
+use plux_lua_manager::{LuaManager, SourceProvider};
+use plux_rs::variable::Variable;
+
+use crate::utils::{get_plugin_path, loader_init};
+
+const CONFIG: &str = r#"
+    name = "virtual"
+    description = "Plugin whose implementation is swapped by the test"
+    author = "Plux"
+"#;
+
+const OLD: &str = r#"
+    function on_unload() error("still busy") end
+    return {
+        { name = "old", inputs = {}, func = function() return "old" end },
+    }
+"#;
+
+const NEW: &str = r#"
+    return {
+        { name = "new", inputs = {}, func = function() return "new" end },
+    }
+"#;
+
+const CACHE: &str = r#"
+    local cache = {}
+    function on_reload_save() return cache end
+    return {
+        { name = "put", inputs = { "key", "value" }, func = function(k, v) cache[k] = v end },
+    }
+"#;
+
+const MIGRATED_CACHE: &str = r#"
+    local cache = {}
+    function on_reload_restore(saved)
+        for k, v in pairs(saved) do cache[k] = { value = v } end
+    end
+    function on_reload_save() return cache end
+    return {
+        { name = "get", inputs = { "key" }, func = function(k) return cache[k].value end },
+    }
+"#;
+
+/// Serves `main.lua` from a string the test can replace.
+struct SwapProvider {
+    main: Mutex<String>,
+}
+
+impl SourceProvider for SwapProvider {
+    fn read_source(&self, rel_path: &str) -> io::Result<String> {
+        match rel_path {
+            "config.toml" => Ok(CONFIG.to_string()),
+            "main.lua" => Ok(self.main.lock().unwrap().clone()),
+            _ => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                rel_path.to_string(),
+            )),
+        }
+    }
+
+    fn exists(&self, rel_path: &str) -> bool {
+        matches!(rel_path, "config.toml" | "main.lua")
+    }
+
+    fn list(&self, _prefix: &str) -> io::Result<Vec<String>> {
+        Ok(vec![])
+    }
+}
+
+#[test]
+fn reload_restores_saved_state() {
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("counter", "1.0.0").to_str().unwrap())
+        .unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+
+    for _ in 0..3 {
+        plugin.call_function("increment", &[]).unwrap().unwrap();
+    }
+
+    let report = manager.reload_plugin(&bundle).unwrap();
+    assert!(report.warnings.is_empty());
+
+    let count = plugin.call_function("increment", &[]).unwrap().unwrap();
+    assert_eq!(count, Some(Variable::I64(4)));
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn reload_warns_about_unconvertible_state() {
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+
+    let bundle = loader
+        .load_plugin_now(
+            get_plugin_path("counter_unsaveable", "1.0.0")
+                .to_str()
+                .unwrap(),
+        )
+        .unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+
+    plugin.call_function("increment", &[]).unwrap().unwrap();
+
+    let report = manager.reload_plugin(&bundle).unwrap();
+    assert_eq!(report.warnings.len(), 1);
+    assert!(report.warnings[0].contains("function"));
+
+    let count = plugin.call_function("increment", &[]).unwrap().unwrap();
+    assert_eq!(count, Some(Variable::I64(2)));
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn reload_of_unloaded_plugin_fails() {
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("counter", "1.0.0").to_str().unwrap())
+        .unwrap();
+    loader.unload_plugin_by_bundle(&bundle).unwrap();
+
+    assert!(manager.reload_plugin(&bundle).is_err());
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn reload_reports_function_changes() {
+    let provider = Arc::new(SwapProvider {
+        main: Mutex::new(OLD.to_string()),
+    });
+    let manager = {
+        let provider = provider.clone();
+        LuaManager::new().with_source_provider(move |_| provider.clone())
+    };
+    let mut loader = loader_init(manager.clone());
+
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("virtual", "1.0.0").to_str().unwrap())
+        .unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+
+    *provider.main.lock().unwrap() = NEW.to_string();
+    let report = manager.reload_plugin(&bundle).unwrap();
+    assert_eq!(report.added, vec!["new".to_string()]);
+    assert_eq!(report.removed, vec!["old".to_string()]);
+    assert_eq!(report.warnings.len(), 1);
+    assert!(report.warnings[0].contains("still busy"));
+
+    assert_eq!(
+        plugin.call_function("new", &[]).unwrap().unwrap(),
+        Some(Variable::String("new".to_string()))
+    );
+    let error = plugin
+        .call_function("old", &[])
+        .unwrap()
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("not found"), "{error}");
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn reload_migrates_saved_state() {
+    let provider = Arc::new(SwapProvider {
+        main: Mutex::new(CACHE.to_string()),
+    });
+    let manager = {
+        let provider = provider.clone();
+        LuaManager::new().with_source_provider(move |_| provider.clone())
+    };
+    let mut loader = loader_init(manager.clone());
+
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("virtual", "1.0.0").to_str().unwrap())
+        .unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+    plugin
+        .call_function(
+            "put",
+            &[Variable::String("answer".to_string()), Variable::I64(42)],
+        )
+        .unwrap()
+        .unwrap();
+
+    *provider.main.lock().unwrap() = MIGRATED_CACHE.to_string();
+    let report = manager.reload_plugin(&bundle).unwrap();
+    assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+    assert_eq!(
+        plugin
+            .call_function("get", &[Variable::String("answer".to_string())])
+            .unwrap()
+            .unwrap(),
+        Some(Variable::I64(42))
+    );
+
+    // The new state has no hook to restore the saved cache
+    *provider.main.lock().unwrap() = CACHE.to_string();
+    let report = manager.reload_plugin(&bundle).unwrap();
+    assert_eq!(report.warnings.len(), 1);
+    assert!(report.warnings[0].contains("on_reload_restore"));
+
+    loader.stop().unwrap();
+}
@@ -0,0 +1,43 @@
+#![cfg(feature = "repl")]
+
+use plux_lua_manager::testing::{PluginFixture, TestHost};
+use plux_rs::variable::Variable;
+
+#[test]
+fn repl_evaluates_in_the_plugin_state() {
+    let mut host = TestHost::new();
+    let bundle = host
+        .load(PluginFixture::new("counter").main(
+            r#"
+            count = 0
+            return {
+                { name = "bump", inputs = {}, output = "i64", func = function()
+                    count = count + 1
+                    return count
+                end },
+            }"#,
+        ))
+        .unwrap();
+    host.assert_call(&bundle, "bump", &[], Some(Variable::I64(1)));
+
+    let repl = host.manager().repl(&bundle).unwrap();
+    assert_eq!(repl.eval("count").unwrap(), Some(Variable::I64(1)));
+    assert_eq!(repl.eval("count = 41").unwrap(), None);
+    assert_eq!(
+        repl.eval("count, 'x'").unwrap(),
+        Some(Variable::List(vec![
+            Variable::I64(41),
+            Variable::String("x".to_string())
+        ]))
+    );
+    assert!(repl.eval("error('boom')").is_err());
+    host.assert_call(&bundle, "bump", &[], Some(Variable::I64(42)));
+
+    let mut output = vec![];
+    let input = "function twice(x)\n  return x * 2\nend\ndoubled = twice(count)\ndoubled\nnil +\n1\n.exit\ncount\n";
+    repl.run(input.as_bytes(), &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("I64(84)"), "{output}");
+    assert!(output.contains("error: "), "{output}");
+    assert!(!output.contains("I64(42)\n"), "{output}");
+}
@@ -0,0 +1,153 @@
+use plux_lua_manager::{LuaManager, testing::PluginFixture};
+use plux_rs::{
+    Loader,
+    function::Request,
+    variable::{Variable, VariableType},
+};
+
+#[test]
+fn optional_requests_may_be_left_unhandled() {
+    let handled = PluginFixture::new("handled")
+        .main(
+            "function on_tick() return 1 end\nfunction describe() return 'handled' end\nreturn {}",
+        )
+        .create()
+        .unwrap();
+    let unhandled = PluginFixture::new("unhandled")
+        .main("function describe() return 'unhandled' end\nreturn {}")
+        .create()
+        .unwrap();
+    let broken = PluginFixture::new("broken")
+        .main("function on_tick() return 1 end\nreturn {}")
+        .create()
+        .unwrap();
+
+    let manager = LuaManager::new().with_optional_request("on_tick");
+    let mut loader = Loader::new();
+    let registered = manager.clone();
+    loader
+        .context(move |mut ctx| {
+            ctx.register_requests([
+                Request::new("on_tick", vec![], Some(VariableType::I64)),
+                Request::new("describe", vec![], Some(VariableType::String)),
+            ]);
+            ctx.register_manager(registered)
+        })
+        .unwrap();
+
+    let handled = loader
+        .load_plugin_now(handled.path().to_str().unwrap())
+        .unwrap();
+    let unhandled = loader
+        .load_plugin_now(unhandled.path().to_str().unwrap())
+        .unwrap();
+    assert!(manager.diagnostics(&handled).unwrap().is_empty());
+    assert_eq!(
+        manager.diagnostics(&unhandled).unwrap(),
+        ["optional request `on_tick` is not handled"]
+    );
+
+    // Requests that are not optional are still required
+    assert!(
+        loader
+            .load_plugin_now(broken.path().to_str().unwrap())
+            .is_err()
+    );
+
+    let plugin = loader.get_plugin_by_bundle(&unhandled).unwrap();
+    assert_eq!(plugin.call_request("on_tick", &[]).unwrap().unwrap(), None);
+    let plugin = loader.get_plugin_by_bundle(&handled).unwrap();
+    assert!(
+        plugin
+            .call_request("on_tick", &[])
+            .unwrap()
+            .unwrap()
+            .is_some()
+    );
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn request_arguments_are_named_by_the_host() {
+    let dir = PluginFixture::new("scaler")
+        .main("function scale(value, factor) return value * factor end\nreturn {}")
+        .create()
+        .unwrap();
+
+    let manager = LuaManager::new().with_request_inputs("scale", ["value"]);
+    let mut loader = Loader::new();
+    loader
+        .context(move |mut ctx| {
+            ctx.register_request(Request::new(
+                "scale",
+                vec![VariableType::Let, VariableType::F64],
+                Some(VariableType::F64),
+            ));
+            ctx.register_manager(manager)
+        })
+        .unwrap();
+    let bundle = loader
+        .load_plugin_now(dir.path().to_str().unwrap())
+        .unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+
+    let request = &plugin.get_requests()[0];
+    let names: Vec<_> = request.inputs().into_iter().map(|arg| arg.name).collect();
+    assert_eq!(names, ["value", "arg_1"]);
+
+    assert_eq!(
+        plugin
+            .call_request("scale", &[Variable::F64(1.5), Variable::F64(2.0)])
+            .unwrap()
+            .unwrap(),
+        Some(Variable::F64(3.0))
+    );
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn request_handlers_may_be_nested_or_exported() {
+    let dir = PluginFixture::new("nested")
+        .main(
+            "handlers = { on_tick = function(n) return n + 1 end }\n\
+             return { describe = function() return 'nested' end }",
+        )
+        .create()
+        .unwrap();
+
+    let manager = LuaManager::new();
+    let mut loader = Loader::new();
+    loader
+        .context(move |mut ctx| {
+            ctx.register_requests([
+                Request::new(
+                    "handlers.on_tick",
+                    vec![VariableType::I64],
+                    Some(VariableType::I64),
+                ),
+                Request::new("describe", vec![], Some(VariableType::String)),
+            ]);
+            ctx.register_manager(manager)
+        })
+        .unwrap();
+    let bundle = loader
+        .load_plugin_now(dir.path().to_str().unwrap())
+        .unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+
+    assert_eq!(
+        plugin
+            .call_request("handlers.on_tick", &[Variable::I64(1)])
+            .unwrap()
+            .unwrap(),
+        Some(Variable::I64(2))
+    );
+    assert_eq!(
+        plugin.call_request("describe", &[]).unwrap().unwrap(),
+        Some(Variable::String("nested".to_string()))
+    );
+
+    loader.stop().unwrap();
+}
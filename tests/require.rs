@@ -0,0 +1,60 @@
+mod utils;
+
+use std::fs;
+
+use plux_lua_manager::LuaManager;
+use plux_rs::variable::Variable;
+
+use crate::utils::loader_init;
+
+const CONFIG: &str = r#"
+name = "escaping"
+description = "Tries to require modules outside of its directory"
+author = "Plux"
+"#;
+
+const MAIN_LUA: &str = r#"
+local function try(name)
+    local ok, result = pcall(require, name)
+    return ok and result or "refused"
+end
+
+return {
+    { name = "inside", inputs = {}, output = "string", func = function() return try("lib.inside") end },
+    { name = "outside", inputs = { "string" }, output = "string", func = try },
+}
+"#;
+
+#[test]
+fn require_stays_inside_the_plugin_directory() {
+    let root = std::env::temp_dir().join(format!("plux-require-{}", std::process::id()));
+    let dir = root.join("escaping-v1.0.0.lua");
+    fs::create_dir_all(dir.join("lib")).unwrap();
+    fs::write(dir.join("config.toml"), CONFIG).unwrap();
+    fs::write(dir.join("main.lua"), MAIN_LUA).unwrap();
+    fs::write(dir.join("lib/inside.lua"), "return 'inside'").unwrap();
+    fs::write(root.join("secret.lua"), "return 'secret'").unwrap();
+
+    let mut loader = loader_init(LuaManager::new());
+    let bundle = loader.load_plugin_now(dir.to_str().unwrap()).unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+    assert_eq!(
+        plugin.call_function("inside", &[]).unwrap().unwrap(),
+        Some(Variable::String("inside".to_string()))
+    );
+
+    let absolute = root.join("secret").to_str().unwrap().replace(".lua", "");
+    for name in ["..secret", "../secret", absolute.as_str()] {
+        assert_eq!(
+            plugin
+                .call_function("outside", &[Variable::String(name.to_string())])
+                .unwrap()
+                .unwrap(),
+            Some(Variable::String("refused".to_string())),
+            "{name}"
+        );
+    }
+
+    loader.stop().unwrap();
+    fs::remove_dir_all(root).unwrap();
+}
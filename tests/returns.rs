@@ -0,0 +1,39 @@
+use plux_lua_manager::testing::{PluginFixture, TestHost};
+use plux_rs::variable::Variable;
+
+#[test]
+fn multiple_results_are_returned_as_a_list() {
+    let mut host = TestHost::new();
+    let bundle = host
+        .load(PluginFixture::new("divmod").main(
+            r#"
+            return {
+                { name = "divmod", inputs = { "a", "b" }, output = "list", func = function(a, b)
+                    return a // b, a % b
+                end },
+                { name = "lookup", inputs = {}, func = function() return nil, "not found" end },
+                { name = "single", inputs = {}, func = function() return 1 end },
+                { name = "none", inputs = {}, func = function() end },
+            }
+            "#,
+        ))
+        .unwrap();
+
+    host.assert_call(
+        &bundle,
+        "divmod",
+        &[Variable::I64(7), Variable::I64(2)],
+        Some(Variable::List(vec![Variable::I64(3), Variable::I64(1)])),
+    );
+    host.assert_call(
+        &bundle,
+        "lookup",
+        &[],
+        Some(Variable::List(vec![
+            Variable::Null,
+            Variable::String("not found".to_string()),
+        ])),
+    );
+    host.assert_call(&bundle, "single", &[], Some(Variable::I64(1)));
+    host.assert_call(&bundle, "none", &[], None);
+}
@@ -0,0 +1,48 @@
+mod utils;
+
+use plux_lua_manager::{LuaManager, RuntimeInfo};
+
+use crate::utils::{get_plugin_path, loader_init};
+
+#[test]
+fn runtime_info_matches_build() {
+    let info = LuaManager::new().runtime_info();
+
+    #[cfg(feature = "lua54")]
+    {
+        assert_eq!(info.implementation, "PUC Lua 5.4");
+        assert_eq!(info.version, "Lua 5.4");
+        assert!(info.features.contains(&"lua54".to_string()));
+    }
+    assert_eq!(info.crate_version, env!("CARGO_PKG_VERSION"));
+
+    let dump = toml::to_string(&info).unwrap();
+    assert_eq!(toml::from_str::<RuntimeInfo>(&dump).unwrap(), info);
+}
+
+#[test]
+fn plugin_runtime_info_describes_state() {
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("counter", "1.0.0").to_str().unwrap())
+        .unwrap();
+
+    let info = manager.plugin_runtime_info(&bundle).unwrap();
+    assert_eq!(info.runtime, manager.runtime_info());
+    for lib in ["string", "table", "math", "coroutine", "package"] {
+        assert!(info.stdlibs.contains(&lib.to_string()), "missing {lib}");
+    }
+    assert!(info.used_memory > 0);
+    assert_eq!(info.memory_limit, None);
+    assert_eq!(info.sandbox_profile.as_deref(), Some("full"));
+
+    // Declared libraries narrow the profile of the plugin
+    let narrowed = loader
+        .load_plugin_now(get_plugin_path("sandbox_narrow", "1.0.0").to_str().unwrap())
+        .unwrap();
+    let info = manager.plugin_runtime_info(&narrowed).unwrap();
+    assert_eq!(info.sandbox_profile, None);
+
+    loader.stop().unwrap();
+}
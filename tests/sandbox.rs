@@ -0,0 +1,109 @@
+mod utils;
+
+use std::sync::{Arc, Mutex};
+
+use mlua::{Lua, LuaOptions, StdLib};
+use plux_lua_manager::{LuaLib, LuaManager, Permission, SandboxPolicy};
+use plux_rs::{Loader, StdInfo, function::FunctionOutput, variable::Variable};
+
+use crate::utils::{get_plugin_path, loader_init};
+
+fn libs(loader: &mut Loader<'static, FunctionOutput, StdInfo>, id: &str) -> Vec<String> {
+    let bundle = loader
+        .load_plugin_now(get_plugin_path(id, "1.0.0").to_str().unwrap())
+        .unwrap();
+    let output = loader
+        .get_plugin_by_bundle(&bundle)
+        .unwrap()
+        .call_function("libs", &[])
+        .unwrap()
+        .unwrap();
+
+    match output {
+        Some(Variable::List(list)) => list
+            .into_iter()
+            .map(|name| match name {
+                Variable::String(name) => name,
+                name => panic!("unexpected {name:?}"),
+            })
+            .collect(),
+        output => panic!("unexpected {output:?}"),
+    }
+}
+
+#[test]
+fn full_sandbox_opens_everything() {
+    let mut loader = loader_init(LuaManager::new());
+    assert_eq!(
+        libs(&mut loader, "sandbox"),
+        vec!["io", "os", "string", "table", "math", "load", "dofile"]
+    );
+}
+
+#[test]
+fn safe_sandbox_keeps_plugin_modules() {
+    let mut loader = loader_init(LuaManager::new().with_sandbox(SandboxPolicy::safe()));
+    assert_eq!(
+        libs(&mut loader, "sandbox"),
+        vec!["string", "table", "math"]
+    );
+}
+
+#[test]
+fn declared_libs_narrow_the_sandbox() {
+    let manager = LuaManager::new().with_sandbox(SandboxPolicy::safe().with_lib(LuaLib::Io));
+    let mut loader = loader_init(manager);
+
+    // `os` is declared but not allowed, `io` and `math` are allowed but not declared
+    assert_eq!(libs(&mut loader, "sandbox_narrow"), vec!["string", "table"]);
+}
+
+#[test]
+fn state_factory_creates_plugin_states() {
+    let created = Arc::new(Mutex::new(vec![]));
+    let record = created.clone();
+    let manager = LuaManager::new().with_state_factory(move |bundle| {
+        record.lock().unwrap().push(bundle.to_string());
+        Lua::new_with(
+            StdLib::STRING | StdLib::TABLE | StdLib::PACKAGE,
+            LuaOptions::default(),
+        )
+    });
+    let mut loader = loader_init(manager);
+
+    assert_eq!(
+        libs(&mut loader, "sandbox"),
+        vec!["string", "table", "load", "dofile"]
+    );
+    assert_eq!(*created.lock().unwrap(), ["sandbox-v1.0.0.lua"]);
+}
+
+#[test]
+fn declared_permissions_remove_uncovered_functions() {
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+    let bundle = loader
+        .load_plugin_now(
+            get_plugin_path("sandbox_permissions", "1.0.0")
+                .to_str()
+                .unwrap(),
+        )
+        .unwrap();
+    let output = loader
+        .get_plugin_by_bundle(&bundle)
+        .unwrap()
+        .call_function("functions", &[])
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(
+        output,
+        Some(Variable::List(vec![
+            Variable::String("io.open".to_string()),
+            Variable::String("os.remove".to_string()),
+            Variable::String("os.time".to_string()),
+        ]))
+    );
+    assert_eq!(manager.permissions(&bundle), Some(vec![Permission::Fs]));
+    assert_eq!(manager.capabilities(&bundle), Some(vec!["fs".to_string()]));
+}
@@ -0,0 +1,83 @@
+mod utils;
+
+use std::{fs, path::PathBuf};
+
+use plux_lua_manager::LuaManager;
+use plux_rs::variable::Variable;
+
+use crate::utils::loader_init;
+
+/// Writes a script plugin to a fresh temporary directory.
+fn write_script(test: &str, file_name: &str, src: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("plux-scripts-{}-{test}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(file_name);
+    fs::write(&path, src).unwrap();
+    path
+}
+
+#[test]
+fn scripts_are_loaded_as_plugins() {
+    let script = write_script(
+        "header",
+        "greeter.lua",
+        r#"--[[plux
+version = "1.2.0"
+description = "Greets people"
+]]
+return {
+    greet = function(name) return "hello " .. name end,
+    id = function() return plugin.id .. " " .. plugin.config.description end,
+}
+"#,
+    );
+
+    // The script is the entry whatever the manager's default
+    let manager = LuaManager::new().with_entry("init.lua");
+    let path = manager.mount_script(&script).unwrap();
+    assert!(path.ends_with("greeter-v1.2.0.lua"));
+
+    let mut loader = loader_init(manager.clone());
+    let bundle = loader.load_plugin_now(path.to_str().unwrap()).unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+    assert_eq!(
+        plugin
+            .call_function("greet", &[Variable::String("plux".to_string())])
+            .unwrap()
+            .unwrap(),
+        Some(Variable::String("hello plux".to_string()))
+    );
+    assert_eq!(
+        plugin.call_function("id", &[]).unwrap().unwrap(),
+        Some(Variable::String("greeter Greets people".to_string()))
+    );
+    assert_eq!(manager.metadata(&bundle).unwrap().name, "greeter");
+
+    loader.stop().unwrap();
+    fs::remove_dir_all(script.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn scripts_named_after_their_bundle_need_no_header() {
+    let script = write_script(
+        "bare",
+        "tiny-v0.1.0.lua",
+        "return { answer = function() return 42 end }",
+    );
+    let untitled = write_script("bare", "untitled.lua", "return {}");
+
+    let manager = LuaManager::new();
+    assert!(manager.mount_script(&untitled).is_err());
+    let path = manager.mount_script(&script).unwrap();
+
+    let mut loader = loader_init(manager);
+    let bundle = loader.load_plugin_now(path.to_str().unwrap()).unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+    assert_eq!(
+        plugin.call_function("answer", &[]).unwrap().unwrap(),
+        Some(Variable::I64(42))
+    );
+
+    loader.stop().unwrap();
+    fs::remove_dir_all(script.parent().unwrap()).unwrap();
+}
@@ -0,0 +1,84 @@
+mod utils;
+
+use plux_lua_manager::{
+    LuaManager,
+    testing::{PluginFixture, TestHost},
+};
+use plux_rs::variable::Variable;
+
+use crate::utils::{get_plugin_path, loader_init};
+
+#[test]
+fn plugins_know_their_own_info() {
+    let mut loader = loader_init(LuaManager::new());
+    let path = get_plugin_path("self_info", "1.2.3");
+    let bundle = loader.load_plugin_now(path.to_str().unwrap()).unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+
+    let strings = |values: &[&str]| {
+        Variable::List(
+            values
+                .iter()
+                .map(|value| Variable::String(value.to_string()))
+                .collect(),
+        )
+    };
+    assert_eq!(
+        plugin.call_function("describe", &[]).unwrap().unwrap(),
+        Some(strings(&[
+            "self_info",
+            "1.2.3",
+            "lua",
+            "Self info",
+            "events",
+            "^3.0",
+            "nil",
+        ]))
+    );
+    assert_eq!(
+        plugin.call_function("path", &[]).unwrap().unwrap(),
+        Some(Variable::String(path.to_string_lossy().to_string()))
+    );
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn plugins_read_their_config_without_changing_it() {
+    let mut host = TestHost::new();
+    let bundle = host
+        .load(
+            PluginFixture::new("configured")
+                .config(
+                    r#"name = "configured"
+description = ""
+author = ""
+
+[settings]
+greeting = "hello"
+retries = 3
+colors = ["red", "green"]
+"#,
+                )
+                .main(
+                    r#"return {
+                        { name = "settings", inputs = {}, output = "string", func = function()
+                            local settings = plugin.config.settings
+                            return settings.greeting .. " " .. settings.retries .. " " .. #settings.colors
+                        end },
+                        { name = "change", inputs = {}, func = function()
+                            plugin.config.settings.retries = 4
+                        end },
+                    }"#,
+                ),
+        )
+        .unwrap();
+
+    host.assert_call(
+        &bundle,
+        "settings",
+        &[],
+        Some(Variable::String("hello 3 2".to_string())),
+    );
+    host.assert_call_fails(&bundle, "change", &[], "plugin.config is read-only");
+}
@@ -0,0 +1,138 @@
+use std::fs;
+
+use plux_lua_manager::{
+    LuaManager, ManagerError, PluginError,
+    testing::{PluginFixture, TestHost},
+};
+use plux_rs::variable::Variable;
+
+fn configurable() -> PluginFixture {
+    PluginFixture::new("configurable")
+        .config(
+            r#"name = "configurable"
+description = ""
+author = ""
+
+[user_settings.volume]
+type = "f64"
+default = 0.5
+description = "How loud notifications are"
+
+[user_settings.nickname]
+type = "string"
+"#,
+        )
+        .main(
+            r#"local changes = {}
+            settings.on_change(function(name, value, old)
+                table.insert(changes, name .. " " .. tostring(old) .. " -> " .. tostring(value))
+            end)
+            return {
+                { name = "get", inputs = { "name" }, func = function(name) return settings.get(name) end },
+                { name = "set", inputs = { "name", "value" }, func = function(name, value)
+                    settings.set(name, value)
+                end },
+                { name = "changes", inputs = {}, output = "string", func = function()
+                    return table.concat(changes, "; ")
+                end },
+            }"#,
+        )
+}
+
+fn string(s: &str) -> Variable {
+    Variable::String(s.to_string())
+}
+
+#[test]
+fn plugins_and_the_host_share_typed_settings() {
+    let mut host = TestHost::new();
+    let bundle = host.load(configurable()).unwrap();
+
+    let schema = host.manager().settings_schema(&bundle).unwrap();
+    assert_eq!(schema.len(), 2);
+    assert_eq!(schema[1].name, "volume");
+    assert_eq!(schema[1].ty, "f64");
+    assert_eq!(schema[1].default, Some(Variable::F64(0.5)));
+    host.assert_call(
+        &bundle,
+        "get",
+        &[string("volume")],
+        Some(Variable::F64(0.5)),
+    );
+    host.assert_call(&bundle, "get", &[string("nickname")], None);
+
+    // Integers convert to the declared type
+    host.assert_call(&bundle, "set", &[string("volume"), Variable::I64(1)], None);
+    host.manager()
+        .set_setting(&bundle, "nickname", string("neo"))
+        .unwrap();
+    assert_eq!(
+        host.manager().setting(&bundle, "volume").unwrap(),
+        Variable::F64(1.0)
+    );
+    host.assert_call(&bundle, "get", &[string("nickname")], Some(string("neo")));
+
+    // `Null` resets to the default
+    host.manager()
+        .set_setting(&bundle, "volume", Variable::Null)
+        .unwrap();
+    host.assert_call(
+        &bundle,
+        "changes",
+        &[],
+        Some(string(
+            "volume 0.5 -> 1.0; nickname nil -> neo; volume 1.0 -> 0.5",
+        )),
+    );
+
+    assert!(matches!(
+        host.manager()
+            .set_setting(&bundle, "volume", string("loud")),
+        Err(ManagerError::Plugin(
+            PluginError::SettingTypeMismatch { .. }
+        ))
+    ));
+    assert!(matches!(
+        host.manager().setting(&bundle, "missing"),
+        Err(ManagerError::Plugin(PluginError::SettingNotFound(_)))
+    ));
+    host.assert_call_fails(
+        &bundle,
+        "set",
+        &[string("nickname"), Variable::Bool(true)],
+        "Setting `nickname`: value is Bool, expected string",
+    );
+}
+
+#[test]
+fn settings_persist_in_the_data_directory() {
+    let data_dir = std::env::temp_dir().join(format!("plux-settings-{}", std::process::id()));
+    let manager = || LuaManager::new().with_data_dir(&data_dir);
+
+    let mut host = TestHost::with_manager(manager());
+    let bundle = host.load(configurable()).unwrap();
+    host.assert_call(
+        &bundle,
+        "set",
+        &[string("volume"), Variable::F64(0.25)],
+        None,
+    );
+    let stored = fs::read_to_string(data_dir.join("configurable/settings.toml")).unwrap();
+    assert_eq!(stored.trim(), "volume = 0.25");
+    drop(host);
+
+    let mut host = TestHost::with_manager(manager());
+    let bundle = host.load(configurable()).unwrap();
+    host.assert_call(
+        &bundle,
+        "get",
+        &[string("volume")],
+        Some(Variable::F64(0.25)),
+    );
+    assert_eq!(
+        host.manager().settings(&bundle).unwrap()["nickname"],
+        Variable::Null
+    );
+
+    fs::remove_dir_all(&data_dir).unwrap();
+}
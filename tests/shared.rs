@@ -0,0 +1,72 @@
+mod utils;
+
+use plux_lua_manager::{AccessPolicy, LuaManager};
+use plux_rs::variable::Variable;
+
+use crate::utils::{get_plugin_path, loader_init};
+
+#[test]
+fn plugins_exchange_values_through_shared_channel() {
+    let manager = LuaManager::new();
+    manager.create_shared(
+        "layout",
+        AccessPolicy::new()
+            .writer("shared_writer")
+            .reader("shared_reader"),
+    );
+
+    let mut loader = loader_init(manager.clone());
+    let [writer, reader, intruder] =
+        ["shared_writer", "shared_reader", "shared_intruder"].map(|id| {
+            loader
+                .load_plugin_now(get_plugin_path(id, "1.0.0").to_str().unwrap())
+                .unwrap()
+        });
+    let call = |bundle, name, args: &[Variable]| {
+        loader
+            .get_plugin_by_bundle(bundle)
+            .unwrap()
+            .call_function(name, args)
+            .unwrap()
+            .unwrap()
+    };
+
+    assert_eq!(call(&reader, "read", &[]), None);
+    call(&writer, "publish", &[Variable::I32(640)]);
+    assert_eq!(call(&reader, "read", &[]), Some(Variable::I64(640)));
+    assert_eq!(
+        manager.shared_value("layout", "width"),
+        Some(Variable::I64(640))
+    );
+
+    assert_eq!(
+        call(&intruder, "read", &[]),
+        Some(Variable::String("access_denied".to_string()))
+    );
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn plugins_and_host_share_the_store() {
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("store_user", "1.0.0").to_str().unwrap())
+        .unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+    let call = |name, args: &[Variable]| plugin.call_function(name, args).unwrap().unwrap();
+    let key = |key: &str| Variable::String(key.to_string());
+
+    call("put", &[key("theme"), key("dark")]);
+    manager.set_store_value("width", Variable::I64(640));
+    assert_eq!(manager.store_value("theme"), Some(key("dark")));
+    assert_eq!(call("get", &[key("width")]), Some(Variable::I64(640)));
+    assert_eq!(call("keys", &[]), Some(key("theme,width")));
+
+    assert_eq!(call("delete", &[key("theme")]), Some(Variable::Bool(true)));
+    assert_eq!(call("delete", &[key("theme")]), Some(Variable::Bool(false)));
+    assert_eq!(manager.store_keys(), vec!["width".to_string()]);
+
+    loader.stop().unwrap();
+}
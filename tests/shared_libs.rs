@@ -0,0 +1,36 @@
+mod utils;
+
+use plux_lua_manager::LuaManager;
+use plux_rs::variable::Variable;
+
+use crate::utils::{get_plugin_path, loader_init};
+
+#[test]
+fn shared_lib_dirs_are_requireable() {
+    let libs = std::env::current_dir().unwrap().join("tests/libs");
+    let mut loader = loader_init(LuaManager::new().with_shared_lib_dirs([libs]));
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("lib_user", "1.0.0").to_str().unwrap())
+        .unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+
+    assert_eq!(
+        plugin
+            .call_function("shout", &[Variable::String("plux".to_string())])
+            .unwrap()
+            .unwrap(),
+        Some(Variable::String("PLUX!".to_string()))
+    );
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn missing_shared_lib_fails_the_load() {
+    let mut loader = loader_init(LuaManager::new());
+    assert!(
+        loader
+            .load_plugin_now(get_plugin_path("lib_user", "1.0.0").to_str().unwrap())
+            .is_err()
+    );
+}
@@ -0,0 +1,75 @@
+mod utils;
+
+use plux_lua_manager::LuaManager;
+use plux_rs::variable::Variable;
+
+use crate::utils::{get_plugin_path, loader_init};
+
+#[test]
+fn plugins_in_a_shared_state_keep_their_own_globals() {
+    let manager = LuaManager::builder().shared_state(true).build();
+    let mut loader = loader_init(manager.clone());
+    let a = loader
+        .load_plugin_now(get_plugin_path("shared_state_a", "1.0.0").to_str().unwrap())
+        .unwrap();
+    let b = loader
+        .load_plugin_now(get_plugin_path("shared_state_b", "1.0.0").to_str().unwrap())
+        .unwrap();
+
+    let whoami = |bundle| {
+        manager
+            .call_batch(bundle, "whoami", &[vec![]])
+            .unwrap()
+            .remove(0)
+            .unwrap()
+    };
+    assert_eq!(
+        whoami(&a),
+        Some(Variable::String(
+            "shared_state_a shared_state_a helper of shared_state_a".to_string()
+        ))
+    );
+    assert_eq!(
+        whoami(&b),
+        Some(Variable::String(
+            "shared_state_b shared_state_b helper of shared_state_b".to_string()
+        ))
+    );
+
+    // Both plugins live in the same state
+    let used = |bundle| manager.plugin_runtime_info(bundle).unwrap().used_memory;
+    assert_eq!(used(&a), used(&b));
+
+    // Reloading a plugin leaves the other one alone
+    manager.reload_plugin(&a).unwrap();
+    assert_eq!(
+        whoami(&b),
+        Some(Variable::String(
+            "shared_state_b shared_state_b helper of shared_state_b".to_string()
+        ))
+    );
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn plugins_get_a_state_each_by_default() {
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+    let a = loader
+        .load_plugin_now(get_plugin_path("shared_state_a", "1.0.0").to_str().unwrap())
+        .unwrap();
+    let b = loader
+        .load_plugin_now(get_plugin_path("shared_state_b", "1.0.0").to_str().unwrap())
+        .unwrap();
+
+    // Calls into a plugin do not allocate in the state of the other one
+    let used = |bundle| manager.plugin_runtime_info(bundle).unwrap().used_memory;
+    let before = used(&b);
+    manager
+        .call_batch(&a, "whoami", &vec![vec![]; 100])
+        .unwrap();
+    assert_eq!(used(&b), before);
+
+    loader.stop().unwrap();
+}
@@ -0,0 +1,152 @@
+mod utils;
+
+use std::{io, sync::Arc};
+
+use hashbrown::HashMap;
+use plux_lua_manager::{LuaManager, SourceProvider};
+use plux_rs::{Bundle, variable::Variable};
+
+use crate::utils::{get_plugin_path, loader_init};
+
+struct MemoryProvider {
+    files: HashMap<String, String>,
+}
+
+impl MemoryProvider {
+    fn new(files: &[(&str, &str)]) -> Self {
+        Self {
+            files: files
+                .iter()
+                .map(|(path, src)| (path.to_string(), src.to_string()))
+                .collect(),
+        }
+    }
+}
+
+impl SourceProvider for MemoryProvider {
+    fn read_source(&self, rel_path: &str) -> io::Result<String> {
+        self.files
+            .get(rel_path)
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, rel_path.to_string()))
+    }
+
+    fn exists(&self, rel_path: &str) -> bool {
+        self.files.contains_key(rel_path)
+    }
+
+    fn list(&self, prefix: &str) -> io::Result<Vec<String>> {
+        let mut files: Vec<_> = self
+            .files
+            .keys()
+            .filter(|path| path.starts_with(prefix))
+            .cloned()
+            .collect();
+        files.sort();
+        Ok(files)
+    }
+}
+
+#[test]
+fn plugin_loads_from_custom_provider() {
+    let provider = Arc::new(MemoryProvider::new(&[
+        (
+            "config.toml",
+            r#"
+                name = "virtual"
+                description = "Plugin served from memory"
+                author = "Plux"
+            "#,
+        ),
+        (
+            "main.lua",
+            r#"
+                local greet = require("lib.greet")
+                return {
+                    { name = "hello", inputs = { "name" }, func = greet.hello },
+                }
+            "#,
+        ),
+        (
+            "lib/greet/init.lua",
+            r#"
+                return {
+                    hello = function(name) return "Hello, " .. name end,
+                }
+            "#,
+        ),
+    ]));
+
+    let manager = LuaManager::new().with_source_provider(move |_| provider.clone());
+    let mut loader = loader_init(manager);
+
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("virtual", "1.0.0").to_str().unwrap())
+        .unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+
+    let greeting = plugin
+        .call_function("hello", &[Variable::String("plux".to_string())])
+        .unwrap()
+        .unwrap();
+    assert_eq!(greeting, Some(Variable::String("Hello, plux".to_string())));
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn resolvers_serve_modules_plugins_do_not_ship() {
+    let provider = Arc::new(MemoryProvider::new(&[
+        (
+            "config.toml",
+            r#"
+                name = "virtual"
+                description = "Plugin requiring host modules"
+                author = "Plux"
+            "#,
+        ),
+        (
+            "main.lua",
+            r#"
+                local host = require("host.info")
+                local shadowed = require("shadowed")
+                local missing = pcall(require, "missing")
+                return {
+                    { name = "host", inputs = {}, func = function() return host end },
+                    { name = "shadowed", inputs = {}, func = function() return shadowed end },
+                    { name = "missing", inputs = {}, func = function() return missing end },
+                }
+            "#,
+        ),
+        ("shadowed.lua", "return 'plugin'"),
+    ]));
+
+    let manager = LuaManager::new()
+        .with_source_provider(move |_| provider.clone())
+        .with_module_resolver(|bundle: &Bundle, name: &str| {
+            Ok(match name {
+                "host.info" => Some(format!("return '{} from host'", bundle.id)),
+                "shadowed" => Some("return 'host'".to_string()),
+                _ => None,
+            })
+        });
+    let mut loader = loader_init(manager);
+
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("virtual", "1.0.0").to_str().unwrap())
+        .unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+
+    let call = |name: &str| plugin.call_function(name, &[]).unwrap().unwrap();
+    assert_eq!(
+        call("host"),
+        Some(Variable::String("virtual from host".to_string()))
+    );
+    assert_eq!(
+        call("shadowed"),
+        Some(Variable::String("plugin".to_string()))
+    );
+    assert_eq!(call("missing"), Some(Variable::Bool(false)));
+
+    loader.stop().unwrap();
+}
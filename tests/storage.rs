@@ -0,0 +1,79 @@
+mod utils;
+
+use std::fs;
+
+use plux_lua_manager::LuaManager;
+use plux_rs::variable::Variable;
+
+use crate::utils::{get_plugin_path, loader_init};
+
+fn string(s: &str) -> Variable {
+    Variable::String(s.to_string())
+}
+
+#[test]
+fn plugins_persist_files_in_their_data_directory() {
+    let data_dir = std::env::temp_dir().join(format!("plux-storage-{}", std::process::id()));
+    let manager = LuaManager::new().with_data_dir(&data_dir);
+    let path = get_plugin_path("storage_user", "1.0.0");
+
+    let mut loader = loader_init(manager.clone());
+    let bundle = loader.load_plugin_now(path.to_str().unwrap()).unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+    plugin
+        .call_function("save", &[string("notes/a.txt"), string("hello")])
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        fs::read_to_string(data_dir.join("storage_user/notes/a.txt")).unwrap(),
+        "hello"
+    );
+    assert_eq!(
+        manager.plugin_data_dir("storage_user"),
+        Some(data_dir.join("storage_user"))
+    );
+    for path in ["../escaped.txt", "/tmp/escaped.txt", ""] {
+        assert_eq!(
+            plugin
+                .call_function("escape", &[string(path)])
+                .unwrap()
+                .unwrap(),
+            Some(Variable::Bool(false)),
+            "{path}"
+        );
+    }
+    loader.stop().unwrap();
+
+    // The data survives the plugin's state
+    let mut loader = loader_init(manager);
+    let bundle = loader.load_plugin_now(path.to_str().unwrap()).unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+    assert_eq!(
+        plugin
+            .call_function("load", &[string("notes/a.txt")])
+            .unwrap()
+            .unwrap(),
+        Some(string("hello"))
+    );
+    assert_eq!(
+        plugin.call_function("files", &[]).unwrap().unwrap(),
+        Some(Variable::List(vec![string("notes/a.txt")]))
+    );
+    assert_eq!(
+        plugin
+            .call_function("remove", &[string("notes/a.txt")])
+            .unwrap()
+            .unwrap(),
+        Some(Variable::Bool(true))
+    );
+    assert_eq!(
+        plugin
+            .call_function("load", &[string("notes/a.txt")])
+            .unwrap()
+            .unwrap(),
+        None
+    );
+    loader.stop().unwrap();
+
+    fs::remove_dir_all(data_dir).unwrap();
+}
@@ -0,0 +1,66 @@
+mod utils;
+
+use plux_lua_manager::LuaManager;
+use plux_rs::{Loader, StdInfo, function::FunctionOutput, variable::Variable};
+
+use crate::utils::{PinnedLoader, get_plugin_path};
+
+const PAYLOAD: &[u8] = b"caf\xE9\x80";
+
+fn load(id: &str) -> PinnedLoader {
+    let mut loader = PinnedLoader::new(LuaManager::new());
+    loader.load(&get_plugin_path(id, "1.0.0"));
+    loader
+}
+
+fn call(
+    loader: &Loader<'static, FunctionOutput, StdInfo>,
+    id: &str,
+    name: &str,
+    args: &[Variable],
+) -> FunctionOutput {
+    loader.get_plugins_by_id(id)[0]
+        .call_function(name, args)
+        .unwrap()
+}
+
+fn bytes(bytes: &[u8]) -> Variable {
+    Variable::List(bytes.iter().copied().map(Variable::U8).collect())
+}
+
+#[test]
+fn utf8_policy_rejects_invalid_strings() {
+    let mut loader = load("strings_utf8");
+
+    let error = call(&loader, "strings_utf8", "get_payload", &[])
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("Function `get_payload`: output"), "{error}");
+    assert!(error.contains("invalid UTF-8"), "{error}");
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn bytes_policy_uses_byte_lists() {
+    let mut loader = load("strings_bytes");
+
+    let payload = call(&loader, "strings_bytes", "get_payload", &[]).unwrap();
+    assert_eq!(payload, Some(bytes(PAYLOAD)));
+
+    // Byte lists become Lua strings on the way in
+    let echoed = call(&loader, "strings_bytes", "echo", &[bytes(PAYLOAD)]).unwrap();
+    assert_eq!(echoed, Some(bytes(PAYLOAD)));
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn lossy_policy_replaces_invalid_sequences() {
+    let mut loader = load("strings_lossy");
+
+    let payload = call(&loader, "strings_lossy", "get_payload", &[]).unwrap();
+    assert_eq!(payload, Some(Variable::String("caf\u{FFFD}".to_string())));
+
+    loader.stop().unwrap();
+}
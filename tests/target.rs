@@ -0,0 +1,45 @@
+use plux_lua_manager::testing::{PluginFixture, TestHost};
+use plux_rs::{utils::RegisterPluginError, variable::Variable};
+
+fn targeted(id: &str, target: &str) -> PluginFixture {
+    PluginFixture::new(id)
+        .config(&format!(
+            "name = \"{id}\"\ndescription = \"\"\nauthor = \"\"\n\n[target]\n{target}\n"
+        ))
+        .main(r#"return { { name = "run", inputs = {}, func = function() return "ran" end } }"#)
+}
+
+#[test]
+fn plugins_register_only_on_their_target_platforms() {
+    let mut host = TestHost::new();
+
+    let native = host
+        .load(targeted(
+            "native",
+            &format!(
+                "os = [\"{}\"]\narch = [\"{}\"]",
+                std::env::consts::OS,
+                std::env::consts::ARCH
+            ),
+        ))
+        .unwrap();
+    host.assert_call(
+        &native,
+        "run",
+        &[],
+        Some(Variable::String("ran".to_string())),
+    );
+
+    let foreign = targeted("foreign", "os = [\"plan9\"]").create().unwrap();
+    let error = match host
+        .loader()
+        .register_plugin(foreign.path().to_str().unwrap())
+    {
+        Err(RegisterPluginError::RegisterPluginByManager(error)) => error.to_string(),
+        result => panic!("unexpected result: {result:?}"),
+    };
+    assert!(
+        error.contains(&format!("does not run on {}", std::env::consts::OS)),
+        "{error}"
+    );
+}
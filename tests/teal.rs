@@ -0,0 +1,94 @@
+#![cfg(feature = "teal")]
+
+use plux_lua_manager::{
+    LuaManager, ManagerError, PluginError, TestingError,
+    testing::{PluginFixture, TestHost},
+};
+use plux_rs::variable::Variable;
+
+/// Stands in for the Teal compiler: strips `: <type>` annotations, and
+/// rejects strings assigned to `number` locals
+const FAKE_TL: &str = r#"
+    local tl = {}
+    function tl.process_string(src, is_lua, env, filename)
+        local result = { ast = src, syntax_errors = {}, type_errors = {} }
+        local line = 1
+        for text in (src .. "\n"):gmatch("(.-)\n") do
+            local x = text:find(': number = "')
+            if x then
+                table.insert(result.type_errors, {
+                    filename = filename, y = line, x = x,
+                    msg = "in local declaration: got string, expected number",
+                })
+            end
+            line = line + 1
+        end
+        return result
+    end
+    function tl.generate(ast)
+        return (ast:gsub(": %a+", ""))
+    end
+    return tl
+"#;
+
+fn config(entry: &str) -> String {
+    format!("name = \"typed\"\ndescription = \"\"\nauthor = \"\"\nentry = \"{entry}\"\n")
+}
+
+#[test]
+fn teal_sources_are_compiled() {
+    let manager = LuaManager::new().with_teal_compiler(FAKE_TL).unwrap();
+    let mut host = TestHost::with_manager(manager);
+    let bundle = host
+        .load(
+            PluginFixture::new("typed")
+                .config(&config("main.tl"))
+                .file("util.tl", "local function twice(x: number): number\n  return x * 2\nend\nreturn { twice = twice }\n")
+                .file(
+                    "main.tl",
+                    r#"local util = require("util")
+return {
+    { name = "run", inputs = {}, output = "i64", func = function(): number
+        local base: number = 21
+        return util.twice(base)
+    end },
+}"#,
+                ),
+        )
+        .unwrap();
+
+    host.assert_call(&bundle, "run", &[], Some(Variable::I64(42)));
+}
+
+#[test]
+fn teal_type_errors_fail_the_load() {
+    let manager = LuaManager::new().with_teal_compiler(FAKE_TL).unwrap();
+    let plugin = PluginFixture::new("typed")
+        .config(&config("main.tl"))
+        .file("main.tl", "local n: number = \"one\"\nreturn {}\n")
+        .create()
+        .unwrap();
+
+    let results = manager.preload(&[plugin.path()]);
+    let Err(ManagerError::Plugin(PluginError::SourceError(message))) = &results[0] else {
+        panic!("expected a source error, got {:?}", results[0]);
+    };
+    assert!(
+        message.contains("main.tl:1:8: in local declaration: got string, expected number"),
+        "{message}"
+    );
+
+    let mut host = TestHost::with_manager(manager);
+    let result = host.load(
+        PluginFixture::new("typed")
+            .config(&config("main.tl"))
+            .file("main.tl", "local n: number = \"one\"\nreturn {}\n"),
+    );
+    assert!(matches!(result, Err(TestingError::Load(..))));
+}
+
+#[test]
+fn invalid_teal_compiler_is_rejected() {
+    let result = LuaManager::new().with_teal_compiler("return (");
+    assert!(matches!(result, Err(ManagerError::Lua(_))));
+}
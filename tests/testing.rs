@@ -0,0 +1,70 @@
+use plux_lua_manager::{
+    LuaManager, TestingError,
+    testing::{PluginFixture, TestHost},
+};
+use plux_rs::variable::Variable;
+
+#[test]
+fn fixtures_load_and_run() {
+    let mut host = TestHost::new();
+    let bundle = host
+        .load(
+            PluginFixture::new("greeter")
+                .version("2.1.0")
+                .file("lib/names.lua", "return { default = 'world' }")
+                .main(
+                    r#"
+                    local names = require("lib.names")
+                    return {
+                        { name = "greet", inputs = {}, func = function()
+                            return "hello " .. names.default
+                        end },
+                        { name = "fail", inputs = {}, func = function() error("boom") end },
+                    }
+                    "#,
+                ),
+        )
+        .unwrap();
+    assert_eq!(bundle.to_string(), "greeter-v2.1.0.lua");
+
+    host.assert_call(
+        &bundle,
+        "greet",
+        &[],
+        Some(Variable::String("hello world".to_string())),
+    );
+    host.assert_call_fails(&bundle, "fail", &[], "boom");
+    assert!(matches!(
+        host.call(&bundle, "missing", &[]),
+        Err(TestingError::Call(..))
+    ));
+}
+
+#[test]
+fn fixtures_report_load_errors() {
+    let mut host = TestHost::with_manager(LuaManager::new().with_strict_capabilities(true));
+    let result = host.load(PluginFixture::new("broken").main("return {"));
+    assert!(matches!(result, Err(TestingError::Load(..))));
+
+    let fixture = PluginFixture::new("unknown").config(
+        r#"
+        name = "unknown"
+        description = "Plugin asking for an unknown capability"
+        author = "Plux"
+        capabilities = ["teleport"]
+        "#,
+    );
+    let result = host.load(fixture);
+    assert!(matches!(result, Err(TestingError::Load(..))));
+}
+
+#[test]
+fn plugin_dirs_are_removed_on_drop() {
+    let dir = PluginFixture::new("temporary").create().unwrap();
+    let path = dir.path().to_path_buf();
+    assert!(path.join("config.toml").exists());
+    assert!(path.join("main.lua").exists());
+
+    drop(dir);
+    assert!(!path.exists());
+}
@@ -0,0 +1,133 @@
+mod utils;
+
+use std::{
+    sync::{Mutex, mpsc},
+    thread,
+    time::{Duration, Instant},
+};
+
+use plux_lua_manager::{
+    LuaManager,
+    testing::{PluginFixture, TestHost},
+};
+use plux_rs::{
+    Loader, StdInfo,
+    function::{DynamicFunction, FunctionOutput},
+    variable::Variable,
+};
+
+use crate::utils::{PinnedLoader, get_plugin_path};
+
+fn load_tickers(manager: &LuaManager) -> PinnedLoader {
+    let mut loader = PinnedLoader::new(manager.clone());
+    for id in ["ticker_a", "ticker_b"] {
+        loader.load(&get_plugin_path(id, "1.0.0"));
+    }
+    loader
+}
+
+fn progress(loader: &Loader<'static, FunctionOutput, StdInfo>, id: &str) -> Variable {
+    loader
+        .get_plugins_by_id(id)
+        .first()
+        .unwrap()
+        .call_function("progress", &[])
+        .unwrap()
+        .unwrap()
+        .unwrap()
+}
+
+#[test]
+fn tick_makes_progress_on_every_plugin() {
+    let manager = LuaManager::new();
+    let mut loader = load_tickers(&manager);
+
+    for _ in 0..5 {
+        let report = manager.tick(Duration::from_secs(5));
+        assert_eq!(report.tasks_resumed, 2);
+        assert!(!report.exhausted);
+    }
+
+    assert_eq!(progress(&loader, "ticker_a"), Variable::I64(5));
+    assert_eq!(progress(&loader, "ticker_b"), Variable::I64(5));
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn tiny_budget_leaves_work_for_the_next_tick() {
+    let manager = LuaManager::new();
+    let mut loader = load_tickers(&manager);
+
+    let report = manager.tick(Duration::from_nanos(1));
+    assert_eq!(report.tasks_resumed, 1);
+    assert!(report.exhausted);
+
+    // The next tick starts with the other plugin
+    let report = manager.tick(Duration::from_nanos(1));
+    assert_eq!(report.tasks_resumed, 1);
+    assert!(report.exhausted);
+
+    assert_eq!(progress(&loader, "ticker_a"), Variable::I64(1));
+    assert_eq!(progress(&loader, "ticker_b"), Variable::I64(1));
+
+    loader.stop().unwrap();
+}
+
+const HELD: &str = r#"
+local steps = 0
+
+api.spawn(function()
+    while true do
+        steps = steps + 1
+        coroutine.yield()
+    end
+end)
+
+return {
+    { name = "hold", inputs = {}, func = function() host.block() end },
+}
+"#;
+
+#[test]
+fn tick_waits_for_states_in_use_by_another_thread() {
+    let (entered, wait_entered) = mpsc::channel();
+    let (release, wait_release) = mpsc::channel::<()>();
+    let wait_release = Mutex::new(wait_release);
+
+    let mut host = TestHost::new();
+    host.loader().context(move |mut ctx| {
+        ctx.register_function(DynamicFunction::new("block", vec![], None, move |_| {
+            entered.send(()).unwrap();
+            wait_release.lock().unwrap().recv().unwrap();
+            Ok(None)
+        }))
+    });
+    let bundle = host
+        .load(
+            PluginFixture::new("held")
+                .config("name = \"held\"\ndescription = \"\"\nauthor = \"\"\ncapabilities = [\"spawn\"]\n")
+                .main(HELD),
+        )
+        .unwrap();
+    let manager = host.manager().clone();
+
+    let holder = {
+        let manager = manager.clone();
+        let bundle = bundle.clone();
+        thread::spawn(move || manager.call_batch(&bundle, "hold", &[vec![]]).unwrap())
+    };
+    wait_entered.recv().unwrap();
+
+    let releaser = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(100));
+        release.send(()).unwrap();
+    });
+    let start = Instant::now();
+    let report = manager.tick(Duration::from_secs(5));
+    assert!(start.elapsed() >= Duration::from_millis(100));
+    assert_eq!(report.tasks_resumed, 1);
+
+    releaser.join().unwrap();
+    assert!(holder.join().unwrap()[0].is_ok());
+}
@@ -0,0 +1,44 @@
+mod utils;
+
+use std::time::{Duration, Instant};
+
+use plux_lua_manager::LuaManager;
+use plux_rs::variable::Variable;
+
+use crate::utils::{get_plugin_path, loader_init};
+
+const TIMEOUT: Duration = Duration::from_millis(50);
+
+#[test]
+fn call_timeout_aborts_runaway_calls() {
+    let manager = LuaManager::new().with_call_timeout(TIMEOUT);
+    let mut loader = loader_init(manager.clone());
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("spinner", "1.0.0").to_str().unwrap())
+        .unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+
+    let start = Instant::now();
+    let error = plugin
+        .call_function("spin", &[Variable::Bool(true)])
+        .unwrap()
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("timed out"), "{error}");
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    // The deadline does not leak into the next call
+    assert_eq!(
+        plugin
+            .call_function("spin", &[Variable::Bool(false)])
+            .unwrap()
+            .unwrap(),
+        Some(Variable::I64(1000))
+    );
+    assert_eq!(
+        manager.plugin_runtime_info(&bundle).unwrap().call_timeout,
+        Some(TIMEOUT)
+    );
+
+    loader.stop().unwrap();
+}
@@ -0,0 +1,102 @@
+#![cfg(feature = "tracing")]
+
+mod utils;
+
+use std::sync::{Arc, Mutex};
+
+use plux_lua_manager::LuaManager;
+use plux_rs::variable::Variable;
+use tracing::{
+    Event, Metadata, Subscriber,
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+};
+
+use crate::utils::{get_plugin_path, loader_init};
+
+/// Records the name and `bundle` field of every new span.
+#[derive(Clone, Default)]
+struct SpanRecorder(Arc<Mutex<Vec<(String, String)>>>);
+
+struct BundleField(String);
+
+impl Visit for BundleField {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "bundle" {
+            self.0 = format!("{value:?}");
+        }
+    }
+}
+
+impl Subscriber for SpanRecorder {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let mut bundle = BundleField(String::new());
+        span.record(&mut bundle);
+
+        let mut spans = self.0.lock().unwrap();
+        spans.push((span.metadata().name().to_string(), bundle.0));
+        Id::from_u64(spans.len() as u64)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, _: &Event<'_>) {}
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+#[test]
+fn plugin_operations_are_traced() {
+    let recorder = SpanRecorder::default();
+    tracing::subscriber::with_default(recorder.clone(), || {
+        let manager = LuaManager::new();
+        let mut loader = loader_init(manager.clone());
+
+        let bundle = loader
+            .load_plugin_now(get_plugin_path("batch", "1.0.0").to_str().unwrap())
+            .unwrap();
+        let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+        plugin
+            .call_function("transform", &[Variable::I32(1)])
+            .unwrap()
+            .unwrap();
+
+        loader.stop().unwrap();
+    });
+
+    let spans = recorder.0.lock().unwrap();
+    let names: Vec<_> = spans.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, vec!["register", "load", "call", "unload"]);
+    assert!(spans.iter().all(|(_, bundle)| bundle == "batch-v1.0.0.lua"));
+}
+
+#[test]
+fn spans_follow_the_log_level() {
+    let recorder = SpanRecorder::default();
+    tracing::subscriber::with_default(recorder.clone(), || {
+        let manager = LuaManager::new().with_log_level(log::LevelFilter::Info);
+        let mut loader = loader_init(manager.clone());
+
+        let bundle = loader
+            .load_plugin_now(get_plugin_path("batch", "1.0.0").to_str().unwrap())
+            .unwrap();
+        let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+        plugin
+            .call_function("transform", &[Variable::I32(1)])
+            .unwrap()
+            .unwrap();
+
+        loader.stop().unwrap();
+    });
+
+    let spans = recorder.0.lock().unwrap();
+    assert!(spans.iter().all(|(name, _)| name != "call"));
+}
@@ -0,0 +1,52 @@
+use plux_lua_manager::{
+    LuaManager, ManagerError, PluginError,
+    testing::{PluginFixture, TestHost},
+};
+use plux_rs::variable::Variable;
+
+/// Lua with `fn` for `function` and `=>` for `return`
+fn short_lua(path: &str, src: &str) -> Result<String, PluginError> {
+    if src.contains("function") {
+        return Err(PluginError::SourceError(format!(
+            "{path}: use `fn` instead of `function`"
+        )));
+    }
+    Ok(src.replace("fn", "function").replace("=>", "return"))
+}
+
+fn fixture() -> PluginFixture {
+    PluginFixture::new("short")
+        .config("name = \"short\"\ndescription = \"\"\nauthor = \"\"\nentry = \"main.slua\"\n")
+        .file("geometry/init.slua", "=> { square = fn(x) => x * x end }\n")
+        .file(
+            "main.slua",
+            r#"local geometry = require("geometry")
+=> {
+    { name = "run", inputs = {}, output = "i64", func = fn() => geometry.square(7) end },
+}"#,
+        )
+}
+
+#[test]
+fn transforms_apply_to_entries_and_modules() {
+    let mut host =
+        TestHost::with_manager(LuaManager::new().with_source_transform("slua", short_lua));
+    let bundle = host.load(fixture()).unwrap();
+
+    host.assert_call(&bundle, "run", &[], Some(Variable::I64(49)));
+}
+
+#[test]
+fn transform_errors_fail_the_load() {
+    let manager = LuaManager::new().with_source_transform("slua", short_lua);
+    let plugin = fixture()
+        .file("main.slua", "=> function() end")
+        .create()
+        .unwrap();
+
+    let results = manager.preload(&[plugin.path()]);
+    assert!(matches!(
+        &results[0],
+        Err(ManagerError::Plugin(PluginError::SourceError(message))) if message.contains("main.slua")
+    ));
+}
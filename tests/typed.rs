@@ -0,0 +1,169 @@
+mod utils;
+
+use plux_lua_manager::{LuaManager, ManagerError, PluginError};
+use plux_rs::variable::Variable;
+
+use crate::utils::{get_plugin_path, loader_init};
+
+#[test]
+fn typed_fn_converts_native_types() {
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("typed", "1.0.0").to_str().unwrap())
+        .unwrap();
+
+    let format_row = manager
+        .typed_fn::<(i64, String), String>(&bundle, "format_row")
+        .unwrap();
+    assert_eq!(
+        format_row.call((7, "seven".to_string())).unwrap(),
+        "7: seven"
+    );
+
+    // Undeclared types accept anything
+    let describe = manager
+        .typed_fn::<(bool,), String>(&bundle, "describe")
+        .unwrap();
+    assert_eq!(describe.call((true,)).unwrap(), "true");
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn typed_fn_rejects_mismatched_signatures() {
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("typed", "1.0.0").to_str().unwrap())
+        .unwrap();
+
+    let error = manager
+        .typed_fn::<(String, String), String>(&bundle, "format_row")
+        .err()
+        .unwrap()
+        .to_string();
+    assert!(
+        error.contains("argument #1 `id` is declared as I64, got String"),
+        "{error}"
+    );
+
+    let error = manager
+        .typed_fn::<(i64,), String>(&bundle, "format_row")
+        .err()
+        .unwrap()
+        .to_string();
+    assert!(error.contains("it takes 2 arguments, got 1"), "{error}");
+
+    let error = manager
+        .typed_fn::<(i64, String), bool>(&bundle, "format_row")
+        .err()
+        .unwrap()
+        .to_string();
+    assert!(
+        error.contains("the output is declared as String, got Bool"),
+        "{error}"
+    );
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn calls_check_declared_types() {
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("typed", "1.0.0").to_str().unwrap())
+        .unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+
+    // Numbers are converted to the declared types
+    assert_eq!(
+        plugin
+            .call_function("scale", &[Variable::I32(3), Variable::I64(2)])
+            .unwrap()
+            .unwrap(),
+        Some(Variable::I32(6))
+    );
+
+    let error = plugin
+        .call_function(
+            "scale",
+            &[Variable::String("3".to_string()), Variable::I32(2)],
+        )
+        .unwrap()
+        .unwrap_err();
+    assert!(
+        matches!(
+            error.downcast_ref::<ManagerError>(),
+            Some(ManagerError::Plugin(PluginError::TypeMismatch { value, .. }))
+                if value == "argument `x`"
+        ),
+        "{error}"
+    );
+
+    let error = plugin
+        .call_function("scale", &[Variable::F64(1.5), Variable::I64(1 << 40)])
+        .unwrap()
+        .unwrap_err()
+        .to_string();
+    assert!(
+        error.contains("argument `factor` is I64, expected I32"),
+        "{error}"
+    );
+
+    let error = plugin
+        .call_function("scale", &[Variable::F64(1.5), Variable::I32(1)])
+        .unwrap()
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("the result is F64, expected I32"), "{error}");
+
+    let error = plugin
+        .call_function("count", &[Variable::String("many".to_string())])
+        .unwrap()
+        .unwrap_err()
+        .to_string();
+    assert!(
+        error.contains("the result is String, expected I64"),
+        "{error}"
+    );
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn calls_fill_in_optional_arguments() {
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("typed", "1.0.0").to_str().unwrap())
+        .unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+    let fetch = |args: &[Variable]| plugin.call_function("fetch", args).unwrap();
+    let url = || Variable::String("a.io".to_string());
+
+    assert_eq!(
+        fetch(&[url()]).unwrap(),
+        Some(Variable::String("a.io x3 -".to_string()))
+    );
+    assert_eq!(
+        fetch(&[url(), Variable::Null, Variable::String("x".to_string())]).unwrap(),
+        Some(Variable::String("a.io x3 x".to_string()))
+    );
+    assert_eq!(
+        fetch(&[url(), Variable::I64(5)]).unwrap(),
+        Some(Variable::String("a.io x5 -".to_string()))
+    );
+
+    let error = fetch(&[]).unwrap_err();
+    assert!(
+        matches!(
+            error.downcast_ref::<ManagerError>(),
+            Some(ManagerError::Plugin(PluginError::MissingArgument(_, name))) if name == "url"
+        ),
+        "{error}"
+    );
+
+    loader.stop().unwrap();
+}
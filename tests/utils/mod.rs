@@ -0,0 +1,71 @@
+use std::{
+    ops::Deref,
+    path::{Path, PathBuf},
+};
+
+use plux_lua_manager::LuaManager;
+use plux_rs::{Bundle, Loader, StdInfo, function::FunctionOutput, utils::StopLoaderError};
+
+#[allow(dead_code)]
+pub fn get_plugin_path(id: &str, version: &str) -> PathBuf {
+    std::env::current_dir()
+        .unwrap()
+        .join(format!("./tests/plugins/{id}-v{version}.lua"))
+}
+
+#[allow(dead_code)]
+pub fn loader_init(manager: LuaManager) -> Loader<'static, FunctionOutput, StdInfo> {
+    let mut loader = Loader::new();
+    loader
+        .context(move |mut ctx| ctx.register_manager(manager))
+        .unwrap();
+    loader
+}
+
+#[allow(dead_code)]
+pub fn benchmark<F, R>(f: F) -> (std::time::Duration, R)
+where
+    F: FnOnce() -> R,
+{
+    let timer = std::time::Instant::now();
+    let data = f();
+    (timer.elapsed(), data)
+}
+
+/// A loader with a [`LuaManager`] registered, kept in place for its plugins.
+///
+/// plux keeps pointers back to the loader in the plugins it loads, so the
+/// loader must not move once a plugin is loaded. It is boxed here and only
+/// lent out by shared reference, so tests can pass the fixture around freely.
+#[allow(dead_code)]
+pub struct PinnedLoader {
+    loader: Box<Loader<'static, FunctionOutput, StdInfo>>,
+}
+
+#[allow(dead_code)]
+impl PinnedLoader {
+    pub fn new(manager: LuaManager) -> Self {
+        let mut loader = Box::new(Loader::new());
+        loader
+            .context(move |mut ctx| ctx.register_manager(manager))
+            .unwrap();
+        Self { loader }
+    }
+
+    /// Loads the plugin at `path`, panicking if it fails.
+    pub fn load(&mut self, path: &Path) -> Bundle {
+        self.loader.load_plugin_now(path.to_str().unwrap()).unwrap()
+    }
+
+    pub fn stop(&mut self) -> Result<(), StopLoaderError> {
+        self.loader.stop()
+    }
+}
+
+impl Deref for PinnedLoader {
+    type Target = Loader<'static, FunctionOutput, StdInfo>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.loader
+    }
+}
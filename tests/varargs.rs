@@ -0,0 +1,124 @@
+use plux_lua_manager::{LuaManager, ManagerError, PluginError, testing::PluginFixture};
+use plux_rs::{
+    Loader,
+    function::Request,
+    utils::LoadPluginError,
+    variable::{Variable, VariableType},
+};
+
+fn string(s: &str) -> Variable {
+    Variable::String(s.to_string())
+}
+
+#[test]
+fn variadic_inputs_collect_remaining_arguments() {
+    let fixture = PluginFixture::new("varargs").main(
+        r#"
+        function join(separator, parts)
+            return table.concat(parts, separator)
+        end
+
+        return {
+            { name = "sum", inputs = { "...values: i64" }, output = "i64", func = function(values)
+                local total = 0
+                for _, value in ipairs(values) do
+                    total = total + value
+                end
+                return total
+            end },
+            {
+                name = "tag",
+                inputs = { "label: string", { name = "items", variadic = true } },
+                func = function(label, items) return label .. ":" .. #items end,
+            },
+        }
+        "#,
+    );
+    let dir = fixture.create().unwrap();
+
+    let mut loader = Loader::new();
+    loader
+        .context(move |mut ctx| {
+            ctx.register_request(Request::new(
+                "join",
+                vec![VariableType::String, VariableType::List],
+                Some(VariableType::String),
+            ));
+            ctx.register_manager(LuaManager::new())
+        })
+        .unwrap();
+    let bundle = loader
+        .load_plugin_now(dir.path().to_str().unwrap())
+        .unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+
+    let sum = |args: &[Variable]| plugin.call_function("sum", args).unwrap();
+    assert_eq!(sum(&[]).unwrap(), Some(Variable::I64(0)));
+    assert_eq!(
+        sum(&[Variable::I64(1), Variable::I32(2), Variable::U8(3)]).unwrap(),
+        Some(Variable::I64(6))
+    );
+    assert_eq!(
+        sum(&[Variable::List(vec![Variable::I64(4), Variable::I64(5)])]).unwrap(),
+        Some(Variable::I64(9))
+    );
+    let error = sum(&[Variable::I64(1), string("2")]).unwrap_err();
+    assert!(
+        matches!(
+            error.downcast_ref::<ManagerError>(),
+            Some(ManagerError::Plugin(PluginError::TypeMismatch { value, .. }))
+                if value == "argument `values[2]`"
+        ),
+        "{error}"
+    );
+
+    assert_eq!(
+        plugin
+            .call_function("tag", &[string("fruit"), string("apple"), string("pear")])
+            .unwrap()
+            .unwrap(),
+        Some(string("fruit:2"))
+    );
+
+    // Surplus arguments of a request are collected into its last list input
+    assert_eq!(
+        plugin
+            .call_request("join", &[string(", "), string("a"), string("b")])
+            .unwrap()
+            .unwrap(),
+        Some(string("a, b"))
+    );
+    assert_eq!(
+        plugin
+            .call_request(
+                "join",
+                &[string("-"), Variable::List(vec![string("c"), string("d")])]
+            )
+            .unwrap()
+            .unwrap(),
+        Some(string("c-d"))
+    );
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn only_the_last_input_may_be_variadic() {
+    let fixture = PluginFixture::new("bad_varargs").main(
+        r#"return { { name = "f", inputs = { "...rest", "last" }, func = function() end } }"#,
+    );
+    let dir = fixture.create().unwrap();
+
+    let mut loader = Loader::new();
+    loader
+        .context(move |mut ctx| ctx.register_manager(LuaManager::new()))
+        .unwrap();
+    let error = match loader.load_plugin_now(dir.path().to_str().unwrap()) {
+        Err((None, Some(LoadPluginError::LoadPluginByManager(error)))) => error.to_string(),
+        result => panic!("unexpected result: {result:?}"),
+    };
+    assert!(
+        error.contains("only the last argument may be variadic, not `rest`"),
+        "{error}"
+    );
+}
@@ -0,0 +1,97 @@
+mod utils;
+
+use plux_lua_manager::LuaManager;
+use plux_rs::{
+    Loader, StdInfo,
+    function::{Arg, DynamicFunction, FunctionOutput},
+    variable::{Variable, VariableType},
+};
+
+use crate::utils::{get_plugin_path, loader_init};
+
+fn binary_function(name: &str, op: fn(i64, i64) -> i64) -> DynamicFunction {
+    DynamicFunction::new(
+        name,
+        vec![
+            Arg::new("a", VariableType::I64),
+            Arg::new("b", VariableType::I64),
+        ],
+        Some(Arg::new("c", VariableType::I64)),
+        move |args| {
+            let a = args[0].parse_ref::<i64>();
+            let b = args[1].parse_ref::<i64>();
+            Ok(Some(op(*a, *b).into()))
+        },
+    )
+}
+
+#[test]
+fn host_functions_keep_identity_across_refresh() {
+    let manager = LuaManager::new();
+    let mut loader = loader_init(manager.clone());
+    loader.context(|mut ctx| ctx.register_function(binary_function("add", |a, b| a + b)));
+
+    let bundle = loader
+        .load_plugin_now(get_plugin_path("host_refs", "1.0.0").to_str().unwrap())
+        .unwrap();
+
+    let call =
+        |loader: &Loader<'static, FunctionOutput, StdInfo>, name: &str, args: &[Variable]| {
+            loader
+                .get_plugin_by_bundle(&bundle)
+                .unwrap()
+                .call_function(name, args)
+                .unwrap()
+                .unwrap()
+        };
+
+    assert_eq!(
+        call(&loader, "same_identity", &[]),
+        Some(Variable::Bool(true))
+    );
+
+    // A host function registered after load becomes visible on refresh
+    loader.context(|mut ctx| ctx.register_function(binary_function("mul", |a, b| a * b)));
+    manager.refresh_vtable().unwrap();
+
+    assert_eq!(
+        call(&loader, "same_identity", &[]),
+        Some(Variable::Bool(true))
+    );
+    assert_eq!(
+        call(&loader, "call_stored", &[2.into(), 3.into()]),
+        Some(Variable::I64(5))
+    );
+    assert_eq!(
+        call(&loader, "call_late", &[2.into(), 3.into()]),
+        Some(Variable::I64(6))
+    );
+
+    loader.stop().unwrap();
+}
+
+#[test]
+fn flat_host_functions_are_opt_in() {
+    for flat in [false, true] {
+        let mut loader = loader_init(LuaManager::new().with_flat_host_functions(flat));
+        loader.context(|mut ctx| ctx.register_function(binary_function("add", |a, b| a + b)));
+        let bundle = loader
+            .load_plugin_now(get_plugin_path("host_flat", "1.0.0").to_str().unwrap())
+            .unwrap();
+        let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+
+        let result = plugin
+            .call_function("call_flat", &[2.into(), 3.into()])
+            .unwrap();
+        match flat {
+            true => assert_eq!(result.unwrap(), Some(Variable::I64(5))),
+            false => assert!(result.is_err()),
+        }
+        assert_eq!(
+            plugin.call_function("same_function", &[]).unwrap().unwrap(),
+            Some(Variable::Bool(flat))
+        );
+
+        loader.stop().unwrap();
+    }
+}
@@ -0,0 +1,75 @@
+#![cfg(feature = "watch")]
+
+mod utils;
+
+use std::{
+    fs,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use plux_lua_manager::LuaManager;
+use plux_rs::variable::Variable;
+
+use crate::utils::loader_init;
+
+const CONFIG: &str = r#"
+name = "watched"
+description = "Plugin rewritten by the test"
+author = "Plux"
+"#;
+
+fn main_lua(answer: i64) -> String {
+    format!(
+        r#"return {{ {{ name = "answer", inputs = {{}}, output = "i64", func = function() return {answer} end }} }}"#
+    )
+}
+
+#[test]
+fn changed_sources_are_reloaded_on_tick() {
+    let dir = std::env::temp_dir()
+        .join(format!("plux-watch-{}", std::process::id()))
+        .join("watched-v1.0.0.lua");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("config.toml"), CONFIG).unwrap();
+    fs::write(dir.join("main.lua"), main_lua(1)).unwrap();
+
+    let reloads = Arc::new(Mutex::new(vec![]));
+    let manager = {
+        let reloads = reloads.clone();
+        LuaManager::new()
+            .with_watch(true)
+            .with_reload_listener(move |bundle, result| {
+                reloads
+                    .lock()
+                    .unwrap()
+                    .push((bundle.clone(), result.is_ok()));
+            })
+    };
+    let mut loader = loader_init(manager.clone());
+    let bundle = loader.load_plugin_now(dir.to_str().unwrap()).unwrap();
+    let plugin = loader.get_plugin_by_bundle(&bundle).unwrap();
+    assert_eq!(
+        plugin.call_function("answer", &[]).unwrap().unwrap(),
+        Some(Variable::I64(1))
+    );
+
+    fs::write(dir.join("main.lua"), main_lua(2)).unwrap();
+    let start = Instant::now();
+    while reloads.lock().unwrap().is_empty() && start.elapsed() < Duration::from_secs(10) {
+        manager.tick(Duration::from_millis(10));
+        std::thread::sleep(Duration::from_millis(20));
+    }
+
+    assert_eq!(
+        reloads.lock().unwrap().first(),
+        Some(&(bundle.clone(), true))
+    );
+    assert_eq!(
+        plugin.call_function("answer", &[]).unwrap().unwrap(),
+        Some(Variable::I64(2))
+    );
+
+    loader.stop().unwrap();
+    fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+}